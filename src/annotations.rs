@@ -0,0 +1,622 @@
+//! Registry of the built-in annotations of the AIDL compiler
+//!
+//! Each known annotation declares where it can be attached (e.g. `@Backing` only on
+//! enums) and which parameters it accepts (e.g. `@JavaDerive(toString=true)`).
+
+use std::fmt;
+
+use crate::ast;
+use crate::diagnostic::{Diagnostic, DiagnosticCode, DiagnosticKind};
+use crate::format::format_type;
+use crate::symbol::Symbol;
+use crate::traverse::{self, SymbolFilter};
+
+/// Declaration to which an annotation can be attached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationTarget {
+    Interface,
+    Parcelable,
+    Union,
+    Enum,
+    Method,
+    Arg,
+    Const,
+    Field,
+}
+
+impl fmt::Display for AnnotationTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AnnotationTarget::Interface => "interface",
+            AnnotationTarget::Parcelable => "parcelable",
+            AnnotationTarget::Union => "union",
+            AnnotationTarget::Enum => "enum",
+            AnnotationTarget::Method => "method",
+            AnnotationTarget::Arg => "argument",
+            AnnotationTarget::Const => "const",
+            AnnotationTarget::Field => "field",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Expected value of an annotation parameter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamKind {
+    /// `true` or `false`
+    Bool,
+    /// Integer literal without `L` suffix (e.g. `28`)
+    Int,
+    /// Integer literal, with or without `L` suffix (e.g. `123456L`)
+    Long,
+    /// String literal (e.g. `"int"`)
+    String,
+    /// String literal or array of string literals (e.g. `{"a", "b"}`)
+    StringArray,
+}
+
+impl ParamKind {
+    fn accepts(&self, value: &str) -> bool {
+        let is_string = |v: &str| v.len() >= 2 && v.starts_with('"') && v.ends_with('"');
+        let is_integer = |v: &str| {
+            let v = v.strip_prefix('-').unwrap_or(v).trim_start();
+            let (digits, radix) = if let Some(hex) = v.strip_prefix("0x").or(v.strip_prefix("0X")) {
+                (hex, 16)
+            } else if let Some(bin) = v.strip_prefix("0b").or(v.strip_prefix("0B")) {
+                (bin, 2)
+            } else {
+                (v, 10)
+            };
+            !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix))
+        };
+        match self {
+            ParamKind::Bool => matches!(value, "true" | "false"),
+            ParamKind::Int => is_integer(value),
+            ParamKind::Long => is_integer(value.trim_end_matches(['l', 'L'])),
+            ParamKind::String => is_string(value),
+            ParamKind::StringArray => {
+                is_string(value) || (value.starts_with('{') && value.ends_with('}'))
+            }
+        }
+    }
+
+    fn get_description(&self) -> &'static str {
+        match self {
+            ParamKind::Bool => "a boolean (true or false)",
+            ParamKind::Int => "an int literal",
+            ParamKind::Long => "a long literal",
+            ParamKind::String => "a string literal",
+            ParamKind::StringArray => "a string literal or an array of string literals",
+        }
+    }
+}
+
+/// Parameter accepted by a known annotation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnnotationParam {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    pub required: bool,
+}
+
+/// Built-in annotation, with its allowed targets and parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KnownAnnotation {
+    /// Name without `@` (e.g. `nullable`)
+    pub name: &'static str,
+    pub targets: &'static [AnnotationTarget],
+    pub params: &'static [AnnotationParam],
+}
+
+impl KnownAnnotation {
+    pub fn get_param(&self, name: &str) -> Option<&'static AnnotationParam> {
+        self.params.iter().find(|p| p.name == name)
+    }
+}
+
+use AnnotationTarget::*;
+
+const ITEMS: &[AnnotationTarget] = &[Interface, Parcelable, Union, Enum];
+const STRUCTURED: &[AnnotationTarget] = &[Parcelable, Union];
+const TYPES: &[AnnotationTarget] = &[Method, Arg, Field];
+const ALL: &[AnnotationTarget] = &[
+    Interface, Parcelable, Union, Enum, Method, Arg, Const, Field,
+];
+
+const fn optional(name: &'static str, kind: ParamKind) -> AnnotationParam {
+    AnnotationParam {
+        name,
+        kind,
+        required: false,
+    }
+}
+
+const fn required(name: &'static str, kind: ParamKind) -> AnnotationParam {
+    AnnotationParam {
+        name,
+        kind,
+        required: true,
+    }
+}
+
+const fn known(
+    name: &'static str,
+    targets: &'static [AnnotationTarget],
+    params: &'static [AnnotationParam],
+) -> KnownAnnotation {
+    KnownAnnotation {
+        name,
+        targets,
+        params,
+    }
+}
+
+/// All the built-in annotations, sorted by name
+pub const KNOWN_ANNOTATIONS: &[KnownAnnotation] = &[
+    known("Backing", &[Enum], &[required("type", ParamKind::String)]),
+    known(
+        "Descriptor",
+        &[Interface],
+        &[required("value", ParamKind::String)],
+    ),
+    known(
+        "EnforcePermission",
+        &[Interface, Method],
+        &[
+            optional("value", ParamKind::String),
+            optional("anyOf", ParamKind::StringArray),
+            optional("allOf", ParamKind::StringArray),
+        ],
+    ),
+    known("FixedSize", STRUCTURED, &[]),
+    known(
+        "Hide",
+        &[Interface, Parcelable, Union, Enum, Method, Const, Field],
+        &[],
+    ),
+    known("JavaDefault", &[Interface], &[]),
+    known("JavaDelegator", &[Interface], &[]),
+    known(
+        "JavaDerive",
+        STRUCTURED,
+        &[
+            optional("toString", ParamKind::Bool),
+            optional("equals", ParamKind::Bool),
+        ],
+    ),
+    known("JavaOnlyImmutable", STRUCTURED, &[]),
+    known("JavaOnlyStableParcelable", &[Parcelable], &[]),
+    known(
+        "JavaPassthrough",
+        ALL,
+        &[required("annotation", ParamKind::String)],
+    ),
+    known(
+        "JavaSuppressLint",
+        ALL,
+        &[required("value", ParamKind::StringArray)],
+    ),
+    known("NdkOnlyStableParcelable", &[Parcelable], &[]),
+    known("PermissionManuallyEnforced", &[Interface, Method], &[]),
+    known("PropagateAllowBlocking", &[Method, Arg], &[]),
+    known("RequiresNoPermission", &[Interface, Method], &[]),
+    known(
+        "RustDerive",
+        STRUCTURED,
+        &[
+            optional("Copy", ParamKind::Bool),
+            optional("Clone", ParamKind::Bool),
+            optional("PartialOrd", ParamKind::Bool),
+            optional("Ord", ParamKind::Bool),
+            optional("PartialEq", ParamKind::Bool),
+            optional("Eq", ParamKind::Bool),
+            optional("Hash", ParamKind::Bool),
+        ],
+    ),
+    known("RustOnlyStableParcelable", &[Parcelable], &[]),
+    known("SensitiveData", &[Interface], &[]),
+    known(
+        "SuppressWarnings",
+        ALL,
+        &[required("value", ParamKind::StringArray)],
+    ),
+    known(
+        "SystemApi",
+        &[Interface, Parcelable, Union, Enum, Method, Const, Field],
+        &[],
+    ),
+    known(
+        "UnsupportedAppUsage",
+        &[Interface, Parcelable, Union, Enum, Method, Const, Field],
+        &[
+            optional("expectedSignature", ParamKind::String),
+            optional("implicitMember", ParamKind::String),
+            optional("maxTargetSdk", ParamKind::Int),
+            optional("publicAlternatives", ParamKind::String),
+            optional("trackingBug", ParamKind::Long),
+        ],
+    ),
+    known("VintfStability", ITEMS, &[]),
+    known("nullable", TYPES, &[optional("heap", ParamKind::Bool)]),
+    known("utf8InCpp", &[Method, Arg, Const, Field], &[]),
+];
+
+/// Find a built-in annotation by name, with or without `@` (e.g. `@nullable`)
+pub fn get_known_annotation(name: &str) -> Option<&'static KnownAnnotation> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    KNOWN_ANNOTATIONS.iter().find(|a| a.name == name)
+}
+
+/// Check the annotations of all the declarations of the AST (e.g. unknown annotations,
+/// `@Backing` on a parcelable, missing or invalid parameters)
+pub(crate) fn check_annotations(ast: &ast::Aidl, diagnostics: &mut Vec<Diagnostic>) {
+    traverse::walk_symbols(ast, SymbolFilter::All, |smb| {
+        let (target, annotations, type_) = match smb {
+            Symbol::Interface(i, _) => (Interface, &i.annotations, None),
+            Symbol::Parcelable(p, _) => (Parcelable, &p.annotations, None),
+            Symbol::Union(u, _) => (Union, &u.annotations, None),
+            Symbol::Enum(e, _) => (Enum, &e.annotations, None),
+            Symbol::Method(m, _) => (Method, &m.annotations, Some(&m.return_type)),
+            Symbol::Arg(a, _) => (Arg, &a.annotations, Some(&a.arg_type)),
+            Symbol::Const(c, _) => (Const, &c.annotations, Some(&c.const_type)),
+            Symbol::Field(f, _) => (Field, &f.annotations, Some(&f.field_type)),
+            _ => return,
+        };
+
+        for annotation in annotations {
+            check_annotation(annotation, target, type_, &smb, diagnostics);
+        }
+    });
+}
+
+fn check_annotation(
+    annotation: &ast::Annotation,
+    target: AnnotationTarget,
+    type_: Option<&ast::Type>,
+    smb: &Symbol,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let range = smb.get_range();
+    let name = &annotation.name;
+
+    let known = match get_known_annotation(name) {
+        Some(known) => known,
+        None => {
+            // e.g. `@Nullable` instead of `@nullable`
+            let similar = KNOWN_ANNOTATIONS
+                .iter()
+                .find(|a| a.name.eq_ignore_ascii_case(name.trim_start_matches('@')));
+
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::Warning,
+                code: DiagnosticCode::UnknownAnnotation,
+                range: range.clone(),
+                message: format!("Unknown annotation `{name}`"),
+                context_message: Some("unknown annotation".to_owned()),
+                hint: similar.map(|a| format!("did you mean `@{}`?", a.name)),
+                related_infos: Vec::new(),
+                fixes: Vec::new(),
+            });
+            return;
+        }
+    };
+
+    if !known.targets.contains(&target) {
+        let allowed: Vec<String> = known.targets.iter().map(|t| t.to_string()).collect();
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Error,
+            code: DiagnosticCode::MisplacedAnnotation,
+            range: range.clone(),
+            message: format!(
+                "Annotation `{name}` is not allowed on {target} `{}`",
+                smb.get_name().unwrap_or_default()
+            ),
+            context_message: Some("misplaced annotation".to_owned()),
+            hint: Some(format!("only allowed on: {}", allowed.join(", "))),
+            related_infos: Vec::new(),
+            fixes: Vec::new(),
+        });
+        return;
+    }
+
+    if let Some(type_) = type_ {
+        if let Some(hint) = check_annotated_type(known, type_) {
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::Error,
+                code: DiagnosticCode::MisplacedAnnotation,
+                range: range.clone(),
+                message: format!(
+                    "Annotation `{name}` is not allowed on type `{}`",
+                    format_type(type_)
+                ),
+                context_message: Some("misplaced annotation".to_owned()),
+                hint: Some(hint.to_owned()),
+                related_infos: Vec::new(),
+                fixes: Vec::new(),
+            });
+        }
+    }
+
+    check_annotation_params(annotation, known, range, diagnostics);
+}
+
+// Annotations which only apply to some types (returns the hint of the diagnostic)
+fn check_annotated_type(known: &KnownAnnotation, type_: &ast::Type) -> Option<&'static str> {
+    match known.name {
+        "nullable" => match type_.kind {
+            ast::TypeKind::Primitive | ast::TypeKind::Void => {
+                Some("primitive types cannot be null")
+            }
+            ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Enum) => {
+                Some("enum types cannot be null")
+            }
+            _ => None,
+        },
+        "utf8InCpp" => {
+            let is_string = |t: &ast::Type| t.kind == ast::TypeKind::String;
+            let ok = match type_.kind {
+                ast::TypeKind::String => true,
+                ast::TypeKind::Array | ast::TypeKind::List => {
+                    type_.generic_types.first().is_some_and(is_string)
+                }
+                _ => false,
+            };
+            (!ok).then_some("only allowed on String types (or arrays and lists of String)")
+        }
+        _ => None,
+    }
+}
+
+fn check_annotation_params(
+    annotation: &ast::Annotation,
+    known: &KnownAnnotation,
+    range: &ast::Range,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let name = &annotation.name;
+    let mut push = |message: String, hint: String| {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidAnnotationParameter,
+            range: range.clone(),
+            message,
+            context_message: Some("invalid annotation parameter".to_owned()),
+            hint: Some(hint),
+            related_infos: Vec::new(),
+            fixes: Vec::new(),
+        })
+    };
+
+    // Sorted for deterministic diagnostics
+    let mut key_values: Vec<(&String, &Option<String>)> = annotation.key_values.iter().collect();
+    key_values.sort();
+
+    for (key, value) in key_values {
+        let param = match known.get_param(key) {
+            Some(param) => param,
+            None if known.params.is_empty() => {
+                push(
+                    format!("Unexpected parameter `{key}` for annotation `{name}`"),
+                    format!("`{name}` has no parameter"),
+                );
+                continue;
+            }
+            None => {
+                let expected: Vec<&str> = known.params.iter().map(|p| p.name).collect();
+                push(
+                    format!("Unknown parameter `{key}` for annotation `{name}`"),
+                    format!("expected one of: {}", expected.join(", ")),
+                );
+                continue;
+            }
+        };
+
+        match value {
+            Some(value) if param.kind.accepts(value) => (),
+            Some(value) => push(
+                format!("Invalid value `{value}` for parameter `{key}` of annotation `{name}`"),
+                format!("expected {}", param.kind.get_description()),
+            ),
+            None => push(
+                format!("Missing value for parameter `{key}` of annotation `{name}`"),
+                format!("expected {}", param.kind.get_description()),
+            ),
+        }
+    }
+
+    for param in known.params.iter().filter(|p| p.required) {
+        if !annotation.key_values.contains_key(param.name) {
+            push(
+                format!("Missing parameter `{}` for annotation `{name}`", param.name),
+                format!("expected {}", param.kind.get_description()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn check(input: &str) -> Vec<Diagnostic> {
+        let mut parser = Parser::new();
+        parser.add_content("id", input);
+        let mut results = parser.validate();
+        results
+            .remove("id")
+            .expect("result")
+            .diagnostics
+            .into_iter()
+            .filter(|d| {
+                matches!(
+                    d.code,
+                    DiagnosticCode::UnknownAnnotation
+                        | DiagnosticCode::MisplacedAnnotation
+                        | DiagnosticCode::InvalidAnnotationParameter
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_get_known_annotation() {
+        assert_eq!(
+            get_known_annotation("@nullable").map(|a| a.name),
+            Some("nullable")
+        );
+        assert_eq!(
+            get_known_annotation("Backing").map(|a| a.name),
+            Some("Backing")
+        );
+        assert!(get_known_annotation("@Nullable").is_none());
+
+        // Sorted (for completion)
+        let names: Vec<&str> = KNOWN_ANNOTATIONS.iter().map(|a| a.name).collect();
+        let mut sorted = names.clone();
+        sorted.sort_unstable();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn test_valid_annotations() {
+        let input = r#"
+            package test;
+            @VintfStability @Descriptor(value="test.IFoo")
+            interface IFoo {
+                @nullable String foo(in @nullable int[] a, @utf8InCpp List<String> b);
+                @utf8InCpp const String C = "c";
+                @SuppressWarnings(value={"AIDL0013"}) @Hide const int D = 1;
+                @JavaDerive(toString=true, equals=false) @FixedSize
+                parcelable Nested { @nullable(heap=true) Nested n; }
+                @Backing(type="int") enum E { A }
+            }
+        "#;
+        assert_eq!(check(input), []);
+    }
+
+    #[test]
+    fn test_unknown_annotation() {
+        let diagnostics =
+            check("package test; interface IFoo { @Nullable String foo(); @Custom void bar(); }");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::UnknownAnnotation);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Warning);
+        assert_eq!(
+            diagnostics[0].hint.as_deref(),
+            Some("did you mean `@nullable`?")
+        );
+        assert_eq!(diagnostics[1].message, "Unknown annotation `@Custom`");
+        assert_eq!(diagnostics[1].hint, None);
+    }
+
+    #[test]
+    fn test_misplaced_annotation() {
+        let input = r#"
+            package test;
+            @Backing(type="int")
+            parcelable Foo {
+                @nullable int a;
+                @nullable E e;
+                @utf8InCpp int[] b;
+                @FixedSize String c;
+                enum E { A }
+            }
+        "#;
+        let diagnostics = check(input);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Annotation `@Backing` is not allowed on parcelable `Foo`",
+                "Annotation `@nullable` is not allowed on type `int`",
+                "Annotation `@nullable` is not allowed on type `E`",
+                "Annotation `@utf8InCpp` is not allowed on type `int[]`",
+                "Annotation `@FixedSize` is not allowed on field `c`",
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.code == DiagnosticCode::MisplacedAnnotation));
+        assert_eq!(
+            diagnostics[0].hint.as_deref(),
+            Some("only allowed on: enum")
+        );
+    }
+
+    #[test]
+    fn test_invalid_annotation_parameters() {
+        let input = r#"
+            package test;
+            @JavaDerive(toString="yes", hash=true)
+            parcelable Foo {
+                @nullable(heap) Foo a;
+                @Backing enum E { A }
+                @VintfStability(value=true) union U { int i; }
+            }
+        "#;
+        let diagnostics = check(input);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Unknown parameter `hash` for annotation `@JavaDerive`",
+                "Invalid value `\"yes\"` for parameter `toString` of annotation `@JavaDerive`",
+                "Missing value for parameter `heap` of annotation `@nullable`",
+                "Missing parameter `type` for annotation `@Backing`",
+                "Unexpected parameter `value` for annotation `@VintfStability`",
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.code == DiagnosticCode::InvalidAnnotationParameter));
+    }
+
+    #[test]
+    fn test_integer_annotation_parameters() {
+        // Usages as found in the AOSP framework interfaces
+        let input = r#"
+            package test;
+            interface IFoo {
+                @UnsupportedAppUsage(maxTargetSdk=28, trackingBug=123456L)
+                void a();
+                @UnsupportedAppUsage(maxTargetSdk = 30, trackingBug = 170729553)
+                void b();
+                @UnsupportedAppUsage(expectedSignature="Lfoo;", implicitMember="bar", publicAlternatives="baz")
+                void c();
+                @UnsupportedAppUsage(maxTargetSdk=0x1C, trackingBug=-1)
+                void d();
+                @UnsupportedAppUsage(maxTargetSdk=28L, trackingBug="123456")
+                void e();
+                @UnsupportedAppUsage(maxTargetSdk="28", trackingBug=true)
+                void f();
+            }
+        "#;
+        let diagnostics = check(input);
+        let messages: Vec<(&str, Option<&str>)> = diagnostics
+            .iter()
+            .map(|d| (d.message.as_str(), d.hint.as_deref()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    "Invalid value `28L` for parameter `maxTargetSdk` of annotation `@UnsupportedAppUsage`",
+                    Some("expected an int literal")
+                ),
+                (
+                    "Invalid value `\"123456\"` for parameter `trackingBug` of annotation `@UnsupportedAppUsage`",
+                    Some("expected a long literal")
+                ),
+                (
+                    "Invalid value `\"28\"` for parameter `maxTargetSdk` of annotation `@UnsupportedAppUsage`",
+                    Some("expected an int literal")
+                ),
+                (
+                    "Invalid value `true` for parameter `trackingBug` of annotation `@UnsupportedAppUsage`",
+                    Some("expected a long literal")
+                ),
+            ]
+        );
+    }
+}
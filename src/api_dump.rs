@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+
+use crate::ast;
+use crate::format::format_annotation;
+
+/// Header of the frozen API dumps (as written by the AOSP `aidl --dumpapi`)
+pub const HEADER: &str = "\
+///////////////////////////////////////////////////////////////////////////////
+// THIS FILE IS IMMUTABLE. DO NOT EDIT IN ANY CASE.                          //
+///////////////////////////////////////////////////////////////////////////////
+
+// This file is a snapshot of an AIDL file. Do not edit it manually. There are
+// two cases:
+// 1). this is a frozen version file - do not edit this in any case.
+// 2). this is a 'current' file. If you make a backwards compatible change to
+//     the interface (from the latest frozen version), the build system will
+//     prompt you to update this file with `m <name>-update-api`.
+//
+// You must not make a backward incompatible change to any AIDL file built
+// with the aidl_interface module type with versions property set. The module
+// type is used to build AIDL files in a way that they can be used across
+// independently updatable components of the system. If a device is shipped
+// with such a backward incompatible change, it has a high risk of breaking
+// later when a module using the interface is updated, e.g., Mainline modules.
+";
+
+const INDENT: &str = "  ";
+
+/// Dump the API of the given (validated) ASTs, as frozen into `aidl_api/<module>/<version>/`
+///
+/// The result maps the path of each dump, relative to the version directory
+/// (e.g. `com/example/IFoo.aidl`), to its content (see dump()).
+pub fn dump_api<'a, I>(asts: I) -> BTreeMap<String, String>
+where
+    I: IntoIterator<Item = &'a ast::Aidl>,
+{
+    asts.into_iter()
+        .map(|ast| {
+            (
+                format!("{}.aidl", ast.get_key().replace('.', "/")),
+                dump(ast),
+            )
+        })
+        .collect()
+}
+
+/// Dump the API of the item of the given (validated) AST in the canonical frozen
+/// representation
+///
+/// The dump starts with the standard header and does not contain imports, docs or
+/// comments. Every type is fully qualified, the arguments always have a direction and
+/// the values of consts, fields and enum elements are evaluated.
+///
+/// The methods, fields and enum elements keep the order of the source, since it
+/// defines the transaction codes and the serialization. The consts and nested items
+/// are sorted by name, as well as the annotations.
+///
+/// Example:
+/// ```
+/// use aidl_parser::{api_dump, Parser};
+///
+/// let mut parser = Parser::new();
+/// parser.add_content("IFoo", "package p; import p.Bar; interface IFoo { /** Doc */ void foo(int i, in Bar b); }");
+/// parser.add_content("Bar", "package p; parcelable Bar {}");
+/// let results = parser.validate();
+///
+/// let ast = results["IFoo"].ast.as_ref().expect("ast");
+/// let dump = api_dump::dump(ast);
+/// assert!(dump.starts_with(api_dump::HEADER));
+/// assert!(dump.ends_with("\npackage p;\ninterface IFoo {\n  void foo(in int i, in p.Bar b);\n}\n"));
+/// ```
+pub fn dump(ast: &ast::Aidl) -> String {
+    let mut dumper = Dumper {
+        ast,
+        level: 0,
+        output: String::from(HEADER),
+    };
+
+    dumper.output.push('\n');
+    dumper.write_line(&format!("package {};", ast.package.name));
+    dumper.write_item(&ast.item);
+
+    dumper.output
+}
+
+struct Dumper<'a> {
+    ast: &'a ast::Aidl,
+    level: usize,
+    output: String,
+}
+
+impl<'a> Dumper<'a> {
+    fn write_item(&mut self, item: &ast::Item) {
+        let (annotations, header) = match item {
+            ast::Item::Interface(i) => (
+                &i.annotations,
+                format!(
+                    "{}interface {}",
+                    if i.oneway { "oneway " } else { "" },
+                    i.name
+                ),
+            ),
+            ast::Item::Parcelable(p) if p.type_parameters.is_empty() => {
+                (&p.annotations, format!("parcelable {}", p.name))
+            }
+            ast::Item::Parcelable(p) => (
+                &p.annotations,
+                format!("parcelable {}<{}>", p.name, p.type_parameters.join(", ")),
+            ),
+            ast::Item::Union(u) => (&u.annotations, format!("union {}", u.name)),
+            ast::Item::Enum(e) => (&e.annotations, format!("enum {}", e.name)),
+        };
+
+        if !annotations.is_empty() {
+            self.write_line(&format_annotations(annotations).join(" "));
+        }
+        self.write_line(&format!("{header} {{"));
+        self.level += 1;
+
+        let mut consts: Vec<&ast::Const> = Vec::new();
+        match item {
+            ast::Item::Interface(i) => {
+                for el in &i.elements {
+                    match el {
+                        ast::InterfaceElement::Method(m) => self.write_method(m),
+                        ast::InterfaceElement::Const(c) => consts.push(c),
+                    }
+                }
+            }
+            ast::Item::Parcelable(p) => {
+                for el in &p.elements {
+                    match el {
+                        ast::ParcelableElement::Field(f) => self.write_field(f),
+                        ast::ParcelableElement::Const(c) => consts.push(c),
+                    }
+                }
+            }
+            ast::Item::Union(u) => {
+                for el in &u.elements {
+                    match el {
+                        ast::UnionElement::Field(f) => self.write_field(f),
+                        ast::UnionElement::Const(c) => consts.push(c),
+                    }
+                }
+            }
+            ast::Item::Enum(e) => {
+                for el in &e.elements {
+                    let value = match (&el.evaluated_value, &el.value) {
+                        (Some(value), _) => format!(" = {value}"),
+                        (None, Some(expr)) => format!(" = {}", self.format_expr(expr)),
+                        (None, None) => String::new(),
+                    };
+                    self.write_line(&format!("{}{value},", el.name));
+                }
+            }
+        }
+
+        consts.sort_by(|c1, c2| c1.name.cmp(&c2.name));
+        for c in consts {
+            let value = match &c.evaluated_value {
+                Some(value) => value.to_string(),
+                None => self.format_expr(&c.value),
+            };
+            self.write_line(&format!(
+                "{}const {} {} = {value};",
+                get_prefix(&c.annotations),
+                format_type(&c.const_type),
+                c.name
+            ));
+        }
+
+        let mut nested_items: Vec<&ast::Item> = item.get_nested_items().iter().collect();
+        nested_items.sort_by_key(|i| i.get_name());
+        for nested in nested_items {
+            self.write_item(nested);
+        }
+
+        self.level -= 1;
+        self.write_line("}");
+    }
+
+    fn write_method(&mut self, method: &ast::Method) {
+        let args: Vec<String> = method
+            .args
+            .iter()
+            .map(|arg| {
+                let direction = match arg.direction {
+                    ast::Direction::Unspecified => String::from("in"),
+                    _ => arg.direction.to_string(),
+                };
+                let mut s = format!(
+                    "{direction} {}{}",
+                    get_prefix(&arg.annotations),
+                    format_type(&arg.arg_type)
+                );
+                if let Some(name) = &arg.name {
+                    s.push_str(&format!(" {name}"));
+                }
+                s
+            })
+            .collect();
+
+        self.write_line(&format!(
+            "{}{}{} {}({}){};",
+            get_prefix(&method.annotations),
+            if method.has_oneway_modifier() {
+                "oneway "
+            } else {
+                ""
+            },
+            format_type(&method.return_type),
+            method.name,
+            args.join(", "),
+            method
+                .transact_code
+                .map(|code| format!(" = {code}"))
+                .unwrap_or_default(),
+        ));
+    }
+
+    fn write_field(&mut self, field: &ast::Field) {
+        let value = match (&field.evaluated_value, &field.value) {
+            (Some(value), _) => format!(" = {value}"),
+            (None, Some(expr)) => format!(" = {}", self.format_expr(expr)),
+            (None, None) => String::new(),
+        };
+
+        self.write_line(&format!(
+            "{}{} {}{value};",
+            get_prefix(&field.annotations),
+            format_type(&field.field_type),
+            field.name
+        ));
+    }
+
+    fn write_line(&mut self, line: &str) {
+        for _ in 0..self.level {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    /// Format a constant expression which could not be evaluated (e.g. the value of
+    /// an enum field), with the references to other items fully qualified
+    fn format_expr(&self, expr: &ast::ConstExpr) -> String {
+        let mut expr = expr.clone();
+        self.qualify_references(&mut expr);
+        expr.to_string()
+    }
+
+    fn qualify_references(&self, expr: &mut ast::ConstExpr) {
+        match &mut expr.kind {
+            ast::ConstExprKind::Reference(reference) => {
+                if let Some((first, rest)) = reference.split_once('.') {
+                    if let Some(qualified_name) = self.get_qualified_name(first) {
+                        *reference = format!("{qualified_name}.{rest}");
+                    }
+                }
+            }
+            ast::ConstExprKind::Array(elements) => {
+                for el in elements {
+                    self.qualify_references(el);
+                }
+            }
+            ast::ConstExprKind::Unary { expr, .. } => self.qualify_references(expr),
+            ast::ConstExprKind::Binary { lhs, rhs, .. } => {
+                self.qualify_references(lhs);
+                self.qualify_references(rhs);
+            }
+            ast::ConstExprKind::Integer(_)
+            | ast::ConstExprKind::Float(_)
+            | ast::ConstExprKind::String(_)
+            | ast::ConstExprKind::Char(_)
+            | ast::ConstExprKind::Bool(_) => (),
+        }
+    }
+
+    /// Qualified name of an imported item or of the item of the file
+    fn get_qualified_name(&self, name: &str) -> Option<String> {
+        if name == self.ast.item.get_name() {
+            return Some(self.ast.get_key());
+        }
+
+        self.ast
+            .imports
+            .iter()
+            .find(|i| i.name == name)
+            .map(ast::Import::get_qualified_name)
+    }
+}
+
+/// Type with the items fully qualified (e.g. `List<com.example.Bar>`)
+pub(crate) fn format_type(type_: &ast::Type) -> String {
+    let name = match &type_.kind {
+        ast::TypeKind::ResolvedItem(key, _) => key.as_str(),
+        ast::TypeKind::AndroidType(at) if at.can_be_qualified() => at.get_qualified_name(),
+        _ => type_.name.as_str(),
+    };
+
+    match (&type_.kind, &type_.generic_types[..]) {
+        (ast::TypeKind::Array, [element_type]) => {
+            format!("{}[]", format_type(element_type))
+        }
+        (_, []) => name.to_owned(),
+        (_, generic_types) => {
+            let generic_types: Vec<String> = generic_types.iter().map(format_type).collect();
+            format!("{name}<{}>", generic_types.join(", "))
+        }
+    }
+}
+
+/// Formatted annotations (e.g. `@Backing(type="int")`), sorted
+pub(crate) fn format_annotations(annotations: &[ast::Annotation]) -> Vec<String> {
+    let mut annotations: Vec<String> = annotations.iter().map(format_annotation).collect();
+    annotations.sort();
+    annotations
+}
+
+// Inline annotations of a declaration (followed by a space)
+fn get_prefix(annotations: &[ast::Annotation]) -> String {
+    format_annotations(annotations)
+        .into_iter()
+        .map(|a| format!("{a} "))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn test_dump_api() {
+        let interface = r#"package com.example;
+            import com.example.Bar;
+            import com.example.Color;
+
+            /** Example service */
+            @VintfStability
+            interface IFoo {
+                // Comment
+                const String NAME = "foo";
+                const int VERSION = 1 + 2;
+
+                /** Doc */
+                @nullable String getName() = 3;
+                oneway void setColor(Color color, in IBinder token) = 1;
+                void update(inout Bar bar, in ParcelFileDescriptor fd, out List<String> tags) = 2;
+
+                parcelable Nested {
+                    int[] values = {1, 2 * 2};
+                }
+                enum Mode { B = 2, A }
+            }
+        "#;
+        let bar = r#"package com.example;
+            import com.example.Color;
+
+            @JavaDerive(toString=true, equals=true)
+            parcelable Bar {
+                const long MAX = 10;
+                Color color = Color.GREEN;
+                @nullable Bar next;
+            }
+        "#;
+        let color = "package com.example; @Backing(type=\"byte\") enum Color { RED = 1, GREEN }";
+
+        let mut parser = Parser::new();
+        parser.add_content("IFoo", interface);
+        parser.add_content("Bar", bar);
+        parser.add_content("Color", color);
+        let results = parser.validate();
+
+        let mut ids: Vec<&&str> = results.keys().collect();
+        ids.sort();
+        let asts = ids.iter().map(|id| {
+            assert_eq!(results[*id].diagnostics, []);
+            results[*id].ast.as_ref().expect("ast")
+        });
+        let dumps = dump_api(asts);
+
+        assert_eq!(
+            dumps.keys().collect::<Vec<_>>(),
+            [
+                "com/example/Bar.aidl",
+                "com/example/Color.aidl",
+                "com/example/IFoo.aidl"
+            ]
+        );
+        let get_dump = |path| dumps[path].strip_prefix(HEADER).expect("header");
+        insta::assert_snapshot!("interface", get_dump("com/example/IFoo.aidl"));
+        insta::assert_snapshot!("parcelable", get_dump("com/example/Bar.aidl"));
+        insta::assert_snapshot!("enum", get_dump("com/example/Color.aidl"));
+    }
+
+    #[test]
+    fn test_dump_oneway_interface() {
+        let mut parser = Parser::new();
+        parser.add_content(
+            "IListener",
+            "package p; oneway interface IListener { void a(); }",
+        );
+        let results = parser.validate();
+        let ast = results["IListener"].ast.as_ref().expect("ast");
+
+        let dump = dump(ast);
+        assert!(dump.ends_with("oneway interface IListener {\n  void a();\n}\n"));
+    }
+}
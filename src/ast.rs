@@ -9,6 +9,9 @@ pub struct Aidl {
     pub imports: Vec<Import>,
     pub declared_parcelables: Vec<Import>,
     pub item: Item,
+    /// Regular and javadoc comments of the file (in order of appearance)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<Comment>,
 }
 
 pub type ItemKey = String;
@@ -19,6 +22,28 @@ impl Aidl {
     pub fn get_key(&self) -> ItemKey {
         format!("{}.{}", self.package.name, self.item.get_name())
     }
+
+    /// Keys and kinds of the item and of all its nested items (e.g. `pkg.Outer.Inner`)
+    pub fn get_all_keys(&self) -> Vec<(ItemKey, ResolvedItemKind)> {
+        self.get_all_items()
+            .into_iter()
+            .map(|(key, item)| (key, item.get_kind()))
+            .collect()
+    }
+
+    /// The item and all its nested items with their keys (nested items first)
+    pub fn get_all_items(&self) -> Vec<(ItemKey, &Item)> {
+        fn collect<'a>(item: &'a Item, key: ItemKey, items: &mut Vec<(ItemKey, &'a Item)>) {
+            for nested in item.get_nested_items() {
+                collect(nested, format!("{}.{}", key, nested.get_name()), items);
+            }
+            items.push((key, item));
+        }
+
+        let mut items = Vec::new();
+        collect(&self.item, self.get_key(), &mut items);
+        items
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -53,6 +78,19 @@ impl Range {
     }
 }
 
+/// Comment with its delimiters, e.g. `// comment` or `/** javadoc */`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub text: String,
+    pub full_range: Range,
+}
+
+impl Comment {
+    pub fn is_javadoc(&self) -> bool {
+        self.text.starts_with("/**") && self.text != "/**/"
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Package {
     pub name: String,
@@ -94,6 +132,13 @@ impl InterfaceElement {
         }
     }
 
+    pub fn as_const(&self) -> Option<&Const> {
+        match &self {
+            InterfaceElement::Const(c) => Some(c),
+            _ => None,
+        }
+    }
+
     pub fn get_name(&self) -> &str {
         match self {
             InterfaceElement::Const(c) => &c.name,
@@ -114,6 +159,7 @@ impl InterfaceElement {
 pub enum ResolvedItemKind {
     Interface,
     Parcelable,
+    Union,
     Enum,
     ForwardDeclaredParcelable,
     UnknownImport,
@@ -124,6 +170,7 @@ pub enum ResolvedItemKind {
 pub enum Item {
     Interface(Interface),
     Parcelable(Parcelable),
+    Union(Union),
     Enum(Enum),
 }
 
@@ -142,6 +189,13 @@ impl Item {
         }
     }
 
+    pub fn as_union(&self) -> Option<&Union> {
+        match &self {
+            Item::Union(u) => Some(u),
+            _ => None,
+        }
+    }
+
     pub fn as_enum(&self) -> Option<&Enum> {
         match &self {
             Item::Enum(e) => Some(e),
@@ -153,6 +207,7 @@ impl Item {
         match self {
             Item::Interface(_) => ResolvedItemKind::Interface,
             Item::Parcelable(_) => ResolvedItemKind::Parcelable,
+            Item::Union(_) => ResolvedItemKind::Union,
             Item::Enum(_) => ResolvedItemKind::Enum,
         }
     }
@@ -161,6 +216,7 @@ impl Item {
         match self {
             Item::Interface(i) => &i.name,
             Item::Parcelable(p) => &p.name,
+            Item::Union(u) => &u.name,
             Item::Enum(e) => &e.name,
         }
     }
@@ -169,6 +225,7 @@ impl Item {
         match self {
             Item::Interface(i) => &i.symbol_range,
             Item::Parcelable(p) => &p.symbol_range,
+            Item::Union(u) => &u.symbol_range,
             Item::Enum(e) => &e.symbol_range,
         }
     }
@@ -177,9 +234,27 @@ impl Item {
         match self {
             Item::Interface(i) => &i.full_range,
             Item::Parcelable(p) => &p.full_range,
+            Item::Union(u) => &u.full_range,
             Item::Enum(e) => &e.full_range,
         }
     }
+
+    /// Type parameters of generic parcelables (e.g. `A` and `B` for `Pair<A, B>`)
+    pub fn get_type_parameters(&self) -> &[String] {
+        match self {
+            Item::Parcelable(p) => &p.type_parameters,
+            Item::Interface(_) | Item::Union(_) | Item::Enum(_) => &[],
+        }
+    }
+
+    /// Items declared inside this item (Android T)
+    pub fn get_nested_items(&self) -> &[Item] {
+        match self {
+            Item::Interface(i) => &i.nested_items,
+            Item::Parcelable(p) => &p.nested_items,
+            Item::Union(_) | Item::Enum(_) => &[],
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -188,6 +263,8 @@ pub struct Interface {
     pub name: String,
     pub elements: Vec<InterfaceElement>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nested_items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub annotations: Vec<Annotation>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub doc: Option<String>,
@@ -195,11 +272,30 @@ pub struct Interface {
     pub symbol_range: Range,
 }
 
+impl Interface {
+    /// Methods whose implicit transaction code would silently change if a method without
+    /// explicit code was inserted at the given position (among the methods)
+    ///
+    /// The methods with explicit transaction codes are never renumbered.
+    pub fn get_renumbered_methods(&self, position: usize) -> Vec<&Method> {
+        self.elements
+            .iter()
+            .filter_map(|el| el.as_method())
+            .skip(position)
+            .filter(|m| m.transact_code.is_none())
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Parcelable {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_parameters: Vec<String>,
     pub elements: Vec<ParcelableElement>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nested_items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub annotations: Vec<Annotation>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub doc: Option<String>,
@@ -207,7 +303,19 @@ pub struct Parcelable {
     pub symbol_range: Range,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Union {
+    pub name: String,
+    pub elements: Vec<UnionElement>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    pub full_range: Range,
+    pub symbol_range: Range,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Enum {
     pub name: String,
     pub elements: Vec<EnumElement>,
@@ -219,12 +327,27 @@ pub struct Enum {
     pub symbol_range: Range,
 }
 
+impl Enum {
+    /// Type of the enum elements as given via `@Backing(type="int")` (`byte` by default)
+    pub fn get_backing_type(&self) -> &str {
+        self.annotations
+            .iter()
+            .find(|a| a.name == "@Backing")
+            .and_then(|a| a.key_values.get("type"))
+            .and_then(|v| v.as_deref())
+            .map_or("byte", |v| v.trim_matches('"'))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Const {
     pub name: String,
     #[serde(rename = "type")]
     pub const_type: Type,
-    pub value: String,
+    pub value: ConstExpr,
+    /// Value of the expression, set during validation if it could be evaluated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evaluated_value: Option<ConstValue>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub annotations: Vec<Annotation>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -244,6 +367,10 @@ pub struct Method {
     pub annotations: Vec<Annotation>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub transact_code: Option<u32>,
+    /// Transaction code of the method, explicit (`transact_code`) or implicit (position
+    /// of the method in the interface, like the binder backends), set during validation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_transact_code: Option<u32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub doc: Option<String>,
     pub symbol_range: Range,
@@ -252,6 +379,14 @@ pub struct Method {
     pub oneway_range: Range,
 }
 
+impl Method {
+    /// Whether the method is declared with the `oneway` modifier (unlike `oneway`,
+    /// which is also set by the validation for all the methods of a oneway interface)
+    pub fn has_oneway_modifier(&self) -> bool {
+        self.oneway && self.oneway_range.start != self.oneway_range.end
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Arg {
     #[serde(default, skip_serializing_if = "Direction::is_unspecified")]
@@ -267,12 +402,13 @@ pub struct Arg {
     pub full_range: Range,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum Direction {
     In(Range),
     Out(Range),
     InOut(Range),
+    #[default]
     Unspecified,
 }
 
@@ -282,12 +418,6 @@ impl Direction {
     }
 }
 
-impl Default for Direction {
-    fn default() -> Self {
-        Direction::Unspecified
-    }
-}
-
 impl fmt::Display for Direction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -329,13 +459,46 @@ impl ParcelableElement {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnionElement {
+    Const(Const),
+    Field(Field),
+}
+
+impl UnionElement {
+    pub fn as_field(&self) -> Option<&Field> {
+        match &self {
+            UnionElement::Field(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            UnionElement::Const(c) => &c.name,
+            UnionElement::Field(f) => &f.name,
+        }
+    }
+
+    pub fn get_symbol_range(&self) -> &Range {
+        match self {
+            UnionElement::Const(c) => &c.symbol_range,
+            UnionElement::Field(f) => &f.symbol_range,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Field {
     pub name: String,
     #[serde(rename = "type")]
     pub field_type: Type,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+    pub value: Option<ConstExpr>,
+    /// Value of the expression, set during validation if it could be evaluated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evaluated_value: Option<ConstValue>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub annotations: Vec<Annotation>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -350,17 +513,271 @@ impl Field {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct EnumElement {
     pub name: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+    pub value: Option<ConstExpr>,
+    /// Value of the element (explicit or implicit, i.e. previous value + 1), set during
+    /// validation if it could be evaluated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evaluated_value: Option<ConstValue>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub doc: Option<String>,
     pub symbol_range: Range,
     pub full_range: Range,
 }
 
+/// Constant expression, e.g. `1 << 3`, `"a" + "b"` or `Other.FOO`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConstExpr {
+    pub kind: ConstExprKind,
+    pub full_range: Range,
+}
+
+impl ConstExpr {
+    pub(crate) fn new(
+        kind: ConstExprKind,
+        lookup: &line_col::LineColLookup,
+        start: usize,
+        end: usize,
+    ) -> Self {
+        ConstExpr {
+            kind,
+            full_range: Range::new(lookup, start, end),
+        }
+    }
+
+    pub(crate) fn binary(lhs: ConstExpr, op: BinaryOp, rhs: ConstExpr) -> Self {
+        let full_range = Range {
+            start: lhs.full_range.start.clone(),
+            end: rhs.full_range.end.clone(),
+        };
+
+        ConstExpr {
+            kind: ConstExprKind::Binary {
+                lhs: Box::new(lhs),
+                op,
+                rhs: Box::new(rhs),
+            },
+            full_range,
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match &self.kind {
+            ConstExprKind::Binary { op, .. } => op.precedence(),
+            ConstExprKind::Unary { .. } => UnaryOp::PRECEDENCE,
+            _ => u8::MAX,
+        }
+    }
+}
+
+impl fmt::Display for ConstExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ConstExprKind::Integer(s) | ConstExprKind::Float(s) => write!(f, "{s}"),
+            ConstExprKind::String(s) => write!(f, "\"{s}\""),
+            ConstExprKind::Char(s) => write!(f, "'{s}'"),
+            ConstExprKind::Bool(b) => write!(f, "{b}"),
+            ConstExprKind::Array(v) => {
+                let elements: Vec<String> = v.iter().map(ToString::to_string).collect();
+                write!(f, "{{{}}}", elements.join(", "))
+            }
+            ConstExprKind::Reference(r) => write!(f, "{r}"),
+            ConstExprKind::Unary { op, expr } => {
+                if expr.precedence() < UnaryOp::PRECEDENCE {
+                    write!(f, "{op}({expr})")
+                } else {
+                    write!(f, "{op}{expr}")
+                }
+            }
+            ConstExprKind::Binary { lhs, op, rhs } => {
+                // Binary operators are left-associative
+                if lhs.precedence() < op.precedence() {
+                    write!(f, "({lhs})")?;
+                } else {
+                    write!(f, "{lhs}")?;
+                }
+                write!(f, " {op} ")?;
+                if rhs.precedence() <= op.precedence() {
+                    write!(f, "({rhs})")
+                } else {
+                    write!(f, "{rhs}")
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstExprKind {
+    /// Integer literal as written in the source (e.g. `42`, `0x7f`, `0b101`, `017` or `3L`)
+    Integer(String),
+    /// Floating-point literal as written in the source (e.g. `1.5`, `.5f` or `1e3`)
+    Float(String),
+    /// String literal without the quotes
+    String(String),
+    /// Character literal without the quotes (e.g. `a` or `\n`)
+    Char(String),
+    Bool(bool),
+    Array(Vec<ConstExpr>),
+    /// Reference to another constant or enum element (e.g. `FOO`, `Other.FOO` or `pkg.Other.FOO`)
+    Reference(String),
+    Unary {
+        op: UnaryOp,
+        expr: Box<ConstExpr>,
+    },
+    Binary {
+        lhs: Box<ConstExpr>,
+        op: BinaryOp,
+        rhs: Box<ConstExpr>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnaryOp {
+    Plus,
+    Minus,
+    BitNot,
+    Not,
+}
+
+impl UnaryOp {
+    const PRECEDENCE: u8 = 11;
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Plus => write!(f, "+"),
+            UnaryOp::Minus => write!(f, "-"),
+            UnaryOp::BitNot => write!(f, "~"),
+            UnaryOp::Not => write!(f, "!"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryOp {
+    LogicalOr,
+    LogicalAnd,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// Operator precedence (higher binds tighter), same as in C++ and Java
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::LogicalOr => 1,
+            BinaryOp::LogicalAnd => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::Eq | BinaryOp::Ne => 6,
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => 7,
+            BinaryOp::Shl | BinaryOp::Shr => 8,
+            BinaryOp::Add | BinaryOp::Sub => 9,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 10,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BinaryOp::LogicalOr => "||",
+            BinaryOp::LogicalAnd => "&&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Typed value of an evaluated constant expression
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstValue {
+    Bool(bool),
+    Byte(i8),
+    Char(char),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Array(Vec<ConstValue>),
+}
+
+impl ConstValue {
+    /// Name of the AIDL type of the value (e.g. `int` or `String`)
+    pub fn get_type_name(&self) -> &'static str {
+        match self {
+            ConstValue::Bool(_) => "boolean",
+            ConstValue::Byte(_) => "byte",
+            ConstValue::Char(_) => "char",
+            ConstValue::Int(_) => "int",
+            ConstValue::Long(_) => "long",
+            ConstValue::Float(_) => "float",
+            ConstValue::Double(_) => "double",
+            ConstValue::String(_) => "String",
+            ConstValue::Array(_) => "array",
+        }
+    }
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstValue::Bool(v) => write!(f, "{v}"),
+            ConstValue::Byte(v) => write!(f, "{v}"),
+            ConstValue::Char(v) => write!(f, "'{}'", v.escape_default()),
+            ConstValue::Int(v) => write!(f, "{v}"),
+            ConstValue::Long(v) => write!(f, "{v}L"),
+            ConstValue::Float(v) => write!(f, "{v:?}f"),
+            ConstValue::Double(v) => write!(f, "{v:?}"),
+            ConstValue::String(v) => write!(f, "\"{v}\""),
+            ConstValue::Array(v) => {
+                let elements: Vec<String> = v.iter().map(ToString::to_string).collect();
+                write!(f, "{{{}}}", elements.join(", "))
+            }
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Annotation {
     pub name: String,
@@ -380,6 +797,8 @@ pub enum TypeKind {
     CharSequence,
     AndroidType(AndroidTypeKind),
     ResolvedItem(String, ResolvedItemKind),
+    /// Type parameter of a generic parcelable (e.g. `A` inside `parcelable Pair<A, B>`)
+    TypeParameter,
     Unresolved,
 }
 
@@ -394,7 +813,8 @@ pub enum AndroidTypeKind {
 }
 
 impl AndroidTypeKind {
-    fn get_all() -> Vec<Self> {
+    /// All the built-in types
+    pub fn get_all() -> Vec<Self> {
         Vec::from([
             Self::IBinder,
             Self::FileDescriptor,
@@ -0,0 +1,284 @@
+//! Validate AIDL files and print the diagnostics
+//!
+//! Usage: `aidl-lint [OPTIONS] <PATH|GLOB>...`
+//!
+//! The files given as argument (directories are searched recursively for `.aidl`
+//! files) are validated against each other and against the files of the import
+//! roots (`-I`), whose own diagnostics are not reported.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use aidl_parser::diagnostic::DiagnosticKind;
+use aidl_parser::render::{self, Style};
+use aidl_parser::{report, ParseFileResult, Parser};
+use anyhow::{anyhow, bail, Context, Result};
+
+const USAGE: &str = "Usage: aidl-lint [OPTIONS] <PATH|GLOB>...
+
+Validate AIDL files and print the diagnostics.
+
+Arguments:
+  <PATH|GLOB>...          AIDL files, directories (searched recursively) or glob patterns
+
+Options:
+  -I <DIR>                Import root, used to resolve the imported items (can be repeated)
+  -f, --format <FORMAT>   Output format: human, plain, sarif or json-lines [default: human]
+      --deny-warnings     Exit with a non-zero code when there are warnings
+  -h, --help              Print help
+  -V, --version           Print version
+
+Exit codes:
+  0  no error (and no warning with --deny-warnings)
+  1  errors (or warnings with --deny-warnings)
+  2  invalid arguments or files which cannot be read";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Human,
+    Plain,
+    Sarif,
+    JsonLines,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    import_roots: Vec<PathBuf>,
+    inputs: Vec<String>,
+    format: Format,
+    deny_warnings: bool,
+}
+
+enum Command {
+    Lint(Args),
+    Help,
+    Version,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let args = match parse_args(&args) {
+        Ok(Command::Lint(args)) => args,
+        Ok(Command::Help) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Version) => {
+            println!("aidl-lint {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let stdout = std::io::stdout();
+    let is_terminal = stdout.is_terminal();
+    match lint(&args, &mut stdout.lock(), is_terminal) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Command> {
+    let mut res = Args {
+        import_roots: Vec::new(),
+        inputs: Vec::new(),
+        format: Format::Human,
+        deny_warnings: false,
+    };
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = |name: &str| {
+            it.next()
+                .cloned()
+                .ok_or_else(|| anyhow!("missing value for `{name}`"))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--deny-warnings" => res.deny_warnings = true,
+            "-I" => res.import_roots.push(PathBuf::from(value("-I")?)),
+            "-f" | "--format" => res.format = parse_format(&value("--format")?)?,
+            s if s.starts_with("-I") => res.import_roots.push(PathBuf::from(&s[2..])),
+            s if s.starts_with("--format=") => res.format = parse_format(&s[9..])?,
+            s if s.starts_with('-') => bail!("unknown option `{s}`"),
+            s => res.inputs.push(s.to_owned()),
+        }
+    }
+
+    if res.inputs.is_empty() {
+        bail!("no input file");
+    }
+
+    Ok(Command::Lint(res))
+}
+
+fn parse_format(s: &str) -> Result<Format> {
+    match s {
+        "human" => Ok(Format::Human),
+        "plain" => Ok(Format::Plain),
+        "sarif" => Ok(Format::Sarif),
+        "json-lines" | "jsonl" => Ok(Format::JsonLines),
+        _ => bail!("invalid format `{s}` (expected human, plain, sarif or json-lines)"),
+    }
+}
+
+/// Validate the files and write the diagnostics, return false if the lint failed
+fn lint<W: Write>(args: &Args, out: &mut W, is_terminal: bool) -> Result<bool> {
+    // Files to validate
+    let mut files = BTreeSet::new();
+    for input in &args.inputs {
+        files.extend(collect_input(input)?);
+    }
+    if files.is_empty() {
+        bail!("no AIDL file found");
+    }
+
+    // Files only used to resolve the imports
+    let mut import_files = BTreeSet::new();
+    for root in &args.import_roots {
+        if !root.is_dir() {
+            bail!("import root `{}` is not a directory", root.display());
+        }
+        import_files.extend(collect_aidl_files(root)?);
+    }
+
+    let mut parser = Parser::new();
+    let mut contents = HashMap::new();
+    for path in files.iter().chain(import_files.iter()) {
+        if contents.contains_key(path) {
+            continue;
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read `{}`", path.display()))?;
+        parser.add_content(path.clone(), &content);
+        contents.insert(path.clone(), content);
+    }
+
+    let results: HashMap<PathBuf, ParseFileResult<PathBuf>> = parser
+        .validate()
+        .into_iter()
+        .filter(|(path, _)| files.contains(path))
+        .collect();
+
+    match args.format {
+        Format::Human | Format::Plain => {
+            let style = match args.format {
+                Format::Human if is_terminal => Style::Color,
+                _ => Style::Plain,
+            };
+            for path in &files {
+                write!(
+                    out,
+                    "{}",
+                    render::render(&results[path], &contents[path], style)
+                )?;
+            }
+        }
+        Format::Sarif => writeln!(out, "{}", report::to_sarif(&results))?,
+        Format::JsonLines => write!(out, "{}", report::to_json_lines(&results))?,
+    }
+
+    let diagnostics = results.values().flat_map(|fr| &fr.diagnostics);
+    let (errors, warnings) = diagnostics.fold((0, 0), |(e, w), d| match d.kind {
+        DiagnosticKind::Error => (e + 1, w),
+        DiagnosticKind::Warning => (e, w + 1),
+    });
+
+    if matches!(args.format, Format::Human | Format::Plain) && errors + warnings > 0 {
+        writeln!(out, "{errors} error(s), {warnings} warning(s)")?;
+    }
+
+    Ok(errors == 0 && (warnings == 0 || !args.deny_warnings))
+}
+
+// File, directory or glob pattern
+fn collect_input(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_dir() {
+        return collect_aidl_files(path);
+    }
+    if path.is_file() {
+        return Ok(Vec::from([path.to_owned()]));
+    }
+
+    let mut files = Vec::new();
+    for entry in glob::glob(input).with_context(|| format!("invalid pattern `{input}`"))? {
+        let path = entry?;
+        if path.is_dir() {
+            files.extend(collect_aidl_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    if files.is_empty() {
+        bail!("no file matching `{input}`");
+    }
+
+    Ok(files)
+}
+
+// All the `.aidl` files of the directory (recursively)
+fn collect_aidl_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("cannot read `{}`", dir.display()))?;
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(collect_aidl_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "aidl") {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() -> Result<()> {
+        let cmd = parse_args(&args(&[
+            "-I",
+            "root1",
+            "-Iroot2",
+            "--format=sarif",
+            "--deny-warnings",
+            "src/**/*.aidl",
+        ]))?;
+        let expected = Args {
+            import_roots: Vec::from([PathBuf::from("root1"), PathBuf::from("root2")]),
+            inputs: Vec::from(["src/**/*.aidl".to_owned()]),
+            format: Format::Sarif,
+            deny_warnings: true,
+        };
+        assert!(matches!(cmd, Command::Lint(a) if a == expected));
+
+        assert!(matches!(parse_args(&args(&["-h"]))?, Command::Help));
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["-f", "xml", "a.aidl"])).is_err());
+        assert!(parse_args(&args(&["--unknown", "a.aidl"])).is_err());
+
+        Ok(())
+    }
+}
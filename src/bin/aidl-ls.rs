@@ -0,0 +1,15 @@
+//! AIDL language server (LSP over stdio)
+//!
+//! Usage: `aidl-ls`
+
+use lsp_server::Connection;
+
+fn main() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    aidl_parser::lsp::run(&connection)?;
+
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
@@ -0,0 +1,328 @@
+//! Backends which generate source code from a validated AST
+//!
+//! The AST must come from [`crate::Parser::validate`] without errors, so that the
+//! types are resolved and the values of the constant expressions are evaluated.
+//!
+//! Each backend has the same entry point, `generate(ast, results)`: the validation
+//! results give access to the items of the other files (e.g. the enums referenced by
+//! the default values of fields).
+
+pub mod java;
+pub mod ndk;
+pub mod rust;
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+
+use crate::ast;
+use crate::parser::ParseFileResult;
+
+/// Reason why the code of an item cannot be generated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodegenError {
+    /// The type is not resolved (e.g. the AST has not been validated)
+    UnresolvedType(String),
+    /// The value of a const, field or enum element has not been evaluated
+    UnevaluatedValue(String),
+    /// The construct is not supported by the backend (e.g. `Map` in Rust)
+    Unsupported(String),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::UnresolvedType(name) => write!(f, "Unresolved type `{name}`"),
+            CodegenError::UnevaluatedValue(name) => {
+                write!(f, "The value of `{name}` has not been evaluated")
+            }
+            CodegenError::Unsupported(what) => write!(f, "{what} is not supported"),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+/// Indentation-aware writer of generated code
+pub(crate) struct CodeWriter {
+    output: String,
+    indent: &'static str,
+    level: usize,
+}
+
+impl CodeWriter {
+    pub(crate) fn new(indent: &'static str) -> Self {
+        CodeWriter {
+            output: String::new(),
+            indent,
+            level: 0,
+        }
+    }
+
+    /// Write a line at the current indentation level
+    pub(crate) fn line<S: AsRef<str>>(&mut self, line: S) {
+        let line = line.as_ref();
+        if !line.is_empty() {
+            for _ in 0..self.level {
+                self.output.push_str(self.indent);
+            }
+            self.output.push_str(line);
+        }
+        self.output.push('\n');
+    }
+
+    /// Write an empty line, unless at the start of the output, of a block or after a label
+    pub(crate) fn blank_line(&mut self) {
+        if !self.output.is_empty()
+            && !self.output.ends_with("\n\n")
+            && !self.output.ends_with("{\n")
+            && !self.output.ends_with(":\n")
+        {
+            self.output.push('\n');
+        }
+    }
+
+    /// Write a line which opens a block and increase the indentation
+    pub(crate) fn open<S: AsRef<str>>(&mut self, line: S) {
+        self.line(line);
+        self.level += 1;
+    }
+
+    /// Write a line which closes a block and opens the next one (e.g. `} else {`)
+    pub(crate) fn reopen<S: AsRef<str>>(&mut self, line: S) {
+        self.level = self.level.saturating_sub(1);
+        self.open(line);
+    }
+
+    /// Write a line one level less indented than the current one (e.g. a C++ label or
+    /// access specifier)
+    pub(crate) fn label<S: AsRef<str>>(&mut self, line: S) {
+        self.level = self.level.saturating_sub(1);
+        self.line(line);
+        self.level += 1;
+    }
+
+    /// Decrease the indentation and write a line which closes a block
+    pub(crate) fn close<S: AsRef<str>>(&mut self, line: S) {
+        self.level = self.level.saturating_sub(1);
+        self.line(line);
+    }
+
+    pub(crate) fn into_string(self) -> String {
+        self.output
+    }
+}
+
+/// Split the key of an item into its package and item names (e.g. `["pkg"]` and
+/// `["Outer", "Inner"]` for `pkg.Outer.Inner`)
+///
+/// The package of the keys of other files is not known, so the item names are
+/// assumed to start with an uppercase letter (as recommended by the AIDL style guide).
+pub(crate) fn split_key<'a>(key: &'a str, ast: &ast::Aidl) -> (Vec<&'a str>, Vec<&'a str>) {
+    let parts: Vec<&str> = key.split('.').collect();
+    let package_len = match key.strip_prefix(&ast.package.name) {
+        Some(rest) if rest.starts_with('.') => ast.package.name.split('.').count(),
+        _ => parts
+            .iter()
+            .position(|p| p.starts_with(|c: char| c.is_ascii_uppercase()))
+            .unwrap_or(parts.len() - 1),
+    };
+
+    let (package, items) = parts.split_at(package_len);
+    (package.to_vec(), items.to_vec())
+}
+
+/// Transaction code (offset from the first call transaction) of each method, given
+/// explicitly (e.g. `void foo() = 3;`) or implicitly by the position of the method
+/// (see `ast::Method::effective_transact_code`)
+pub(crate) fn get_transaction_codes(interface: &ast::Interface) -> Vec<(&ast::Method, u32)> {
+    interface
+        .elements
+        .iter()
+        .filter_map(|el| el.as_method())
+        .enumerate()
+        .map(|(i, m)| {
+            let code = m.effective_transact_code.or(m.transact_code);
+            (m, code.unwrap_or(i as u32))
+        })
+        .collect()
+}
+
+pub(crate) fn has_annotation(annotations: &[ast::Annotation], name: &str) -> bool {
+    annotations.iter().any(|a| a.name == name)
+}
+
+/// Items of the project (from the validation results and from the generated AST) by key
+pub(crate) fn get_items<'a, ID>(
+    ast: &'a ast::Aidl,
+    results: &'a HashMap<ID, ParseFileResult<ID>>,
+) -> HashMap<ast::ItemKey, &'a ast::Item>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    results
+        .values()
+        .filter_map(|fr| fr.ast.as_ref())
+        .chain(std::iter::once(ast))
+        .flat_map(|ast| ast.get_all_items())
+        .collect()
+}
+
+/// Key of the enum and name of the element given as default value of an enum field
+/// (e.g. `pkg.Color` and `RED` for `Color c = Color.RED;`)
+///
+/// The values of enum fields are not evaluated: the element is only checked against
+/// the items of the project.
+pub(crate) fn get_enum_element<'a>(
+    field: &'a ast::Field,
+    items: &HashMap<ast::ItemKey, &ast::Item>,
+) -> Result<(&'a str, &'a str), CodegenError> {
+    let value = field.value.as_ref().map(|v| &v.kind);
+    if let (
+        ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Enum),
+        Some(ast::ConstExprKind::Reference(reference)),
+    ) = (&field.field_type.kind, value)
+    {
+        let element = reference.rsplit('.').next().unwrap_or(reference);
+        let enum_ = items.get(key).and_then(|item| item.as_enum());
+        if enum_.is_some_and(|e| e.elements.iter().any(|el| el.name == element)) {
+            return Ok((key, element));
+        }
+    }
+
+    Err(CodegenError::UnevaluatedValue(field.name.clone()))
+}
+
+pub(crate) fn get_evaluated_value<'a>(
+    value: &'a Option<ast::ConstValue>,
+    name: &str,
+) -> Result<&'a ast::ConstValue, CodegenError> {
+    value
+        .as_ref()
+        .ok_or_else(|| CodegenError::UnevaluatedValue(name.to_owned()))
+}
+
+/// Name of the interface without the `I` prefix (e.g. `Foo` for `IFoo`), used for
+/// the native (`BnFoo`) and proxy (`BpFoo`) objects
+pub(crate) fn get_class_name(name: &str) -> &str {
+    match name.strip_prefix('I') {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_uppercase()) => rest,
+        _ => name,
+    }
+}
+
+/// Double-quoted string literal (same escaping for Java and C++)
+pub(crate) fn to_string_literal(value: &str) -> String {
+    format!("\"{}\"", escape(value).replace('"', "\\\""))
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+/// Validate the given files (which must not have any diagnostic) and generate the code
+/// of one of them with the given backend
+#[cfg(test)]
+pub(crate) fn generate_file<T, F>(
+    contents: &[(&'static str, &str)],
+    id: &'static str,
+    generate: F,
+) -> Result<T, CodegenError>
+where
+    F: Fn(
+        &ast::Aidl,
+        &HashMap<&'static str, ParseFileResult<&'static str>>,
+    ) -> Result<T, CodegenError>,
+{
+    let mut parser = crate::Parser::new();
+    for (id, content) in contents {
+        parser.add_content(*id, content);
+    }
+
+    let results = parser.validate();
+    let result = &results[id];
+    assert_eq!(result.diagnostics, []);
+    generate(result.ast.as_ref().expect("ast"), &results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn get_ast(content: &str) -> ast::Aidl {
+        let mut parser = Parser::new();
+        parser.add_content("id", content);
+        let results = parser.validate();
+        results["id"].ast.clone().expect("ast")
+    }
+
+    #[test]
+    fn test_split_key() {
+        let ast = get_ast("package com.Example; parcelable Foo {}");
+
+        assert_eq!(
+            split_key("com.Example.Foo.Inner", &ast),
+            (vec!["com", "Example"], vec!["Foo", "Inner"])
+        );
+        assert_eq!(
+            split_key("other.pkg.Bar.Inner", &ast),
+            (vec!["other", "pkg"], vec!["Bar", "Inner"])
+        );
+        assert_eq!(split_key("other.bar", &ast), (vec!["other"], vec!["bar"]));
+    }
+
+    #[test]
+    fn test_get_transaction_codes() {
+        let ast =
+            get_ast("package p; interface IFoo { void a() = 2; const int C = 1; void b() = 5; }");
+        let interface = ast.item.as_interface().expect("interface");
+
+        let codes: Vec<(&str, u32)> = get_transaction_codes(interface)
+            .into_iter()
+            .map(|(m, code)| (m.name.as_str(), code))
+            .collect();
+        assert_eq!(codes, [("a", 2), ("b", 5)]);
+    }
+
+    #[test]
+    fn test_get_enum_element() {
+        let mut parser = Parser::new();
+        parser.add_content("Color", "package p; enum Color { RED, GREEN }");
+        parser.add_content(
+            "Foo",
+            "package p; import p.Color; parcelable Foo { Color c1 = Color.GREEN; Color c2 = Color.BLUE; int i = 1; }",
+        );
+        let results = parser.validate();
+        let ast = results["Foo"].ast.as_ref().expect("ast");
+        let items = get_items(ast, &results);
+        let fields: Vec<&ast::Field> = match &ast.item {
+            ast::Item::Parcelable(p) => p
+                .elements
+                .iter()
+                .filter_map(|el| match el {
+                    ast::ParcelableElement::Field(f) => Some(f),
+                    ast::ParcelableElement::Const(_) => None,
+                })
+                .collect(),
+            _ => panic!("parcelable expected"),
+        };
+
+        assert_eq!(
+            get_enum_element(fields[0], &items),
+            Ok(("p.Color", "GREEN"))
+        );
+        assert_eq!(
+            get_enum_element(fields[1], &items),
+            Err(CodegenError::UnevaluatedValue(String::from("c2")))
+        );
+        assert_eq!(
+            get_enum_element(fields[2], &items),
+            Err(CodegenError::UnevaluatedValue(String::from("i")))
+        );
+    }
+}
@@ -0,0 +1,1047 @@
+//! Java backend, generating the classes of the Android framework (`IFoo.Stub`,
+//! `IFoo.Stub.Proxy`, `android.os.Parcelable`, ...)
+//!
+//! Like for the Java AIDL compiler, enums are generated as annotation types whose
+//! elements are constants of the backing type, and enum values are passed as this
+//! backing type. Unions are not supported.
+//!
+//! Example:
+//! ```
+//! use aidl_parser::{codegen, Parser};
+//!
+//! let mut parser = Parser::new();
+//! parser.add_content("id", "package p; interface IFoo { int get(); }");
+//! let results = parser.validate();
+//!
+//! let ast = results["id"].ast.as_ref().expect("ast");
+//! let code = codegen::java::generate(ast, &results).expect("code");
+//! assert!(code.contains("public int get() throws android.os.RemoteException;"));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use super::{escape, get_enum_element, get_evaluated_value, get_items, get_transaction_codes};
+use super::{to_string_literal, CodeWriter, CodegenError};
+use crate::ast;
+use crate::parser::ParseFileResult;
+
+/// Generate the Java code of the item of the given (validated) AST, including its
+/// nested items
+///
+/// The results are used to find the backing type of the enums defined in other files.
+pub fn generate<ID>(
+    ast: &ast::Aidl,
+    results: &HashMap<ID, ParseFileResult<ID>>,
+) -> Result<String, CodegenError>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    let mut generator = Generator {
+        items: get_items(ast, results),
+        w: CodeWriter::new("    "),
+    };
+
+    generator.w.line(format!(
+        "// Generated from `{}`, do not edit",
+        ast.get_key()
+    ));
+    generator.w.line(format!("package {};", ast.package.name));
+    generator.w.blank_line();
+    generator.write_item(&ast.item, &ast.get_key(), false)?;
+
+    Ok(generator.w.into_string())
+}
+
+struct Generator<'a> {
+    items: HashMap<ast::ItemKey, &'a ast::Item>,
+    w: CodeWriter,
+}
+
+impl<'a> Generator<'a> {
+    fn write_item(
+        &mut self,
+        item: &ast::Item,
+        key: &str,
+        nested: bool,
+    ) -> Result<(), CodegenError> {
+        let modifiers = if nested { "public static" } else { "public" };
+
+        match item {
+            ast::Item::Interface(i) => self.write_interface(i, key, modifiers)?,
+            ast::Item::Parcelable(p) => self.write_parcelable(p, modifiers)?,
+            ast::Item::Enum(e) => self.write_enum(e, modifiers)?,
+            ast::Item::Union(u) => {
+                return Err(CodegenError::Unsupported(format!("Union `{}`", u.name)))
+            }
+        }
+
+        // Close the declaration of the item after its nested items
+        for nested in item.get_nested_items() {
+            self.w.blank_line();
+            self.write_item(nested, &format!("{}.{}", key, nested.get_name()), true)?;
+        }
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn write_interface(
+        &mut self,
+        interface: &ast::Interface,
+        key: &str,
+        modifiers: &str,
+    ) -> Result<(), CodegenError> {
+        let name = &interface.name;
+        let methods = get_transaction_codes(interface);
+
+        self.write_doc(&interface.doc);
+        self.w.open(format!(
+            "{modifiers} interface {name} extends android.os.IInterface {{"
+        ));
+        self.w.line(format!(
+            "public static final java.lang.String DESCRIPTOR = {};",
+            to_string_literal(key)
+        ));
+
+        // Consts
+        let consts: Vec<&ast::Const> = interface
+            .elements
+            .iter()
+            .filter_map(|el| el.as_const())
+            .collect();
+        self.write_consts(&consts)?;
+
+        // Methods
+        for (method, _) in &methods {
+            self.w.blank_line();
+            self.write_doc(&method.doc);
+            let signature = self.get_method_signature(method)?;
+            self.w.line(format!("{signature};"));
+        }
+
+        // Stub
+        self.w.blank_line();
+        self.w
+            .line("/** Local-side IPC implementation stub class. */");
+        self.w.open(format!(
+            "public static abstract class Stub extends android.os.Binder implements {name} {{"
+        ));
+        for (method, code) in &methods {
+            self.w.line(format!(
+                "static final int TRANSACTION_{} = (android.os.IBinder.FIRST_CALL_TRANSACTION + {code});",
+                method.name
+            ));
+        }
+        self.w.blank_line();
+        self.w.open("public Stub() {");
+        self.w.line("this.attachInterface(this, DESCRIPTOR);");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w
+            .line("/** Cast an IBinder object into an interface, generating a proxy if needed. */");
+        self.w.open(format!(
+            "public static {name} asInterface(android.os.IBinder obj) {{"
+        ));
+        self.w.open("if (obj == null) {");
+        self.w.line("return null;");
+        self.w.close("}");
+        self.w
+            .line("android.os.IInterface iin = obj.queryLocalInterface(DESCRIPTOR);");
+        self.w
+            .open(format!("if (iin != null && iin instanceof {name}) {{"));
+        self.w.line(format!("return ({name}) iin;"));
+        self.w.close("}");
+        self.w.line("return new Stub.Proxy(obj);");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w.line("@Override");
+        self.w.open("public android.os.IBinder asBinder() {");
+        self.w.line("return this;");
+        self.w.close("}");
+        self.w.blank_line();
+        self.write_on_transact(interface, &methods)?;
+
+        // Proxy
+        self.w.blank_line();
+        self.w
+            .open(format!("private static class Proxy implements {name} {{"));
+        self.w.line("private android.os.IBinder mRemote;");
+        self.w.blank_line();
+        self.w.open("Proxy(android.os.IBinder remote) {");
+        self.w.line("mRemote = remote;");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w.line("@Override");
+        self.w.open("public android.os.IBinder asBinder() {");
+        self.w.line("return mRemote;");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w
+            .open("public java.lang.String getInterfaceDescriptor() {");
+        self.w.line("return DESCRIPTOR;");
+        self.w.close("}");
+        for (method, _) in &methods {
+            self.w.blank_line();
+            self.write_proxy_method(interface, method)?;
+        }
+        self.w.close("}");
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn get_method_signature(&self, method: &ast::Method) -> Result<String, CodegenError> {
+        let args = method
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let arg_type = self.get_type(&arg.arg_type)?;
+                Ok(format!("{arg_type} {}", get_arg_name(arg, i)))
+            })
+            .collect::<Result<Vec<_>, CodegenError>>()?;
+
+        Ok(format!(
+            "public {} {}({}) throws android.os.RemoteException",
+            self.get_type(&method.return_type)?,
+            method.name,
+            args.join(", ")
+        ))
+    }
+
+    fn write_on_transact(
+        &mut self,
+        interface: &ast::Interface,
+        methods: &[(&ast::Method, u32)],
+    ) -> Result<(), CodegenError> {
+        self.w.line("@Override");
+        self.w.open("public boolean onTransact(int code, android.os.Parcel data, android.os.Parcel reply, int flags) throws android.os.RemoteException {");
+        self.w.open("if (code == INTERFACE_TRANSACTION) {");
+        self.w.line("reply.writeString(DESCRIPTOR);");
+        self.w.line("return true;");
+        self.w.close("}");
+        self.w.open("if (code >= android.os.IBinder.FIRST_CALL_TRANSACTION && code <= android.os.IBinder.LAST_CALL_TRANSACTION) {");
+        self.w.line("data.enforceInterface(DESCRIPTOR);");
+        self.w.close("}");
+        self.w.open("switch (code) {");
+
+        for (method, _) in methods {
+            self.w.open(format!("case TRANSACTION_{}: {{", method.name));
+
+            let mut call_args = Vec::new();
+            for (i, arg) in method.args.iter().enumerate() {
+                let arg_type = self.get_type(&arg.arg_type)?;
+                let arg_name = format!("_arg{i}");
+                let value = match arg.direction {
+                    ast::Direction::Out(_) => self.get_new_out_value(&arg.arg_type, "data")?,
+                    _ => self.get_read(&arg.arg_type, "data")?,
+                };
+                self.w.line(format!("{arg_type} {arg_name} = {value};"));
+                call_args.push(arg_name);
+            }
+
+            let call = format!("this.{}({})", method.name, call_args.join(", "));
+            let has_result = !matches!(method.return_type.kind, ast::TypeKind::Void);
+            if has_result {
+                let return_type = self.get_type(&method.return_type)?;
+                self.w.line(format!("{return_type} _result = {call};"));
+            } else {
+                self.w.line(format!("{call};"));
+            }
+
+            if !interface.oneway && !method.oneway {
+                self.w.line("reply.writeNoException();");
+                if has_result {
+                    let write = self.get_write(&method.return_type, "reply", "_result")?;
+                    self.w.line(write);
+                }
+                for (i, arg) in method.args.iter().enumerate() {
+                    if let ast::Direction::Out(_) | ast::Direction::InOut(_) = arg.direction {
+                        let write = self.get_write(&arg.arg_type, "reply", &format!("_arg{i}"))?;
+                        self.w.line(write);
+                    }
+                }
+            }
+
+            self.w.line("return true;");
+            self.w.close("}");
+        }
+
+        self.w.open("default: {");
+        self.w
+            .line("return super.onTransact(code, data, reply, flags);");
+        self.w.close("}");
+        self.w.close("}");
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn write_proxy_method(
+        &mut self,
+        interface: &ast::Interface,
+        method: &ast::Method,
+    ) -> Result<(), CodegenError> {
+        let oneway = interface.oneway || method.oneway;
+        let has_result = !matches!(method.return_type.kind, ast::TypeKind::Void);
+
+        self.w.line("@Override");
+        self.w
+            .open(format!("{} {{", self.get_method_signature(method)?));
+        self.w
+            .line("android.os.Parcel _data = android.os.Parcel.obtain();");
+        if !oneway {
+            self.w
+                .line("android.os.Parcel _reply = android.os.Parcel.obtain();");
+        }
+        if has_result {
+            self.w
+                .line(format!("{} _result;", self.get_type(&method.return_type)?));
+        }
+
+        self.w.open("try {");
+        self.w.line("_data.writeInterfaceToken(DESCRIPTOR);");
+        for (i, arg) in method.args.iter().enumerate() {
+            let arg_name = get_arg_name(arg, i);
+            match arg.direction {
+                ast::Direction::In(_) | ast::Direction::InOut(_) | ast::Direction::Unspecified => {
+                    let write = self.get_write(&arg.arg_type, "_data", &arg_name)?;
+                    self.w.line(write);
+                }
+                ast::Direction::Out(_) => {
+                    // The size of out arrays is sent, so that the callee can allocate them
+                    if let ast::TypeKind::Array = arg.arg_type.kind {
+                        self.w.line(format!("_data.writeInt({arg_name}.length);"));
+                    }
+                }
+            }
+        }
+
+        if oneway {
+            self.w.line(format!(
+                "mRemote.transact(Stub.TRANSACTION_{}, _data, null, android.os.IBinder.FLAG_ONEWAY);",
+                method.name
+            ));
+        } else {
+            self.w.line(format!(
+                "mRemote.transact(Stub.TRANSACTION_{}, _data, _reply, 0);",
+                method.name
+            ));
+            self.w.line("_reply.readException();");
+            if has_result {
+                let read = self.get_read(&method.return_type, "_reply")?;
+                self.w.line(format!("_result = {read};"));
+            }
+            for (i, arg) in method.args.iter().enumerate() {
+                if let ast::Direction::Out(_) | ast::Direction::InOut(_) = arg.direction {
+                    let read_into =
+                        self.get_read_into(&arg.arg_type, "_reply", &get_arg_name(arg, i))?;
+                    self.w.line(read_into);
+                }
+            }
+        }
+        self.w.reopen("} finally {");
+        if !oneway {
+            self.w.line("_reply.recycle();");
+        }
+        self.w.line("_data.recycle();");
+        self.w.close("}");
+
+        if has_result {
+            self.w.line("return _result;");
+        }
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn write_parcelable(
+        &mut self,
+        parcelable: &ast::Parcelable,
+        modifiers: &str,
+    ) -> Result<(), CodegenError> {
+        if !parcelable.type_parameters.is_empty() {
+            return Err(CodegenError::Unsupported(format!(
+                "Generic parcelable `{}`",
+                parcelable.name
+            )));
+        }
+
+        let name = &parcelable.name;
+        let fields: Vec<&ast::Field> = parcelable
+            .elements
+            .iter()
+            .filter_map(|el| el.as_field())
+            .collect();
+
+        self.write_doc(&parcelable.doc);
+        self.w.open(format!(
+            "{modifiers} class {name} implements android.os.Parcelable {{"
+        ));
+
+        // Fields
+        for field in &fields {
+            self.write_doc(&field.doc);
+            let field_type = self.get_type(&field.field_type)?;
+            match self.get_default_value(field, &parcelable.annotations)? {
+                Some(value) if is_holder(&field.field_type) => {
+                    self.w.line(format!(
+                        "public final {field_type} {} = {value};",
+                        field.name
+                    ));
+                }
+                Some(value) => {
+                    self.w
+                        .line(format!("public {field_type} {} = {value};", field.name));
+                }
+                None => self.w.line(format!("public {field_type} {};", field.name)),
+            }
+        }
+
+        // Consts
+        let consts: Vec<&ast::Const> = parcelable
+            .elements
+            .iter()
+            .filter_map(|el| match el {
+                ast::ParcelableElement::Const(c) => Some(c),
+                ast::ParcelableElement::Field(_) => None,
+            })
+            .collect();
+        self.write_consts(&consts)?;
+
+        // Creator
+        self.w.blank_line();
+        self.w.open(format!("public static final android.os.Parcelable.Creator<{name}> CREATOR = new android.os.Parcelable.Creator<{name}>() {{"));
+        self.w.line("@Override");
+        self.w.open(format!(
+            "public {name} createFromParcel(android.os.Parcel _aidl_source) {{"
+        ));
+        self.w.line(format!("{name} _aidl_out = new {name}();"));
+        self.w.line("_aidl_out.readFromParcel(_aidl_source);");
+        self.w.line("return _aidl_out;");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w.line("@Override");
+        self.w
+            .open(format!("public {name}[] newArray(int _aidl_size) {{"));
+        self.w.line(format!("return new {name}[_aidl_size];"));
+        self.w.close("}");
+        self.w.close("};");
+
+        // Serialization (prefixed by the size, so that fields can be added later)
+        self.w.blank_line();
+        self.w.line("@Override");
+        self.w.open(
+            "public final void writeToParcel(android.os.Parcel _aidl_parcel, int _aidl_flag) {",
+        );
+        self.w
+            .line("int _aidl_start_pos = _aidl_parcel.dataPosition();");
+        self.w.line("_aidl_parcel.writeInt(0);");
+        for field in &fields {
+            let write = self.get_write(&field.field_type, "_aidl_parcel", &field.name)?;
+            self.w.line(write);
+        }
+        self.w
+            .line("int _aidl_end_pos = _aidl_parcel.dataPosition();");
+        self.w
+            .line("_aidl_parcel.setDataPosition(_aidl_start_pos);");
+        self.w
+            .line("_aidl_parcel.writeInt(_aidl_end_pos - _aidl_start_pos);");
+        self.w.line("_aidl_parcel.setDataPosition(_aidl_end_pos);");
+        self.w.close("}");
+
+        self.w.blank_line();
+        self.w
+            .open("public final void readFromParcel(android.os.Parcel _aidl_parcel) {");
+        self.w
+            .line("int _aidl_start_pos = _aidl_parcel.dataPosition();");
+        self.w
+            .line("int _aidl_parcelable_size = _aidl_parcel.readInt();");
+        self.w.open("try {");
+        self.w.open("if (_aidl_parcelable_size < 4) {");
+        self.w
+            .line("throw new android.os.BadParcelableException(\"Parcelable too small\");");
+        self.w.close("}");
+        for field in &fields {
+            self.w.open(
+                "if (_aidl_parcel.dataPosition() - _aidl_start_pos >= _aidl_parcelable_size) {",
+            );
+            self.w.line("return;");
+            self.w.close("}");
+            if is_holder(&field.field_type) {
+                let read_into =
+                    self.get_read_into(&field.field_type, "_aidl_parcel", &field.name)?;
+                self.w.line(read_into);
+            } else {
+                let read = self.get_read(&field.field_type, "_aidl_parcel")?;
+                self.w.line(format!("{} = {read};", field.name));
+            }
+        }
+        self.w.reopen("} finally {");
+        self.w
+            .open("if (_aidl_start_pos > (Integer.MAX_VALUE - _aidl_parcelable_size)) {");
+        self.w.line(
+            "throw new android.os.BadParcelableException(\"Overflow in the size of parcelable\");",
+        );
+        self.w.close("}");
+        self.w
+            .line("_aidl_parcel.setDataPosition(_aidl_start_pos + _aidl_parcelable_size);");
+        self.w.close("}");
+        self.w.close("}");
+
+        self.w.blank_line();
+        self.w.line("@Override");
+        self.w.open("public int describeContents() {");
+        self.w.line("return 0;");
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn write_enum(&mut self, enum_: &ast::Enum, modifiers: &str) -> Result<(), CodegenError> {
+        let backing_type = enum_.get_backing_type();
+
+        self.write_doc(&enum_.doc);
+        self.w
+            .open(format!("{modifiers} @interface {} {{", enum_.name));
+        for element in &enum_.elements {
+            let value = get_evaluated_value(&element.evaluated_value, &element.name)?;
+            self.write_doc(&element.doc);
+            self.w.line(format!(
+                "public static final {backing_type} {} = {};",
+                element.name,
+                get_literal(value)
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn write_consts(&mut self, consts: &[&ast::Const]) -> Result<(), CodegenError> {
+        if consts.is_empty() {
+            return Ok(());
+        }
+
+        self.w.blank_line();
+        for c in consts {
+            let value = get_evaluated_value(&c.evaluated_value, &c.name)?;
+            let const_type = self.get_type(&c.const_type)?;
+            self.write_doc(&c.doc);
+            self.w.line(format!(
+                "public static final {const_type} {} = {};",
+                c.name,
+                get_literal(value)
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn write_doc(&mut self, doc: &Option<String>) {
+        if let Some(doc) = doc {
+            let lines: Vec<&str> = doc.lines().collect();
+            if let [line] = lines.as_slice() {
+                self.w.line(format!("/** {line} */"));
+                return;
+            }
+
+            self.w.line("/**");
+            for line in lines {
+                if line.is_empty() {
+                    self.w.line(" *");
+                } else {
+                    self.w.line(format!(" * {line}"));
+                }
+            }
+            self.w.line(" */");
+        }
+    }
+
+    fn get_type(&self, type_: &ast::Type) -> Result<String, CodegenError> {
+        Ok(match &type_.kind {
+            ast::TypeKind::Primitive | ast::TypeKind::Void => type_.name.clone(),
+            ast::TypeKind::String => String::from("java.lang.String"),
+            ast::TypeKind::CharSequence => String::from("java.lang.CharSequence"),
+            ast::TypeKind::Array => format!("{}[]", self.get_type(get_element_type(type_)?)?),
+            ast::TypeKind::List => {
+                format!(
+                    "java.util.List<{}>",
+                    self.get_type(get_element_type(type_)?)?
+                )
+            }
+            ast::TypeKind::Map => {
+                return Err(CodegenError::Unsupported(format!("Type `{}`", type_.name)))
+            }
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::FileDescriptor) => {
+                String::from("java.io.FileDescriptor")
+            }
+            ast::TypeKind::AndroidType(android_type) => {
+                android_type.get_qualified_name().to_owned()
+            }
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Enum) => {
+                self.get_backing_type(key, type_)?.to_owned()
+            }
+            ast::TypeKind::Unresolved
+            | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => {
+                return Err(CodegenError::UnresolvedType(type_.name.clone()))
+            }
+            ast::TypeKind::ResolvedItem(key, _) => key.clone(),
+            ast::TypeKind::TypeParameter => type_.name.clone(),
+        })
+    }
+
+    fn get_backing_type(&self, key: &str, type_: &ast::Type) -> Result<&'a str, CodegenError> {
+        self.items
+            .get(key)
+            .and_then(|item| item.as_enum())
+            .map(ast::Enum::get_backing_type)
+            .ok_or_else(|| CodegenError::UnresolvedType(type_.name.clone()))
+    }
+
+    /// Statement writing the value into the parcel
+    fn get_write(
+        &self,
+        type_: &ast::Type,
+        parcel: &str,
+        value: &str,
+    ) -> Result<String, CodegenError> {
+        Ok(match &type_.kind {
+            // There is no `Parcel.writeChar()`
+            ast::TypeKind::Primitive if type_.name == "char" => {
+                format!("{parcel}.writeInt((int) {value});")
+            }
+            ast::TypeKind::Primitive => {
+                format!(
+                    "{parcel}.write{}({value});",
+                    get_primitive_method(&type_.name)?
+                )
+            }
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Enum) => format!(
+                "{parcel}.write{}({value});",
+                get_primitive_method(self.get_backing_type(key, type_)?)?
+            ),
+            ast::TypeKind::String => format!("{parcel}.writeString({value});"),
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::IBinder) => {
+                format!("{parcel}.writeStrongBinder({value});")
+            }
+            ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Interface) => {
+                format!("{parcel}.writeStrongInterface({value});")
+            }
+            ast::TypeKind::Array => {
+                let element_type = get_element_type(type_)?;
+                match self.get_array_method(element_type)? {
+                    Some(method) => format!("{parcel}.write{method}Array({value});"),
+                    None if is_interface(element_type) => {
+                        format!("{parcel}.writeInterfaceArray({value});")
+                    }
+                    None => format!("{parcel}.writeTypedArray({value}, 0);"),
+                }
+            }
+            ast::TypeKind::List => {
+                let element_type = get_element_type(type_)?;
+                match self.get_list_method(element_type)? {
+                    Some(method) => format!("{parcel}.write{method}List({value});"),
+                    None if is_interface(element_type) => {
+                        format!("{parcel}.writeInterfaceList({value});")
+                    }
+                    None => format!("{parcel}.writeTypedList({value}, 0);"),
+                }
+            }
+            _ => {
+                self.get_creator(type_)?;
+                format!("{parcel}.writeTypedObject({value}, 0);")
+            }
+        })
+    }
+
+    /// Expression reading a new value from the parcel
+    fn get_read(&self, type_: &ast::Type, parcel: &str) -> Result<String, CodegenError> {
+        Ok(match &type_.kind {
+            ast::TypeKind::Primitive if type_.name == "char" => {
+                format!("(char) {parcel}.readInt()")
+            }
+            ast::TypeKind::Primitive => {
+                format!("{parcel}.read{}()", get_primitive_method(&type_.name)?)
+            }
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Enum) => format!(
+                "{parcel}.read{}()",
+                get_primitive_method(self.get_backing_type(key, type_)?)?
+            ),
+            ast::TypeKind::String => format!("{parcel}.readString()"),
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::IBinder) => {
+                format!("{parcel}.readStrongBinder()")
+            }
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Interface) => {
+                format!("{key}.Stub.asInterface({parcel}.readStrongBinder())")
+            }
+            ast::TypeKind::Array => {
+                let element_type = get_element_type(type_)?;
+                match self.get_array_method(element_type)? {
+                    Some(method) => format!("{parcel}.create{method}Array()"),
+                    None => match &element_type.kind {
+                        ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Interface) => {
+                            format!(
+                                "{parcel}.createInterfaceArray({key}[]::new, {key}.Stub::asInterface)"
+                            )
+                        }
+                        _ => format!(
+                            "{parcel}.createTypedArray({})",
+                            self.get_creator(element_type)?
+                        ),
+                    },
+                }
+            }
+            ast::TypeKind::List => {
+                let element_type = get_element_type(type_)?;
+                match self.get_list_method(element_type)? {
+                    Some("String") => format!("{parcel}.createStringArrayList()"),
+                    Some(method) => format!("{parcel}.create{method}ArrayList()"),
+                    None => match &element_type.kind {
+                        ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Interface) => {
+                            format!("{parcel}.createInterfaceArrayList({key}.Stub::asInterface)")
+                        }
+                        _ => format!(
+                            "{parcel}.createTypedArrayList({})",
+                            self.get_creator(element_type)?
+                        ),
+                    },
+                }
+            }
+            _ => format!("{parcel}.readTypedObject({})", self.get_creator(type_)?),
+        })
+    }
+
+    /// Statement reading the parcel into an existing value (out and inout arguments)
+    fn get_read_into(
+        &self,
+        type_: &ast::Type,
+        parcel: &str,
+        value: &str,
+    ) -> Result<String, CodegenError> {
+        Ok(match &type_.kind {
+            ast::TypeKind::Array => {
+                let element_type = get_element_type(type_)?;
+                match self.get_array_method(element_type)? {
+                    Some(method) => format!("{parcel}.read{method}Array({value});"),
+                    None if is_interface(element_type) => {
+                        return Err(CodegenError::Unsupported(format!(
+                            "Out array of interfaces `{value}`"
+                        )))
+                    }
+                    None => format!(
+                        "{parcel}.readTypedArray({value}, {});",
+                        self.get_creator(element_type)?
+                    ),
+                }
+            }
+            ast::TypeKind::List => {
+                let element_type = get_element_type(type_)?;
+                match self.get_list_method(element_type)? {
+                    Some(method) => format!("{parcel}.read{method}List({value});"),
+                    None if is_interface(element_type) => {
+                        return Err(CodegenError::Unsupported(format!(
+                            "Out list of interfaces `{value}`"
+                        )))
+                    }
+                    None => format!(
+                        "{parcel}.readTypedList({value}, {});",
+                        self.get_creator(element_type)?
+                    ),
+                }
+            }
+            ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Parcelable)
+            | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::ForwardDeclaredParcelable)
+            | ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder) => {
+                format!("if ({parcel}.readInt() != 0) {{ {value}.readFromParcel({parcel}); }}")
+            }
+            _ => {
+                return Err(CodegenError::Unsupported(format!(
+                    "Out value of type `{}`",
+                    type_.name
+                )))
+            }
+        })
+    }
+
+    /// Expression creating the value of an out argument (arrays are allocated with the
+    /// size given by the caller)
+    fn get_new_out_value(&self, type_: &ast::Type, parcel: &str) -> Result<String, CodegenError> {
+        Ok(match &type_.kind {
+            ast::TypeKind::Array => {
+                let element_type = self.get_type(get_element_type(type_)?)?;
+                format!("new {element_type}[{parcel}.readInt()]")
+            }
+            ast::TypeKind::List => {
+                let element_type = self.get_type(get_element_type(type_)?)?;
+                format!("new java.util.ArrayList<{element_type}>()")
+            }
+            _ => format!("new {}()", self.get_type(type_)?),
+        })
+    }
+
+    /// Name of the `Parcel` methods for arrays of the given element type (e.g. `Int`
+    /// for `writeIntArray()`), if any
+    fn get_array_method(&self, element_type: &ast::Type) -> Result<Option<&str>, CodegenError> {
+        Ok(match &element_type.kind {
+            ast::TypeKind::Primitive => Some(get_primitive_method(&element_type.name)?),
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Enum) => Some(
+                get_primitive_method(self.get_backing_type(key, element_type)?)?,
+            ),
+            ast::TypeKind::String => Some("String"),
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::IBinder) => Some("Binder"),
+            _ => None,
+        })
+    }
+
+    /// Name of the `Parcel` methods for lists of the given element type, if any
+    fn get_list_method(&self, element_type: &ast::Type) -> Result<Option<&str>, CodegenError> {
+        Ok(match &element_type.kind {
+            ast::TypeKind::String => Some("String"),
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::IBinder) => Some("Binder"),
+            ast::TypeKind::Primitive
+            | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Enum) => {
+                return Err(CodegenError::Unsupported(format!(
+                    "List of `{}`",
+                    element_type.name
+                )))
+            }
+            _ => None,
+        })
+    }
+
+    /// `CREATOR` of a parcelable type
+    fn get_creator(&self, type_: &ast::Type) -> Result<String, CodegenError> {
+        match &type_.kind {
+            ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Parcelable)
+            | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::ForwardDeclaredParcelable)
+            | ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelFileDescriptor)
+            | ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder) => {
+                Ok(format!("{}.CREATOR", self.get_type(type_)?))
+            }
+            ast::TypeKind::Unresolved
+            | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => {
+                Err(CodegenError::UnresolvedType(type_.name.clone()))
+            }
+            _ => Err(CodegenError::Unsupported(format!("Type `{}`", type_.name))),
+        }
+    }
+
+    fn get_default_value(
+        &self,
+        field: &ast::Field,
+        parent_annotations: &[ast::Annotation],
+    ) -> Result<Option<String>, CodegenError> {
+        if let Some(value) = &field.evaluated_value {
+            return Ok(Some(get_literal(value)));
+        }
+
+        if field.value.is_some() {
+            let (key, element) = get_enum_element(field, &self.items)?;
+            return Ok(Some(format!("{key}.{element}")));
+        }
+
+        if is_holder(&field.field_type) {
+            let stability = if super::has_annotation(parent_annotations, "@VintfStability") {
+                "PARCELABLE_STABILITY_VINTF"
+            } else {
+                "PARCELABLE_STABILITY_LOCAL"
+            };
+            return Ok(Some(format!(
+                "new android.os.ParcelableHolder(android.os.Parcelable.{stability})"
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Suffix of the `Parcel` methods for primitive types (e.g. `Int` for `writeInt()`)
+fn get_primitive_method(name: &str) -> Result<&'static str, CodegenError> {
+    match name {
+        "boolean" => Ok("Boolean"),
+        "byte" => Ok("Byte"),
+        "char" => Ok("Char"),
+        "int" => Ok("Int"),
+        "long" => Ok("Long"),
+        "float" => Ok("Float"),
+        "double" => Ok("Double"),
+        _ => Err(CodegenError::Unsupported(format!("Type `{name}`"))),
+    }
+}
+
+fn get_element_type(type_: &ast::Type) -> Result<&ast::Type, CodegenError> {
+    type_
+        .generic_types
+        .first()
+        .ok_or_else(|| CodegenError::Unsupported(format!("Non-generic `{}`", type_.name)))
+}
+
+fn get_literal(value: &ast::ConstValue) -> String {
+    match value {
+        ast::ConstValue::Bool(v) => v.to_string(),
+        ast::ConstValue::Byte(v) => v.to_string(),
+        ast::ConstValue::Char(v) => match v {
+            '\'' => String::from("'\\''"),
+            _ => format!("'{}'", escape(&v.to_string())),
+        },
+        ast::ConstValue::Int(v) => v.to_string(),
+        ast::ConstValue::Long(v) => format!("{v}L"),
+        ast::ConstValue::Float(v) if v.is_nan() => String::from("Float.NaN"),
+        ast::ConstValue::Float(v) if v.is_infinite() && *v > 0.0 => {
+            String::from("Float.POSITIVE_INFINITY")
+        }
+        ast::ConstValue::Float(v) if v.is_infinite() => String::from("Float.NEGATIVE_INFINITY"),
+        ast::ConstValue::Float(v) => format!("{v:?}f"),
+        ast::ConstValue::Double(v) if v.is_nan() => String::from("Double.NaN"),
+        ast::ConstValue::Double(v) if v.is_infinite() && *v > 0.0 => {
+            String::from("Double.POSITIVE_INFINITY")
+        }
+        ast::ConstValue::Double(v) if v.is_infinite() => String::from("Double.NEGATIVE_INFINITY"),
+        ast::ConstValue::Double(v) => format!("{v:?}"),
+        ast::ConstValue::String(v) => to_string_literal(v),
+        ast::ConstValue::Array(values) => {
+            let literals: Vec<String> = values.iter().map(get_literal).collect();
+            format!("{{{}}}", literals.join(", "))
+        }
+    }
+}
+
+fn is_interface(type_: &ast::Type) -> bool {
+    matches!(
+        type_.kind,
+        ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Interface)
+    )
+}
+
+fn is_holder(type_: &ast::Type) -> bool {
+    matches!(
+        type_.kind,
+        ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder)
+    )
+}
+
+fn get_arg_name(arg: &ast::Arg, index: usize) -> String {
+    match &arg.name {
+        Some(name) => name.clone(),
+        None => format!("_arg{index}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::generate_file;
+
+    #[test]
+    fn test_interface() {
+        let interface = r#"package com.example;
+            import com.example.Bar;
+            import com.example.Color;
+            import com.example.IListener;
+
+            /** Example service */
+            interface IFoo {
+                /** Version */
+                const int VERSION = 1 + 2;
+                const String NAME = "foo";
+
+                String getName() = 1;
+                void setValues(in int[] values, out int[] previous) = 2;
+                Bar getBar(in Color color, in @nullable String filter) = 4;
+                void update(inout Bar bar, in List<String> tags, out List<Bar> bars) = 5;
+                oneway void register(IListener listener) = 6;
+            }
+        "#;
+        let bar = "package com.example; parcelable Bar {}";
+        let color = "package com.example; @Backing(type=\"int\") enum Color { RED }";
+        let listener = "package com.example; oneway interface IListener { void onEvent(); }";
+
+        let code = generate_file(
+            &[
+                ("IFoo", interface),
+                ("Bar", bar),
+                ("Color", color),
+                ("IListener", listener),
+            ],
+            "IFoo",
+            generate,
+        )
+        .expect("code");
+        insta::assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_parcelable() {
+        let parcelable = r#"package com.example;
+            import com.example.Color;
+            import com.example.IListener;
+
+            /**
+             * Example parcelable
+             *
+             * With details
+             */
+            parcelable Bar {
+                const int MAX = 10;
+
+                /** Name */
+                String name = "bar";
+                int count = MAX - 1;
+                long[] data = {1, -1};
+                float ratio = 0.5;
+                char c = 'x';
+                Color color = Color.GREEN;
+                Color[] colors;
+                IListener listener;
+                ParcelFileDescriptor fd;
+                ParcelableHolder extension;
+
+                parcelable Inner {
+                    boolean enabled;
+                }
+            }
+        "#;
+        let color = "package com.example; enum Color { RED, GREEN }";
+        let listener = "package com.example; interface IListener {}";
+
+        let code = generate_file(
+            &[
+                ("Bar", parcelable),
+                ("Color", color),
+                ("IListener", listener),
+            ],
+            "Bar",
+            generate,
+        )
+        .expect("code");
+        insta::assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_enum() {
+        let enum_ = r#"package com.example;
+            /** Example enum */
+            @Backing(type="long")
+            enum Status {
+                /** Success */
+                OK,
+                ERROR = -1,
+                UNKNOWN = 1 << 4,
+            }
+        "#;
+
+        let code = generate_file(&[("Status", enum_)], "Status", generate).expect("code");
+        insta::assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let content = "package p; union U { int a; }";
+        assert_eq!(
+            generate_file(&[("U", content)], "U", generate),
+            Err(CodegenError::Unsupported(String::from("Union `U`")))
+        );
+    }
+}
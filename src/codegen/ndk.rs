@@ -0,0 +1,1154 @@
+//! C++ backend, generating code for the NDK binder library (`libbinder_ndk`)
+//!
+//! Each item is generated as a header (e.g. `aidl/com/example/IFoo.h`, declaring
+//! `IFoo`, `BpFoo` and `BnFoo` in the namespace `aidl::com::example`) and a source
+//! (e.g. `com/example/IFoo.cpp`). Nested items are declared inside the class of their
+//! parent. Unions are not supported.
+//!
+//! Example:
+//! ```
+//! use aidl_parser::{codegen, Parser};
+//!
+//! let mut parser = Parser::new();
+//! parser.add_content("id", "package p; interface IFoo { @nullable String get(); }");
+//! let results = parser.validate();
+//!
+//! let ast = results["id"].ast.as_ref().expect("ast");
+//! let files = codegen::ndk::generate(ast, &results).expect("files");
+//! assert_eq!(files.header_path, "aidl/p/IFoo.h");
+//! assert!(files
+//!     .header
+//!     .contains("virtual ::ndk::ScopedAStatus get(std::optional<std::string>* _aidl_return) = 0;"));
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use super::{escape, split_key, to_string_literal, CodeWriter, CodegenError};
+use super::{get_class_name, get_evaluated_value, get_transaction_codes, has_annotation};
+use super::{get_enum_element, get_items};
+use crate::ast;
+use crate::parser::ParseFileResult;
+use crate::traverse;
+
+const STD_INCLUDES: &[&str] = &[
+    "<cstdint>",
+    "<memory>",
+    "<optional>",
+    "<string>",
+    "<vector>",
+];
+
+const NDK_INCLUDES: &[&str] = &[
+    "<android/binder_interface_utils.h>",
+    "<android/binder_parcel_utils.h>",
+    "<android/binder_parcelable_utils.h>",
+];
+
+/// Header and source generated for an item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CppFiles {
+    /// Path of the header, relative to the include directory (e.g. `aidl/p/IFoo.h`)
+    pub header_path: String,
+    pub header: String,
+    /// Path of the source, relative to the source directory (e.g. `p/IFoo.cpp`)
+    pub source_path: String,
+    pub source: String,
+}
+
+/// Generate the C++ header and source of the item of the given (validated) AST,
+/// including its nested items
+///
+/// The results are used to check the enum elements referenced by the default values
+/// of fields.
+pub fn generate<ID>(
+    ast: &ast::Aidl,
+    results: &HashMap<ID, ParseFileResult<ID>>,
+) -> Result<CppFiles, CodegenError>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    let key = ast.get_key();
+    let header_path = get_header_path(&key, ast);
+    let source_path = format!("{}.cpp", key.replace('.', "/"));
+
+    let mut generator = Generator {
+        ast,
+        items: get_items(ast, results),
+        h: CodeWriter::new("    "),
+        s: CodeWriter::new("    "),
+    };
+
+    // Header
+    let comment = format!("// Generated from `{key}`, do not edit");
+    generator.h.line(&comment);
+    generator.h.line("#pragma once");
+    generator.h.blank_line();
+    if matches!(ast.item, ast::Item::Enum(_)) {
+        generator.h.line("#include <cstdint>");
+    } else {
+        for include in STD_INCLUDES.iter().chain(NDK_INCLUDES) {
+            generator.h.line(format!("#include {include}"));
+        }
+    }
+    let mut referenced_headers = BTreeSet::new();
+    traverse::walk_types(ast, |t| {
+        if let ast::TypeKind::ResolvedItem(key, _) = &t.kind {
+            referenced_headers.insert(get_header_path(key, ast));
+        }
+    });
+    referenced_headers.remove(&header_path);
+    for path in referenced_headers {
+        generator.h.line(format!("#include <{path}>"));
+    }
+
+    // Source
+    generator.s.line(&comment);
+    generator.s.line(format!("#include <{header_path}>"));
+
+    for w in [&mut generator.h, &mut generator.s] {
+        w.blank_line();
+        for name in std::iter::once("aidl").chain(ast.package.name.split('.')) {
+            w.line(format!("namespace {name} {{"));
+        }
+        w.line("");
+    }
+
+    generator.write_item(&ast.item, &key, "")?;
+
+    for w in [&mut generator.h, &mut generator.s] {
+        w.blank_line();
+        for name in std::iter::once("aidl")
+            .chain(ast.package.name.split('.'))
+            .rev()
+        {
+            w.line(format!("}}  // namespace {name}"));
+        }
+    }
+
+    Ok(CppFiles {
+        header_path,
+        header: generator.h.into_string(),
+        source_path,
+        source: generator.s.into_string(),
+    })
+}
+
+struct Generator<'a> {
+    ast: &'a ast::Aidl,
+    items: HashMap<ast::ItemKey, &'a ast::Item>,
+    /// Writer of the header
+    h: CodeWriter,
+    /// Writer of the source
+    s: CodeWriter,
+}
+
+impl<'a> Generator<'a> {
+    /// Write the declarations of the item into the header and its definitions into the
+    /// source, qualified with the given scope (e.g. `IOuter::` for nested items)
+    fn write_item(&mut self, item: &ast::Item, key: &str, scope: &str) -> Result<(), CodegenError> {
+        match item {
+            ast::Item::Interface(i) => self.write_interface(i, key, scope),
+            ast::Item::Parcelable(p) => self.write_parcelable(p, key, scope),
+            ast::Item::Enum(e) => self.write_enum(e),
+            ast::Item::Union(u) => Err(CodegenError::Unsupported(format!("Union `{}`", u.name))),
+        }
+    }
+
+    /// Write the nested items at the start of the class of their parent
+    fn write_nested_items(
+        &mut self,
+        nested_items: &[ast::Item],
+        key: &str,
+        scope: &str,
+    ) -> Result<(), CodegenError> {
+        for nested in nested_items {
+            self.h.blank_line();
+            self.write_item(nested, &format!("{}.{}", key, nested.get_name()), scope)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_interface(
+        &mut self,
+        interface: &ast::Interface,
+        key: &str,
+        scope: &str,
+    ) -> Result<(), CodegenError> {
+        let name = &interface.name;
+        let class_name = get_class_name(name);
+        let native = format!("Bn{class_name}");
+        let proxy = format!("Bp{class_name}");
+        let mangled = key.replace('.', "_");
+        let on_transact = format!("_aidl_{mangled}_onTransact");
+        let clazz = format!("_g_aidl_{mangled}_clazz");
+        let methods = get_transaction_codes(interface);
+
+        // Interface (header)
+        self.h.blank_line();
+        self.write_doc(&interface.doc);
+        self.h
+            .open(format!("class {name} : public ::ndk::ICInterface {{"));
+        self.h.label("public:");
+        self.write_nested_items(&interface.nested_items, key, &format!("{scope}{name}::"))?;
+        self.h.blank_line();
+        self.h.line("static const char* descriptor;");
+        self.h.line(format!("{name}();"));
+        self.h.line(format!("virtual ~{name}();"));
+
+        let consts: Vec<&ast::Const> = interface
+            .elements
+            .iter()
+            .filter_map(|el| el.as_const())
+            .collect();
+        self.write_consts(&consts, &format!("{scope}{name}"))?;
+
+        self.h.blank_line();
+        for (method, code) in &methods {
+            self.h.line(format!(
+                "static constexpr uint32_t TRANSACTION_{} = FIRST_CALL_TRANSACTION + {code};",
+                method.name
+            ));
+        }
+
+        self.h.blank_line();
+        self.h.line(format!(
+            "static std::shared_ptr<{name}> fromBinder(const ::ndk::SpAIBinder& binder);"
+        ));
+        self.h.line(format!("static binder_status_t writeToParcel(AParcel* parcel, const std::shared_ptr<{name}>& instance);"));
+        self.h.line(format!("static binder_status_t readFromParcel(const AParcel* parcel, std::shared_ptr<{name}>* instance);"));
+        for (method, _) in &methods {
+            self.h.blank_line();
+            self.write_doc(&method.doc);
+            let args = self.get_args(method)?;
+            self.h.line(format!(
+                "virtual ::ndk::ScopedAStatus {}({}) = 0;",
+                method.name,
+                args.join(", ")
+            ));
+        }
+        self.h.close("};");
+
+        // Proxy (header)
+        self.h.blank_line();
+        self.h.open(format!(
+            "class {proxy} : public ::ndk::BpCInterface<{name}> {{"
+        ));
+        self.h.label("public:");
+        self.h.line(format!(
+            "explicit {proxy}(const ::ndk::SpAIBinder& binder);"
+        ));
+        self.h.line(format!("virtual ~{proxy}();"));
+        for (method, _) in &methods {
+            self.h.blank_line();
+            let args = self.get_args(method)?;
+            self.h.line(format!(
+                "::ndk::ScopedAStatus {}({}) override;",
+                method.name,
+                args.join(", ")
+            ));
+        }
+        self.h.close("};");
+
+        // Native (header)
+        self.h.blank_line();
+        self.h.open(format!(
+            "class {native} : public ::ndk::BnCInterface<{name}> {{"
+        ));
+        self.h.label("public:");
+        self.h.line(format!("{native}();"));
+        self.h.line(format!("virtual ~{native}();"));
+        self.h.blank_line();
+        self.h.label("protected:");
+        self.h.line("::ndk::SpAIBinder createBinder() override;");
+        self.h.close("};");
+
+        // Transactions (source)
+        self.s.blank_line();
+        self.s.open(format!("static binder_status_t {on_transact}(AIBinder* _aidl_binder, transaction_code_t _aidl_code, const AParcel* _aidl_in, AParcel* _aidl_out) {{"));
+        self.s.line("(void)_aidl_in;");
+        self.s.line("(void)_aidl_out;");
+        self.s
+            .line("binder_status_t _aidl_ret_status = STATUS_UNKNOWN_TRANSACTION;");
+        self.s.line(format!("std::shared_ptr<{scope}{native}> _aidl_impl = std::static_pointer_cast<{scope}{native}>(::ndk::ICInterface::asInterface(_aidl_binder));"));
+        self.s.open("switch (_aidl_code) {");
+        for (method, _) in &methods {
+            self.write_on_transact_case(interface, method, &format!("{scope}{name}"))?;
+        }
+        self.s.close("}");
+        self.s.line("return _aidl_ret_status;");
+        self.s.close("}");
+        self.s.blank_line();
+        self.s.line(format!("static AIBinder_Class* {clazz} = ::ndk::ICInterface::defineClass({scope}{name}::descriptor, {on_transact});"));
+
+        // Proxy (source)
+        self.s.blank_line();
+        self.s.line(format!(
+            "{scope}{proxy}::{proxy}(const ::ndk::SpAIBinder& binder) : BpCInterface(binder) {{}}"
+        ));
+        self.s.line(format!("{scope}{proxy}::~{proxy}() {{}}"));
+        for (method, _) in &methods {
+            self.s.blank_line();
+            self.write_proxy_method(
+                interface,
+                method,
+                &format!("{scope}{proxy}"),
+                &format!("{scope}{name}"),
+            )?;
+        }
+
+        // Native (source)
+        self.s.blank_line();
+        self.s.line(format!("{scope}{native}::{native}() {{}}"));
+        self.s.line(format!("{scope}{native}::~{native}() {{}}"));
+        self.s.blank_line();
+        self.s.open(format!(
+            "::ndk::SpAIBinder {scope}{native}::createBinder() {{"
+        ));
+        self.s.line(format!(
+            "AIBinder* binder = AIBinder_new({clazz}, static_cast<void*>(this));"
+        ));
+        self.s.line("return ::ndk::SpAIBinder(binder);");
+        self.s.close("}");
+
+        // Interface (source)
+        let qualified_name = format!("{scope}{name}");
+        self.s.blank_line();
+        self.s.line(format!(
+            "const char* {qualified_name}::descriptor = {};",
+            to_string_literal(key)
+        ));
+        self.s.line(format!("{qualified_name}::{name}() {{}}"));
+        self.s.line(format!("{qualified_name}::~{name}() {{}}"));
+        self.write_string_consts(&consts, &qualified_name)?;
+        self.s.blank_line();
+        self.s.open(format!("std::shared_ptr<{qualified_name}> {qualified_name}::fromBinder(const ::ndk::SpAIBinder& binder) {{"));
+        self.s.open(format!(
+            "if (!AIBinder_associateClass(binder.get(), {clazz})) {{"
+        ));
+        self.s.line("return nullptr;");
+        self.s.close("}");
+        self.s.line("std::shared_ptr<::ndk::ICInterface> interface = ::ndk::ICInterface::asInterface(binder.get());");
+        self.s.open("if (interface) {");
+        self.s.line(format!(
+            "return std::static_pointer_cast<{name}>(interface);"
+        ));
+        self.s.close("}");
+        self.s.line(format!(
+            "return ::ndk::SharedRefBase::make<{scope}{proxy}>(binder);"
+        ));
+        self.s.close("}");
+        self.s.blank_line();
+        self.s.open(format!("binder_status_t {qualified_name}::writeToParcel(AParcel* parcel, const std::shared_ptr<{name}>& instance) {{"));
+        self.s.line(
+            "return AParcel_writeStrongBinder(parcel, instance ? instance->asBinder().get() : nullptr);",
+        );
+        self.s.close("}");
+        self.s.blank_line();
+        self.s.open(format!("binder_status_t {qualified_name}::readFromParcel(const AParcel* parcel, std::shared_ptr<{name}>* instance) {{"));
+        self.s.line("::ndk::SpAIBinder binder;");
+        self.s
+            .line("binder_status_t status = AParcel_readStrongBinder(parcel, binder.getR());");
+        self.s.line("if (status != STATUS_OK) return status;");
+        self.s
+            .line(format!("*instance = {name}::fromBinder(binder);"));
+        self.s.line("return STATUS_OK;");
+        self.s.close("}");
+
+        Ok(())
+    }
+
+    fn write_on_transact_case(
+        &mut self,
+        interface: &ast::Interface,
+        method: &ast::Method,
+        qualified_name: &str,
+    ) -> Result<(), CodegenError> {
+        let has_return = !matches!(method.return_type.kind, ast::TypeKind::Void);
+
+        self.s.open(format!(
+            "case {qualified_name}::TRANSACTION_{}: {{",
+            method.name
+        ));
+
+        // Declarations
+        let mut call_args = Vec::new();
+        for (i, arg) in method.args.iter().enumerate() {
+            let arg_name = get_arg_name(arg, i);
+            let arg_type = self.get_type(&arg.arg_type, is_nullable(&arg.annotations))?;
+            self.s.line(format!("{arg_type} {arg_name};"));
+            call_args.push(match arg.direction {
+                ast::Direction::Out(_) | ast::Direction::InOut(_) => format!("&{arg_name}"),
+                ast::Direction::In(_) | ast::Direction::Unspecified => arg_name,
+            });
+        }
+        if has_return {
+            let return_type =
+                self.get_type(&method.return_type, is_nullable(&method.annotations))?;
+            self.s.line(format!("{return_type} _aidl_return;"));
+            call_args.push(String::from("&_aidl_return"));
+        }
+        self.s.blank_line();
+
+        // Arguments
+        for (i, arg) in method.args.iter().enumerate() {
+            let arg_name = get_arg_name(arg, i);
+            match arg.direction {
+                ast::Direction::In(_) | ast::Direction::InOut(_) | ast::Direction::Unspecified => {
+                    let read = self.get_read(&arg.arg_type, "_aidl_in", &format!("&{arg_name}"))?;
+                    self.write_checked(&read, "break");
+                }
+                ast::Direction::Out(_) => {
+                    if is_vector(&arg.arg_type) {
+                        self.write_checked(
+                            &format!("::ndk::AParcel_resizeVector(_aidl_in, &{arg_name})"),
+                            "break",
+                        );
+                    }
+                }
+            }
+        }
+
+        self.s.line(format!(
+            "::ndk::ScopedAStatus _aidl_status = _aidl_impl->{}({});",
+            method.name,
+            call_args.join(", ")
+        ));
+
+        if interface.oneway || method.oneway {
+            self.s.line("_aidl_ret_status = STATUS_OK;");
+            self.s.line("break;");
+            self.s.close("}");
+            return Ok(());
+        }
+
+        self.write_checked(
+            "AParcel_writeStatusHeader(_aidl_out, _aidl_status.get())",
+            "break",
+        );
+        self.s.line("if (!AStatus_isOk(_aidl_status.get())) break;");
+        self.s.blank_line();
+        if has_return {
+            let write = self.get_write(&method.return_type, "_aidl_out", "_aidl_return")?;
+            self.write_checked(&write, "break");
+        }
+        for (i, arg) in method.args.iter().enumerate() {
+            if let ast::Direction::Out(_) | ast::Direction::InOut(_) = arg.direction {
+                let write = self.get_write(&arg.arg_type, "_aidl_out", &get_arg_name(arg, i))?;
+                self.write_checked(&write, "break");
+            }
+        }
+        self.s.line("break;");
+        self.s.close("}");
+
+        Ok(())
+    }
+
+    fn write_proxy_method(
+        &mut self,
+        interface: &ast::Interface,
+        method: &ast::Method,
+        proxy: &str,
+        qualified_name: &str,
+    ) -> Result<(), CodegenError> {
+        let oneway = interface.oneway || method.oneway;
+        let args = self.get_args(method)?;
+
+        self.s.open(format!(
+            "::ndk::ScopedAStatus {proxy}::{}({}) {{",
+            method.name,
+            args.join(", ")
+        ));
+        self.s.line("binder_status_t _aidl_ret_status = STATUS_OK;");
+        self.s.line("::ndk::ScopedAStatus _aidl_status;");
+        self.s.line("::ndk::ScopedAParcel _aidl_in;");
+        self.s.line("::ndk::ScopedAParcel _aidl_out;");
+        self.s.blank_line();
+        self.write_checked(
+            "AIBinder_prepareTransaction(asBinder().get(), _aidl_in.getR())",
+            "goto _aidl_error",
+        );
+
+        for (i, arg) in method.args.iter().enumerate() {
+            let arg_name = get_arg_name(arg, i);
+            match arg.direction {
+                ast::Direction::In(_) | ast::Direction::Unspecified => {
+                    let write = self.get_write(&arg.arg_type, "_aidl_in.get()", &arg_name)?;
+                    self.write_checked(&write, "goto _aidl_error");
+                }
+                ast::Direction::InOut(_) => {
+                    let write =
+                        self.get_write(&arg.arg_type, "_aidl_in.get()", &format!("*{arg_name}"))?;
+                    self.write_checked(&write, "goto _aidl_error");
+                }
+                ast::Direction::Out(_) => {
+                    // The size of out vectors is sent, so that the callee can allocate them
+                    if is_vector(&arg.arg_type) {
+                        self.write_checked(
+                            &format!("::ndk::AParcel_writeVectorSize(_aidl_in.get(), *{arg_name})"),
+                            "goto _aidl_error",
+                        );
+                    }
+                }
+            }
+        }
+
+        let flags = if oneway { "FLAG_ONEWAY" } else { "0" };
+        self.write_checked(
+            &format!("AIBinder_transact(asBinder().get(), {qualified_name}::TRANSACTION_{}, _aidl_in.getR(), _aidl_out.getR(), {flags})", method.name),
+            "goto _aidl_error",
+        );
+
+        if !oneway {
+            self.write_checked(
+                "AParcel_readStatusHeader(_aidl_out.get(), _aidl_status.getR())",
+                "goto _aidl_error",
+            );
+            self.s
+                .line("if (!AStatus_isOk(_aidl_status.get())) goto _aidl_status_return;");
+            self.s.blank_line();
+            if !matches!(method.return_type.kind, ast::TypeKind::Void) {
+                let read = self.get_read(&method.return_type, "_aidl_out.get()", "_aidl_return")?;
+                self.write_checked(&read, "goto _aidl_error");
+            }
+            for (i, arg) in method.args.iter().enumerate() {
+                if let ast::Direction::Out(_) | ast::Direction::InOut(_) = arg.direction {
+                    let read =
+                        self.get_read(&arg.arg_type, "_aidl_out.get()", &get_arg_name(arg, i))?;
+                    self.write_checked(&read, "goto _aidl_error");
+                }
+            }
+        }
+
+        self.s.label("_aidl_error:");
+        self.s
+            .line("_aidl_status.set(AStatus_fromStatus(_aidl_ret_status));");
+        if !oneway {
+            self.s.label("_aidl_status_return:");
+        }
+        self.s.line("return _aidl_status;");
+        self.s.close("}");
+
+        Ok(())
+    }
+
+    fn write_parcelable(
+        &mut self,
+        parcelable: &ast::Parcelable,
+        key: &str,
+        scope: &str,
+    ) -> Result<(), CodegenError> {
+        if !parcelable.type_parameters.is_empty() {
+            return Err(CodegenError::Unsupported(format!(
+                "Generic parcelable `{}`",
+                parcelable.name
+            )));
+        }
+
+        let name = &parcelable.name;
+        let qualified_name = format!("{scope}{name}");
+        let fields: Vec<&ast::Field> = parcelable
+            .elements
+            .iter()
+            .filter_map(|el| el.as_field())
+            .collect();
+        let stability = if has_annotation(&parcelable.annotations, "@VintfStability") {
+            "::ndk::STABILITY_VINTF"
+        } else {
+            "::ndk::STABILITY_LOCAL"
+        };
+
+        // Class (header)
+        self.h.blank_line();
+        self.write_doc(&parcelable.doc);
+        self.h.open(format!("class {name} {{"));
+        self.h.label("public:");
+        self.write_nested_items(
+            &parcelable.nested_items,
+            key,
+            &format!("{qualified_name}::"),
+        )?;
+        self.h.blank_line();
+        self.h.line("typedef std::false_type fixed_size;");
+        self.h.line("static const char* descriptor;");
+        self.h.blank_line();
+        for field in &fields {
+            self.write_doc(&field.doc);
+            let field_type = self.get_type(&field.field_type, is_nullable(&field.annotations))?;
+            match self.get_default_value(field, stability)? {
+                Some(value) if is_holder(&field.field_type) => self
+                    .h
+                    .line(format!("{field_type} {}{{{value}}};", field.name)),
+                Some(value) => self
+                    .h
+                    .line(format!("{field_type} {} = {value};", field.name)),
+                None => self.h.line(format!("{field_type} {};", field.name)),
+            }
+        }
+
+        let consts: Vec<&ast::Const> = parcelable
+            .elements
+            .iter()
+            .filter_map(|el| match el {
+                ast::ParcelableElement::Const(c) => Some(c),
+                ast::ParcelableElement::Field(_) => None,
+            })
+            .collect();
+        self.write_consts(&consts, &qualified_name)?;
+
+        self.h.blank_line();
+        self.h
+            .line("binder_status_t readFromParcel(const AParcel* parcel);");
+        self.h
+            .line("binder_status_t writeToParcel(AParcel* parcel) const;");
+        self.h.blank_line();
+        self.h.line(format!(
+            "static const ::ndk::parcelable_stability_t _aidl_stability = {stability};"
+        ));
+        self.h.close("};");
+
+        // Definitions (source)
+        self.s.blank_line();
+        self.s.line(format!(
+            "const char* {qualified_name}::descriptor = {};",
+            to_string_literal(key)
+        ));
+        self.write_string_consts(&consts, &qualified_name)?;
+
+        // Deserialization, prefixed by the size, so that fields can be added later
+        self.s.blank_line();
+        self.s.open(format!(
+            "binder_status_t {qualified_name}::readFromParcel(const AParcel* _aidl_parcel) {{"
+        ));
+        self.s.line("binder_status_t _aidl_ret_status = STATUS_OK;");
+        self.s
+            .line("int32_t _aidl_start_pos = AParcel_getDataPosition(_aidl_parcel);");
+        self.s.line("int32_t _aidl_parcelable_size = 0;");
+        self.write_checked(
+            "AParcel_readInt32(_aidl_parcel, &_aidl_parcelable_size)",
+            "return _aidl_ret_status",
+        );
+        self.s
+            .line("if (_aidl_parcelable_size < 4) return STATUS_BAD_VALUE;");
+        self.s.line(
+            "if (_aidl_start_pos > INT32_MAX - _aidl_parcelable_size) return STATUS_BAD_VALUE;",
+        );
+        for field in &fields {
+            self.s.blank_line();
+            self.s.open("if (AParcel_getDataPosition(_aidl_parcel) - _aidl_start_pos >= _aidl_parcelable_size) {");
+            self.s.line(
+                "AParcel_setDataPosition(_aidl_parcel, _aidl_start_pos + _aidl_parcelable_size);",
+            );
+            self.s.line("return _aidl_ret_status;");
+            self.s.close("}");
+            let read = self.get_read(
+                &field.field_type,
+                "_aidl_parcel",
+                &format!("&{}", field.name),
+            )?;
+            self.write_checked(&read, "return _aidl_ret_status");
+        }
+        self.s.blank_line();
+        self.s.line(
+            "AParcel_setDataPosition(_aidl_parcel, _aidl_start_pos + _aidl_parcelable_size);",
+        );
+        self.s.line("return _aidl_ret_status;");
+        self.s.close("}");
+
+        // Serialization
+        self.s.blank_line();
+        self.s.open(format!(
+            "binder_status_t {qualified_name}::writeToParcel(AParcel* _aidl_parcel) const {{"
+        ));
+        self.s.line("binder_status_t _aidl_ret_status;");
+        self.s
+            .line("size_t _aidl_start_pos = AParcel_getDataPosition(_aidl_parcel);");
+        self.write_checked(
+            "AParcel_writeInt32(_aidl_parcel, 0)",
+            "return _aidl_ret_status",
+        );
+        for field in &fields {
+            let write = self.get_write(&field.field_type, "_aidl_parcel", &field.name)?;
+            self.write_checked(&write, "return _aidl_ret_status");
+        }
+        self.s
+            .line("size_t _aidl_end_pos = AParcel_getDataPosition(_aidl_parcel);");
+        self.s
+            .line("AParcel_setDataPosition(_aidl_parcel, _aidl_start_pos);");
+        self.s
+            .line("AParcel_writeInt32(_aidl_parcel, _aidl_end_pos - _aidl_start_pos);");
+        self.s
+            .line("AParcel_setDataPosition(_aidl_parcel, _aidl_end_pos);");
+        self.s.line("return _aidl_ret_status;");
+        self.s.close("}");
+
+        Ok(())
+    }
+
+    fn write_enum(&mut self, enum_: &ast::Enum) -> Result<(), CodegenError> {
+        let backing_type = get_primitive_type(enum_.get_backing_type())?;
+
+        self.h.blank_line();
+        self.write_doc(&enum_.doc);
+        self.h
+            .open(format!("enum class {} : {backing_type} {{", enum_.name));
+        for element in &enum_.elements {
+            let value = get_evaluated_value(&element.evaluated_value, &element.name)?;
+            self.write_doc(&element.doc);
+            self.h
+                .line(format!("{} = {},", element.name, get_literal(value)));
+        }
+        self.h.close("};");
+
+        Ok(())
+    }
+
+    /// Declare the consts in the header (`constexpr`, except strings which are defined
+    /// in the source via write_string_consts())
+    fn write_consts(
+        &mut self,
+        consts: &[&ast::Const],
+        qualified_name: &str,
+    ) -> Result<(), CodegenError> {
+        if consts.is_empty() {
+            return Ok(());
+        }
+
+        self.h.blank_line();
+        for c in consts {
+            let value = get_evaluated_value(&c.evaluated_value, &c.name)?;
+            self.write_doc(&c.doc);
+            match value {
+                ast::ConstValue::String(_) => {
+                    self.h.line(format!("static const char* {};", c.name));
+                }
+                ast::ConstValue::Array(_) => {
+                    return Err(CodegenError::Unsupported(format!(
+                        "Array const `{qualified_name}::{}`",
+                        c.name
+                    )))
+                }
+                _ => {
+                    let const_type = self.get_type(&c.const_type, false)?;
+                    self.h.line(format!(
+                        "static constexpr {const_type} {} = {};",
+                        c.name,
+                        get_literal(value)
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_string_consts(
+        &mut self,
+        consts: &[&ast::Const],
+        qualified_name: &str,
+    ) -> Result<(), CodegenError> {
+        for c in consts {
+            if let ast::ConstValue::String(s) = get_evaluated_value(&c.evaluated_value, &c.name)? {
+                self.s.line(format!(
+                    "const char* {qualified_name}::{} = {};",
+                    c.name,
+                    to_string_literal(s)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_doc(&mut self, doc: &Option<String>) {
+        if let Some(doc) = doc {
+            let lines: Vec<&str> = doc.lines().collect();
+            if let [line] = lines.as_slice() {
+                self.h.line(format!("/** {line} */"));
+                return;
+            }
+
+            self.h.line("/**");
+            for line in lines {
+                if line.is_empty() {
+                    self.h.line(" *");
+                } else {
+                    self.h.line(format!(" * {line}"));
+                }
+            }
+            self.h.line(" */");
+        }
+    }
+
+    /// Write a call into the source and check its status (e.g. `break` on error)
+    fn write_checked(&mut self, call: &str, on_error: &str) {
+        self.s.line(format!("_aidl_ret_status = {call};"));
+        self.s
+            .line(format!("if (_aidl_ret_status != STATUS_OK) {on_error};"));
+        self.s.blank_line();
+    }
+
+    /// Arguments of a method: `in` arguments by value (primitives and enums) or by
+    /// const reference, `out`/`inout` arguments and the return value by pointer
+    fn get_args(&self, method: &ast::Method) -> Result<Vec<String>, CodegenError> {
+        let mut args = Vec::new();
+        for (i, arg) in method.args.iter().enumerate() {
+            let arg_type = self.get_type(&arg.arg_type, is_nullable(&arg.annotations))?;
+            let arg_name = get_arg_name(arg, i);
+            args.push(match arg.direction {
+                ast::Direction::In(_) | ast::Direction::Unspecified if is_copy(&arg.arg_type) => {
+                    format!("{arg_type} {arg_name}")
+                }
+                ast::Direction::In(_) | ast::Direction::Unspecified => {
+                    format!("const {arg_type}& {arg_name}")
+                }
+                ast::Direction::Out(_) | ast::Direction::InOut(_) => {
+                    format!("{arg_type}* {arg_name}")
+                }
+            });
+        }
+
+        if !matches!(method.return_type.kind, ast::TypeKind::Void) {
+            let return_type =
+                self.get_type(&method.return_type, is_nullable(&method.annotations))?;
+            args.push(format!("{return_type}* _aidl_return"));
+        }
+
+        Ok(args)
+    }
+
+    fn get_type(&self, type_: &ast::Type, nullable: bool) -> Result<String, CodegenError> {
+        let cpp_type = match &type_.kind {
+            ast::TypeKind::Primitive => get_primitive_type(&type_.name)?.to_owned(),
+            ast::TypeKind::Void => String::from("void"),
+            ast::TypeKind::String => String::from("std::string"),
+            ast::TypeKind::Array | ast::TypeKind::List => {
+                let element_type = type_.generic_types.first().ok_or_else(|| {
+                    CodegenError::Unsupported(format!("Non-generic `{}`", type_.name))
+                })?;
+                if matches!(type_.kind, ast::TypeKind::Array) && element_type.name == "byte" {
+                    String::from("std::vector<uint8_t>")
+                } else {
+                    format!("std::vector<{}>", self.get_type(element_type, false)?)
+                }
+            }
+            // Binders and file descriptors can be null without std::optional
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::IBinder) => {
+                return Ok(String::from("::ndk::SpAIBinder"))
+            }
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelFileDescriptor) => {
+                return Ok(String::from("::ndk::ScopedFileDescriptor"))
+            }
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Interface) => {
+                return Ok(format!("std::shared_ptr<{}>", self.get_path(key)))
+            }
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder) => {
+                String::from("::ndk::AParcelableHolder")
+            }
+            ast::TypeKind::Unresolved
+            | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => {
+                return Err(CodegenError::UnresolvedType(type_.name.clone()))
+            }
+            ast::TypeKind::ResolvedItem(key, _) => self.get_path(key),
+            ast::TypeKind::TypeParameter => type_.name.clone(),
+            ast::TypeKind::Map
+            | ast::TypeKind::CharSequence
+            | ast::TypeKind::AndroidType(ast::AndroidTypeKind::FileDescriptor) => {
+                return Err(CodegenError::Unsupported(format!("Type `{}`", type_.name)))
+            }
+        };
+
+        if nullable {
+            Ok(format!("std::optional<{cpp_type}>"))
+        } else {
+            Ok(cpp_type)
+        }
+    }
+
+    /// Expression writing the value into the parcel
+    fn get_write(
+        &self,
+        type_: &ast::Type,
+        parcel: &str,
+        value: &str,
+    ) -> Result<String, CodegenError> {
+        Ok(match &type_.kind {
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Interface) => {
+                format!("{}::writeToParcel({parcel}, {value})", self.get_path(key))
+            }
+            _ => format!("::ndk::AParcel_writeData({parcel}, {value})"),
+        })
+    }
+
+    /// Expression reading the parcel into the given pointer
+    fn get_read(
+        &self,
+        type_: &ast::Type,
+        parcel: &str,
+        target: &str,
+    ) -> Result<String, CodegenError> {
+        Ok(match &type_.kind {
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Interface) => {
+                format!("{}::readFromParcel({parcel}, {target})", self.get_path(key))
+            }
+            _ => format!("::ndk::AParcel_readData({parcel}, {target})"),
+        })
+    }
+
+    fn get_default_value(
+        &self,
+        field: &ast::Field,
+        stability: &str,
+    ) -> Result<Option<String>, CodegenError> {
+        if let Some(value) = &field.evaluated_value {
+            return Ok(Some(get_literal(value)));
+        }
+
+        if field.value.is_some() {
+            let (key, element) = get_enum_element(field, &self.items)?;
+            return Ok(Some(format!("{}::{element}", self.get_path(key))));
+        }
+
+        if is_holder(&field.field_type) {
+            return Ok(Some(stability.to_owned()));
+        }
+
+        // Primitives and enums are zero-initialized like in the other backends
+        if is_copy(&field.field_type) && !is_nullable(&field.annotations) {
+            return Ok(Some(match field.field_type.kind {
+                ast::TypeKind::Primitive if field.field_type.name == "boolean" => {
+                    String::from("false")
+                }
+                ast::TypeKind::Primitive => String::from("0"),
+                _ => format!("{}(0)", self.get_type(&field.field_type, false)?),
+            }));
+        }
+
+        Ok(None)
+    }
+
+    /// Qualified name of an item (e.g. `::aidl::pkg::Outer::Inner`)
+    fn get_path(&self, key: &str) -> String {
+        let (package, items) = split_key(key, self.ast);
+        format!(
+            "::aidl::{}",
+            package
+                .into_iter()
+                .chain(items)
+                .collect::<Vec<_>>()
+                .join("::")
+        )
+    }
+}
+
+/// Header of the item (or of its top-level parent for nested items)
+fn get_header_path(key: &str, ast: &ast::Aidl) -> String {
+    let (package, items) = split_key(key, ast);
+    let top_level = items.first().copied().unwrap_or_default();
+    format!("aidl/{}/{top_level}.h", package.join("/"))
+}
+
+fn get_primitive_type(name: &str) -> Result<&'static str, CodegenError> {
+    match name {
+        "boolean" => Ok("bool"),
+        "byte" => Ok("int8_t"),
+        "char" => Ok("char16_t"),
+        "int" => Ok("int32_t"),
+        "long" => Ok("int64_t"),
+        "float" => Ok("float"),
+        "double" => Ok("double"),
+        _ => Err(CodegenError::Unsupported(format!("Type `{name}`"))),
+    }
+}
+
+fn get_literal(value: &ast::ConstValue) -> String {
+    match value {
+        ast::ConstValue::Bool(v) => v.to_string(),
+        ast::ConstValue::Byte(v) => v.to_string(),
+        ast::ConstValue::Char(v) => match v {
+            '\'' => String::from("u'\\''"),
+            _ => format!("u'{}'", escape(&v.to_string())),
+        },
+        ast::ConstValue::Int(i32::MIN) => String::from("INT32_MIN"),
+        ast::ConstValue::Int(v) => v.to_string(),
+        ast::ConstValue::Long(i64::MIN) => String::from("INT64_MIN"),
+        ast::ConstValue::Long(v) => format!("{v}L"),
+        ast::ConstValue::Float(v) if v.is_nan() => {
+            String::from("std::numeric_limits<float>::quiet_NaN()")
+        }
+        ast::ConstValue::Float(v) if v.is_infinite() => {
+            let sign = if *v < 0.0 { "-" } else { "" };
+            format!("{sign}std::numeric_limits<float>::infinity()")
+        }
+        ast::ConstValue::Float(v) => format!("{v:?}f"),
+        ast::ConstValue::Double(v) if v.is_nan() => {
+            String::from("std::numeric_limits<double>::quiet_NaN()")
+        }
+        ast::ConstValue::Double(v) if v.is_infinite() => {
+            let sign = if *v < 0.0 { "-" } else { "" };
+            format!("{sign}std::numeric_limits<double>::infinity()")
+        }
+        ast::ConstValue::Double(v) => format!("{v:?}"),
+        ast::ConstValue::String(v) => to_string_literal(v),
+        ast::ConstValue::Array(values) => {
+            let literals: Vec<String> = values.iter().map(get_literal).collect();
+            format!("{{{}}}", literals.join(", "))
+        }
+    }
+}
+
+fn is_nullable(annotations: &[ast::Annotation]) -> bool {
+    has_annotation(annotations, "@nullable")
+}
+
+fn is_copy(type_: &ast::Type) -> bool {
+    matches!(
+        type_.kind,
+        ast::TypeKind::Primitive | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Enum)
+    )
+}
+
+fn is_vector(type_: &ast::Type) -> bool {
+    matches!(type_.kind, ast::TypeKind::Array | ast::TypeKind::List)
+}
+
+fn is_holder(type_: &ast::Type) -> bool {
+    matches!(
+        type_.kind,
+        ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder)
+    )
+}
+
+/// Name of an argument, prefixed by its direction (`in_` or `out_`)
+fn get_arg_name(arg: &ast::Arg, index: usize) -> String {
+    let prefix = match arg.direction {
+        ast::Direction::In(_) | ast::Direction::Unspecified => "in",
+        ast::Direction::Out(_) | ast::Direction::InOut(_) => "out",
+    };
+
+    match &arg.name {
+        Some(name) => format!("{prefix}_{name}"),
+        None => format!("{prefix}_arg{index}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::generate_file;
+
+    #[test]
+    fn test_interface() {
+        let interface = r#"package com.example;
+            import com.example.Bar;
+            import com.example.Color;
+            import com.example.IListener;
+
+            /** Example service */
+            interface IFoo {
+                /** Version */
+                const int VERSION = 1 + 2;
+                const String NAME = "foo";
+
+                @nullable String getName();
+                void setValues(in int[] values, out int[] previous);
+                Bar getBar(in Color color, in IBinder token);
+                void update(inout Bar bar, in List<String> tags);
+                oneway void registerListener(IListener listener);
+
+                enum Mode { A, B }
+            }
+        "#;
+        let bar = "package com.example; parcelable Bar {}";
+        let color = "package com.example; @Backing(type=\"int\") enum Color { RED }";
+        let listener = "package com.example; oneway interface IListener { void onEvent(); }";
+
+        let files = generate_file(
+            &[
+                ("IFoo", interface),
+                ("Bar", bar),
+                ("Color", color),
+                ("IListener", listener),
+            ],
+            "IFoo",
+            generate,
+        )
+        .expect("files");
+        assert_eq!(files.header_path, "aidl/com/example/IFoo.h");
+        assert_eq!(files.source_path, "com/example/IFoo.cpp");
+        insta::assert_snapshot!("interface_header", files.header);
+        insta::assert_snapshot!("interface_source", files.source);
+    }
+
+    #[test]
+    fn test_parcelable() {
+        let parcelable = r#"package com.example;
+            import com.example.Color;
+            import com.example.IListener;
+
+            /** Example parcelable */
+            @VintfStability
+            parcelable Bar {
+                const int MAX = 10;
+                const String PREFIX = "bar:";
+
+                /** Name */
+                String name = "bar";
+                int count = MAX - 1;
+                @nullable String description;
+                long[] data = {1, -1};
+                char c = 'x';
+                Color color = Color.GREEN;
+                Color other;
+                IListener listener;
+                ParcelFileDescriptor fd;
+                ParcelableHolder extension;
+            }
+        "#;
+        let color = "package com.example; @VintfStability enum Color { RED, GREEN }";
+        let listener = "package com.example; @VintfStability interface IListener {}";
+
+        let files = generate_file(
+            &[
+                ("Bar", parcelable),
+                ("Color", color),
+                ("IListener", listener),
+            ],
+            "Bar",
+            generate,
+        )
+        .expect("files");
+        insta::assert_snapshot!("parcelable_header", files.header);
+        insta::assert_snapshot!("parcelable_source", files.source);
+    }
+
+    #[test]
+    fn test_enum() {
+        let enum_ = r#"package com.example;
+            /** Example enum */
+            @Backing(type="long")
+            enum Status {
+                /** Success */
+                OK,
+                ERROR = -1,
+                UNKNOWN = 1 << 4,
+            }
+        "#;
+
+        let files = generate_file(&[("Status", enum_)], "Status", generate).expect("files");
+        insta::assert_snapshot!("enum_header", files.header);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let content = "package p; interface IFoo { void foo(in FileDescriptor fd); }";
+        assert_eq!(
+            generate_file(&[("IFoo", content)], "IFoo", generate),
+            Err(CodegenError::Unsupported(String::from(
+                "Type `FileDescriptor`"
+            )))
+        );
+    }
+}
@@ -0,0 +1,1142 @@
+//! Rust backend, generating code for the `binder` crate
+//!
+//! The file of each item is expected to be the module `crate::<package>::<Item>`
+//! (e.g. `crate::com::example::IFoo` for `com.example.IFoo`), nested items being
+//! declared as sub-modules (e.g. `crate::com::example::IFoo::Inner`).
+//!
+//! Example:
+//! ```
+//! use aidl_parser::{codegen, Parser};
+//!
+//! let mut parser = Parser::new();
+//! parser.add_content("id", "package p; enum Color { RED, GREEN }");
+//! let results = parser.validate();
+//!
+//! let ast = results["id"].ast.as_ref().expect("ast");
+//! let code = codegen::rust::generate(ast, &results).expect("code");
+//! assert!(code.contains("pub struct Color(pub i8);"));
+//! assert!(code.contains("pub const GREEN: Self = Self(1);"));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use super::{get_class_name, get_evaluated_value, get_transaction_codes, has_annotation};
+use super::{get_enum_element, get_items, split_key, CodeWriter, CodegenError};
+use crate::ast;
+use crate::parser::ParseFileResult;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+const RESULT_TYPE: &str = "std::result::Result<(), binder::StatusCode>";
+
+/// Generate the Rust code of the item of the given (validated) AST, including its
+/// nested items
+///
+/// The results are used to check the enum elements referenced by the default values
+/// of fields.
+pub fn generate<ID>(
+    ast: &ast::Aidl,
+    results: &HashMap<ID, ParseFileResult<ID>>,
+) -> Result<String, CodegenError>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    let mut generator = Generator {
+        ast,
+        items: get_items(ast, results),
+        w: CodeWriter::new("    "),
+    };
+
+    generator.w.line(format!(
+        "// Generated from `{}`, do not edit",
+        ast.get_key()
+    ));
+    generator.w.line(
+        "#![allow(non_upper_case_globals, non_snake_case, non_camel_case_types, unused_mut)]",
+    );
+    generator.w.blank_line();
+    generator.write_item(&ast.item, &ast.get_key())?;
+
+    Ok(generator.w.into_string())
+}
+
+struct Generator<'a> {
+    ast: &'a ast::Aidl,
+    items: HashMap<ast::ItemKey, &'a ast::Item>,
+    w: CodeWriter,
+}
+
+impl<'a> Generator<'a> {
+    fn write_item(&mut self, item: &ast::Item, key: &str) -> Result<(), CodegenError> {
+        match item {
+            ast::Item::Interface(i) => self.write_interface(i, key)?,
+            ast::Item::Parcelable(p) => self.write_parcelable(p, key)?,
+            ast::Item::Union(u) => self.write_union(u, key)?,
+            ast::Item::Enum(e) => self.write_enum(e)?,
+        }
+
+        for nested in item.get_nested_items() {
+            self.w.blank_line();
+            self.w
+                .open(format!("pub mod {} {{", to_ident(nested.get_name())));
+            self.write_item(nested, &format!("{}.{}", key, nested.get_name()))?;
+            self.w.close("}");
+        }
+
+        Ok(())
+    }
+
+    fn write_interface(
+        &mut self,
+        interface: &ast::Interface,
+        key: &str,
+    ) -> Result<(), CodegenError> {
+        let name = to_ident(&interface.name);
+        let class_name = get_class_name(&interface.name);
+        let native = format!("Bn{class_name}");
+        let proxy = format!("Bp{class_name}");
+        let methods = get_transaction_codes(interface);
+
+        // Trait
+        self.write_doc(&interface.doc);
+        self.w
+            .open(format!("pub trait {name}: binder::Interface + Send {{"));
+        self.w
+            .open("fn get_descriptor() -> &'static str where Self: Sized {");
+        self.w.line(format!("{key:?}"));
+        self.w.close("}");
+        for (method, _) in &methods {
+            self.w.blank_line();
+            self.write_doc(&method.doc);
+            let signature = self.get_method_signature(method)?;
+            self.w.line(format!("{signature};"));
+        }
+        self.w.close("}");
+
+        // Consts
+        let consts: Vec<&ast::Const> = interface
+            .elements
+            .iter()
+            .filter_map(|el| el.as_const())
+            .collect();
+        self.write_consts(&consts)?;
+
+        // Binder objects
+        self.w.blank_line();
+        self.w.open("binder::declare_binder_interface! {");
+        self.w.open(format!("{name}[{key:?}] {{"));
+        self.w.line(format!("native: {native}(on_transact),"));
+        self.w.line(format!("proxy: {proxy},"));
+        self.w.close("}");
+        self.w.close("}");
+
+        // Transaction codes
+        self.w.blank_line();
+        self.w.open("pub mod transactions {");
+        for (method, code) in &methods {
+            self.w.line(format!(
+                "pub const {}: binder::binder_impl::TransactionCode = binder::binder_impl::FIRST_CALL_TRANSACTION + {code};",
+                to_ident(&method.name)
+            ));
+        }
+        self.w.close("}");
+
+        // Proxy
+        self.w.blank_line();
+        self.w.open(format!("impl {name} for {proxy} {{"));
+        for (i, (method, _)) in methods.iter().enumerate() {
+            if i > 0 {
+                self.w.blank_line();
+            }
+            self.write_proxy_method(interface, method)?;
+        }
+        self.w.close("}");
+
+        // Native
+        self.w.blank_line();
+        self.w.open(format!(
+            "impl {name} for binder::binder_impl::Binder<{native}> {{"
+        ));
+        for (i, (method, _)) in methods.iter().enumerate() {
+            if i > 0 {
+                self.w.blank_line();
+            }
+            let signature = self.get_method_signature(method)?;
+            let args: Vec<String> = method
+                .args
+                .iter()
+                .enumerate()
+                .map(|(i, a)| get_arg_name(a, i))
+                .collect();
+            self.w.open(format!("{signature} {{"));
+            self.w.line(format!(
+                "self.0.{}({})",
+                to_ident(&method.name),
+                args.join(", ")
+            ));
+            self.w.close("}");
+        }
+        self.w.close("}");
+
+        self.w.blank_line();
+        self.write_on_transact(interface, &name, &methods)
+    }
+
+    fn get_method_signature(&self, method: &ast::Method) -> Result<String, CodegenError> {
+        let mut args = vec![String::from("&self")];
+        for (i, arg) in method.args.iter().enumerate() {
+            let nullable = has_annotation(&arg.annotations, "@nullable");
+            let arg_type = match arg.direction {
+                ast::Direction::In(_) | ast::Direction::Unspecified => {
+                    self.get_in_arg_type(&arg.arg_type, nullable)?
+                }
+                ast::Direction::Out(_) | ast::Direction::InOut(_) => {
+                    format!("&mut {}", self.get_type(&arg.arg_type, nullable)?)
+                }
+            };
+            args.push(format!("{}: {arg_type}", get_arg_name(arg, i)));
+        }
+
+        let return_type = self.get_type(
+            &method.return_type,
+            has_annotation(&method.annotations, "@nullable"),
+        )?;
+
+        Ok(format!(
+            "fn {}({}) -> binder::Result<{return_type}>",
+            to_ident(&method.name),
+            args.join(", ")
+        ))
+    }
+
+    fn write_proxy_method(
+        &mut self,
+        interface: &ast::Interface,
+        method: &ast::Method,
+    ) -> Result<(), CodegenError> {
+        let signature = self.get_method_signature(method)?;
+        let oneway = interface.oneway || method.oneway;
+        let method_name = to_ident(&method.name);
+
+        self.w.open(format!("{signature} {{"));
+        self.w
+            .line("let mut _aidl_data = self.binder.prepare_transact()?;");
+        for (i, arg) in method.args.iter().enumerate() {
+            let arg_name = get_arg_name(arg, i);
+            let nullable = has_annotation(&arg.annotations, "@nullable");
+            match arg.direction {
+                ast::Direction::In(_) | ast::Direction::Unspecified => {
+                    if nullable || is_copy(&arg.arg_type) {
+                        self.w.line(format!("_aidl_data.write(&{arg_name})?;"));
+                    } else {
+                        self.w.line(format!("_aidl_data.write({arg_name})?;"));
+                    }
+                }
+                ast::Direction::InOut(_) => {
+                    self.w.line(format!("_aidl_data.write({arg_name})?;"));
+                }
+                ast::Direction::Out(_) => {
+                    if is_array(&arg.arg_type) {
+                        if nullable {
+                            self.w.line(format!(
+                                "_aidl_data.write_slice_size({arg_name}.as_deref())?;"
+                            ));
+                        } else {
+                            self.w.line(format!(
+                                "_aidl_data.write_slice_size(Some(&{arg_name}[..]))?;"
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        if oneway {
+            self.w.line(format!("self.binder.submit_transact(transactions::{method_name}, _aidl_data, binder::binder_impl::FLAG_ONEWAY | binder::binder_impl::FLAG_PRIVATE_LOCAL)?;"));
+            self.w.line("Ok(())");
+            self.w.close("}");
+            return Ok(());
+        }
+
+        self.w.line(format!("let _aidl_reply = self.binder.submit_transact(transactions::{method_name}, _aidl_data, binder::binder_impl::FLAG_PRIVATE_LOCAL)?;"));
+        self.w
+            .line("let _aidl_status: binder::Status = _aidl_reply.read()?;");
+        self.w.open("if !_aidl_status.is_ok() {");
+        self.w.line("return Err(_aidl_status);");
+        self.w.close("}");
+
+        let has_return = !matches!(method.return_type.kind, ast::TypeKind::Void);
+        if has_return {
+            let return_type = self.get_type(
+                &method.return_type,
+                has_annotation(&method.annotations, "@nullable"),
+            )?;
+            self.w.line(format!(
+                "let _aidl_return: {return_type} = _aidl_reply.read()?;"
+            ));
+        }
+        for (i, arg) in method.args.iter().enumerate() {
+            if let ast::Direction::Out(_) | ast::Direction::InOut(_) = arg.direction {
+                self.w
+                    .line(format!("_aidl_reply.read_onto({})?;", get_arg_name(arg, i)));
+            }
+        }
+        self.w.line(if has_return {
+            "Ok(_aidl_return)"
+        } else {
+            "Ok(())"
+        });
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn write_on_transact(
+        &mut self,
+        interface: &ast::Interface,
+        name: &str,
+        methods: &[(&ast::Method, u32)],
+    ) -> Result<(), CodegenError> {
+        self.w.open(format!("fn on_transact(_aidl_service: &dyn {name}, _aidl_code: binder::binder_impl::TransactionCode, _aidl_data: &binder::binder_impl::BorrowedParcel<'_>, _aidl_reply: &mut binder::binder_impl::BorrowedParcel<'_>) -> {RESULT_TYPE} {{"));
+        self.w.open("match _aidl_code {");
+
+        for (method, _) in methods {
+            self.w
+                .open(format!("transactions::{} => {{", to_ident(&method.name)));
+
+            let mut call_args = Vec::new();
+            for (i, arg) in method.args.iter().enumerate() {
+                let arg_name = get_arg_name(arg, i);
+                let nullable = has_annotation(&arg.annotations, "@nullable");
+                let arg_type = self.get_type(&arg.arg_type, nullable)?;
+                match arg.direction {
+                    ast::Direction::In(_) | ast::Direction::Unspecified => {
+                        self.w
+                            .line(format!("let {arg_name}: {arg_type} = _aidl_data.read()?;"));
+                        call_args.push(if is_copy(&arg.arg_type) {
+                            arg_name
+                        } else if nullable && is_sliceable(&arg.arg_type) {
+                            format!("{arg_name}.as_deref()")
+                        } else if nullable {
+                            format!("{arg_name}.as_ref()")
+                        } else {
+                            format!("&{arg_name}")
+                        });
+                    }
+                    ast::Direction::InOut(_) => {
+                        self.w.line(format!(
+                            "let mut {arg_name}: {arg_type} = _aidl_data.read()?;"
+                        ));
+                        call_args.push(format!("&mut {arg_name}"));
+                    }
+                    ast::Direction::Out(_) => {
+                        self.w.line(format!(
+                            "let mut {arg_name}: {arg_type} = Default::default();"
+                        ));
+                        if is_array(&arg.arg_type) {
+                            let resize = if nullable {
+                                "resize_nullable_out_vec"
+                            } else {
+                                "resize_out_vec"
+                            };
+                            self.w
+                                .line(format!("_aidl_data.{resize}(&mut {arg_name})?;"));
+                        }
+                        call_args.push(format!("&mut {arg_name}"));
+                    }
+                }
+            }
+
+            self.w.line(format!(
+                "let _aidl_return = _aidl_service.{}({});",
+                to_ident(&method.name),
+                call_args.join(", ")
+            ));
+
+            if !interface.oneway && !method.oneway {
+                let has_return = !matches!(method.return_type.kind, ast::TypeKind::Void);
+                self.w.open("match &_aidl_return {");
+                self.w.open(if has_return {
+                    "Ok(_aidl_return) => {"
+                } else {
+                    "Ok(()) => {"
+                });
+                self.w
+                    .line("_aidl_reply.write(&binder::Status::from(binder::StatusCode::OK))?;");
+                if has_return {
+                    self.w.line("_aidl_reply.write(_aidl_return)?;");
+                }
+                for (i, arg) in method.args.iter().enumerate() {
+                    if let ast::Direction::Out(_) | ast::Direction::InOut(_) = arg.direction {
+                        self.w
+                            .line(format!("_aidl_reply.write(&{})?;", get_arg_name(arg, i)));
+                    }
+                }
+                self.w.close("}");
+                self.w
+                    .line("Err(_aidl_status) => _aidl_reply.write(_aidl_status)?,");
+                self.w.close("}");
+            }
+
+            self.w.line("Ok(())");
+            self.w.close("}");
+        }
+
+        self.w
+            .line("_ => Err(binder::StatusCode::UNKNOWN_TRANSACTION),");
+        self.w.close("}");
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn write_parcelable(
+        &mut self,
+        parcelable: &ast::Parcelable,
+        key: &str,
+    ) -> Result<(), CodegenError> {
+        if !parcelable.type_parameters.is_empty() {
+            return Err(CodegenError::Unsupported(format!(
+                "Generic parcelable `{}`",
+                parcelable.name
+            )));
+        }
+
+        let name = to_ident(&parcelable.name);
+        let fields: Vec<&ast::Field> = parcelable
+            .elements
+            .iter()
+            .filter_map(|el| el.as_field())
+            .collect();
+
+        // Struct
+        self.write_doc(&parcelable.doc);
+        self.w.line(format!(
+            "#[derive({})]",
+            get_derives(&parcelable.annotations).join(", ")
+        ));
+        if fields.is_empty() {
+            self.w.line(format!("pub struct {name} {{}}"));
+        } else {
+            self.w.open(format!("pub struct {name} {{"));
+            for field in &fields {
+                self.write_doc(&field.doc);
+                let field_type = self.get_field_type(field)?;
+                self.w
+                    .line(format!("pub {}: {field_type},", to_ident(&field.name)));
+            }
+            self.w.close("}");
+        }
+
+        // Consts
+        let consts: Vec<&ast::Const> = parcelable
+            .elements
+            .iter()
+            .filter_map(|el| match el {
+                ast::ParcelableElement::Const(c) => Some(c),
+                ast::ParcelableElement::Field(_) => None,
+            })
+            .collect();
+        self.write_consts(&consts)?;
+
+        // Default values
+        self.w.blank_line();
+        self.w.open(format!("impl Default for {name} {{"));
+        self.w.open("fn default() -> Self {");
+        if fields.is_empty() {
+            self.w.line("Self {}");
+        } else {
+            self.w.open("Self {");
+            for field in &fields {
+                let value = self.get_default_value(field, &parcelable.annotations)?;
+                self.w.line(format!("{}: {value},", to_ident(&field.name)));
+            }
+            self.w.close("}");
+        }
+        self.w.close("}");
+        self.w.close("}");
+
+        // Parcelable
+        let subparcel = if fields.is_empty() {
+            "_subparcel"
+        } else {
+            "subparcel"
+        };
+        self.w.blank_line();
+        self.w
+            .open(format!("impl binder::Parcelable for {name} {{"));
+        self.w.open(format!("fn write_to_parcel(&self, parcel: &mut binder::binder_impl::BorrowedParcel<'_>) -> {RESULT_TYPE} {{"));
+        self.w.open(format!("parcel.sized_write(|{subparcel}| {{"));
+        for field in &fields {
+            self.w.line(format!(
+                "subparcel.write(&self.{})?;",
+                to_ident(&field.name)
+            ));
+        }
+        self.w.line("Ok(())");
+        self.w.close("})");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w.open(format!("fn read_from_parcel(&mut self, parcel: &binder::binder_impl::BorrowedParcel<'_>) -> {RESULT_TYPE} {{"));
+        self.w.open(format!("parcel.sized_read(|{subparcel}| {{"));
+        for field in &fields {
+            self.w.open("if subparcel.has_more_data() {");
+            self.w.line(format!(
+                "self.{} = subparcel.read()?;",
+                to_ident(&field.name)
+            ));
+            self.w.close("}");
+        }
+        self.w.line("Ok(())");
+        self.w.close("})");
+        self.w.close("}");
+        self.w.close("}");
+
+        self.write_parcelable_metadata(&name, key);
+
+        Ok(())
+    }
+
+    fn write_union(&mut self, union: &ast::Union, key: &str) -> Result<(), CodegenError> {
+        let name = to_ident(&union.name);
+        let fields: Vec<&ast::Field> = union
+            .elements
+            .iter()
+            .filter_map(|el| el.as_field())
+            .collect();
+        let first_field = fields.first().ok_or_else(|| {
+            CodegenError::Unsupported(format!("Union `{}` without field", union.name))
+        })?;
+
+        // Enum
+        self.write_doc(&union.doc);
+        self.w.line(format!(
+            "#[derive({})]",
+            get_derives(&union.annotations).join(", ")
+        ));
+        self.w.open(format!("pub enum {name} {{"));
+        for field in &fields {
+            self.write_doc(&field.doc);
+            let field_type = self.get_field_type(field)?;
+            self.w
+                .line(format!("{}({field_type}),", to_ident(&field.name)));
+        }
+        self.w.close("}");
+
+        // Consts
+        let consts: Vec<&ast::Const> = union
+            .elements
+            .iter()
+            .filter_map(|el| match el {
+                ast::UnionElement::Const(c) => Some(c),
+                ast::UnionElement::Field(_) => None,
+            })
+            .collect();
+        self.write_consts(&consts)?;
+
+        // Default value (first field)
+        self.w.blank_line();
+        self.w.open(format!("impl Default for {name} {{"));
+        self.w.open("fn default() -> Self {");
+        let value = self.get_default_value(first_field, &union.annotations)?;
+        self.w
+            .line(format!("Self::{}({value})", to_ident(&first_field.name)));
+        self.w.close("}");
+        self.w.close("}");
+
+        // Parcelable (tag followed by the value)
+        self.w.blank_line();
+        self.w
+            .open(format!("impl binder::Parcelable for {name} {{"));
+        self.w.open(format!("fn write_to_parcel(&self, parcel: &mut binder::binder_impl::BorrowedParcel<'_>) -> {RESULT_TYPE} {{"));
+        self.w.open("match self {");
+        for (tag, field) in fields.iter().enumerate() {
+            self.w
+                .open(format!("Self::{}(v) => {{", to_ident(&field.name)));
+            self.w.line(format!("parcel.write(&{tag}i32)?;"));
+            self.w.line("parcel.write(v)");
+            self.w.close("}");
+        }
+        self.w.close("}");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w.open(format!("fn read_from_parcel(&mut self, parcel: &binder::binder_impl::BorrowedParcel<'_>) -> {RESULT_TYPE} {{"));
+        self.w.line("let tag: i32 = parcel.read()?;");
+        self.w.open("match tag {");
+        for (tag, field) in fields.iter().enumerate() {
+            let field_type = self.get_field_type(field)?;
+            self.w.open(format!("{tag} => {{"));
+            self.w
+                .line(format!("let value: {field_type} = parcel.read()?;"));
+            self.w
+                .line(format!("*self = Self::{}(value);", to_ident(&field.name)));
+            self.w.line("Ok(())");
+            self.w.close("}");
+        }
+        self.w.line("_ => Err(binder::StatusCode::BAD_VALUE),");
+        self.w.close("}");
+        self.w.close("}");
+        self.w.close("}");
+
+        self.write_parcelable_metadata(&name, key);
+
+        Ok(())
+    }
+
+    fn write_parcelable_metadata(&mut self, name: &str, key: &str) {
+        self.w.blank_line();
+        self.w
+            .line(format!("binder::impl_serialize_for_parcelable!({name});"));
+        self.w
+            .line(format!("binder::impl_deserialize_for_parcelable!({name});"));
+        self.w.blank_line();
+        self.w.open(format!(
+            "impl binder::binder_impl::ParcelableMetadata for {name} {{"
+        ));
+        self.w.open("fn get_descriptor() -> &'static str {");
+        self.w.line(format!("{key:?}"));
+        self.w.close("}");
+        self.w.close("}");
+    }
+
+    fn write_enum(&mut self, enum_: &ast::Enum) -> Result<(), CodegenError> {
+        let name = to_ident(&enum_.name);
+        let backing_type = match enum_.get_backing_type() {
+            "byte" => "i8",
+            "int" => "i32",
+            "long" => "i64",
+            other => return Err(CodegenError::Unsupported(format!("Backing type `{other}`"))),
+        };
+
+        // Newtype
+        self.write_doc(&enum_.doc);
+        self.w
+            .line("#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]");
+        self.w.line("#[repr(transparent)]");
+        self.w
+            .line(format!("pub struct {name}(pub {backing_type});"));
+
+        // Elements
+        self.w.blank_line();
+        self.w.open(format!("impl {name} {{"));
+        for element in &enum_.elements {
+            let value = get_evaluated_value(&element.evaluated_value, &element.name)?;
+            self.write_doc(&element.doc);
+            self.w.line(format!(
+                "pub const {}: Self = Self({value});",
+                to_ident(&element.name)
+            ));
+        }
+        self.w.blank_line();
+        let elements: Vec<String> = enum_
+            .elements
+            .iter()
+            .map(|el| format!("Self::{}", to_ident(&el.name)))
+            .collect();
+        self.w.open(format!(
+            "pub const fn enum_values() -> [Self; {}] {{",
+            elements.len()
+        ));
+        self.w.line(format!("[{}]", elements.join(", ")));
+        self.w.close("}");
+        self.w.close("}");
+
+        // Serialization (as the backing type)
+        self.w.blank_line();
+        self.w
+            .open(format!("impl binder::binder_impl::Serialize for {name} {{"));
+        self.w.open(format!("fn serialize(&self, parcel: &mut binder::binder_impl::BorrowedParcel<'_>) -> {RESULT_TYPE} {{"));
+        self.w.line("parcel.write(&self.0)");
+        self.w.close("}");
+        self.w.close("}");
+
+        self.w.blank_line();
+        self.w.open(format!(
+            "impl binder::binder_impl::SerializeArray for {name} {{"
+        ));
+        self.w.open(format!("fn serialize_array(slice: &[Self], parcel: &mut binder::binder_impl::BorrowedParcel<'_>) -> {RESULT_TYPE} {{"));
+        self.w.line(format!(
+            "let v: Vec<{backing_type}> = slice.iter().map(|x| x.0).collect();"
+        ));
+        self.w.line(format!(
+            "<{backing_type} as binder::binder_impl::SerializeArray>::serialize_array(&v[..], parcel)"
+        ));
+        self.w.close("}");
+        self.w.close("}");
+
+        self.w.blank_line();
+        self.w.open(format!(
+            "impl binder::binder_impl::Deserialize for {name} {{"
+        ));
+        self.w.line("type UninitType = Self;");
+        self.w.blank_line();
+        self.w.open("fn uninit() -> Self::UninitType {");
+        self.w.line("Self::UninitType::default()");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w
+            .open("fn from_init(value: Self) -> Self::UninitType {");
+        self.w.line("value");
+        self.w.close("}");
+        self.w.blank_line();
+        self.w.open("fn deserialize(parcel: &binder::binder_impl::BorrowedParcel<'_>) -> std::result::Result<Self, binder::StatusCode> {");
+        self.w.line("parcel.read().map(Self)");
+        self.w.close("}");
+        self.w.close("}");
+
+        self.w.blank_line();
+        self.w.open(format!(
+            "impl binder::binder_impl::DeserializeArray for {name} {{"
+        ));
+        self.w.open("fn deserialize_array(parcel: &binder::binder_impl::BorrowedParcel<'_>) -> std::result::Result<Option<Vec<Self>>, binder::StatusCode> {");
+        self.w.line(format!("let v: Option<Vec<{backing_type}>> = <{backing_type} as binder::binder_impl::DeserializeArray>::deserialize_array(parcel)?;"));
+        self.w
+            .line("Ok(v.map(|v| v.into_iter().map(Self).collect()))");
+        self.w.close("}");
+        self.w.close("}");
+
+        Ok(())
+    }
+
+    fn write_consts(&mut self, consts: &[&ast::Const]) -> Result<(), CodegenError> {
+        if consts.is_empty() {
+            return Ok(());
+        }
+
+        self.w.blank_line();
+        for c in consts {
+            let value = get_evaluated_value(&c.evaluated_value, &c.name)?;
+            let (const_type, value) = match value {
+                ast::ConstValue::String(s) => (String::from("&str"), format!("{s:?}")),
+                ast::ConstValue::Array(_) => {
+                    return Err(CodegenError::Unsupported(format!(
+                        "Array const `{}`",
+                        c.name
+                    )))
+                }
+                _ => (
+                    self.get_type(&c.const_type, false)?,
+                    self.get_literal(value, &c.const_type)?,
+                ),
+            };
+
+            self.write_doc(&c.doc);
+            self.w.line(format!(
+                "pub const {}: {const_type} = {value};",
+                to_ident(&c.name)
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn write_doc(&mut self, doc: &Option<String>) {
+        if let Some(doc) = doc {
+            for line in doc.lines() {
+                if line.is_empty() {
+                    self.w.line("///");
+                } else {
+                    self.w.line(format!("/// {line}"));
+                }
+            }
+        }
+    }
+
+    /// Type of an owned value (e.g. a field, a return value or an out argument)
+    fn get_type(&self, type_: &ast::Type, nullable: bool) -> Result<String, CodegenError> {
+        let rust_type = match &type_.kind {
+            ast::TypeKind::Primitive => get_primitive_type(&type_.name)?.to_owned(),
+            ast::TypeKind::Void => String::from("()"),
+            ast::TypeKind::String => String::from("String"),
+            ast::TypeKind::Array | ast::TypeKind::List => {
+                format!("Vec<{}>", self.get_element_type(type_)?)
+            }
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::IBinder) => {
+                String::from("binder::SpIBinder")
+            }
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelFileDescriptor) => {
+                String::from("binder::ParcelFileDescriptor")
+            }
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder) => {
+                String::from("binder::ParcelableHolder")
+            }
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Interface) => {
+                format!("binder::Strong<dyn {}>", self.get_path(key))
+            }
+            ast::TypeKind::Unresolved
+            | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => {
+                return Err(CodegenError::UnresolvedType(type_.name.clone()))
+            }
+            ast::TypeKind::ResolvedItem(key, _) => self.get_path(key),
+            ast::TypeKind::TypeParameter => type_.name.clone(),
+            ast::TypeKind::Map
+            | ast::TypeKind::CharSequence
+            | ast::TypeKind::AndroidType(ast::AndroidTypeKind::FileDescriptor) => {
+                return Err(CodegenError::Unsupported(format!("Type `{}`", type_.name)))
+            }
+        };
+
+        if nullable {
+            Ok(format!("Option<{rust_type}>"))
+        } else {
+            Ok(rust_type)
+        }
+    }
+
+    /// Element type of arrays and lists (`u8` for `byte[]`)
+    fn get_element_type(&self, type_: &ast::Type) -> Result<String, CodegenError> {
+        let element_type = type_
+            .generic_types
+            .first()
+            .ok_or_else(|| CodegenError::Unsupported(format!("Non-generic `{}`", type_.name)))?;
+
+        if matches!(type_.kind, ast::TypeKind::Array) && element_type.name == "byte" {
+            Ok(String::from("u8"))
+        } else {
+            self.get_type(element_type, false)
+        }
+    }
+
+    /// Type of an `in` argument (borrowed, unless copyable)
+    fn get_in_arg_type(&self, type_: &ast::Type, nullable: bool) -> Result<String, CodegenError> {
+        if is_copy(type_) {
+            return self.get_type(type_, nullable);
+        }
+
+        let borrowed = match type_.kind {
+            ast::TypeKind::String => String::from("&str"),
+            ast::TypeKind::Array | ast::TypeKind::List => {
+                format!("&[{}]", self.get_element_type(type_)?)
+            }
+            _ => format!("&{}", self.get_type(type_, false)?),
+        };
+
+        if nullable {
+            Ok(format!("Option<{borrowed}>"))
+        } else {
+            Ok(borrowed)
+        }
+    }
+
+    /// Type of a field, interfaces and binders being always optional since they have
+    /// no default value
+    fn get_field_type(&self, field: &ast::Field) -> Result<String, CodegenError> {
+        self.get_type(&field.field_type, is_nullable_field(field))
+    }
+
+    fn get_default_value(
+        &self,
+        field: &ast::Field,
+        parent_annotations: &[ast::Annotation],
+    ) -> Result<String, CodegenError> {
+        if let Some(value) = &field.evaluated_value {
+            let literal = self.get_literal(value, &field.field_type)?;
+            return Ok(if is_nullable_field(field) {
+                format!("Some({literal})")
+            } else {
+                literal
+            });
+        }
+
+        if field.value.is_some() {
+            let (key, element) = get_enum_element(field, &self.items)?;
+            return Ok(format!("{}::{}", self.get_path(key), to_ident(element)));
+        }
+
+        match field.field_type.kind {
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder) => {
+                let stability = if has_annotation(parent_annotations, "@VintfStability") {
+                    "Vintf"
+                } else {
+                    "Local"
+                };
+                Ok(format!(
+                    "binder::ParcelableHolder::new(binder::binder_impl::Stability::{stability})"
+                ))
+            }
+            _ => Ok(String::from("Default::default()")),
+        }
+    }
+
+    /// Literal of a value to be assigned to a field of the given type
+    fn get_literal(
+        &self,
+        value: &ast::ConstValue,
+        type_: &ast::Type,
+    ) -> Result<String, CodegenError> {
+        let literal = match value {
+            ast::ConstValue::Bool(v) => v.to_string(),
+            ast::ConstValue::Byte(v) => v.to_string(),
+            ast::ConstValue::Char(v) => (*v as u32).to_string(),
+            ast::ConstValue::Int(v) => v.to_string(),
+            ast::ConstValue::Long(v) => v.to_string(),
+            ast::ConstValue::Float(v) => get_float_literal(f64::from(*v), "f32"),
+            ast::ConstValue::Double(v) => get_float_literal(*v, "f64"),
+            ast::ConstValue::String(v) => return Ok(format!("{v:?}.into()")),
+            ast::ConstValue::Array(values) => {
+                let element_type = type_.generic_types.first().ok_or_else(|| {
+                    CodegenError::Unsupported(format!("Non-generic `{}`", type_.name))
+                })?;
+                let literals = values
+                    .iter()
+                    .map(|v| match v {
+                        ast::ConstValue::Byte(b) if matches!(type_.kind, ast::TypeKind::Array) => {
+                            Ok((*b as u8).to_string())
+                        }
+                        _ => self.get_literal(v, element_type),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(format!("vec![{}]", literals.join(", ")));
+            }
+        };
+
+        match &type_.kind {
+            ast::TypeKind::ResolvedItem(key, ast::ResolvedItemKind::Enum) => {
+                Ok(format!("{}({literal})", self.get_path(key)))
+            }
+            _ => Ok(literal),
+        }
+    }
+
+    /// Path of the type of an item (e.g. `crate::pkg::Outer::Inner::Inner`)
+    fn get_path(&self, key: &str) -> String {
+        let (package, items) = split_key(key, self.ast);
+        let name = items.last().copied().unwrap_or_default();
+
+        let path: Vec<String> = package
+            .into_iter()
+            .chain(items)
+            .chain(std::iter::once(name))
+            .map(to_ident)
+            .collect();
+        format!("crate::{}", path.join("::"))
+    }
+}
+
+fn get_primitive_type(name: &str) -> Result<&'static str, CodegenError> {
+    match name {
+        "boolean" => Ok("bool"),
+        "byte" => Ok("i8"),
+        "char" => Ok("u16"),
+        "int" => Ok("i32"),
+        "long" => Ok("i64"),
+        "float" => Ok("f32"),
+        "double" => Ok("f64"),
+        _ => Err(CodegenError::Unsupported(format!("Type `{name}`"))),
+    }
+}
+
+fn get_float_literal(value: f64, suffix: &str) -> String {
+    if value.is_nan() {
+        format!("{suffix}::NAN")
+    } else if value == f64::INFINITY {
+        format!("{suffix}::INFINITY")
+    } else if value == f64::NEG_INFINITY {
+        format!("{suffix}::NEG_INFINITY")
+    } else if suffix == "f32" {
+        format!("{:?}", value as f32)
+    } else {
+        format!("{value:?}")
+    }
+}
+
+/// Traits derived by parcelables and unions (`Debug` and the ones enabled via
+/// `@RustDerive`, e.g. `@RustDerive(Clone=true, PartialEq=true)`)
+fn get_derives(annotations: &[ast::Annotation]) -> Vec<String> {
+    let mut derives: Vec<String> = annotations
+        .iter()
+        .filter(|a| a.name == "@RustDerive")
+        .flat_map(|a| a.key_values.iter())
+        .filter(|(_, v)| v.as_deref() == Some("true"))
+        .map(|(k, _)| k.clone())
+        .collect();
+    derives.sort();
+    derives.insert(0, String::from("Debug"));
+    derives
+}
+
+fn is_copy(type_: &ast::Type) -> bool {
+    matches!(
+        type_.kind,
+        ast::TypeKind::Primitive | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Enum)
+    )
+}
+
+fn is_array(type_: &ast::Type) -> bool {
+    matches!(type_.kind, ast::TypeKind::Array | ast::TypeKind::List)
+}
+
+/// Types borrowed via `as_deref()` when nullable (`&str` and slices)
+fn is_sliceable(type_: &ast::Type) -> bool {
+    is_array(type_) || matches!(type_.kind, ast::TypeKind::String)
+}
+
+fn is_nullable_field(field: &ast::Field) -> bool {
+    has_annotation(&field.annotations, "@nullable")
+        || matches!(
+            field.field_type.kind,
+            ast::TypeKind::AndroidType(ast::AndroidTypeKind::IBinder)
+                | ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Interface)
+        )
+}
+
+fn get_arg_name(arg: &ast::Arg, index: usize) -> String {
+    match &arg.name {
+        Some(name) => format!("_arg_{name}"),
+        None => format!("_arg_{index}"),
+    }
+}
+
+fn to_ident(name: &str) -> String {
+    if matches!(name, "self" | "Self" | "super" | "crate") {
+        format!("{name}_")
+    } else if RUST_KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::generate_file;
+
+    #[test]
+    fn test_interface() {
+        let interface = r#"package com.example;
+            import com.example.Bar;
+            import com.example.Color;
+            import com.example.IListener;
+
+            /** Example service */
+            interface IFoo {
+                /** Version */
+                const int VERSION = 1 + 2;
+                const String NAME = "foo";
+
+                String getName();
+                void setValues(in int[] values, out int[] previous);
+                @nullable Bar getBar(in Color color, in @nullable String filter);
+                void update(inout Bar bar, in List<String> tags, in byte[] data);
+                oneway void register(IListener listener);
+            }
+        "#;
+        let bar = "package com.example; parcelable Bar {}";
+        let color = "package com.example; enum Color { RED }";
+        let listener = "package com.example; oneway interface IListener { void onEvent(); }";
+
+        let code = generate_file(
+            &[
+                ("IFoo", interface),
+                ("Bar", bar),
+                ("Color", color),
+                ("IListener", listener),
+            ],
+            "IFoo",
+            generate,
+        )
+        .expect("code");
+        insta::assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_parcelable() {
+        let parcelable = r#"package com.example;
+            import com.example.Color;
+            import com.example.IListener;
+
+            /** Example parcelable */
+            @RustDerive(Clone=true, PartialEq=true)
+            parcelable Bar {
+                const int MAX = 10;
+
+                /** Name */
+                String name = "bar";
+                int count = MAX - 1;
+                @nullable String description;
+                byte[] data = {1, -1};
+                double ratio = 0.5;
+                char c = 'x';
+                Color color = Color.GREEN;
+                Color[] colors;
+                IListener listener;
+                ParcelableHolder extension;
+
+                parcelable Inner {
+                    boolean enabled;
+                }
+            }
+        "#;
+        let color = "package com.example; enum Color { RED, GREEN }";
+        let listener = "package com.example; interface IListener {}";
+
+        let code = generate_file(
+            &[
+                ("Bar", parcelable),
+                ("Color", color),
+                ("IListener", listener),
+            ],
+            "Bar",
+            generate,
+        )
+        .expect("code");
+        insta::assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_union() {
+        let union = r#"package com.example;
+            union Value {
+                int number = 3;
+                String text;
+                long[] values;
+            }
+        "#;
+
+        let code = generate_file(&[("Value", union)], "Value", generate).expect("code");
+        insta::assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_enum() {
+        let enum_ = r#"package com.example;
+            /** Example enum */
+            @Backing(type="int")
+            enum Status {
+                /** Success */
+                OK,
+                ERROR = -1,
+                UNKNOWN = 1 << 4,
+                type,
+            }
+        "#;
+
+        let code = generate_file(&[("Status", enum_)], "Status", generate).expect("code");
+        insta::assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let content = "package p; interface IFoo { void foo(in Map<String, String> m); }";
+        assert_eq!(
+            generate_file(&[("IFoo", content)], "IFoo", generate),
+            Err(CodegenError::Unsupported(String::from("Type `Map`")))
+        );
+    }
+
+    #[test]
+    fn test_to_ident() {
+        assert_eq!(to_ident("foo"), "foo");
+        assert_eq!(to_ident("type"), "r#type");
+        assert_eq!(to_ident("self"), "self_");
+    }
+}
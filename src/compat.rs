@@ -0,0 +1,680 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::path::Path;
+
+use crate::api_dump::format_type;
+use crate::ast;
+use crate::codegen::get_transaction_codes;
+use crate::diagnostic::{Diagnostic, DiagnosticCode, DiagnosticKind, RelatedInfo};
+use crate::parser::ParseFileResult;
+
+/// Incompatibilities between two versions of a set of AIDL files (see
+/// [`check_compatibility`])
+#[derive(Debug, Clone)]
+pub struct Compatibility<ID> {
+    /// Diagnostics by file of the new version
+    pub diagnostics: HashMap<ID, Vec<Diagnostic>>,
+
+    /// Diagnostics of the removed top-level items, by file of the old version
+    ///
+    /// Their range is the old definition, which is also given as related info (with the
+    /// name of the old file), so they must not be merged into the results of the new
+    /// version.
+    pub removed_items: HashMap<ID, Vec<Diagnostic>>,
+}
+
+/// Check that the new version of a set of AIDL files is backward compatible with the
+/// old one (e.g. the latest frozen version of a stable AIDL package)
+///
+/// The items are matched by qualified name and the following changes are reported:
+/// - removed items, methods, fields, constants and enum elements
+/// - items which changed kind (e.g. a parcelable into a union)
+/// - changed transaction codes (explicit or implicit) and method signatures
+/// - fields which are not appended after the existing ones, or whose type changed
+/// - changed values of constants and enum elements, and changed backing types
+///
+/// The diagnostics are grouped by file of the new version, with a related info pointing
+/// at the old definition (in the file of the old version, see `RelatedInfo::file`).
+/// The removed top-level items have no file in the new version and are reported
+/// separately, by file of the old version (see [`Compatibility::removed_items`]).
+///
+/// Example:
+/// ```
+/// use aidl_parser::compat;
+/// use aidl_parser::diagnostic::DiagnosticCode;
+/// use aidl_parser::Parser;
+///
+/// let mut old = Parser::new();
+/// old.add_content("old", "package p; interface IFoo { void a(); void b(); }");
+/// let mut new = Parser::new();
+/// new.add_content("new", "package p; interface IFoo { void b(); void a(); }");
+///
+/// let compatibility = compat::check_compatibility(&old.validate(), &new.validate());
+/// let codes: Vec<_> = compatibility.diagnostics["new"].iter().map(|d| d.code).collect();
+/// assert_eq!(codes, [DiagnosticCode::ChangedTransactCode, DiagnosticCode::ChangedTransactCode]);
+/// assert!(compatibility.removed_items.is_empty());
+/// ```
+pub fn check_compatibility<ID>(
+    old: &HashMap<ID, ParseFileResult<ID>>,
+    new: &HashMap<ID, ParseFileResult<ID>>,
+) -> Compatibility<ID>
+where
+    ID: Eq + Hash + Clone + Debug + AsRef<Path>,
+{
+    let new_asts: HashMap<String, (&ID, &ast::Aidl)> = new
+        .iter()
+        .filter_map(|(id, res)| res.ast.as_ref().map(|ast| (ast.get_key(), (id, ast))))
+        .collect();
+
+    let mut compatibility = Compatibility {
+        diagnostics: HashMap::new(),
+        removed_items: HashMap::new(),
+    };
+    for (old_id, old_res) in old {
+        let old_ast = match &old_res.ast {
+            Some(ast) => ast,
+            None => continue,
+        };
+
+        // The old definitions are in the old file
+        let old_file = old_res.get_file_name();
+        match new_asts.get(&old_ast.get_key()) {
+            Some((new_id, new_ast)) => {
+                let mut file_diagnostics = Vec::new();
+                check_item(&old_ast.item, &new_ast.item, &mut file_diagnostics);
+
+                for info in file_diagnostics
+                    .iter_mut()
+                    .flat_map(|d| &mut d.related_infos)
+                {
+                    info.file = Some(old_file.clone());
+                }
+                if !file_diagnostics.is_empty() {
+                    compatibility
+                        .diagnostics
+                        .entry((*new_id).clone())
+                        .or_default()
+                        .extend(file_diagnostics);
+                }
+            }
+            None => {
+                let old_range = old_ast.item.get_symbol_range();
+                let mut diagnostic = create_diagnostic(
+                    DiagnosticCode::RemovedItem,
+                    old_range,
+                    format!("Item `{}` has been removed", old_ast.get_key()),
+                    old_range,
+                );
+                diagnostic.related_infos[0].file = Some(old_file);
+                compatibility
+                    .removed_items
+                    .entry(old_id.clone())
+                    .or_default()
+                    .push(diagnostic);
+            }
+        }
+    }
+
+    compatibility
+}
+
+fn check_item(old: &ast::Item, new: &ast::Item, diagnostics: &mut Vec<Diagnostic>) {
+    match (old, new) {
+        (ast::Item::Interface(old), ast::Item::Interface(new)) => {
+            check_interface(old, new, diagnostics)
+        }
+        (ast::Item::Parcelable(old), ast::Item::Parcelable(new)) => {
+            check_fields(
+                old.elements.iter().filter_map(|el| el.as_field()),
+                new.elements.iter().filter_map(|el| el.as_field()),
+                &new.symbol_range,
+                diagnostics,
+            );
+            check_consts(
+                old.elements.iter().filter_map(|el| match el {
+                    ast::ParcelableElement::Const(c) => Some(c),
+                    ast::ParcelableElement::Field(_) => None,
+                }),
+                new.elements.iter().filter_map(|el| match el {
+                    ast::ParcelableElement::Const(c) => Some(c),
+                    ast::ParcelableElement::Field(_) => None,
+                }),
+                &new.symbol_range,
+                diagnostics,
+            );
+        }
+        (ast::Item::Union(old), ast::Item::Union(new)) => {
+            check_fields(
+                old.elements.iter().filter_map(|el| el.as_field()),
+                new.elements.iter().filter_map(|el| el.as_field()),
+                &new.symbol_range,
+                diagnostics,
+            );
+            check_consts(
+                old.elements.iter().filter_map(|el| match el {
+                    ast::UnionElement::Const(c) => Some(c),
+                    ast::UnionElement::Field(_) => None,
+                }),
+                new.elements.iter().filter_map(|el| match el {
+                    ast::UnionElement::Const(c) => Some(c),
+                    ast::UnionElement::Field(_) => None,
+                }),
+                &new.symbol_range,
+                diagnostics,
+            );
+        }
+        (ast::Item::Enum(old), ast::Item::Enum(new)) => check_enum(old, new, diagnostics),
+        _ => {
+            diagnostics.push(create_diagnostic(
+                DiagnosticCode::ChangedItemKind,
+                new.get_symbol_range(),
+                format!(
+                    "Item `{}` has been changed from {} to {}",
+                    new.get_name(),
+                    get_item_kind(old),
+                    get_item_kind(new)
+                ),
+                old.get_symbol_range(),
+            ));
+            return;
+        }
+    }
+
+    // Nested items
+    for old_nested in old.get_nested_items() {
+        match new
+            .get_nested_items()
+            .iter()
+            .find(|i| i.get_name() == old_nested.get_name())
+        {
+            Some(new_nested) => check_item(old_nested, new_nested, diagnostics),
+            None => diagnostics.push(create_diagnostic(
+                DiagnosticCode::RemovedItem,
+                new.get_symbol_range(),
+                format!("Nested item `{}` has been removed", old_nested.get_name()),
+                old_nested.get_symbol_range(),
+            )),
+        }
+    }
+}
+
+fn check_interface(old: &ast::Interface, new: &ast::Interface, diagnostics: &mut Vec<Diagnostic>) {
+    let new_methods = get_transaction_codes(new);
+    for (old_method, old_code) in get_transaction_codes(old) {
+        let (new_method, new_code) =
+            match new_methods.iter().find(|(m, _)| m.name == old_method.name) {
+                Some(m) => m,
+                None => {
+                    diagnostics.push(create_diagnostic(
+                        DiagnosticCode::RemovedMethod,
+                        &new.symbol_range,
+                        format!("Method `{}` has been removed", old_method.name),
+                        &old_method.symbol_range,
+                    ));
+                    continue;
+                }
+            };
+
+        if old_code != *new_code {
+            diagnostics.push(create_diagnostic(
+                DiagnosticCode::ChangedTransactCode,
+                &new_method.symbol_range,
+                format!(
+                    "Transaction code of method `{}` has been changed from {old_code} to {new_code}",
+                    new_method.name
+                ),
+                &old_method.symbol_range,
+            ));
+        }
+
+        let old_signature = get_signature(old, old_method);
+        if old_signature != get_signature(new, new_method) {
+            let mut diagnostic = create_diagnostic(
+                DiagnosticCode::ChangedMethodSignature,
+                &new_method.symbol_range,
+                format!("Signature of method `{}` has been changed", new_method.name),
+                &old_method.symbol_range,
+            );
+            diagnostic.hint = Some(format!("previous signature: `{old_signature}`"));
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    check_consts(
+        old.elements.iter().filter_map(|el| el.as_const()),
+        new.elements.iter().filter_map(|el| el.as_const()),
+        &new.symbol_range,
+        diagnostics,
+    );
+}
+
+// Fields (of parcelables and unions) are serialized in order, so they must keep
+// their position
+fn check_fields<'a, I, J>(old: I, new: J, new_range: &ast::Range, diagnostics: &mut Vec<Diagnostic>)
+where
+    I: Iterator<Item = &'a ast::Field>,
+    J: Iterator<Item = &'a ast::Field>,
+{
+    let new_fields: Vec<&ast::Field> = new.collect();
+    for (old_index, old_field) in old.enumerate() {
+        let (new_index, new_field) = match new_fields
+            .iter()
+            .enumerate()
+            .find(|(_, f)| f.name == old_field.name)
+        {
+            Some(f) => f,
+            None => {
+                diagnostics.push(create_diagnostic(
+                    DiagnosticCode::RemovedField,
+                    new_range,
+                    format!("Field `{}` has been removed", old_field.name),
+                    &old_field.symbol_range,
+                ));
+                continue;
+            }
+        };
+
+        if old_index != new_index {
+            diagnostics.push(create_diagnostic(
+                DiagnosticCode::ReorderedField,
+                &new_field.symbol_range,
+                format!(
+                    "Field `{}` has been moved from position {old_index} to {new_index}",
+                    new_field.name
+                ),
+                &old_field.symbol_range,
+            ));
+        }
+
+        let old_type = format_type(&old_field.field_type);
+        let new_type = format_type(&new_field.field_type);
+        if old_type != new_type {
+            diagnostics.push(create_diagnostic(
+                DiagnosticCode::ChangedFieldType,
+                &new_field.symbol_range,
+                format!(
+                    "Type of field `{}` has been changed from `{old_type}` to `{new_type}`",
+                    new_field.name
+                ),
+                &old_field.symbol_range,
+            ));
+        }
+    }
+}
+
+fn check_consts<'a, I, J>(old: I, new: J, new_range: &ast::Range, diagnostics: &mut Vec<Diagnostic>)
+where
+    I: Iterator<Item = &'a ast::Const>,
+    J: Iterator<Item = &'a ast::Const>,
+{
+    let new_consts: Vec<&ast::Const> = new.collect();
+    for old_const in old {
+        match new_consts.iter().find(|c| c.name == old_const.name) {
+            Some(new_const) => {
+                if format_type(&old_const.const_type) != format_type(&new_const.const_type)
+                    || old_const.evaluated_value != new_const.evaluated_value
+                {
+                    diagnostics.push(create_diagnostic(
+                        DiagnosticCode::ChangedConstantValue,
+                        &new_const.symbol_range,
+                        format!("Value of constant `{}` has been changed", new_const.name),
+                        &old_const.symbol_range,
+                    ));
+                }
+            }
+            None => diagnostics.push(create_diagnostic(
+                DiagnosticCode::RemovedConstant,
+                new_range,
+                format!("Constant `{}` has been removed", old_const.name),
+                &old_const.symbol_range,
+            )),
+        }
+    }
+}
+
+fn check_enum(old: &ast::Enum, new: &ast::Enum, diagnostics: &mut Vec<Diagnostic>) {
+    if old.get_backing_type() != new.get_backing_type() {
+        diagnostics.push(create_diagnostic(
+            DiagnosticCode::ChangedBackingType,
+            &new.symbol_range,
+            format!(
+                "Backing type of enum `{}` has been changed from `{}` to `{}`",
+                new.name,
+                old.get_backing_type(),
+                new.get_backing_type()
+            ),
+            &old.symbol_range,
+        ));
+    }
+
+    for old_element in &old.elements {
+        match new.elements.iter().find(|el| el.name == old_element.name) {
+            Some(new_element) => {
+                // The values are compared independently of the backing type
+                let old_value = old_element.evaluated_value.as_ref().and_then(get_integer);
+                let new_value = new_element.evaluated_value.as_ref().and_then(get_integer);
+                if old_value != new_value {
+                    diagnostics.push(create_diagnostic(
+                        DiagnosticCode::ChangedEnumValue,
+                        &new_element.symbol_range,
+                        format!(
+                            "Value of enum element `{}` has been changed",
+                            new_element.name
+                        ),
+                        &old_element.symbol_range,
+                    ));
+                }
+            }
+            None => diagnostics.push(create_diagnostic(
+                DiagnosticCode::RemovedEnumElement,
+                &new.symbol_range,
+                format!("Enum element `{}` has been removed", old_element.name),
+                &old_element.symbol_range,
+            )),
+        }
+    }
+}
+
+fn create_diagnostic(
+    code: DiagnosticCode,
+    range: &ast::Range,
+    message: String,
+    old_range: &ast::Range,
+) -> Diagnostic {
+    Diagnostic {
+        kind: DiagnosticKind::Error,
+        code,
+        range: range.clone(),
+        message,
+        context_message: Some(code.name().replace('-', " ")),
+        hint: None,
+        related_infos: Vec::from([RelatedInfo {
+            range: old_range.clone(),
+            message: String::from("previous definition"),
+            file: None,
+        }]),
+        fixes: Vec::new(),
+    }
+}
+
+// Signature of a method which is relevant for the compatibility, e.g.
+// `oneway void foo(in p.Bar, out int[])` (the names of the arguments do not matter)
+fn get_signature(interface: &ast::Interface, method: &ast::Method) -> String {
+    let args: Vec<String> = method
+        .args
+        .iter()
+        .map(|arg| {
+            let direction = match arg.direction {
+                ast::Direction::Unspecified => String::from("in"),
+                _ => arg.direction.to_string(),
+            };
+            format!("{direction} {}", format_type(&arg.arg_type))
+        })
+        .collect();
+
+    format!(
+        "{}{} {}({})",
+        if interface.oneway || method.oneway {
+            "oneway "
+        } else {
+            ""
+        },
+        format_type(&method.return_type),
+        method.name,
+        args.join(", ")
+    )
+}
+
+fn get_integer(value: &ast::ConstValue) -> Option<i64> {
+    match value {
+        ast::ConstValue::Byte(v) => Some(i64::from(*v)),
+        ast::ConstValue::Int(v) => Some(i64::from(*v)),
+        ast::ConstValue::Long(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn get_item_kind(item: &ast::Item) -> &'static str {
+    match item {
+        ast::Item::Interface(_) => "interface",
+        ast::Item::Parcelable(_) => "parcelable",
+        ast::Item::Union(_) => "union",
+        ast::Item::Enum(_) => "enum",
+    }
+}
+
+/// Old and new contents of `IFoo.aidl` with a changed transaction code, i.e. a diagnostic
+/// of the new file with a related info in the old one
+#[cfg(test)]
+pub(crate) const CHANGED_TRANSACT_CODE: (&str, &str) = (
+    "package p; interface IFoo { void a() = 1; }",
+    "package p;\ninterface IFoo { void a() = 2; }",
+);
+
+/// Validate the new version of [`CHANGED_TRANSACT_CODE`] (`{root}new/IFoo.aidl`) with
+/// the compatibility diagnostics against the old one (`{root}old/IFoo.aidl`)
+#[cfg(test)]
+pub(crate) fn check_changed_transact_code(root: &str) -> HashMap<String, ParseFileResult<String>> {
+    let (old_content, new_content) = CHANGED_TRANSACT_CODE;
+    let mut old = crate::Parser::new();
+    old.add_content(format!("{root}old/IFoo.aidl"), old_content);
+    let mut new = crate::Parser::new();
+    new.add_content(format!("{root}new/IFoo.aidl"), new_content);
+
+    let mut results = new.validate();
+    let compatibility = check_compatibility(&old.validate(), &results);
+    assert!(compatibility.removed_items.is_empty());
+    for (id, diagnostics) in compatibility.diagnostics {
+        results.get_mut(&id).expect("result").diagnostics = diagnostics;
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn check(old: &str, new: &str) -> Vec<(DiagnosticCode, String)> {
+        let mut old_parser = Parser::new();
+        old_parser.add_content("old", old);
+        let mut new_parser = Parser::new();
+        new_parser.add_content("new", new);
+
+        let mut compatibility = check_compatibility(&old_parser.validate(), &new_parser.validate());
+        let new_diagnostics = compatibility.diagnostics.remove("new").unwrap_or_default();
+        assert!(compatibility.diagnostics.is_empty());
+        assert!(compatibility.removed_items.is_empty());
+        for d in &new_diagnostics {
+            assert_eq!(d.related_infos.len(), 1);
+            assert_eq!(d.related_infos[0].file.as_deref(), Some("old"));
+        }
+
+        new_diagnostics
+            .into_iter()
+            .map(|d| (d.code, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_compatible() {
+        let old = "package p; interface IFoo { const int C = 1; void a(int i); }";
+        let new = "package p; interface IFoo { const int C = 2 - 1; void a(in int j); void b(); const int D = 2; }";
+        assert_eq!(check(old, new), []);
+
+        let old = "package p; parcelable Bar { int a; }";
+        let new = "package p; parcelable Bar { /** Doc */ int a; String b; }";
+        assert_eq!(check(old, new), []);
+    }
+
+    #[test]
+    fn test_related_info_in_old_file() {
+        let old = "package p; interface IFoo { void a() = 1; const int C = 1; }";
+        let new = "package p;\n\n/** Doc */\ninterface IFoo {\n    const int C = 2;\n    void a() = 2;\n}";
+        let mut old_parser = Parser::new();
+        old_parser.add_content("old", old);
+        let mut new_parser = Parser::new();
+        new_parser.add_content("new", new);
+
+        let compatibility = check_compatibility(&old_parser.validate(), &new_parser.validate());
+        let diagnostics = &compatibility.diagnostics["new"];
+        assert_eq!(diagnostics.len(), 2);
+
+        // The range of the diagnostic is in the new file, the related range in the old one
+        for (d, name) in diagnostics.iter().zip(["a", "C"]) {
+            assert_eq!(&new[d.range.start.offset..d.range.end.offset], name);
+            let info = &d.related_infos[0];
+            assert_eq!(info.file.as_deref(), Some("old"));
+            assert_eq!(&old[info.range.start.offset..info.range.end.offset], name);
+            assert_eq!(info.range.start.line_col.0, 1);
+        }
+    }
+
+    #[test]
+    fn test_interface() {
+        let old = r#"package p;
+            interface IFoo {
+                const String NAME = "foo";
+                const int VERSION = 1;
+                void a() = 1;
+                void b(in int[] values) = 2;
+                void c() = 3;
+                int d() = 4;
+                interface INested {}
+            }
+        "#;
+        let new = r#"package p;
+            interface IFoo {
+                const int VERSION = 2;
+                void a() = 5;
+                void b(out int[] values) = 2;
+                oneway void c() = 3;
+            }
+        "#;
+
+        assert_eq!(
+            check(old, new),
+            [
+                (
+                    DiagnosticCode::ChangedTransactCode,
+                    String::from("Transaction code of method `a` has been changed from 1 to 5")
+                ),
+                (
+                    DiagnosticCode::ChangedMethodSignature,
+                    String::from("Signature of method `b` has been changed")
+                ),
+                (
+                    DiagnosticCode::ChangedMethodSignature,
+                    String::from("Signature of method `c` has been changed")
+                ),
+                (
+                    DiagnosticCode::RemovedMethod,
+                    String::from("Method `d` has been removed")
+                ),
+                (
+                    DiagnosticCode::RemovedConstant,
+                    String::from("Constant `NAME` has been removed")
+                ),
+                (
+                    DiagnosticCode::ChangedConstantValue,
+                    String::from("Value of constant `VERSION` has been changed")
+                ),
+                (
+                    DiagnosticCode::RemovedItem,
+                    String::from("Nested item `INested` has been removed")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parcelable() {
+        let old = "package p; parcelable Bar { int a; String b; long c; int d; }";
+        let new = "package p; parcelable Bar { int a; int z; String b; int c; }";
+
+        assert_eq!(
+            check(old, new),
+            [
+                (
+                    DiagnosticCode::ReorderedField,
+                    String::from("Field `b` has been moved from position 1 to 2")
+                ),
+                (
+                    DiagnosticCode::ReorderedField,
+                    String::from("Field `c` has been moved from position 2 to 3")
+                ),
+                (
+                    DiagnosticCode::ChangedFieldType,
+                    String::from("Type of field `c` has been changed from `long` to `int`")
+                ),
+                (
+                    DiagnosticCode::RemovedField,
+                    String::from("Field `d` has been removed")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enum() {
+        let old = "package p; enum E { A, B, C }";
+        let new = r#"package p; @Backing(type="int") enum E { A, C, B }"#;
+
+        assert_eq!(
+            check(old, new),
+            [
+                (
+                    DiagnosticCode::ChangedBackingType,
+                    String::from("Backing type of enum `E` has been changed from `byte` to `int`")
+                ),
+                (
+                    DiagnosticCode::ChangedEnumValue,
+                    String::from("Value of enum element `B` has been changed")
+                ),
+                (
+                    DiagnosticCode::ChangedEnumValue,
+                    String::from("Value of enum element `C` has been changed")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_item_kind() {
+        let old = "package p; parcelable Bar { int a; }";
+        let new = "package p; union Bar { int a; }";
+
+        assert_eq!(
+            check(old, new),
+            [(
+                DiagnosticCode::ChangedItemKind,
+                String::from("Item `Bar` has been changed from parcelable to union")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_removed_item() {
+        let mut old_parser = Parser::new();
+        old_parser.add_content("IFoo", "package p; interface IFoo {}");
+        old_parser.add_content("Bar", "package p; parcelable Bar {}");
+        let mut new_parser = Parser::new();
+        new_parser.add_content("IFoo", "package p; interface IFoo {}");
+
+        let compatibility = check_compatibility(&old_parser.validate(), &new_parser.validate());
+        assert!(compatibility.diagnostics.is_empty());
+
+        // Reported in the old file, which has no counterpart in the new version
+        assert_eq!(compatibility.removed_items.len(), 1);
+        let diagnostics = &compatibility.removed_items["Bar"];
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::RemovedItem);
+        assert_eq!(diagnostics[0].message, "Item `p.Bar` has been removed");
+        let info = &diagnostics[0].related_infos[0];
+        assert_eq!(info.file.as_deref(), Some("Bar"));
+        assert_eq!(info.range, diagnostics[0].range);
+        assert_eq!(info.range.start.offset, 22);
+    }
+}
@@ -0,0 +1,1067 @@
+use std::sync::LazyLock;
+
+use serde_derive::Serialize;
+
+use crate::annotations::{self, AnnotationTarget};
+use crate::ast;
+use crate::cst::{self, TokenKind};
+use crate::diagnostic::Fix;
+
+const PRIMITIVE_TYPES: &[&str] = &[
+    "boolean", "byte", "char", "short", "int", "long", "float", "double",
+];
+const BUILTIN_TYPES: &[&str] = &["String", "CharSequence", "List", "Map"];
+const ITEM_KEYWORDS: &[&str] = &["interface", "parcelable", "enum", "union"];
+const DIRECTIONS: &[&str] = &["in", "out", "inout"];
+const ITEM_TARGETS: &[AnnotationTarget] = &[
+    AnnotationTarget::Interface,
+    AnnotationTarget::Parcelable,
+    AnnotationTarget::Union,
+    AnnotationTarget::Enum,
+];
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Type,
+    Interface,
+    Parcelable,
+    Union,
+    Enum,
+    EnumElement,
+    Const,
+    Annotation,
+    Package,
+}
+
+/// A suggestion for the text at the cursor, as returned by Parser::complete()
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+
+    /// Additional information (e.g. the qualified name of an item)
+    pub detail: Option<String>,
+
+    /// Range of the (partially typed) text which is replaced by the label
+    pub range: ast::Range,
+
+    /// Additional edits, e.g. to import the suggested item
+    pub fixes: Vec<Fix>,
+}
+
+// What is expected at the cursor
+#[derive(Debug, PartialEq)]
+enum Context<'a> {
+    Annotation(Vec<AnnotationTarget>),
+    Import,
+    Package,
+    Member {
+        qualifier: &'a str,
+        types_only: bool,
+    },
+    Value,
+    Type {
+        directions: bool,
+    },
+    TopLevel {
+        oneway: bool,
+    },
+    Body {
+        interface: bool,
+    },
+}
+
+// An item of the project
+struct KnownItem<'a> {
+    key: ast::ItemKey,
+    package: &'a str,
+    item: &'a ast::Item,
+}
+
+impl<'a> KnownItem<'a> {
+    // Key without the package, e.g. `Outer.Inner`
+    fn get_path(&self) -> &str {
+        self.key
+            .strip_prefix(self.package)
+            .and_then(|p| p.strip_prefix('.'))
+            .unwrap_or(&self.key)
+    }
+}
+
+// Package, imports and declared items of the completed file (from its content, which
+// is usually not valid while typing)
+struct Scope<'a> {
+    package: Option<&'a str>,
+    imports: Vec<&'a str>,
+    declared: Vec<&'a str>,
+    import_insertion: (usize, &'static str),
+}
+
+impl<'a> Scope<'a> {
+    fn new(content: &'a str, tokens: &[cst::Token]) -> Self {
+        let tokens: Vec<&cst::Token> = tokens.iter().filter(|t| !t.kind.is_trivia()).collect();
+
+        let mut package = None;
+        let mut imports = Vec::new();
+        let mut declared = Vec::new();
+        let mut package_end = None;
+        let mut imports_end = None;
+
+        for (i, token) in tokens.iter().enumerate() {
+            match (token.kind, token.text.as_str()) {
+                (TokenKind::Keyword, "package") if package.is_none() => {
+                    if let Some((name, end)) = get_qualified_name(content, &tokens[i + 1..]) {
+                        package = Some(name);
+                        package_end = Some(end);
+                    }
+                }
+                (TokenKind::Keyword, "import") => {
+                    if let Some((name, end)) = get_qualified_name(content, &tokens[i + 1..]) {
+                        imports.push(name);
+                        imports_end = Some(end);
+                    }
+                }
+                (TokenKind::Keyword, keyword) if ITEM_KEYWORDS.contains(&keyword) => {
+                    if let Some(name) = tokens.get(i + 1).filter(|t| t.kind == TokenKind::Ident) {
+                        let range = &name.full_range;
+                        declared.push(&content[range.start.offset..range.end.offset]);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // After the last import or after the package
+        let import_insertion = match (imports_end, package_end) {
+            (Some(end), _) => (end, "\n"),
+            (None, Some(end)) => (end, "\n\n"),
+            (None, None) => (0, ""),
+        };
+
+        Scope {
+            package,
+            imports,
+            declared,
+            import_insertion,
+        }
+    }
+
+    // Name used to reference the item from the file and the import which is required
+    fn get_reference(&self, known: &KnownItem) -> (String, Option<String>) {
+        let path = known.get_path();
+
+        for import in &self.imports {
+            let last = import.rsplit('.').next().unwrap_or(import);
+            if known.key == *import {
+                return (last.to_owned(), None);
+            }
+            if let Some(rest) = known.key.strip_prefix(&format!("{import}.")) {
+                return (format!("{last}.{rest}"), None);
+            }
+        }
+
+        if self.package == Some(known.package) {
+            (path.to_owned(), None)
+        } else {
+            let first = path.split('.').next().unwrap_or(path);
+            (path.to_owned(), Some(format!("{}.{first}", known.package)))
+        }
+    }
+
+    // Key of the item referenced by the given name (e.g. `Item`, `Outer.Inner` or
+    // `pkg.Item`)
+    fn resolve<'b>(&self, name: &str, items: &'b [KnownItem]) -> Option<&'b KnownItem<'b>> {
+        let find = |key: &str| items.iter().find(|k| k.key == key);
+        let (first, rest) = match name.split_once('.') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (name, None),
+        };
+
+        // Imported
+        let imported = self.imports.iter().find_map(|import| {
+            let last = import.rsplit('.').next()?;
+            (last == first).then(|| match rest {
+                Some(rest) => format!("{import}.{rest}"),
+                None => import.to_string(),
+            })
+        });
+
+        imported
+            .and_then(|key| find(&key))
+            .or_else(|| find(&format!("{}.{name}", self.package?)))
+            .or_else(|| find(name))
+            .or_else(|| {
+                // Nested item of an enclosing item
+                let suffix = format!(".{name}");
+                items.iter().find(|k| {
+                    Some(k.package) == self.package
+                        && k.key.ends_with(&suffix)
+                        && self.is_declared(k)
+                })
+            })
+    }
+
+    // Item which is not imported (e.g. `Outer.Inner` for `pkg.Outer.Inner`), if the
+    // name is not ambiguous, with the import which is required
+    fn resolve_unimported<'b>(
+        &self,
+        name: &str,
+        items: &'b [KnownItem],
+    ) -> Option<(&'b KnownItem<'b>, Option<String>)> {
+        let mut candidates = items.iter().filter(|k| k.get_path() == name);
+        let known = candidates.next()?;
+        if candidates.next().is_some() {
+            return None;
+        }
+
+        Some((known, self.get_reference(known).1))
+    }
+
+    fn is_declared(&self, known: &KnownItem) -> bool {
+        let first = known.get_path().split('.').next().unwrap_or_default();
+        Some(known.package) == self.package && self.declared.contains(&first)
+    }
+
+    // Edit which adds the import (if not already imported)
+    fn get_import_fixes(&self, import: Option<String>, position: &ast::Position) -> Vec<Fix> {
+        import
+            .filter(|import| !self.imports.contains(&import.as_str()))
+            .map(|import| {
+                Vec::from([Fix::insert(
+                    &format!("import `{import}`"),
+                    position,
+                    if self.import_insertion.1.is_empty() {
+                        format!("import {import};\n")
+                    } else {
+                        format!("{}import {import};", self.import_insertion.1)
+                    },
+                )])
+            })
+            .unwrap_or_default()
+    }
+}
+
+// Qualified name at the start of the (non-trivia) tokens, terminated by `;`, with the
+// end offset of the statement
+fn get_qualified_name<'a>(content: &'a str, tokens: &[&cst::Token]) -> Option<(&'a str, usize)> {
+    let start = tokens.first()?.full_range.start.offset;
+    let mut end = start;
+    let mut expect_ident = true;
+
+    for token in tokens {
+        match (expect_ident, token.kind, token.text.as_str()) {
+            (true, TokenKind::Ident, _) => end = token.full_range.end.offset,
+            (false, TokenKind::Punct, ".") => (),
+            (false, TokenKind::Punct, ";") => {
+                return Some((&content[start..end], token.full_range.end.offset));
+            }
+            _ => return None,
+        }
+        expect_ident = !expect_ident;
+    }
+
+    None
+}
+
+/// Suggestions for the given content at the given position (1-based line and column)
+pub(crate) fn complete<'a, I>(
+    content: &str,
+    line_col: (usize, usize),
+    asts: I,
+) -> Vec<CompletionItem>
+where
+    I: IntoIterator<Item = &'a ast::Aidl>,
+{
+    let offset = to_offset(content, line_col);
+    let tokens = cst::lex(content);
+    let code = match get_code(content, &tokens, offset) {
+        Some(code) => code,
+        None => return Vec::new(),
+    };
+    let (code, after) = code.split_at(offset);
+
+    let qualified_start = code
+        .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.')
+        .len();
+    let qualified = &code[qualified_start..];
+    let word_start = qualified_start + qualified.rfind('.').map_or(0, |i| i + 1);
+    let word = &code[word_start..];
+
+    let context = match get_context(&code[..qualified_start], qualified, after) {
+        Some(context) => context,
+        None => return Vec::new(),
+    };
+
+    let mut known_items: Vec<KnownItem> = Vec::new();
+    let mut packages: Vec<&str> = Vec::new();
+    for ast in asts {
+        packages.push(&ast.package.name);
+        for (key, item) in ast.get_all_items() {
+            known_items.push(KnownItem {
+                key,
+                package: &ast.package.name,
+                item,
+            });
+        }
+    }
+    known_items.sort_by(|k1, k2| k1.key.cmp(&k2.key));
+    known_items.dedup_by(|k1, k2| k1.key == k2.key);
+    packages.sort_unstable();
+    packages.dedup();
+
+    let scope = Scope::new(content, &tokens);
+    let lookup = line_col::LineColLookup::new(content);
+    let import_position = ast::Position::new(&lookup, scope.import_insertion.0);
+
+    let mut suggestions = Suggestions {
+        items: Vec::new(),
+        filter: word,
+        range: ast::Range {
+            start: ast::Position::new(&lookup, word_start),
+            end: ast::Position::new(&lookup, offset),
+        },
+    };
+
+    match context {
+        Context::Annotation(targets) => {
+            for known in annotations::KNOWN_ANNOTATIONS {
+                if known.targets.iter().any(|t| targets.contains(t)) {
+                    suggestions.add(known.name, CompletionKind::Annotation, None);
+                }
+            }
+        }
+        Context::Import => {
+            suggestions.filter = qualified;
+            suggestions.range.start = ast::Position::new(&lookup, qualified_start);
+            for known in &known_items {
+                if !scope.imports.contains(&known.key.as_str()) && !scope.is_declared(known) {
+                    let kind = get_item_kind(known.item);
+                    suggestions.add(&known.key, kind, None);
+                }
+            }
+            for android in ast::AndroidTypeKind::get_all() {
+                suggestions.add(android.get_qualified_name(), CompletionKind::Type, None);
+            }
+        }
+        Context::Package => {
+            suggestions.filter = qualified;
+            suggestions.range.start = ast::Position::new(&lookup, qualified_start);
+            for package in packages {
+                suggestions.add(package, CompletionKind::Package, None);
+            }
+        }
+        Context::Member {
+            qualifier,
+            types_only,
+        } => {
+            // Members of an item, imported if required (e.g. `Outer.Inner`)
+            let resolved = scope
+                .resolve(qualifier, &known_items)
+                .map(|known| (known, None))
+                .or_else(|| scope.resolve_unimported(qualifier, &known_items));
+            if let Some((known, import)) = resolved {
+                let fixes = scope.get_import_fixes(import, &import_position);
+                add_members(&mut suggestions, known, &known_items, types_only, &fixes);
+            }
+
+            // Qualified name (e.g. `pkg.Item`)
+            let prefix = format!("{qualifier}.");
+            for known in &known_items {
+                if let Some(rest) = known.key.strip_prefix(&prefix) {
+                    let kind = get_item_kind(known.item);
+                    suggestions.add(rest, kind, Some(known.key.clone()));
+                }
+            }
+            for android in ast::AndroidTypeKind::get_all() {
+                if let Some(rest) = android.get_qualified_name().strip_prefix(&prefix) {
+                    let detail = Some(android.get_qualified_name().to_owned());
+                    suggestions.add(rest, CompletionKind::Type, detail);
+                }
+            }
+        }
+        Context::Value => {
+            for known in known_items.iter().filter(|k| scope.is_declared(k)) {
+                match known.item {
+                    ast::Item::Enum(_) => add_members(&mut suggestions, known, &[], false, &[]),
+                    _ => add_consts(&mut suggestions, known, &[]),
+                }
+            }
+            for keyword in ["true", "false"] {
+                suggestions.add(keyword, CompletionKind::Keyword, None);
+            }
+            add_items(&mut suggestions, &scope, &known_items, &import_position);
+        }
+        Context::Type { directions } => {
+            if directions {
+                for direction in DIRECTIONS {
+                    suggestions.add(direction, CompletionKind::Keyword, None);
+                }
+            }
+            add_types(&mut suggestions, &scope, &known_items, &import_position);
+        }
+        Context::TopLevel { oneway } => {
+            let keywords: &[&str] = if oneway {
+                &["interface"]
+            } else {
+                &[
+                    "package",
+                    "import",
+                    "interface",
+                    "parcelable",
+                    "enum",
+                    "union",
+                    "oneway",
+                ]
+            };
+            for keyword in keywords {
+                suggestions.add(keyword, CompletionKind::Keyword, None);
+            }
+        }
+        Context::Body { interface } => {
+            suggestions.add("const", CompletionKind::Keyword, None);
+            if interface {
+                suggestions.add("oneway", CompletionKind::Keyword, None);
+                suggestions.add("void", CompletionKind::Keyword, None);
+            }
+            for keyword in ITEM_KEYWORDS {
+                suggestions.add(keyword, CompletionKind::Keyword, None);
+            }
+            add_types(&mut suggestions, &scope, &known_items, &import_position);
+        }
+    }
+
+    suggestions.items
+}
+
+// Items filtered by the partially typed text
+struct Suggestions<'a> {
+    items: Vec<CompletionItem>,
+    filter: &'a str,
+    range: ast::Range,
+}
+
+impl<'a> Suggestions<'a> {
+    fn add(&mut self, label: &str, kind: CompletionKind, detail: Option<String>) {
+        self.add_with_fixes(label, kind, detail, Vec::new());
+    }
+
+    fn add_with_fixes(
+        &mut self,
+        label: &str,
+        kind: CompletionKind,
+        detail: Option<String>,
+        fixes: Vec<Fix>,
+    ) {
+        let matches = label
+            .to_lowercase()
+            .starts_with(&self.filter.to_lowercase());
+        let exists = self
+            .items
+            .iter()
+            .any(|i| i.label == label && i.detail == detail);
+
+        if matches && !exists {
+            self.items.push(CompletionItem {
+                label: label.to_owned(),
+                kind,
+                detail,
+                range: self.range.clone(),
+                fixes,
+            });
+        }
+    }
+}
+
+fn add_types(
+    suggestions: &mut Suggestions,
+    scope: &Scope,
+    known_items: &[KnownItem],
+    import_position: &ast::Position,
+) {
+    for name in PRIMITIVE_TYPES.iter().chain(BUILTIN_TYPES) {
+        suggestions.add(name, CompletionKind::Type, None);
+    }
+    for android in ast::AndroidTypeKind::get_all() {
+        let detail = Some(android.get_qualified_name().to_owned());
+        suggestions.add(android.get_name(), CompletionKind::Type, detail);
+    }
+    add_items(suggestions, scope, known_items, import_position);
+}
+
+// Items of the project, imported if required
+fn add_items(
+    suggestions: &mut Suggestions,
+    scope: &Scope,
+    known_items: &[KnownItem],
+    import_position: &ast::Position,
+) {
+    for known in known_items {
+        let (label, import) = scope.get_reference(known);
+        let fixes = scope.get_import_fixes(import, import_position);
+
+        let kind = get_item_kind(known.item);
+        suggestions.add_with_fixes(&label, kind, Some(known.key.clone()), fixes);
+    }
+}
+
+// Consts, enum elements (unless only types are expected) and nested items
+fn add_members(
+    suggestions: &mut Suggestions,
+    known: &KnownItem,
+    known_items: &[KnownItem],
+    types_only: bool,
+    fixes: &[Fix],
+) {
+    match known.item {
+        _ if types_only => (),
+        ast::Item::Enum(e) => {
+            for el in &e.elements {
+                let detail = Some(e.name.clone());
+                let kind = CompletionKind::EnumElement;
+                suggestions.add_with_fixes(&el.name, kind, detail, fixes.to_vec());
+            }
+        }
+        _ => add_consts(suggestions, known, fixes),
+    }
+
+    let prefix = format!("{}.", known.key);
+    for nested in known_items {
+        if let Some(name) = nested.key.strip_prefix(&prefix) {
+            let kind = get_item_kind(nested.item);
+            suggestions.add_with_fixes(name, kind, Some(nested.key.clone()), fixes.to_vec());
+        }
+    }
+}
+
+fn add_consts(suggestions: &mut Suggestions, known: &KnownItem, fixes: &[Fix]) {
+    let consts: Vec<&ast::Const> = match known.item {
+        ast::Item::Interface(i) => i.elements.iter().filter_map(|el| el.as_const()).collect(),
+        ast::Item::Parcelable(p) => p
+            .elements
+            .iter()
+            .filter_map(|el| match el {
+                ast::ParcelableElement::Const(c) => Some(c),
+                ast::ParcelableElement::Field(_) => None,
+            })
+            .collect(),
+        ast::Item::Union(u) => u
+            .elements
+            .iter()
+            .filter_map(|el| match el {
+                ast::UnionElement::Const(c) => Some(c),
+                ast::UnionElement::Field(_) => None,
+            })
+            .collect(),
+        ast::Item::Enum(_) => Vec::new(),
+    };
+
+    for c in consts {
+        let detail = Some(format!("const {}", c.const_type.name));
+        suggestions.add_with_fixes(&c.name, CompletionKind::Const, detail, fixes.to_vec());
+    }
+}
+
+fn get_item_kind(item: &ast::Item) -> CompletionKind {
+    match item {
+        ast::Item::Interface(_) => CompletionKind::Interface,
+        ast::Item::Parcelable(_) => CompletionKind::Parcelable,
+        ast::Item::Union(_) => CompletionKind::Union,
+        ast::Item::Enum(_) => CompletionKind::Enum,
+    }
+}
+
+// Context from the code before the completed (qualified) name (and from the code
+// after it for annotations)
+fn get_context<'a>(before: &str, qualified: &'a str, after: &str) -> Option<Context<'a>> {
+    if let Some(before) = before.strip_suffix('@') {
+        let after = after.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_');
+        return Some(Context::Annotation(get_annotation_targets(before, after)));
+    }
+
+    let statement_start = before.rfind([';', '{', '}']).map_or(0, |i| i + 1);
+    let statement = remove_annotations(&before[statement_start..])?;
+    let tokens: Vec<&str> = statement.split_whitespace().collect();
+
+    match tokens.first() {
+        Some(&"import") if tokens.len() == 1 => return Some(Context::Import),
+        Some(&"package") if tokens.len() == 1 => return Some(Context::Package),
+        Some(&"import") | Some(&"package") => return None,
+        _ => (),
+    }
+
+    let context = get_declaration_context(before, &statement, &tokens)?;
+
+    match (qualified.rsplit_once('.'), context) {
+        (None, context) => Some(context),
+        (Some((qualifier, _)), Context::Value) => Some(Context::Member {
+            qualifier,
+            types_only: false,
+        }),
+        (Some((qualifier, _)), Context::Type { .. } | Context::Body { .. }) => {
+            Some(Context::Member {
+                qualifier,
+                types_only: true,
+            })
+        }
+        (Some(_), _) => None,
+    }
+}
+
+// Context inside a declaration (e.g. a method or a const), from the code before the
+// completed name and from its current statement
+fn get_declaration_context(
+    before: &str,
+    statement: &str,
+    tokens: &[&str],
+) -> Option<Context<'static>> {
+    let enclosing = get_enclosing_items(before);
+    if enclosing.last() == Some(&"enum") {
+        // Value of an element, otherwise the name of a new element
+        let element = statement.rsplit(',').next().unwrap_or_default();
+        return element.contains('=').then_some(Context::Value);
+    }
+    if statement.contains('=') {
+        return Some(Context::Value);
+    }
+
+    let count = |c| statement.matches(c).count();
+    if count('<') > count('>') {
+        return Some(Context::Type { directions: false });
+    }
+    if count('(') > count(')') {
+        let arg = statement.rsplit(['(', ',']).next().unwrap_or_default();
+        return match arg.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => Some(Context::Type { directions: true }),
+            [direction] if DIRECTIONS.contains(&direction) => {
+                Some(Context::Type { directions: false })
+            }
+            _ => None,
+        };
+    }
+
+    match (enclosing.last(), tokens) {
+        (None, []) => Some(Context::TopLevel { oneway: false }),
+        (None, ["oneway"]) => Some(Context::TopLevel { oneway: true }),
+        (Some(item), []) => Some(Context::Body {
+            interface: *item == "interface",
+        }),
+        (Some(_), ["const" | "oneway"]) => Some(Context::Type { directions: false }),
+        _ => None,
+    }
+}
+
+// Declarations which can be annotated at the end of the code, restricted by the
+// annotated declaration after the annotation (if already typed)
+fn get_annotation_targets(before: &str, after: &str) -> Vec<AnnotationTarget> {
+    let statement_start = before.rfind([';', '{', '}']).map_or(0, |i| i + 1);
+    let statement = &before[statement_start..];
+
+    let mut targets = if statement.matches('(').count() > statement.matches(')').count() {
+        Vec::from([AnnotationTarget::Arg])
+    } else {
+        let members: &[AnnotationTarget] = match get_enclosing_items(before).last() {
+            None => &[],
+            Some(&"interface") => &[AnnotationTarget::Method, AnnotationTarget::Const],
+            Some(&"parcelable" | &"union") => &[AnnotationTarget::Field, AnnotationTarget::Const],
+            // Enum elements (and unknown items) cannot be annotated
+            Some(_) => return Vec::new(),
+        };
+        [members, ITEM_TARGETS].concat()
+    };
+
+    // Skip the parameters of the completed annotation
+    let after = after.trim_start();
+    let after = match after.strip_prefix('(') {
+        Some(params) => params.split_once(')').map_or("", |(_, rest)| rest),
+        None => after,
+    };
+
+    // End of the annotated declaration (outside of the type arguments)
+    let mut depth = 0usize;
+    let end = after.char_indices().find(|(_, c)| match c {
+        '<' => {
+            depth += 1;
+            false
+        }
+        '>' => {
+            depth = depth.saturating_sub(1);
+            false
+        }
+        ';' | '{' | '(' | '=' | ',' | ')' => depth == 0,
+        _ => false,
+    });
+    let (declaration, terminator) = match end {
+        Some((i, c)) => (remove_annotations(&after[..i]), c),
+        None => return targets,
+    };
+    let words: Vec<&str> = match &declaration {
+        Some(declaration) => declaration.split_whitespace().collect(),
+        None => return targets,
+    };
+
+    let keyword = words.iter().find(|w| ITEM_KEYWORDS.contains(w));
+    let annotated: &[AnnotationTarget] = if let Some(keyword) = keyword {
+        match *keyword {
+            "interface" => &[AnnotationTarget::Interface],
+            "parcelable" => &[AnnotationTarget::Parcelable],
+            "union" => &[AnnotationTarget::Union],
+            _ => &[AnnotationTarget::Enum],
+        }
+    } else if words.contains(&"const") {
+        &[AnnotationTarget::Const]
+    } else {
+        match terminator {
+            '(' => &[AnnotationTarget::Method],
+            ';' | '=' => &[AnnotationTarget::Field],
+            ',' | ')' => &[AnnotationTarget::Arg],
+            _ => return targets,
+        }
+    };
+    targets.retain(|t| annotated.contains(t));
+
+    targets
+}
+
+// Keywords of the items enclosing the end of the code (innermost last)
+fn get_enclosing_items(code: &str) -> Vec<&'static str> {
+    let mut enclosing = Vec::new();
+    let mut statement_start = 0;
+
+    for (i, c) in code.char_indices() {
+        match c {
+            '{' => {
+                let statement = &code[statement_start..i];
+                let keyword = ITEM_KEYWORDS
+                    .iter()
+                    .find(|k| statement.split_whitespace().any(|t| t == **k))
+                    .copied()
+                    .unwrap_or_default();
+                enclosing.push(keyword);
+            }
+            '}' => {
+                enclosing.pop();
+            }
+            ';' => (),
+            _ => continue,
+        }
+        statement_start = i + 1;
+    }
+
+    enclosing
+}
+
+// The statement without its annotations, or None if the arguments of an annotation
+// are being typed
+fn remove_annotations(statement: &str) -> Option<String> {
+    static ANNOTATION_RE: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"@\w+(\s*\([^)]*\))?").unwrap());
+    let statement = ANNOTATION_RE.replace_all(statement, " ");
+
+    if statement.contains('@') {
+        None
+    } else {
+        Some(statement.into_owned())
+    }
+}
+
+// The content with the comments and the string and char literals replaced by spaces
+// (to keep the same offsets), or None if the offset is inside one of them
+fn get_code(content: &str, tokens: &[cst::Token], offset: usize) -> Option<String> {
+    let mut code = String::with_capacity(content.len());
+
+    for (i, token) in tokens.iter().enumerate() {
+        let range = &token.full_range;
+        let is_blanked = match token.kind {
+            TokenKind::LineComment => {
+                if range.start.offset < offset && offset <= range.end.offset {
+                    return None;
+                }
+                true
+            }
+            TokenKind::BlockComment | TokenKind::String | TokenKind::Char => {
+                if range.start.offset < offset && offset < range.end.offset {
+                    return None;
+                }
+                true
+            }
+            // Unterminated string literal (e.g. `"abc`) or block comment (`/* abc`)
+            TokenKind::Unknown | TokenKind::Punct if range.start.offset < offset => {
+                let is_unterminated_string =
+                    token.text == "\"" && !content[range.end.offset..offset].contains('\n');
+                let is_unterminated_comment = token.text == "/"
+                    && tokens
+                        .get(i + 1)
+                        .is_some_and(|t| t.text == "*" && t.full_range.start == range.end);
+                if is_unterminated_string || is_unterminated_comment {
+                    return None;
+                }
+                false
+            }
+            _ => false,
+        };
+
+        if is_blanked {
+            code.extend(token.text.chars().flat_map(|c| {
+                std::iter::repeat_n(if c == '\n' { '\n' } else { ' ' }, c.len_utf8())
+            }));
+        } else {
+            code.push_str(&token.text);
+        }
+    }
+
+    Some(code)
+}
+
+// Offset of the given (1-based) line and column (in grapheme clusters, or in chars
+// which only differs for combined characters)
+fn to_offset(content: &str, line_col: (usize, usize)) -> usize {
+    let mut offset = 0;
+    let mut lines = content.split_inclusive('\n');
+
+    for _ in 1..line_col.0 {
+        match lines.next() {
+            Some(line) => offset += line.len(),
+            None => return content.len(),
+        }
+    }
+
+    let line = lines.next().unwrap_or_default();
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    offset
+        + line
+            .char_indices()
+            .nth(line_col.1.saturating_sub(1))
+            .map_or(line.len(), |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BAR: &str = r#"package b;
+parcelable Bar {
+    parcelable Inner {}
+    const int MAX = 3;
+}"#;
+
+    const COLOR: &str = "package b;\nenum Color { RED, GREEN }";
+
+    // Complete the content at the `$` marker
+    fn complete_at(content_with_marker: &str) -> Vec<CompletionItem> {
+        let offset = content_with_marker.find('$').expect("marker");
+        let content = content_with_marker.replace('$', "");
+        let lookup = line_col::LineColLookup::new(&content);
+
+        let mut parser = crate::Parser::new();
+        parser.add_content("b/Bar.aidl", BAR);
+        parser.add_content("b/Color.aidl", COLOR);
+        parser.add_content("a/IFoo.aidl", &content);
+        parser.complete(&"a/IFoo.aidl", lookup.get_by_cluster(offset))
+    }
+
+    fn get_labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|i| i.label.as_str()).collect()
+    }
+
+    #[test]
+    fn test_complete_types() {
+        let items = complete_at("package a;\ninterface IFoo {\n    void foo(in $");
+        let labels = get_labels(&items);
+        assert!(labels.contains(&"int"));
+        assert!(labels.contains(&"String"));
+        assert!(labels.contains(&"IBinder"));
+        assert!(labels.contains(&"Bar"));
+        assert!(labels.contains(&"Color"));
+        assert!(!labels.contains(&"in"));
+
+        // Not imported
+        let bar = items.iter().find(|i| i.label == "Bar").expect("Bar");
+        assert_eq!(bar.kind, CompletionKind::Parcelable);
+        assert_eq!(bar.detail.as_deref(), Some("b.Bar"));
+        assert_eq!(bar.fixes.len(), 1);
+        assert_eq!(bar.fixes[0].replacement, "\n\nimport b.Bar;");
+        assert_eq!(bar.fixes[0].range.start.offset, 10);
+
+        // Imported and filtered
+        let items = complete_at("package a;\nimport b.Bar;\ninterface IFoo {\n    void foo(Ba$");
+        let labels = get_labels(&items);
+        assert_eq!(labels, ["Bar", "Bar.Inner"]);
+        assert!(items.iter().all(|i| i.fixes.is_empty()));
+        assert_eq!(items[0].range.start.line_col, (4, 14));
+        assert_eq!(items[0].range.end.line_col, (4, 16));
+
+        // Directions
+        let items = complete_at("package a;\ninterface IFoo {\n  void foo($");
+        let labels = get_labels(&items);
+        assert!(labels.contains(&"in") && labels.contains(&"inout"));
+        let items = complete_at("package a;\ninterface IFoo {\n  void foo(i$");
+        let labels = get_labels(&items);
+        assert_eq!(labels, ["in", "inout", "int", "IBinder"]);
+
+        // Generics
+        let items = complete_at("package a; interface IFoo { List<St$");
+        let labels = get_labels(&items);
+        assert_eq!(labels, ["String"]);
+
+        // Names are not completed
+        assert!(complete_at("package a;\ninterface IFoo {\n  void foo(in int $").is_empty());
+        assert!(complete_at("package a;\ninterface $").is_empty());
+        assert!(complete_at("package a; interface IFoo { int $").is_empty());
+    }
+
+    #[test]
+    fn test_complete_contexts() {
+        // Import
+        let items = complete_at("package a;\nimport b.$");
+        assert_eq!(get_labels(&items), ["b.Bar", "b.Bar.Inner", "b.Color"],);
+        assert_eq!(items[0].range.start.line_col, (2, 8));
+
+        // Enum elements
+        let items = complete_at("package a;\nimport b.Color;\nparcelable P { Color c = Color.$");
+        assert_eq!(get_labels(&items), ["RED", "GREEN"]);
+        assert_eq!(items[0].kind, CompletionKind::EnumElement);
+
+        // Consts and nested items
+        let items = complete_at("package a; import b.Bar; interface IFoo { const int X = Bar.$");
+        assert_eq!(get_labels(&items), ["MAX", "Inner"]);
+
+        // Qualified name
+        let items = complete_at("package a; interface IFoo { void foo(in b.$");
+        let labels = get_labels(&items);
+        assert_eq!(labels, ["Bar", "Bar.Inner", "Color"]);
+
+        // Annotations
+        let items = complete_at("package a; interface IFoo { @nu$");
+        assert_eq!(get_labels(&items), ["nullable"]);
+        assert_eq!(items[0].kind, CompletionKind::Annotation);
+
+        // Keywords
+        let items = complete_at("package a;\n$");
+        let labels = get_labels(&items);
+        assert!(labels.contains(&"import") && labels.contains(&"parcelable"));
+        assert!(!labels.contains(&"int"));
+        let items = complete_at("package a;\ninterface IFoo {\n    $");
+        let labels = get_labels(&items);
+        assert!(labels.contains(&"oneway") && labels.contains(&"void"));
+        assert!(labels.contains(&"int") && labels.contains(&"Bar"));
+
+        // Values of the current scope
+        let items = complete_at("package a;\nenum E { A, B = $\n}");
+        let labels = get_labels(&items);
+        assert!(labels.contains(&"true"));
+        assert!(complete_at("package a;\nenum E { A, $").is_empty());
+
+        // Comments and strings
+        assert!(complete_at("package a; // in $").is_empty());
+        assert!(complete_at("package a; /* in $ */").is_empty());
+        assert!(complete_at("package a; /* in $").is_empty());
+        let items = complete_at("package a; /* { */ interface IFoo { const String S = \"{\"; $");
+        assert!(get_labels(&items).contains(&"void"));
+        assert!(complete_at("package a; interface IFoo { const String S = \"$").is_empty());
+    }
+
+    #[test]
+    fn test_complete_members() {
+        // Only nested items for types, imported if required
+        let items = complete_at("package a;\ninterface IFoo {\n    void foo(in Bar.$");
+        assert_eq!(get_labels(&items), ["Inner"]);
+        assert_eq!(items[0].detail.as_deref(), Some("b.Bar.Inner"));
+        assert_eq!(items[0].fixes.len(), 1);
+        assert_eq!(items[0].fixes[0].replacement, "\n\nimport b.Bar;");
+        let items = complete_at("package a; import b.Bar; interface IFoo { Bar.$");
+        assert_eq!(get_labels(&items), ["Inner"]);
+        assert!(items[0].fixes.is_empty());
+
+        // Values, imported if required
+        let items = complete_at("package a; interface IFoo { const int X = Bar.$");
+        assert_eq!(get_labels(&items), ["MAX", "Inner"]);
+        assert!(items.iter().all(|i| i.fixes.len() == 1));
+
+        // Names are not completed
+        assert!(complete_at("package a; interface Bar.$").is_empty());
+    }
+
+    #[test]
+    fn test_complete_annotation_targets() {
+        let get_annotations = |content| {
+            complete_at(content)
+                .into_iter()
+                .map(|i| i.label)
+                .collect::<Vec<_>>()
+        };
+
+        // Method
+        let labels = get_annotations("package a; interface IFoo { @$ void foo(); }");
+        assert!(labels.contains(&"nullable".to_owned()));
+        assert!(labels.contains(&"PropagateAllowBlocking".to_owned()));
+        assert!(!labels.contains(&"Backing".to_owned()));
+        assert!(!labels.contains(&"JavaDefault".to_owned()));
+
+        // Argument, with other annotations and parameters
+        let labels =
+            get_annotations("package a; interface IFoo { void foo(@nullable @$ in Bar b); }");
+        assert!(labels.contains(&"PropagateAllowBlocking".to_owned()));
+        assert!(!labels.contains(&"RequiresNoPermission".to_owned()));
+
+        // Items
+        let labels = get_annotations("package a;\n@$ enum E { A }");
+        assert!(labels.contains(&"Backing".to_owned()));
+        assert!(!labels.contains(&"nullable".to_owned()));
+        assert!(!labels.contains(&"JavaDefault".to_owned()));
+        let labels = get_annotations("package a;\n@Ja$(toString=true) parcelable P {}");
+        assert_eq!(
+            labels,
+            [
+                "JavaDerive",
+                "JavaOnlyImmutable",
+                "JavaOnlyStableParcelable",
+                "JavaPassthrough",
+                "JavaSuppressLint"
+            ]
+        );
+
+        // Fields and consts (nested items are possible until the declaration is typed)
+        let labels = get_annotations("package a; parcelable P { @$ Map<String, int> m; }");
+        assert!(labels.contains(&"nullable".to_owned()));
+        assert!(!labels.contains(&"FixedSize".to_owned()));
+        let labels = get_annotations("package a; parcelable P { @$ const int C = 1; }");
+        assert!(labels.contains(&"utf8InCpp".to_owned()));
+        assert!(!labels.contains(&"nullable".to_owned()));
+        let labels = get_annotations("package a; parcelable P { @$");
+        assert!(labels.contains(&"nullable".to_owned()));
+        assert!(labels.contains(&"FixedSize".to_owned()));
+
+        // Enum elements
+        assert!(get_annotations("package a; enum E { @$ A }").is_empty());
+    }
+
+    #[test]
+    fn test_complete_scope() {
+        // Imports and declarations in comments are ignored
+        let content = "package a;\n// import b.Bar;\n/* parcelable Bar; */\nimport b.Color;\n\
+                       interface IFoo {\n    void foo(in Ba$";
+        let items = complete_at(content);
+        let bar = items.iter().find(|i| i.label == "Bar").expect("Bar");
+        assert_eq!(bar.detail.as_deref(), Some("b.Bar"));
+        assert_eq!(bar.fixes.len(), 1);
+        assert_eq!(bar.fixes[0].replacement, "\nimport b.Bar;");
+        assert_eq!(bar.fixes[0].range.start.line_col, (4, 16));
+
+        let content = "package  a.b /* c */ ;\nimport x.Y;\nimport z;\nenum E {}";
+        let scope = Scope::new(content, &cst::lex(content));
+        assert_eq!(scope.package, Some("a.b"));
+        assert_eq!(scope.imports, ["x.Y", "z"]);
+        assert_eq!(scope.declared, ["E"]);
+        assert_eq!(scope.import_insertion, (44, "\n"));
+    }
+
+    #[test]
+    fn test_to_offset() {
+        let content = "ab\né😀x\n";
+        assert_eq!(to_offset(content, (1, 2)), 1);
+        assert_eq!(to_offset(content, (1, 10)), 2);
+        assert_eq!(to_offset(content, (2, 3)), 9);
+        assert_eq!(to_offset(content, (5, 1)), content.len());
+    }
+}
@@ -0,0 +1,768 @@
+//! Lossless concrete syntax tree (CST)
+//!
+//! Unlike the AST, the CST keeps every token of the content, including whitespaces
+//! and comments, so that the content can be printed back byte for byte. It is an
+//! optional layer (independent of [`Parser`](crate::Parser)) e.g. for tools which
+//! modify the source and want to keep its layout.
+//!
+//! The tree is built from the structure of the declarations and is tolerant to errors:
+//! the tokens which do not fit are kept in [`NodeKind::Error`] nodes. The whitespaces
+//! and comments between 2 declarations belong to the enclosing node.
+//!
+//! Example:
+//! ```
+//! use aidl_parser::cst::{self, NodeKind};
+//!
+//! let content = "package p;\n\n// Comment\ninterface IFoo { void foo(in int i); }\n";
+//! let root = cst::parse(content);
+//! assert_eq!(cst::print(&root), content);
+//!
+//! let interface = root.get_nodes().find(|n| n.kind == NodeKind::Interface).unwrap();
+//! assert_eq!(interface.to_string(), "interface IFoo { void foo(in int i); }");
+//!
+//! let (ast, diagnostics) = root.to_ast();
+//! assert!(ast.is_some() && diagnostics.is_empty());
+//! ```
+
+use std::fmt;
+use std::sync::LazyLock;
+
+use crate::ast;
+use crate::diagnostic::Diagnostic;
+use crate::parser;
+
+// Keywords, incl. the reserved keywords of Java and C++
+//
+// The grammar has no lexer of its own and is fed with the tokens of this lexer (see
+// `rules::get_grammar_tokens`, which splits the keywords into the terminals of the
+// grammar).
+const KEYWORDS: &[&str] = &[
+    "package",
+    "import",
+    "interface",
+    "parcelable",
+    "union",
+    "enum",
+    "oneway",
+    "const",
+    "in",
+    "out",
+    "inout",
+    "void",
+    "byte",
+    "short",
+    "int",
+    "long",
+    "float",
+    "double",
+    "boolean",
+    "char",
+    "String",
+    "CharSequence",
+    "List",
+    "Map",
+    "true",
+    "false",
+    // Reserved
+    "break",
+    "case",
+    "catch",
+    "class",
+    "continue",
+    "default",
+    "do",
+    "else",
+    "for",
+    "goto",
+    "if",
+    "new",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "volatile",
+    "while",
+];
+
+// Patterns of the tokens (the first matching pattern wins)
+const TOKEN_PATTERNS: &[(TokenKind, &str)] = &[
+    (TokenKind::Whitespace, r"\s+"),
+    (TokenKind::LineComment, r"//[^\n\r]*"),
+    (TokenKind::BlockComment, r"/\*[^*]*\*+(?:[^/*][^*]*\*+)*/"),
+    (TokenKind::String, r#""[^"\n\r]*""#),
+    (TokenKind::Char, r"'(?:\\[^'\n\r]+|[^'\\\n\r])'"),
+    (TokenKind::Annotation, r"@[a-zA-Z_][a-zA-Z0-9_]*"),
+    (
+        TokenKind::Float,
+        r"(?:\d+\.\d*|\.\d+)(?:[eE][+-]?\d+)?[fFdD]?|\d+[eE][+-]?\d+[fFdD]?|\d+[fFdD]",
+    ),
+    (
+        TokenKind::Integer,
+        r"(?:0[xX][0-9a-fA-F]+|0[bB][01]+|[0-9]+)[lL]?",
+    ),
+    (TokenKind::Ident, r"[a-zA-Z_][a-zA-Z0-9_]*"),
+    (
+        TokenKind::Punct,
+        r"==|!=|<=|>=|&&|\|\||[;,{}()\[\]<>=.+\-*/%~!&|^]",
+    ),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    /// Keyword, incl. the built-in types and the boolean literals
+    Keyword,
+    Ident,
+    /// Name of an annotation (e.g. `@nullable`)
+    Annotation,
+    Integer,
+    Float,
+    String,
+    Char,
+    /// Punctuation or operator (e.g. `;`, `{` or `<=`)
+    Punct,
+    /// Character which is not part of the AIDL syntax
+    Unknown,
+}
+
+impl TokenKind {
+    /// Whitespaces and comments
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub full_range: ast::Range,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    /// Root node (the whole content)
+    Aidl,
+    Package,
+    Import,
+    /// e.g. `parcelable Foo;`
+    DeclaredParcelable,
+    Interface,
+    Parcelable,
+    Union,
+    Enum,
+    Const,
+    Method,
+    Arg,
+    Field,
+    EnumElement,
+    /// Annotation with its parameters (e.g. `@Backing(type="int")`)
+    Annotation,
+    /// Tokens which are not part of a valid declaration
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    /// The child nodes (without the tokens)
+    pub fn get_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|el| match el {
+            SyntaxElement::Node(n) => Some(n),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// All the tokens of the node and of its descendants, in order
+    pub fn get_tokens(&self) -> Vec<&Token> {
+        fn collect<'a>(node: &'a SyntaxNode, tokens: &mut Vec<&'a Token>) {
+            for el in &node.children {
+                match el {
+                    SyntaxElement::Node(n) => collect(n, tokens),
+                    SyntaxElement::Token(t) => tokens.push(t),
+                }
+            }
+        }
+
+        let mut tokens = Vec::new();
+        collect(self, &mut tokens);
+        tokens
+    }
+
+    /// Range from the first to the last token (None for an empty node)
+    pub fn get_range(&self) -> Option<ast::Range> {
+        let tokens = self.get_tokens();
+        Some(ast::Range {
+            start: tokens.first()?.full_range.start.clone(),
+            end: tokens.last()?.full_range.end.clone(),
+        })
+    }
+
+    /// The token containing the given offset
+    pub fn find_token_at_offset(&self, offset: usize) -> Option<&Token> {
+        self.get_tokens()
+            .into_iter()
+            .find(|t| t.full_range.start.offset <= offset && offset < t.full_range.end.offset)
+    }
+
+    /// Build the AST of the node, as done by the parser (without validation)
+    ///
+    /// The grammar is fed with the tokens of the tree, so the ranges of the AST match
+    /// the ranges of the tokens (relative to the start of the node).
+    pub fn to_ast(&self) -> (Option<ast::Aidl>, Vec<Diagnostic>) {
+        let content = self.to_string();
+        let lookup = line_col::LineColLookup::new(&content);
+
+        // Offsets relative to the text of the node
+        let tokens = self.get_tokens();
+        let start = tokens.first().map_or(0, |t| t.full_range.start.offset);
+        let tokens: Vec<Token> = tokens
+            .into_iter()
+            .map(|t| Token {
+                full_range: ast::Range::new(
+                    &lookup,
+                    t.full_range.start.offset - start,
+                    t.full_range.end.offset - start,
+                ),
+                ..t.clone()
+            })
+            .collect();
+
+        parser::parse_tokens(&content, &tokens, &lookup)
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.get_tokens() {
+            write!(f, "{}", token.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the content into a lossless syntax tree
+pub fn parse(content: &str) -> SyntaxNode {
+    let mut builder = Builder {
+        tokens: lex(content),
+        pos: 0,
+        stack: Vec::from([(NodeKind::Aidl, Vec::new())]),
+    };
+    builder.parse_file();
+
+    let (kind, children) = builder.stack.pop().expect("root node");
+    SyntaxNode { kind, children }
+}
+
+/// The exact content from which the tree has been parsed
+pub fn print(node: &SyntaxNode) -> String {
+    node.to_string()
+}
+
+/// Split the content into tokens (including whitespaces and comments)
+pub fn lex(content: &str) -> Vec<Token> {
+    let lookup = line_col::LineColLookup::new(content);
+    lex_with_lookup(content, &lookup)
+}
+
+pub(crate) fn lex_with_lookup(content: &str, lookup: &line_col::LineColLookup) -> Vec<Token> {
+    static PATTERNS: LazyLock<Vec<(TokenKind, regex::Regex)>> = LazyLock::new(|| {
+        TOKEN_PATTERNS
+            .iter()
+            .map(|(kind, p)| (*kind, regex::Regex::new(&format!("^(?:{p})")).unwrap()))
+            .collect()
+    });
+
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < content.len() {
+        let rest = &content[pos..];
+        let (mut kind, len) = PATTERNS
+            .iter()
+            .find_map(|(kind, re)| re.find(rest).map(|m| (*kind, m.end())))
+            .unwrap_or_else(|| {
+                let len = rest.chars().next().map_or(1, char::len_utf8);
+                (TokenKind::Unknown, len)
+            });
+
+        let text = &rest[..len];
+        if kind == TokenKind::Ident && KEYWORDS.contains(&text) {
+            kind = TokenKind::Keyword;
+        }
+
+        tokens.push(Token {
+            kind,
+            text: text.to_owned(),
+            full_range: ast::Range::new(lookup, pos, pos + len),
+        });
+        pos += len;
+    }
+
+    tokens
+}
+
+// Where a declaration is parsed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    File,
+    Body,
+    EnumBody,
+}
+
+// Build the tree from the tokens (the nodes being built are on the stack)
+struct Builder {
+    tokens: Vec<Token>,
+    pos: usize,
+    stack: Vec<(NodeKind, Vec<SyntaxElement>)>,
+}
+
+impl Builder {
+    fn parse_file(&mut self) {
+        while let Some(text) = self.peek_text() {
+            match text {
+                "package" => self.parse_statement(NodeKind::Package),
+                "import" => self.parse_statement(NodeKind::Import),
+                _ => self.parse_declaration(Scope::File),
+            }
+        }
+
+        // Trailing whitespaces and comments
+        self.skip_trivia();
+    }
+
+    // Tokens until `;`
+    fn parse_statement(&mut self, kind: NodeKind) {
+        self.start_node(kind);
+        self.bump_until_semicolon();
+        self.finish_node();
+    }
+
+    fn parse_declaration(&mut self, scope: Scope) {
+        let kind = self.get_declaration_kind(scope);
+        self.skip_trivia();
+        let start = self.pos;
+
+        self.start_node(kind);
+        self.parse_annotations();
+
+        match kind {
+            NodeKind::Interface | NodeKind::Parcelable | NodeKind::Union | NodeKind::Enum => {
+                while !matches!(self.peek_text(), None | Some("{" | "}" | ";")) {
+                    self.bump();
+                }
+                if self.peek_text() == Some("{") {
+                    self.bump();
+                    let scope = if kind == NodeKind::Enum {
+                        Scope::EnumBody
+                    } else {
+                        Scope::Body
+                    };
+                    while !matches!(self.peek_text(), None | Some("}")) {
+                        self.parse_declaration(scope);
+                    }
+                }
+                if matches!(self.peek_text(), Some("}" | ";")) {
+                    self.bump();
+                }
+            }
+            NodeKind::Method => {
+                while !matches!(self.peek_text(), None | Some("(" | "{" | "}" | ";")) {
+                    self.bump();
+                }
+                if self.peek_text() == Some("(") {
+                    self.bump();
+                    self.parse_args();
+                }
+                self.bump_until_semicolon();
+            }
+            NodeKind::EnumElement => {
+                let mut depth = 0usize;
+                while let Some(text) = self.peek_text() {
+                    match text {
+                        "}" if depth == 0 => break,
+                        "," if depth == 0 => {
+                            self.bump();
+                            break;
+                        }
+                        "(" | "[" | "{" => depth += 1,
+                        ")" | "]" | "}" => depth = depth.saturating_sub(1),
+                        _ => (),
+                    }
+                    self.bump();
+                }
+            }
+            _ => self.bump_until_semicolon(),
+        }
+
+        // Make sure that the parser advances (e.g. `}` at the top level)
+        if self.pos == start {
+            self.bump();
+        }
+
+        self.finish_node();
+    }
+
+    // Arguments of a method until `)`
+    fn parse_args(&mut self) {
+        loop {
+            match self.peek_text() {
+                None | Some("{" | "}" | ";") => return,
+                Some(")") => break,
+                Some(",") => {
+                    self.bump();
+                    continue;
+                }
+                _ => (),
+            }
+
+            self.start_node(NodeKind::Arg);
+            let mut depth = 0usize;
+            while let Some(token) = self.peek() {
+                match token.text.as_str() {
+                    "," | ")" if depth == 0 => break,
+                    "{" | "}" | ";" => break,
+                    "(" | "<" | "[" => depth += 1,
+                    ")" | ">" | "]" => depth = depth.saturating_sub(1),
+                    _ => (),
+                }
+
+                if token.kind == TokenKind::Annotation {
+                    self.parse_annotations();
+                } else {
+                    self.bump();
+                }
+            }
+            self.finish_node();
+        }
+
+        self.bump();
+    }
+
+    // e.g. `@nullable @Backing(type="int")`
+    fn parse_annotations(&mut self) {
+        while self.peek().is_some_and(|t| t.kind == TokenKind::Annotation) {
+            self.start_node(NodeKind::Annotation);
+            self.bump();
+
+            if self.peek_text() == Some("(") {
+                let mut depth = 0usize;
+                while let Some(text) = self.peek_text() {
+                    match text {
+                        "(" => depth += 1,
+                        ")" => depth -= 1,
+                        ";" | "}" => break,
+                        _ => (),
+                    }
+                    self.bump();
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+
+            self.finish_node();
+        }
+    }
+
+    // Kind of the declaration which starts at the next token
+    fn get_declaration_kind(&self, scope: Scope) -> NodeKind {
+        if scope == Scope::EnumBody {
+            return NodeKind::EnumElement;
+        }
+
+        // Texts of the tokens after the annotations until the end of the declaration
+        let mut texts = Vec::new();
+        let mut depth = 0usize;
+        let mut after_annotation = false;
+        let mut i = self.pos;
+        while let Some(next) = self.next_significant(i) {
+            let token = &self.tokens[next];
+            i = next + 1;
+
+            // Skip the annotations and their parameters
+            if texts.is_empty() {
+                let is_annotation_param = match token.text.as_str() {
+                    "(" if after_annotation || depth > 0 => {
+                        depth += 1;
+                        true
+                    }
+                    ")" if depth > 0 => {
+                        depth -= 1;
+                        true
+                    }
+                    _ => depth > 0,
+                };
+                after_annotation = token.kind == TokenKind::Annotation;
+                if after_annotation || is_annotation_param {
+                    continue;
+                }
+            }
+
+            texts.push(token.text.as_str());
+            if matches!(token.text.as_str(), ";" | "{" | "}") {
+                break;
+            }
+        }
+
+        let keyword = match texts[..] {
+            ["oneway", "interface", ..] => "interface",
+            [first, ..] => first,
+            [] => "",
+        };
+        match (keyword, scope) {
+            ("interface", _) => NodeKind::Interface,
+            ("union", _) => NodeKind::Union,
+            ("enum", _) => NodeKind::Enum,
+            ("parcelable", Scope::File) if texts.last() == Some(&";") => {
+                NodeKind::DeclaredParcelable
+            }
+            ("parcelable", _) => NodeKind::Parcelable,
+            ("const", Scope::Body) => NodeKind::Const,
+            (_, Scope::Body) => {
+                // e.g. `void foo(int i) = 3;` but not `int i = (1 + 2);`
+                let is_method = texts.iter().take_while(|t| **t != "=").any(|t| *t == "(");
+                if is_method {
+                    NodeKind::Method
+                } else {
+                    NodeKind::Field
+                }
+            }
+            _ => NodeKind::Error,
+        }
+    }
+
+    // Tokens until `;` (included) or until the end of the enclosing body
+    fn bump_until_semicolon(&mut self) {
+        let mut depth = 0usize;
+        while let Some(text) = self.peek_text() {
+            match text {
+                "}" if depth == 0 => return,
+                ";" if depth == 0 => {
+                    self.bump();
+                    return;
+                }
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+            self.bump();
+        }
+    }
+
+    fn next_significant(&self, from: usize) -> Option<usize> {
+        (from..self.tokens.len()).find(|i| !self.tokens[*i].kind.is_trivia())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.next_significant(self.pos).map(|i| &self.tokens[i])
+    }
+
+    fn peek_text(&self) -> Option<&str> {
+        self.peek().map(|t| t.text.as_str())
+    }
+
+    // Add the whitespaces and comments to the current node
+    fn skip_trivia(&mut self) {
+        while let Some(token) = self.tokens.get(self.pos) {
+            if !token.kind.is_trivia() {
+                break;
+            }
+            self.push_token();
+        }
+    }
+
+    // Add the next token (and the trivia before it) to the current node
+    fn bump(&mut self) {
+        self.skip_trivia();
+        if self.pos < self.tokens.len() {
+            self.push_token();
+        }
+    }
+
+    fn push_token(&mut self) {
+        let token = self.tokens[self.pos].clone();
+        self.pos += 1;
+        self.push(SyntaxElement::Token(token));
+    }
+
+    // Start a node (the trivia before it belongs to the current node)
+    fn start_node(&mut self, kind: NodeKind) {
+        self.skip_trivia();
+        self.stack.push((kind, Vec::new()));
+    }
+
+    fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().expect("started node");
+        self.push(SyntaxElement::Node(SyntaxNode { kind, children }));
+    }
+
+    fn push(&mut self, el: SyntaxElement) {
+        self.stack.last_mut().expect("root node").1.push(el);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = r#"/* License */
+package com.example;
+
+import com.example.IBar;
+parcelable Declared;
+
+/** The interface */
+@VintfStability
+oneway interface IFoo {
+    const int[] C = {1, 2};  // Array
+    @nullable String foo(in @nullable Map<String, int> m, out int[] c) = 3;
+
+    @Backing(type="byte") enum E { A = 1 << 2, B, }
+    parcelable P<T> { T t; int field = (1 + 2); }
+}
+"#;
+
+    fn get_kinds(node: &SyntaxNode) -> Vec<NodeKind> {
+        node.get_nodes().map(|n| n.kind).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for content in [
+            CONTENT,
+            "",
+            "  \r\n// Only a comment",
+            "package p;\r\ninterface IFoo {\r\n\tvoid é😀(); }",
+            "package p; } } interface { oops # $ \"unterminated\n const",
+            "package p; interface IFoo { void foo(in int i, ; }",
+            "package p; enum E { A = (1, B",
+        ] {
+            assert_eq!(print(&parse(content)), content);
+        }
+
+        // Partially typed content
+        for (i, _) in CONTENT.char_indices() {
+            assert_eq!(print(&parse(&CONTENT[..i])), &CONTENT[..i]);
+        }
+    }
+
+    #[test]
+    fn test_structure() {
+        let root = parse(CONTENT);
+        assert_eq!(root.kind, NodeKind::Aidl);
+        assert_eq!(
+            get_kinds(&root),
+            [
+                NodeKind::Package,
+                NodeKind::Import,
+                NodeKind::DeclaredParcelable,
+                NodeKind::Interface
+            ]
+        );
+
+        // The leading comments and whitespaces belong to the parent
+        let interface = root.get_nodes().last().unwrap();
+        assert!(interface
+            .to_string()
+            .starts_with("@VintfStability\noneway interface"));
+        assert_eq!(
+            get_kinds(interface),
+            [
+                NodeKind::Annotation,
+                NodeKind::Const,
+                NodeKind::Method,
+                NodeKind::Enum,
+                NodeKind::Parcelable
+            ]
+        );
+
+        let nodes: Vec<&SyntaxNode> = interface.get_nodes().collect();
+        assert_eq!(nodes[1].to_string(), "const int[] C = {1, 2};");
+
+        let method = nodes[2];
+        assert_eq!(
+            get_kinds(method),
+            [NodeKind::Annotation, NodeKind::Arg, NodeKind::Arg]
+        );
+        let args: Vec<String> = method.get_nodes().skip(1).map(|n| n.to_string()).collect();
+        assert_eq!(args, ["in @nullable Map<String, int> m", "out int[] c"]);
+
+        let enum_ = nodes[3];
+        let elements: Vec<String> = enum_.get_nodes().map(|n| n.to_string()).collect();
+        assert_eq!(elements, ["@Backing(type=\"byte\")", "A = 1 << 2,", "B,"]);
+
+        let parcelable = nodes[4];
+        assert_eq!(get_kinds(parcelable), [NodeKind::Field, NodeKind::Field]);
+
+        // Invalid declarations
+        let root = parse("package p; } interface IFoo { # }");
+        assert_eq!(
+            get_kinds(&root),
+            [NodeKind::Package, NodeKind::Error, NodeKind::Interface]
+        );
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = lex("const float F = .5f; // x\n@Hide 0x1FL 'a' <= €");
+        let kinds: Vec<(TokenKind, &str)> = tokens
+            .iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .map(|t| (t.kind, t.text.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (TokenKind::Keyword, "const"),
+                (TokenKind::Keyword, "float"),
+                (TokenKind::Ident, "F"),
+                (TokenKind::Punct, "="),
+                (TokenKind::Float, ".5f"),
+                (TokenKind::Punct, ";"),
+                (TokenKind::LineComment, "// x"),
+                (TokenKind::Annotation, "@Hide"),
+                (TokenKind::Integer, "0x1FL"),
+                (TokenKind::Char, "'a'"),
+                (TokenKind::Punct, "<="),
+                (TokenKind::Unknown, "€"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_ast() {
+        let root = parse(CONTENT);
+        let (ast, diagnostics) = root.to_ast();
+        assert!(diagnostics.is_empty());
+
+        // Same ranges
+        let ast = ast.expect("AST");
+        assert_eq!(ast.item.get_nested_items().len(), 2);
+        let token = root
+            .find_token_at_offset(ast.item.get_symbol_range().start.offset)
+            .expect("token");
+        assert_eq!(token.text, "IFoo");
+        assert_eq!(&token.full_range, ast.item.get_symbol_range());
+        assert_eq!(ast.comments.len(), 3);
+    }
+}
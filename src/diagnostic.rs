@@ -1,10 +1,14 @@
-use crate::ast::Range;
+use crate::ast::{Position, Range};
 use crate::rules;
 use serde_derive::Serialize;
 
 #[derive(Serialize, Clone, Debug, PartialEq, Eq)]
 pub struct Diagnostic {
     pub kind: DiagnosticKind,
+
+    /// Stable identifier of the diagnostic (e.g. `AIDL0007`)
+    pub code: DiagnosticCode,
+
     pub range: Range,
     pub message: String,
 
@@ -13,6 +17,9 @@ pub struct Diagnostic {
 
     pub hint: Option<String>,
     pub related_infos: Vec<RelatedInfo>,
+
+    /// Machine-applicable fixes (e.g. remove an unused import)
+    pub fixes: Vec<Fix>,
 }
 
 #[derive(Serialize, Clone, Debug, PartialEq, Eq)]
@@ -21,17 +28,506 @@ pub enum DiagnosticKind {
     Warning,
 }
 
+/// Stable code of a diagnostic
+///
+/// The codes are never reused nor renumbered, so they can be used to filter diagnostics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    /// Invalid token
+    InvalidToken,
+    /// Unexpected end of file
+    UnrecognizedEof,
+    /// Unexpected token
+    UnrecognizedToken,
+    /// Extra token
+    ExtraToken,
+    /// Unknown type
+    UnknownType,
+    /// Duplicated import
+    DuplicatedImport,
+    /// Unused import
+    UnusedImport,
+    /// Unresolved import
+    UnresolvedImport,
+    /// Declared parcelable conflicts with import
+    ConflictingDeclaredParcelable,
+    /// Multiple parcelable declarations
+    MultipleParcelableDeclarations,
+    /// Unused declared parcelable
+    UnusedDeclaredParcelable,
+    /// Usage of declared parcelable
+    DeclaredParcelableUsage,
+    /// Non-generic list
+    NonGenericList,
+    /// Non-generic map
+    NonGenericMap,
+    /// Wrong number of type arguments
+    WrongTypeArgumentCount,
+    /// Duplicated method name
+    DuplicatedMethodName,
+    /// Mixed usage of method ids
+    MixedMethodIds,
+    /// Duplicated method id
+    DuplicatedMethodId,
+    /// Invalid method transaction code
+    InvalidTransactCode,
+    /// Invalid return type of async method
+    InvalidAsyncReturnType,
+    /// Redundant oneway
+    RedundantOneway,
+    /// Missing direction
+    MissingDirection,
+    /// Invalid direction
+    InvalidDirection,
+    /// Invalid argument
+    InvalidArgument,
+    /// Empty union
+    EmptyUnion,
+    /// Invalid default value for union field
+    InvalidUnionDefaultValue,
+    /// Invalid backing type
+    InvalidBackingType,
+    /// Duplicated enum value
+    DuplicatedEnumValue,
+    /// Unsupported multi-dimensional array
+    MultiDimensionalArray,
+    /// Invalid array element
+    InvalidArrayElement,
+    /// Invalid list element
+    InvalidListElement,
+    /// Invalid map key
+    InvalidMapKey,
+    /// Invalid map value
+    InvalidMapValue,
+    /// Invalid operator
+    InvalidOperator,
+    /// Invalid literal
+    InvalidLiteral,
+    /// Unknown constant
+    UnknownConstant,
+    /// Cyclic reference
+    CyclicReference,
+    /// Forward reference
+    ForwardReference,
+    /// Mismatched types
+    MismatchedTypes,
+    /// Value out of range
+    ValueOutOfRange,
+    /// Overflow in constant expression
+    Overflow,
+    /// Division by zero
+    DivisionByZero,
+    /// Invalid shift amount
+    InvalidShift,
+    /// Removed item
+    RemovedItem,
+    /// Changed kind of item
+    ChangedItemKind,
+    /// Removed method
+    RemovedMethod,
+    /// Changed transaction code
+    ChangedTransactCode,
+    /// Changed method signature
+    ChangedMethodSignature,
+    /// Removed field
+    RemovedField,
+    /// Reordered field
+    ReorderedField,
+    /// Changed field type
+    ChangedFieldType,
+    /// Removed constant
+    RemovedConstant,
+    /// Changed constant value
+    ChangedConstantValue,
+    /// Removed enum element
+    RemovedEnumElement,
+    /// Changed enum value
+    ChangedEnumValue,
+    /// Changed backing type
+    ChangedBackingType,
+    /// Annotation which is not a built-in annotation
+    UnknownAnnotation,
+    /// Annotation not allowed on the declaration or its type
+    MisplacedAnnotation,
+    /// Unknown, missing or invalid annotation parameter
+    InvalidAnnotationParameter,
+    /// Stable interface whose methods have implicit transaction codes
+    ImplicitTransactCode,
+}
+
+impl DiagnosticCode {
+    /// All the diagnostic codes, in ascending order
+    pub const ALL: &'static [DiagnosticCode] = &[
+        DiagnosticCode::InvalidToken,
+        DiagnosticCode::UnrecognizedEof,
+        DiagnosticCode::UnrecognizedToken,
+        DiagnosticCode::ExtraToken,
+        DiagnosticCode::UnknownType,
+        DiagnosticCode::DuplicatedImport,
+        DiagnosticCode::UnusedImport,
+        DiagnosticCode::UnresolvedImport,
+        DiagnosticCode::ConflictingDeclaredParcelable,
+        DiagnosticCode::MultipleParcelableDeclarations,
+        DiagnosticCode::UnusedDeclaredParcelable,
+        DiagnosticCode::DeclaredParcelableUsage,
+        DiagnosticCode::NonGenericList,
+        DiagnosticCode::NonGenericMap,
+        DiagnosticCode::WrongTypeArgumentCount,
+        DiagnosticCode::DuplicatedMethodName,
+        DiagnosticCode::MixedMethodIds,
+        DiagnosticCode::DuplicatedMethodId,
+        DiagnosticCode::InvalidTransactCode,
+        DiagnosticCode::InvalidAsyncReturnType,
+        DiagnosticCode::RedundantOneway,
+        DiagnosticCode::MissingDirection,
+        DiagnosticCode::InvalidDirection,
+        DiagnosticCode::InvalidArgument,
+        DiagnosticCode::EmptyUnion,
+        DiagnosticCode::InvalidUnionDefaultValue,
+        DiagnosticCode::InvalidBackingType,
+        DiagnosticCode::DuplicatedEnumValue,
+        DiagnosticCode::MultiDimensionalArray,
+        DiagnosticCode::InvalidArrayElement,
+        DiagnosticCode::InvalidListElement,
+        DiagnosticCode::InvalidMapKey,
+        DiagnosticCode::InvalidMapValue,
+        DiagnosticCode::InvalidOperator,
+        DiagnosticCode::InvalidLiteral,
+        DiagnosticCode::UnknownConstant,
+        DiagnosticCode::CyclicReference,
+        DiagnosticCode::ForwardReference,
+        DiagnosticCode::MismatchedTypes,
+        DiagnosticCode::ValueOutOfRange,
+        DiagnosticCode::Overflow,
+        DiagnosticCode::DivisionByZero,
+        DiagnosticCode::InvalidShift,
+        DiagnosticCode::RemovedItem,
+        DiagnosticCode::ChangedItemKind,
+        DiagnosticCode::RemovedMethod,
+        DiagnosticCode::ChangedTransactCode,
+        DiagnosticCode::ChangedMethodSignature,
+        DiagnosticCode::RemovedField,
+        DiagnosticCode::ReorderedField,
+        DiagnosticCode::ChangedFieldType,
+        DiagnosticCode::RemovedConstant,
+        DiagnosticCode::ChangedConstantValue,
+        DiagnosticCode::RemovedEnumElement,
+        DiagnosticCode::ChangedEnumValue,
+        DiagnosticCode::ChangedBackingType,
+        DiagnosticCode::UnknownAnnotation,
+        DiagnosticCode::MisplacedAnnotation,
+        DiagnosticCode::InvalidAnnotationParameter,
+        DiagnosticCode::ImplicitTransactCode,
+    ];
+
+    /// Code as a string (e.g. `AIDL0007`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticCode::InvalidToken => "AIDL0001",
+            DiagnosticCode::UnrecognizedEof => "AIDL0002",
+            DiagnosticCode::UnrecognizedToken => "AIDL0003",
+            DiagnosticCode::ExtraToken => "AIDL0004",
+            DiagnosticCode::UnknownType => "AIDL0005",
+            DiagnosticCode::DuplicatedImport => "AIDL0006",
+            DiagnosticCode::UnusedImport => "AIDL0007",
+            DiagnosticCode::UnresolvedImport => "AIDL0008",
+            DiagnosticCode::ConflictingDeclaredParcelable => "AIDL0009",
+            DiagnosticCode::MultipleParcelableDeclarations => "AIDL0010",
+            DiagnosticCode::UnusedDeclaredParcelable => "AIDL0011",
+            DiagnosticCode::DeclaredParcelableUsage => "AIDL0012",
+            DiagnosticCode::NonGenericList => "AIDL0013",
+            DiagnosticCode::NonGenericMap => "AIDL0014",
+            DiagnosticCode::WrongTypeArgumentCount => "AIDL0015",
+            DiagnosticCode::DuplicatedMethodName => "AIDL0016",
+            DiagnosticCode::MixedMethodIds => "AIDL0017",
+            DiagnosticCode::DuplicatedMethodId => "AIDL0018",
+            DiagnosticCode::InvalidTransactCode => "AIDL0019",
+            DiagnosticCode::InvalidAsyncReturnType => "AIDL0020",
+            DiagnosticCode::RedundantOneway => "AIDL0021",
+            DiagnosticCode::MissingDirection => "AIDL0022",
+            DiagnosticCode::InvalidDirection => "AIDL0023",
+            DiagnosticCode::InvalidArgument => "AIDL0024",
+            DiagnosticCode::EmptyUnion => "AIDL0025",
+            DiagnosticCode::InvalidUnionDefaultValue => "AIDL0026",
+            DiagnosticCode::InvalidBackingType => "AIDL0027",
+            DiagnosticCode::DuplicatedEnumValue => "AIDL0028",
+            DiagnosticCode::MultiDimensionalArray => "AIDL0029",
+            DiagnosticCode::InvalidArrayElement => "AIDL0030",
+            DiagnosticCode::InvalidListElement => "AIDL0031",
+            DiagnosticCode::InvalidMapKey => "AIDL0032",
+            DiagnosticCode::InvalidMapValue => "AIDL0033",
+            DiagnosticCode::InvalidOperator => "AIDL0034",
+            DiagnosticCode::InvalidLiteral => "AIDL0035",
+            DiagnosticCode::UnknownConstant => "AIDL0036",
+            DiagnosticCode::CyclicReference => "AIDL0037",
+            DiagnosticCode::ForwardReference => "AIDL0038",
+            DiagnosticCode::MismatchedTypes => "AIDL0039",
+            DiagnosticCode::ValueOutOfRange => "AIDL0040",
+            DiagnosticCode::Overflow => "AIDL0041",
+            DiagnosticCode::DivisionByZero => "AIDL0042",
+            DiagnosticCode::InvalidShift => "AIDL0043",
+            DiagnosticCode::RemovedItem => "AIDL0044",
+            DiagnosticCode::ChangedItemKind => "AIDL0045",
+            DiagnosticCode::RemovedMethod => "AIDL0046",
+            DiagnosticCode::ChangedTransactCode => "AIDL0047",
+            DiagnosticCode::ChangedMethodSignature => "AIDL0048",
+            DiagnosticCode::RemovedField => "AIDL0049",
+            DiagnosticCode::ReorderedField => "AIDL0050",
+            DiagnosticCode::ChangedFieldType => "AIDL0051",
+            DiagnosticCode::RemovedConstant => "AIDL0052",
+            DiagnosticCode::ChangedConstantValue => "AIDL0053",
+            DiagnosticCode::RemovedEnumElement => "AIDL0054",
+            DiagnosticCode::ChangedEnumValue => "AIDL0055",
+            DiagnosticCode::ChangedBackingType => "AIDL0056",
+            DiagnosticCode::UnknownAnnotation => "AIDL0057",
+            DiagnosticCode::MisplacedAnnotation => "AIDL0058",
+            DiagnosticCode::InvalidAnnotationParameter => "AIDL0059",
+            DiagnosticCode::ImplicitTransactCode => "AIDL0060",
+        }
+    }
+
+    /// Human-readable name of the diagnostic (e.g. `unused-import`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiagnosticCode::InvalidToken => "invalid-token",
+            DiagnosticCode::UnrecognizedEof => "unrecognized-eof",
+            DiagnosticCode::UnrecognizedToken => "unrecognized-token",
+            DiagnosticCode::ExtraToken => "extra-token",
+            DiagnosticCode::UnknownType => "unknown-type",
+            DiagnosticCode::DuplicatedImport => "duplicated-import",
+            DiagnosticCode::UnusedImport => "unused-import",
+            DiagnosticCode::UnresolvedImport => "unresolved-import",
+            DiagnosticCode::ConflictingDeclaredParcelable => "conflicting-declared-parcelable",
+            DiagnosticCode::MultipleParcelableDeclarations => "multiple-parcelable-declarations",
+            DiagnosticCode::UnusedDeclaredParcelable => "unused-declared-parcelable",
+            DiagnosticCode::DeclaredParcelableUsage => "declared-parcelable-usage",
+            DiagnosticCode::NonGenericList => "non-generic-list",
+            DiagnosticCode::NonGenericMap => "non-generic-map",
+            DiagnosticCode::WrongTypeArgumentCount => "wrong-type-argument-count",
+            DiagnosticCode::DuplicatedMethodName => "duplicated-method-name",
+            DiagnosticCode::MixedMethodIds => "mixed-method-ids",
+            DiagnosticCode::DuplicatedMethodId => "duplicated-method-id",
+            DiagnosticCode::InvalidTransactCode => "invalid-transact-code",
+            DiagnosticCode::InvalidAsyncReturnType => "invalid-async-return-type",
+            DiagnosticCode::RedundantOneway => "redundant-oneway",
+            DiagnosticCode::MissingDirection => "missing-direction",
+            DiagnosticCode::InvalidDirection => "invalid-direction",
+            DiagnosticCode::InvalidArgument => "invalid-argument",
+            DiagnosticCode::EmptyUnion => "empty-union",
+            DiagnosticCode::InvalidUnionDefaultValue => "invalid-union-default-value",
+            DiagnosticCode::InvalidBackingType => "invalid-backing-type",
+            DiagnosticCode::DuplicatedEnumValue => "duplicated-enum-value",
+            DiagnosticCode::MultiDimensionalArray => "multi-dimensional-array",
+            DiagnosticCode::InvalidArrayElement => "invalid-array-element",
+            DiagnosticCode::InvalidListElement => "invalid-list-element",
+            DiagnosticCode::InvalidMapKey => "invalid-map-key",
+            DiagnosticCode::InvalidMapValue => "invalid-map-value",
+            DiagnosticCode::InvalidOperator => "invalid-operator",
+            DiagnosticCode::InvalidLiteral => "invalid-literal",
+            DiagnosticCode::UnknownConstant => "unknown-constant",
+            DiagnosticCode::CyclicReference => "cyclic-reference",
+            DiagnosticCode::ForwardReference => "forward-reference",
+            DiagnosticCode::MismatchedTypes => "mismatched-types",
+            DiagnosticCode::ValueOutOfRange => "value-out-of-range",
+            DiagnosticCode::Overflow => "overflow",
+            DiagnosticCode::DivisionByZero => "division-by-zero",
+            DiagnosticCode::InvalidShift => "invalid-shift",
+            DiagnosticCode::RemovedItem => "removed-item",
+            DiagnosticCode::ChangedItemKind => "changed-item-kind",
+            DiagnosticCode::RemovedMethod => "removed-method",
+            DiagnosticCode::ChangedTransactCode => "changed-transact-code",
+            DiagnosticCode::ChangedMethodSignature => "changed-method-signature",
+            DiagnosticCode::RemovedField => "removed-field",
+            DiagnosticCode::ReorderedField => "reordered-field",
+            DiagnosticCode::ChangedFieldType => "changed-field-type",
+            DiagnosticCode::RemovedConstant => "removed-constant",
+            DiagnosticCode::ChangedConstantValue => "changed-constant-value",
+            DiagnosticCode::RemovedEnumElement => "removed-enum-element",
+            DiagnosticCode::ChangedEnumValue => "changed-enum-value",
+            DiagnosticCode::ChangedBackingType => "changed-backing-type",
+            DiagnosticCode::UnknownAnnotation => "unknown-annotation",
+            DiagnosticCode::MisplacedAnnotation => "misplaced-annotation",
+            DiagnosticCode::InvalidAnnotationParameter => "invalid-annotation-parameter",
+            DiagnosticCode::ImplicitTransactCode => "implicit-transact-code",
+        }
+    }
+
+    /// Short explanation of the diagnostic
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            DiagnosticCode::InvalidToken => "The parser encountered characters which do not form a valid AIDL token.",
+            DiagnosticCode::UnrecognizedEof => "The file ended before the current declaration was complete.",
+            DiagnosticCode::UnrecognizedToken => "The parser encountered a token which is not allowed at this position.",
+            DiagnosticCode::ExtraToken => "The parser encountered a token after the end of the declaration.",
+            DiagnosticCode::UnknownType => "The type could not be resolved: it is neither a primitive, a built-in type, an item of the same package nor an imported item.",
+            DiagnosticCode::DuplicatedImport => "The same item is imported more than once.",
+            DiagnosticCode::UnusedImport => "The imported item is never referenced and the import can be removed.",
+            DiagnosticCode::UnresolvedImport => "The imported item is not defined in any of the parsed files.",
+            DiagnosticCode::ConflictingDeclaredParcelable => "A declared parcelable has the same name as an imported item.",
+            DiagnosticCode::MultipleParcelableDeclarations => "The same parcelable is declared more than once.",
+            DiagnosticCode::UnusedDeclaredParcelable => "The declared parcelable is never referenced and the declaration can be removed.",
+            DiagnosticCode::DeclaredParcelableUsage => "Declared parcelables are not checked and should be replaced by structured parcelables when possible.",
+            DiagnosticCode::NonGenericList => "`List` without element type is not recommended, e.g. use `List<String>` instead.",
+            DiagnosticCode::NonGenericMap => "`Map` without key and value types is not recommended, e.g. use `Map<String, int>` instead.",
+            DiagnosticCode::WrongTypeArgumentCount => "A generic type is used with a different number of type arguments than type parameters, or type arguments are given for a non-generic type.",
+            DiagnosticCode::DuplicatedMethodName => "Methods of an interface must have distinct names (no overloading).",
+            DiagnosticCode::MixedMethodIds => "Either all methods of an interface have an explicit transaction code or none of them has one.",
+            DiagnosticCode::DuplicatedMethodId => "Methods of an interface must have distinct transaction codes.",
+            DiagnosticCode::InvalidTransactCode => "The transaction code of a method must be a valid non-negative integer.",
+            DiagnosticCode::InvalidAsyncReturnType => "Oneway methods cannot return a value, their return type must be `void`.",
+            DiagnosticCode::RedundantOneway => "Methods of a oneway interface are implicitly oneway.",
+            DiagnosticCode::MissingDirection => "Arguments of non-primitive types must have a direction (`in`, `out` or `inout`).",
+            DiagnosticCode::InvalidDirection => "The direction is not allowed for the type of the argument (e.g. `out` for a primitive or an interface).",
+            DiagnosticCode::InvalidArgument => "The type cannot be used as a method argument (e.g. `void`).",
+            DiagnosticCode::EmptyUnion => "A union needs at least one field.",
+            DiagnosticCode::InvalidUnionDefaultValue => "Only the first field of a union may have a default value.",
+            DiagnosticCode::InvalidBackingType => "The backing type of an enum must be `byte`, `int` or `long`.",
+            DiagnosticCode::DuplicatedEnumValue => "Several elements of an enum have the same value.",
+            DiagnosticCode::MultiDimensionalArray => "Arrays must be one-dimensional.",
+            DiagnosticCode::InvalidArrayElement => "The type cannot be used as array element.",
+            DiagnosticCode::InvalidListElement => "The type cannot be used as list element.",
+            DiagnosticCode::InvalidMapKey => "The type cannot be used as map key (only `String` is allowed).",
+            DiagnosticCode::InvalidMapValue => "The type cannot be used as map value.",
+            DiagnosticCode::InvalidOperator => "Shift operators must be written without whitespace between the two characters (`<<` or `>>`).",
+            DiagnosticCode::InvalidLiteral => "The literal of a constant expression is malformed or too large.",
+            DiagnosticCode::UnknownConstant => "A constant expression references a constant or an enum element which is not defined.",
+            DiagnosticCode::CyclicReference => "The value of a constant depends on itself.",
+            DiagnosticCode::ForwardReference => "An enum element references an element of the same enum which is declared after it.",
+            DiagnosticCode::MismatchedTypes => "The operands or the value of a constant expression do not have the expected type.",
+            DiagnosticCode::ValueOutOfRange => "The value of a constant expression does not fit into the declared type.",
+            DiagnosticCode::Overflow => "An arithmetic operation of a constant expression overflows.",
+            DiagnosticCode::DivisionByZero => "A constant expression divides by zero.",
+            DiagnosticCode::InvalidShift => "The shift amount of a constant expression must be between 0 and the number of bits of the operand minus one.",
+            DiagnosticCode::RemovedItem => "An item of the previous version has been removed.",
+            DiagnosticCode::ChangedItemKind => "An item of the previous version has been changed into another kind of item (e.g. a parcelable into a union).",
+            DiagnosticCode::RemovedMethod => "A method of the previous version has been removed.",
+            DiagnosticCode::ChangedTransactCode => "The transaction code of a method differs from the previous version (e.g. because a method has been inserted before it).",
+            DiagnosticCode::ChangedMethodSignature => "The return type, the arguments or the oneway modifier of a method differ from the previous version.",
+            DiagnosticCode::RemovedField => "A field of the previous version has been removed.",
+            DiagnosticCode::ReorderedField => "The position of a field differs from the previous version: new fields must be added after the existing ones.",
+            DiagnosticCode::ChangedFieldType => "The type of a field differs from the previous version.",
+            DiagnosticCode::RemovedConstant => "A constant of the previous version has been removed.",
+            DiagnosticCode::ChangedConstantValue => "The type or the value of a constant differs from the previous version.",
+            DiagnosticCode::RemovedEnumElement => "An element of an enum of the previous version has been removed.",
+            DiagnosticCode::ChangedEnumValue => "The value of an enum element differs from the previous version.",
+            DiagnosticCode::ChangedBackingType => "The backing type of an enum differs from the previous version.",
+            DiagnosticCode::UnknownAnnotation => "The annotation is not one of the built-in annotations of the AIDL compiler.",
+            DiagnosticCode::MisplacedAnnotation => "The annotation is not allowed on this kind of declaration (e.g. `@Backing` on a parcelable) or on this type (e.g. `@nullable` on a primitive).",
+            DiagnosticCode::InvalidAnnotationParameter => "A parameter of the annotation is unknown, missing or has a value of the wrong kind.",
+            DiagnosticCode::ImplicitTransactCode => "The methods of a stable (`@VintfStability`) interface are numbered by their position: inserting a method before an existing one silently renumbers the next methods and breaks the compatibility.",
+        }
+    }
+
+    /// Whether the content could not be parsed (e.g. unexpected token), in which case
+    /// the diagnostic is not affected by the lint levels and suppressions
+    pub fn is_syntax_error(&self) -> bool {
+        matches!(
+            self,
+            DiagnosticCode::InvalidToken
+                | DiagnosticCode::UnrecognizedEof
+                | DiagnosticCode::UnrecognizedToken
+                | DiagnosticCode::ExtraToken
+                | DiagnosticCode::InvalidOperator
+        )
+    }
+
+    /// Find the diagnostic code from its string representation (e.g. `AIDL0007`)
+    pub fn from_code(code: &str) -> Option<DiagnosticCode> {
+        Self::ALL.iter().find(|c| c.as_str() == code).copied()
+    }
+
+    /// Find the diagnostic code from its name (e.g. `unused-import`)
+    pub fn from_name(name: &str) -> Option<DiagnosticCode> {
+        Self::ALL.iter().find(|c| c.name() == name).copied()
+    }
+}
+
+impl std::fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl serde::Serialize for DiagnosticCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 #[derive(Serialize, Clone, Debug, PartialEq, Eq)]
 pub struct RelatedInfo {
     pub range: Range,
     pub message: String,
+
+    /// Name of the file of the range (see ParseFileResult::get_file_name()) when it is
+    /// not the file of the diagnostic (e.g. the old version of a file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Text edit which fixes a diagnostic
+///
+/// The text in the range is replaced with the given replacement (an empty range
+/// means insertion, an empty replacement means deletion).
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Fix {
+    /// Description of the fix (e.g. "remove unused import")
+    pub message: String,
+    pub range: Range,
+    pub replacement: String,
+}
+
+impl Fix {
+    pub(crate) fn remove(message: &str, range: Range) -> Self {
+        Fix {
+            message: message.to_owned(),
+            range,
+            replacement: String::new(),
+        }
+    }
+
+    pub(crate) fn insert(message: &str, position: &Position, text: String) -> Self {
+        Fix {
+            message: message.to_owned(),
+            range: Range {
+                start: position.clone(),
+                end: position.clone(),
+            },
+            replacement: text,
+        }
+    }
+}
+
+/// Apply the given fixes to the content of a file
+///
+/// Fixes which overlap with a previously applied fix are ignored.
+pub fn apply_fixes<'a, I>(content: &str, fixes: I) -> String
+where
+    I: IntoIterator<Item = &'a Fix>,
+{
+    let mut fixes: Vec<&Fix> = fixes.into_iter().collect();
+    fixes.sort_by_key(|f| (f.range.start.offset, f.range.end.offset));
+
+    let mut res = String::with_capacity(content.len());
+    let mut offset = 0;
+    for fix in fixes {
+        if fix.range.start.offset < offset || fix.range.end.offset > content.len() {
+            continue;
+        }
+        res.push_str(&content[offset..fix.range.start.offset]);
+        res.push_str(&fix.replacement);
+        offset = fix.range.end.offset;
+    }
+    res.push_str(&content[offset..]);
+
+    res
 }
 
 pub type ErrorRecovery<'input> =
-    lalrpop_util::ErrorRecovery<usize, rules::aidl::Token<'input>, &'static str>;
+    lalrpop_util::ErrorRecovery<usize, rules::Token<'input>, rules::InvalidToken>;
 
 pub type ParseError<'input> =
-    lalrpop_util::ParseError<usize, rules::aidl::Token<'input>, &'static str>;
+    lalrpop_util::ParseError<usize, rules::Token<'input>, rules::InvalidToken>;
 
 impl Diagnostic {
     pub(crate) fn from_error_recovery(
@@ -50,24 +546,33 @@ impl Diagnostic {
         e: ParseError,
     ) -> Option<Diagnostic> {
         match e {
-            lalrpop_util::ParseError::InvalidToken { location } => Some(Diagnostic {
+            // The invalid tokens are reported by the CST lexer which feeds the grammar
+            lalrpop_util::ParseError::InvalidToken { location }
+            | lalrpop_util::ParseError::User {
+                error: rules::InvalidToken { location },
+            } => Some(Diagnostic {
                 kind: DiagnosticKind::Error,
+                code: DiagnosticCode::InvalidToken,
                 message: "Invalid token".to_owned(),
                 context_message: Some("invalid token".to_owned()),
                 range: Range::new(lookup, location, location),
                 hint: None,
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             }),
             lalrpop_util::ParseError::UnrecognizedEOF { location, expected } => Some(Diagnostic {
                 kind: DiagnosticKind::Error,
+                code: DiagnosticCode::UnrecognizedEof,
                 message: format!("Unrecognized EOF.\n{}", expected_token_str(&expected)),
                 context_message: Some("unrecognized EOF".to_owned()),
                 range: Range::new(lookup, location, location),
                 hint: None,
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             }),
             lalrpop_util::ParseError::UnrecognizedToken { token, expected } => Some(Diagnostic {
                 kind: DiagnosticKind::Error,
+                code: DiagnosticCode::UnrecognizedToken,
                 message: format!(
                     "Unrecognized token `{}`.\n{}",
                     token.1,
@@ -77,16 +582,18 @@ impl Diagnostic {
                 range: Range::new(lookup, token.0, token.2),
                 hint: None,
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             }),
             lalrpop_util::ParseError::ExtraToken { token } => Some(Diagnostic {
                 kind: DiagnosticKind::Error,
+                code: DiagnosticCode::ExtraToken,
                 message: format!("Extra token `{}`", token.1,),
                 context_message: Some("extra token".to_owned()),
                 range: Range::new(lookup, token.0, token.2),
                 hint: None,
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             }),
-            lalrpop_util::ParseError::User { error: _ } => None, // User errors already produced a Diagnostic
         }
     }
 }
@@ -99,8 +606,38 @@ fn expected_token_str(v: &[String]) -> String {
         2 => format!("Expected {} or {}", v[0], v[1]),
         _ => format!(
             "Expected one of {} or {}",
-            v[0..v.len() - 2].join(", "),
+            v[0..v.len() - 1].join(", "),
             v[v.len() - 1]
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_codes() {
+        for (i, code) in DiagnosticCode::ALL.iter().enumerate() {
+            // Codes are numbered sequentially
+            assert_eq!(code.as_str(), format!("AIDL{:04}", i + 1));
+            assert_eq!(DiagnosticCode::from_code(code.as_str()), Some(*code));
+            assert_eq!(DiagnosticCode::from_name(code.name()), Some(*code));
+            assert!(!code.explanation().is_empty());
+        }
+
+        assert_eq!(DiagnosticCode::from_code("AIDL9999"), None);
+    }
+
+    #[test]
+    fn test_expected_token_str() {
+        let tokens = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(expected_token_str(&tokens(&[])), "");
+        assert_eq!(expected_token_str(&tokens(&["A"])), "Expected A");
+        assert_eq!(expected_token_str(&tokens(&["A", "B"])), "Expected A or B");
+        assert_eq!(
+            expected_token_str(&tokens(&["A", "B", "C"])),
+            "Expected one of A, B or C"
+        );
+    }
+}
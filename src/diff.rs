@@ -0,0 +1,633 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::api_dump::{format_annotations, format_type};
+use crate::ast;
+use crate::parser::ParseFileResult;
+
+/// Change of an element between two revisions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Added {
+        element: ElementKind,
+        /// Qualified name of the element (e.g. `com.example.IFoo.getName`)
+        path: String,
+    },
+    Removed {
+        element: ElementKind,
+        path: String,
+    },
+    /// Change of one property of an element which exists in both revisions
+    Modified {
+        element: ElementKind,
+        path: String,
+        property: Property,
+        /// Value of the property in the old revision (`None` if not set, e.g. no doc)
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { element, path } => write!(f, "added {element} `{path}`"),
+            Change::Removed { element, path } => write!(f, "removed {element} `{path}`"),
+            Change::Modified {
+                element,
+                path,
+                property,
+                old,
+                new,
+            } => {
+                let format_value = |v: &Option<String>| match v {
+                    Some(v) => format!("`{v}`"),
+                    None => String::from("none"),
+                };
+                write!(
+                    f,
+                    "modified {property} of {element} `{path}`: {} -> {}",
+                    format_value(old),
+                    format_value(new)
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ElementKind {
+    Interface,
+    Parcelable,
+    Union,
+    Enum,
+    Method,
+    Field,
+    Const,
+    EnumElement,
+}
+
+impl fmt::Display for ElementKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElementKind::Interface => write!(f, "interface"),
+            ElementKind::Parcelable => write!(f, "parcelable"),
+            ElementKind::Union => write!(f, "union"),
+            ElementKind::Enum => write!(f, "enum"),
+            ElementKind::Method => write!(f, "method"),
+            ElementKind::Field => write!(f, "field"),
+            ElementKind::Const => write!(f, "const"),
+            ElementKind::EnumElement => write!(f, "enum element"),
+        }
+    }
+}
+
+/// Property of a modified element
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Property {
+    /// Kind of an item (e.g. parcelable instead of union)
+    Kind,
+    Annotations,
+    Doc,
+    /// Oneway modifier of an interface or a method
+    Oneway,
+    /// Type parameters of a generic parcelable
+    TypeParameters,
+    /// Type of a field or a const, or return type of a method
+    Type,
+    /// Directions, types and names of the arguments of a method
+    Arguments,
+    /// Explicit transaction code of a method
+    TransactCode,
+    /// Value of a const, default value of a field or value of an enum element
+    Value,
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Property::Kind => write!(f, "kind"),
+            Property::Annotations => write!(f, "annotations"),
+            Property::Doc => write!(f, "doc"),
+            Property::Oneway => write!(f, "oneway"),
+            Property::TypeParameters => write!(f, "type parameters"),
+            Property::Type => write!(f, "type"),
+            Property::Arguments => write!(f, "arguments"),
+            Property::TransactCode => write!(f, "transaction code"),
+            Property::Value => write!(f, "value"),
+        }
+    }
+}
+
+/// Semantic diff between two revisions of a set of AIDL files
+///
+/// The items are matched by qualified name and the files without AST are ignored.
+/// The changes are sorted by the qualified name of their item (see diff_asts()).
+///
+/// Example:
+/// ```
+/// use aidl_parser::diff;
+/// use aidl_parser::Parser;
+///
+/// let mut old = Parser::new();
+/// old.add_content("IFoo.aidl", "package p; interface IFoo { void a(); }");
+/// let mut new = Parser::new();
+/// new.add_content("IFoo.aidl", "package p;\n\ninterface IFoo {\n    /** Doc */\n    void a();\n    void b();\n}\n");
+///
+/// let changes = diff::diff(&old.validate(), &new.validate());
+/// let changes: Vec<String> = changes.iter().map(ToString::to_string).collect();
+/// assert_eq!(
+///     changes,
+///     [
+///         "modified doc of method `p.IFoo.a`: none -> `Doc`",
+///         "added method `p.IFoo.b`",
+///     ]
+/// );
+/// ```
+pub fn diff<ID>(
+    old: &HashMap<ID, ParseFileResult<ID>>,
+    new: &HashMap<ID, ParseFileResult<ID>>,
+) -> Vec<Change>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    let get_asts = |results: &'_ HashMap<ID, ParseFileResult<ID>>| {
+        results
+            .values()
+            .filter_map(|res| res.ast.clone())
+            .map(|ast| (ast.get_key(), ast))
+            .collect::<BTreeMap<String, ast::Aidl>>()
+    };
+    let old_asts = get_asts(old);
+    let new_asts = get_asts(new);
+
+    let mut keys: Vec<&String> = old_asts.keys().chain(new_asts.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut changes = Vec::new();
+    for key in keys {
+        match (old_asts.get(key), new_asts.get(key)) {
+            (Some(old), Some(new)) => changes.extend(diff_asts(old, new)),
+            (Some(old), None) => changes.push(Change::Removed {
+                element: get_item_kind(&old.item),
+                path: key.clone(),
+            }),
+            (None, Some(new)) => changes.push(Change::Added {
+                element: get_item_kind(&new.item),
+                path: key.clone(),
+            }),
+            (None, None) => (),
+        }
+    }
+
+    changes
+}
+
+/// Semantic diff between two revisions of an AIDL file
+///
+/// The positions, comments and formatting are ignored: only the declarations, their
+/// annotations and their docs are compared (with fully qualified types). The changes
+/// of an item come before the changes of its members and of its nested items, in the
+/// order of the new revision (followed by the removed elements).
+pub fn diff_asts(old: &ast::Aidl, new: &ast::Aidl) -> Vec<Change> {
+    let mut differ = Differ {
+        changes: Vec::new(),
+    };
+    differ.diff_item(&new.get_key(), &old.item, &new.item);
+
+    differ.changes
+}
+
+// Comparable properties and members of an element
+struct Element<'a> {
+    name: &'a str,
+    kind: ElementKind,
+    properties: Vec<(Property, Option<String>)>,
+    item: Option<&'a ast::Item>,
+}
+
+struct Differ {
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn diff_item(&mut self, path: &str, old: &ast::Item, new: &ast::Item) {
+        let old_kind = get_item_kind(old);
+        let new_kind = get_item_kind(new);
+        if old_kind != new_kind {
+            self.changes.push(Change::Modified {
+                element: new_kind,
+                path: path.to_owned(),
+                property: Property::Kind,
+                old: Some(old_kind.to_string()),
+                new: Some(new_kind.to_string()),
+            });
+        }
+
+        self.diff_properties(
+            path,
+            new_kind,
+            &get_item_properties(old),
+            &get_item_properties(new),
+        );
+
+        let old_members = get_members(old);
+        let new_members = get_members(new);
+        for new_member in &new_members {
+            let member_path = format!("{path}.{}", new_member.name);
+            match old_members
+                .iter()
+                .find(|m| m.name == new_member.name && is_same_category(m, new_member))
+            {
+                Some(old_member) => match (old_member.item, new_member.item) {
+                    (Some(old_item), Some(new_item)) => {
+                        self.diff_item(&member_path, old_item, new_item)
+                    }
+                    _ => self.diff_properties(
+                        &member_path,
+                        new_member.kind,
+                        &old_member.properties,
+                        &new_member.properties,
+                    ),
+                },
+                None => self.changes.push(Change::Added {
+                    element: new_member.kind,
+                    path: member_path,
+                }),
+            }
+        }
+
+        for old_member in &old_members {
+            if !new_members
+                .iter()
+                .any(|m| m.name == old_member.name && is_same_category(m, old_member))
+            {
+                self.changes.push(Change::Removed {
+                    element: old_member.kind,
+                    path: format!("{path}.{}", old_member.name),
+                });
+            }
+        }
+    }
+
+    fn diff_properties(
+        &mut self,
+        path: &str,
+        element: ElementKind,
+        old: &[(Property, Option<String>)],
+        new: &[(Property, Option<String>)],
+    ) {
+        for (property, new_value) in new {
+            let old_value = old
+                .iter()
+                .find(|(p, _)| p == property)
+                .and_then(|(_, v)| v.clone());
+            if old_value != *new_value {
+                self.changes.push(Change::Modified {
+                    element,
+                    path: path.to_owned(),
+                    property: *property,
+                    old: old_value,
+                    new: new_value.clone(),
+                });
+            }
+        }
+    }
+}
+
+// Items are matched with items, and other members with members of the same kind
+fn is_same_category(m1: &Element, m2: &Element) -> bool {
+    m1.item.is_some() == m2.item.is_some() && (m1.item.is_some() || m1.kind == m2.kind)
+}
+
+fn get_item_kind(item: &ast::Item) -> ElementKind {
+    match item {
+        ast::Item::Interface(_) => ElementKind::Interface,
+        ast::Item::Parcelable(_) => ElementKind::Parcelable,
+        ast::Item::Union(_) => ElementKind::Union,
+        ast::Item::Enum(_) => ElementKind::Enum,
+    }
+}
+
+fn get_item_properties(item: &ast::Item) -> Vec<(Property, Option<String>)> {
+    let (annotations, doc) = match item {
+        ast::Item::Interface(i) => (&i.annotations, &i.doc),
+        ast::Item::Parcelable(p) => (&p.annotations, &p.doc),
+        ast::Item::Union(u) => (&u.annotations, &u.doc),
+        ast::Item::Enum(e) => (&e.annotations, &e.doc),
+    };
+
+    let mut properties = Vec::from([
+        (Property::Annotations, join_annotations(annotations)),
+        (Property::Doc, doc.clone()),
+    ]);
+    match item {
+        ast::Item::Interface(i) => properties.push((Property::Oneway, Some(i.oneway.to_string()))),
+        ast::Item::Parcelable(p) if !p.type_parameters.is_empty() => {
+            properties.push((Property::TypeParameters, Some(p.type_parameters.join(", "))))
+        }
+        _ => (),
+    }
+
+    properties
+}
+
+fn get_members(item: &ast::Item) -> Vec<Element<'_>> {
+    let mut members: Vec<Element> = match item {
+        ast::Item::Interface(i) => i
+            .elements
+            .iter()
+            .map(|el| match el {
+                ast::InterfaceElement::Const(c) => get_const_element(c),
+                ast::InterfaceElement::Method(m) => get_method_element(m),
+            })
+            .collect(),
+        ast::Item::Parcelable(p) => p
+            .elements
+            .iter()
+            .map(|el| match el {
+                ast::ParcelableElement::Const(c) => get_const_element(c),
+                ast::ParcelableElement::Field(f) => get_field_element(f),
+            })
+            .collect(),
+        ast::Item::Union(u) => u
+            .elements
+            .iter()
+            .map(|el| match el {
+                ast::UnionElement::Const(c) => get_const_element(c),
+                ast::UnionElement::Field(f) => get_field_element(f),
+            })
+            .collect(),
+        ast::Item::Enum(e) => e
+            .elements
+            .iter()
+            .map(|el| Element {
+                name: &el.name,
+                kind: ElementKind::EnumElement,
+                properties: Vec::from([
+                    (
+                        Property::Value,
+                        format_value(&el.evaluated_value, el.value.as_ref()),
+                    ),
+                    (Property::Doc, el.doc.clone()),
+                ]),
+                item: None,
+            })
+            .collect(),
+    };
+
+    members.extend(item.get_nested_items().iter().map(|nested| Element {
+        name: nested.get_name(),
+        kind: get_item_kind(nested),
+        properties: Vec::new(),
+        item: Some(nested),
+    }));
+
+    members
+}
+
+fn get_const_element(c: &ast::Const) -> Element<'_> {
+    Element {
+        name: &c.name,
+        kind: ElementKind::Const,
+        properties: Vec::from([
+            (Property::Annotations, join_annotations(&c.annotations)),
+            (Property::Doc, c.doc.clone()),
+            (Property::Type, Some(format_type(&c.const_type))),
+            (
+                Property::Value,
+                format_value(&c.evaluated_value, Some(&c.value)),
+            ),
+        ]),
+        item: None,
+    }
+}
+
+fn get_method_element(m: &ast::Method) -> Element<'_> {
+    let args: Vec<String> = m
+        .args
+        .iter()
+        .map(|arg| {
+            let mut s = String::new();
+            if !matches!(arg.direction, ast::Direction::Unspecified) {
+                s.push_str(&format!("{} ", arg.direction));
+            }
+            for annotation in format_annotations(&arg.annotations) {
+                s.push_str(&format!("{annotation} "));
+            }
+            s.push_str(&format_type(&arg.arg_type));
+            if let Some(name) = &arg.name {
+                s.push_str(&format!(" {name}"));
+            }
+            s
+        })
+        .collect();
+
+    Element {
+        name: &m.name,
+        kind: ElementKind::Method,
+        properties: Vec::from([
+            (Property::Annotations, join_annotations(&m.annotations)),
+            (Property::Doc, m.doc.clone()),
+            (Property::Oneway, Some(m.has_oneway_modifier().to_string())),
+            (Property::Type, Some(format_type(&m.return_type))),
+            (Property::Arguments, Some(args.join(", "))),
+            (
+                Property::TransactCode,
+                m.transact_code.map(|code| code.to_string()),
+            ),
+        ]),
+        item: None,
+    }
+}
+
+fn get_field_element(f: &ast::Field) -> Element<'_> {
+    Element {
+        name: &f.name,
+        kind: ElementKind::Field,
+        properties: Vec::from([
+            (Property::Annotations, join_annotations(&f.annotations)),
+            (Property::Doc, f.doc.clone()),
+            (Property::Type, Some(format_type(&f.field_type))),
+            (
+                Property::Value,
+                format_value(&f.evaluated_value, f.value.as_ref()),
+            ),
+        ]),
+        item: None,
+    }
+}
+
+fn join_annotations(annotations: &[ast::Annotation]) -> Option<String> {
+    if annotations.is_empty() {
+        None
+    } else {
+        Some(format_annotations(annotations).join(" "))
+    }
+}
+
+// Evaluated value, or the expression if it could not be evaluated
+fn format_value(
+    evaluated_value: &Option<ast::ConstValue>,
+    expr: Option<&ast::ConstExpr>,
+) -> Option<String> {
+    match (evaluated_value, expr) {
+        (Some(value), _) => Some(value.to_string()),
+        (None, Some(expr)) => Some(expr.to_string()),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn diff_contents(old: &str, new: &str) -> Vec<Change> {
+        let mut old_parser = Parser::new();
+        old_parser.add_content("id", old);
+        let mut new_parser = Parser::new();
+        new_parser.add_content("id", new);
+
+        diff(&old_parser.validate(), &new_parser.validate())
+    }
+
+    #[test]
+    fn test_no_change() {
+        let old = "package p; /** Foo */ interface IFoo { const int C = 1; void foo(in int i); }";
+        let new = r#"
+            package p;
+
+            // Comment
+            /** Foo */
+            interface IFoo {
+                const int C = 3 - 2;
+
+                void foo(in int i);
+            }
+        "#;
+
+        assert_eq!(diff_contents(old, new), []);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = r#"package p;
+            interface IFoo {
+                const int C = 1;
+                void a(in int i);
+                void b();
+                parcelable Nested { int x; }
+            }
+        "#;
+        let new = r#"package p;
+            /** Foo */
+            @VintfStability
+            oneway interface IFoo {
+                const long C = 1;
+                void a(in int[] values) = 2;
+                void c();
+                parcelable Nested { int x = 3; }
+                enum Added { A }
+            }
+        "#;
+
+        let changes: Vec<String> = diff_contents(old, new)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "modified annotations of interface `p.IFoo`: none -> `@VintfStability`",
+                "modified doc of interface `p.IFoo`: none -> `Foo`",
+                "modified oneway of interface `p.IFoo`: `false` -> `true`",
+                "modified type of const `p.IFoo.C`: `int` -> `long`",
+                "modified value of const `p.IFoo.C`: `1` -> `1L`",
+                "modified arguments of method `p.IFoo.a`: `in int i` -> `in int[] values`",
+                "modified transaction code of method `p.IFoo.a`: none -> `2`",
+                "added method `p.IFoo.c`",
+                "modified value of field `p.IFoo.Nested.x`: none -> `3`",
+                "added enum `p.IFoo.Added`",
+                "removed method `p.IFoo.b`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_oneway_method() {
+        let changes: Vec<String> = diff_contents(
+            "package p; interface IFoo { void a(); }",
+            "package p; interface IFoo { oneway void a(); }",
+        )
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(
+            changes,
+            ["modified oneway of method `p.IFoo.a`: `false` -> `true`"]
+        );
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let mut old_parser = Parser::new();
+        old_parser.add_content("Bar", "package p; parcelable Bar {}");
+        old_parser.add_content("Color", "package p; enum Color { RED }");
+        let mut new_parser = Parser::new();
+        new_parser.add_content("Color", "package p; union Color { int red; }");
+        new_parser.add_content("Baz", "package p; parcelable Baz {}");
+
+        assert_eq!(
+            diff(&old_parser.validate(), &new_parser.validate()),
+            [
+                Change::Removed {
+                    element: ElementKind::Parcelable,
+                    path: String::from("p.Bar"),
+                },
+                Change::Added {
+                    element: ElementKind::Parcelable,
+                    path: String::from("p.Baz"),
+                },
+                Change::Modified {
+                    element: ElementKind::Union,
+                    path: String::from("p.Color"),
+                    property: Property::Kind,
+                    old: Some(String::from("enum")),
+                    new: Some(String::from("union")),
+                },
+                Change::Added {
+                    element: ElementKind::Field,
+                    path: String::from("p.Color.red"),
+                },
+                Change::Removed {
+                    element: ElementKind::EnumElement,
+                    path: String::from("p.Color.RED"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let change = Change::Modified {
+            element: ElementKind::Method,
+            path: String::from("p.IFoo.a"),
+            property: Property::TransactCode,
+            old: None,
+            new: Some(String::from("2")),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&change).expect("json"),
+            r#"{"change":"modified","element":"method","path":"p.IFoo.a","property":"transact_code","old":null,"new":"2"}"#
+        );
+    }
+}
@@ -0,0 +1,1335 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::ast;
+use crate::diagnostic::{self, Diagnostic, DiagnosticCode, DiagnosticKind};
+use crate::traverse;
+use crate::validation;
+
+/// Evaluates the constant expressions of all the files (consts, enum values
+/// and default values of fields) into typed values
+///
+/// Consts and enum elements can be referenced from other files, so they are
+/// collected upfront and evaluated on demand (only once).
+pub(crate) struct Evaluator<'a> {
+    defined: &'a HashMap<String, ast::ResolvedItemKind>,
+    definitions: HashMap<String, Rc<Definition>>,
+    states: HashMap<String, State>,
+}
+
+// Const or enum element which can be referenced (e.g. `pkg.Item.CONST`)
+struct Definition {
+    expr: Option<ast::ConstExpr>,
+    const_type: ast::Type,
+    enumerator: Option<Enumerator>,
+    item_key: ast::ItemKey,
+    imports: Rc<HashSet<String>>,
+    symbol_range: ast::Range,
+}
+
+// Position of an enum element inside its enum
+struct Enumerator {
+    index: usize,
+    /// Key of the previous enum element (used to compute the implicit value)
+    previous: Option<String>,
+}
+
+// Element whose value is evaluated
+enum ValueOwner<'e> {
+    Const(&'e mut ast::Const),
+    Field(&'e mut ast::Field),
+    EnumElement(&'e mut ast::EnumElement),
+}
+
+enum State {
+    InProgress,
+    Done(Option<ast::ConstValue>, Vec<Diagnostic>),
+}
+
+// Item where the expression is declared, used to resolve the references
+struct Scope<'s> {
+    item_key: ast::ItemKeyRef<'s>,
+    imports: &'s HashSet<String>,
+    /// Index of the enum element whose value is evaluated (if any)
+    enumerator_index: Option<usize>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub(crate) fn new<'b>(
+        asts: impl Iterator<Item = &'b ast::Aidl>,
+        defined: &'a HashMap<String, ast::ResolvedItemKind>,
+    ) -> Self {
+        let mut definitions = HashMap::new();
+
+        for ast in asts {
+            let imports: Rc<HashSet<String>> =
+                Rc::new(ast.imports.iter().map(|i| i.get_qualified_name()).collect());
+
+            for (item_key, item) in ast.get_all_items() {
+                let mut add = |name: &str,
+                               expr: Option<&ast::ConstExpr>,
+                               t: ast::Type,
+                               enumerator: Option<Enumerator>,
+                               symbol_range: &ast::Range| {
+                    definitions
+                        .entry(format!("{item_key}.{name}"))
+                        .or_insert_with(|| {
+                            Rc::new(Definition {
+                                expr: expr.cloned(),
+                                const_type: t,
+                                enumerator,
+                                item_key: item_key.clone(),
+                                imports: imports.clone(),
+                                symbol_range: symbol_range.clone(),
+                            })
+                        });
+                };
+
+                let consts: Vec<&ast::Const> = match item {
+                    ast::Item::Interface(i) => i
+                        .elements
+                        .iter()
+                        .filter_map(|el| match el {
+                            ast::InterfaceElement::Const(c) => Some(c),
+                            ast::InterfaceElement::Method(_) => None,
+                        })
+                        .collect(),
+                    ast::Item::Parcelable(p) => p
+                        .elements
+                        .iter()
+                        .filter_map(|el| match el {
+                            ast::ParcelableElement::Const(c) => Some(c),
+                            ast::ParcelableElement::Field(_) => None,
+                        })
+                        .collect(),
+                    ast::Item::Union(u) => u
+                        .elements
+                        .iter()
+                        .filter_map(|el| match el {
+                            ast::UnionElement::Const(c) => Some(c),
+                            ast::UnionElement::Field(_) => None,
+                        })
+                        .collect(),
+                    ast::Item::Enum(e) => {
+                        // The enum elements have the backing type of the enum
+                        let backing_type = ast::Type {
+                            name: e.get_backing_type().to_owned(),
+                            kind: ast::TypeKind::Primitive,
+                            generic_types: Vec::new(),
+                            symbol_range: e.symbol_range.clone(),
+                            full_range: e.symbol_range.clone(),
+                        };
+
+                        for (index, el) in e.elements.iter().enumerate() {
+                            let enumerator = Enumerator {
+                                index,
+                                previous: index
+                                    .checked_sub(1)
+                                    .map(|i| format!("{item_key}.{}", e.elements[i].name)),
+                            };
+                            add(
+                                &el.name,
+                                el.value.as_ref(),
+                                backing_type.clone(),
+                                Some(enumerator),
+                                &el.symbol_range,
+                            );
+                        }
+                        Vec::new()
+                    }
+                };
+
+                for c in consts {
+                    add(
+                        &c.name,
+                        Some(&c.value),
+                        c.const_type.clone(),
+                        None,
+                        &c.symbol_range,
+                    );
+                }
+            }
+        }
+
+        Evaluator {
+            defined,
+            definitions,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Set the evaluated values of the consts, enum elements and fields of the given AST
+    /// and add the diagnostics of their expressions
+    pub(crate) fn evaluate_ast(&mut self, ast: &mut ast::Aidl, diagnostics: &mut Vec<Diagnostic>) {
+        let imports: HashSet<String> = ast.imports.iter().map(|i| i.get_qualified_name()).collect();
+
+        traverse::walk_items_mut(ast, |item, item_key| {
+            let scope = Scope {
+                item_key,
+                imports: &imports,
+                enumerator_index: None,
+            };
+
+            let elements: Vec<ValueOwner> = match item {
+                ast::Item::Interface(i) => i
+                    .elements
+                    .iter_mut()
+                    .filter_map(|el| match el {
+                        ast::InterfaceElement::Const(c) => Some(ValueOwner::Const(c)),
+                        ast::InterfaceElement::Method(_) => None,
+                    })
+                    .collect(),
+                ast::Item::Parcelable(p) => p
+                    .elements
+                    .iter_mut()
+                    .map(|el| match el {
+                        ast::ParcelableElement::Const(c) => ValueOwner::Const(c),
+                        ast::ParcelableElement::Field(f) => ValueOwner::Field(f),
+                    })
+                    .collect(),
+                ast::Item::Union(u) => u
+                    .elements
+                    .iter_mut()
+                    .map(|el| match el {
+                        ast::UnionElement::Const(c) => ValueOwner::Const(c),
+                        ast::UnionElement::Field(f) => ValueOwner::Field(f),
+                    })
+                    .collect(),
+                ast::Item::Enum(e) => e.elements.iter_mut().map(ValueOwner::EnumElement).collect(),
+            };
+
+            for element in elements {
+                match element {
+                    ValueOwner::Const(c) => {
+                        let key = format!("{item_key}.{}", c.name);
+                        c.evaluated_value = self.evaluate_definition(&key, diagnostics);
+                    }
+                    ValueOwner::EnumElement(el) => {
+                        let key = format!("{item_key}.{}", el.name);
+                        el.evaluated_value = self.evaluate_definition(&key, diagnostics);
+                    }
+                    ValueOwner::Field(f) => {
+                        if let Some(expr) = &f.value {
+                            f.evaluated_value = self.evaluate_with_type(
+                                expr,
+                                Some(&f.field_type),
+                                &scope,
+                                diagnostics,
+                            );
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Keys of the items referenced by the constant expressions of the given AST
+    /// (e.g. `pkg.Other` for `Other.FOO`)
+    pub(crate) fn get_referenced_items(&self, ast: &ast::Aidl) -> HashSet<ast::ItemKey> {
+        let mut referenced_items = HashSet::new();
+
+        self.walk_references(ast, |_, reference| match reference {
+            Reference::Resolved(key) => {
+                if let Some((item_key, _)) = key.rsplit_once('.') {
+                    referenced_items.insert(item_key.to_owned());
+                }
+            }
+            Reference::Unknown(item_key) => {
+                referenced_items.insert(item_key);
+            }
+            Reference::Unresolved => (),
+        });
+
+        referenced_items
+    }
+
+    /// References of the constant expressions of the given AST which could be resolved,
+    /// with their expression and the key of the const or enum element (e.g. `pkg.Other.FOO`)
+    pub(crate) fn get_resolved_references<'e>(
+        &self,
+        ast: &'e ast::Aidl,
+    ) -> Vec<(&'e ast::ConstExpr, String)> {
+        let mut references = Vec::new();
+
+        self.walk_references(ast, |expr, reference| {
+            if let Reference::Resolved(key) = reference {
+                references.push((expr, key));
+            }
+        });
+
+        references
+    }
+
+    // Visit the references of the constant expressions of the given AST
+    fn walk_references<'e, F>(&self, ast: &'e ast::Aidl, mut f: F)
+    where
+        F: FnMut(&'e ast::ConstExpr, Reference),
+    {
+        fn walk<'e, F: FnMut(&'e ast::ConstExpr, &'e str)>(expr: &'e ast::ConstExpr, f: &mut F) {
+            match &expr.kind {
+                ast::ConstExprKind::Reference(name) => f(expr, name),
+                ast::ConstExprKind::Array(elements) => elements.iter().for_each(|el| walk(el, f)),
+                ast::ConstExprKind::Unary { expr, .. } => walk(expr, f),
+                ast::ConstExprKind::Binary { lhs, rhs, .. } => {
+                    walk(lhs, f);
+                    walk(rhs, f);
+                }
+                _ => (),
+            }
+        }
+
+        let imports: HashSet<String> = ast.imports.iter().map(|i| i.get_qualified_name()).collect();
+
+        for (item_key, item) in ast.get_all_items() {
+            let scope = Scope {
+                item_key: &item_key,
+                imports: &imports,
+                enumerator_index: None,
+            };
+
+            let exprs: Vec<&ast::ConstExpr> = match item {
+                ast::Item::Interface(i) => i
+                    .elements
+                    .iter()
+                    .filter_map(|el| match el {
+                        ast::InterfaceElement::Const(c) => Some(&c.value),
+                        ast::InterfaceElement::Method(_) => None,
+                    })
+                    .collect(),
+                ast::Item::Parcelable(p) => p
+                    .elements
+                    .iter()
+                    .filter_map(|el| match el {
+                        ast::ParcelableElement::Const(c) => Some(&c.value),
+                        ast::ParcelableElement::Field(f) => f.value.as_ref(),
+                    })
+                    .collect(),
+                ast::Item::Union(u) => u
+                    .elements
+                    .iter()
+                    .filter_map(|el| match el {
+                        ast::UnionElement::Const(c) => Some(&c.value),
+                        ast::UnionElement::Field(f) => f.value.as_ref(),
+                    })
+                    .collect(),
+                ast::Item::Enum(e) => e
+                    .elements
+                    .iter()
+                    .filter_map(|el| el.value.as_ref())
+                    .collect(),
+            };
+
+            for expr in exprs {
+                walk(expr, &mut |expr, name| {
+                    f(expr, self.resolve_reference(name, &scope))
+                });
+            }
+        }
+    }
+
+    // Evaluate the definition (once) and add the diagnostics of its expression
+    fn evaluate_definition(
+        &mut self,
+        key: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<ast::ConstValue> {
+        let value = self.evaluate_key(key);
+        if let Some(State::Done(_, definition_diagnostics)) = self.states.get(key) {
+            diagnostics.extend(definition_diagnostics.iter().cloned());
+        }
+
+        value
+    }
+
+    fn evaluate_key(&mut self, key: &str) -> Option<ast::ConstValue> {
+        if let Some(State::Done(value, _)) = self.states.get(key) {
+            return value.clone();
+        }
+
+        let definition = self.definitions.get(key)?.clone();
+
+        self.states.insert(key.to_owned(), State::InProgress);
+
+        let scope = Scope {
+            item_key: &definition.item_key,
+            imports: &definition.imports,
+            enumerator_index: definition.enumerator.as_ref().map(|e| e.index),
+        };
+        let mut diagnostics = Vec::new();
+        let value = match (&definition.expr, &definition.enumerator) {
+            (Some(expr), _) => self.evaluate_with_type(
+                expr,
+                Some(&definition.const_type),
+                &scope,
+                &mut diagnostics,
+            ),
+            (None, Some(enumerator)) => {
+                self.evaluate_implicit_value(&definition, enumerator, &mut diagnostics)
+            }
+            (None, None) => None,
+        };
+
+        self.states
+            .insert(key.to_owned(), State::Done(value.clone(), diagnostics));
+
+        value
+    }
+
+    // The implicit value of an enum element is the value of the previous element + 1
+    // (or 0 for the first element)
+    fn evaluate_implicit_value(
+        &mut self,
+        definition: &Definition,
+        enumerator: &Enumerator,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<ast::ConstValue> {
+        let value = match &enumerator.previous {
+            Some(previous) => {
+                let previous_value = self.evaluate_key(previous)?;
+                match to_integral(&previous_value)?.checked_add(1) {
+                    Some(v) => {
+                        i32::try_from(v).map_or(ast::ConstValue::Long(v), ast::ConstValue::Int)
+                    }
+                    None => return overflow(&definition.symbol_range, diagnostics),
+                }
+            }
+            None => ast::ConstValue::Int(0),
+        };
+
+        let backing_type = &definition.const_type;
+        convert(value, backing_type, &definition.symbol_range, diagnostics)
+    }
+
+    // Evaluate the expression and convert it into the given type (if any)
+    fn evaluate_with_type(
+        &mut self,
+        expr: &ast::ConstExpr,
+        type_: Option<&ast::Type>,
+        scope: &Scope,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<ast::ConstValue> {
+        let type_ = match type_ {
+            Some(t) => t,
+            None => return self.evaluate(expr, scope, diagnostics),
+        };
+
+        match (&type_.kind, &expr.kind) {
+            // Convert each element of the array (to get the proper range in case of error)
+            (ast::TypeKind::Array, ast::ConstExprKind::Array(elements)) => {
+                let element_type = type_.generic_types.first()?;
+                let values: Vec<Option<ast::ConstValue>> = elements
+                    .iter()
+                    .map(|el| self.evaluate_with_type(el, Some(element_type), scope, diagnostics))
+                    .collect();
+
+                values
+                    .into_iter()
+                    .collect::<Option<Vec<_>>>()
+                    .map(ast::ConstValue::Array)
+            }
+            (
+                ast::TypeKind::Primitive
+                | ast::TypeKind::String
+                | ast::TypeKind::CharSequence
+                | ast::TypeKind::Array,
+                _,
+            ) => {
+                let value = self.evaluate(expr, scope, diagnostics)?;
+                convert(value, type_, &expr.full_range, diagnostics)
+            }
+            // Other types (e.g. enums) cannot be checked
+            _ => None,
+        }
+    }
+
+    fn evaluate(
+        &mut self,
+        expr: &ast::ConstExpr,
+        scope: &Scope,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<ast::ConstValue> {
+        let range = &expr.full_range;
+
+        match &expr.kind {
+            ast::ConstExprKind::Integer(literal) => match parse_integer(literal) {
+                Ok(value) => Some(value),
+                Err(message) => error(
+                    DiagnosticCode::InvalidLiteral,
+                    range,
+                    message,
+                    "invalid literal",
+                    diagnostics,
+                ),
+            },
+            ast::ConstExprKind::Float(literal) => match parse_float(literal) {
+                Some(value) => Some(value),
+                None => error(
+                    DiagnosticCode::InvalidLiteral,
+                    range,
+                    format!("Invalid floating-point literal `{literal}`"),
+                    "invalid literal",
+                    diagnostics,
+                ),
+            },
+            ast::ConstExprKind::String(s) => Some(ast::ConstValue::String(s.clone())),
+            ast::ConstExprKind::Char(s) => match parse_char(s) {
+                Some(c) => Some(ast::ConstValue::Char(c)),
+                None => error(
+                    DiagnosticCode::InvalidLiteral,
+                    range,
+                    format!("Invalid character literal `'{s}'`"),
+                    "invalid literal",
+                    diagnostics,
+                ),
+            },
+            ast::ConstExprKind::Bool(b) => Some(ast::ConstValue::Bool(*b)),
+            ast::ConstExprKind::Array(elements) => {
+                let values: Vec<Option<ast::ConstValue>> = elements
+                    .iter()
+                    .map(|el| self.evaluate(el, scope, diagnostics))
+                    .collect();
+
+                values
+                    .into_iter()
+                    .collect::<Option<Vec<_>>>()
+                    .map(ast::ConstValue::Array)
+            }
+            ast::ConstExprKind::Reference(name) => {
+                self.evaluate_reference(name, range, scope, diagnostics)
+            }
+            ast::ConstExprKind::Unary { op, expr } => {
+                if let (ast::UnaryOp::Minus, ast::ConstExprKind::Integer(literal)) =
+                    (op, &expr.kind)
+                {
+                    if let Some(value) = parse_min_integer(literal) {
+                        return Some(value);
+                    }
+                }
+
+                let value = self.evaluate(expr, scope, diagnostics)?;
+                evaluate_unary(*op, value, range, diagnostics)
+            }
+            ast::ConstExprKind::Binary { lhs, op, rhs } => {
+                let lhs = self.evaluate(lhs, scope, diagnostics);
+                let rhs = self.evaluate(rhs, scope, diagnostics);
+                evaluate_binary(*op, lhs?, rhs?, range, diagnostics)
+            }
+        }
+    }
+
+    fn evaluate_reference(
+        &mut self,
+        name: &str,
+        range: &ast::Range,
+        scope: &Scope,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<ast::ConstValue> {
+        let key = match self.resolve_reference(name, scope) {
+            Reference::Resolved(key) => key,
+            Reference::Unknown(_) => return None,
+            Reference::Unresolved => {
+                return error(
+                    DiagnosticCode::UnknownConstant,
+                    range,
+                    format!("Unknown constant `{name}`"),
+                    "unknown constant",
+                    diagnostics,
+                );
+            }
+        };
+
+        // Enum elements cannot reference the elements declared after them
+        if let Some(index) = scope.enumerator_index {
+            let is_declared_later = self
+                .definitions
+                .get(&key)
+                .filter(|d| d.item_key == scope.item_key)
+                .and_then(|d| d.enumerator.as_ref())
+                .is_some_and(|e| e.index >= index);
+            if is_declared_later {
+                return error(
+                    DiagnosticCode::ForwardReference,
+                    range,
+                    format!("Enum element `{name}` is referenced before its declaration"),
+                    "forward reference",
+                    diagnostics,
+                );
+            }
+        }
+
+        if let Some(State::InProgress) = self.states.get(&key) {
+            return error(
+                DiagnosticCode::CyclicReference,
+                range,
+                format!("Cyclic reference to `{name}`"),
+                "cyclic reference",
+                diagnostics,
+            );
+        }
+
+        self.evaluate_key(&key)
+    }
+
+    fn resolve_reference(&self, name: &str, scope: &Scope) -> Reference {
+        // Keys of the enclosing items, from the innermost to the top-level item
+        let scope_keys: Vec<&str> = scope
+            .item_key
+            .match_indices('.')
+            .map(|(i, _)| &scope.item_key[..i])
+            .chain([scope.item_key])
+            .filter(|key| self.defined.contains_key(*key))
+            .rev()
+            .collect();
+
+        let (item_name, const_name) = match name.rsplit_once('.') {
+            Some(split) => split,
+            None => {
+                // Const or enum element declared in the current or in an enclosing item
+                return scope_keys
+                    .iter()
+                    .map(|key| format!("{key}.{name}"))
+                    .find(|key| self.definitions.contains_key(key))
+                    .map_or(Reference::Unresolved, Reference::Resolved);
+            }
+        };
+
+        let item = match self.defined.get(item_name) {
+            // Fully qualified item (e.g. `pkg.Item.CONST`)
+            Some(kind) => Some((item_name.to_owned(), kind.clone())),
+            None => {
+                validation::resolve_item_key(item_name, &scope_keys, scope.imports, self.defined)
+            }
+        };
+
+        match item {
+            Some((key, ast::ResolvedItemKind::UnknownImport)) => Reference::Unknown(key),
+            Some((key, _)) => {
+                let key = format!("{key}.{const_name}");
+                if self.definitions.contains_key(&key) {
+                    Reference::Resolved(key)
+                } else {
+                    Reference::Unresolved
+                }
+            }
+            None => Reference::Unresolved,
+        }
+    }
+}
+
+enum Reference {
+    /// Key of the const or enum element (e.g. `pkg.Item.CONST`)
+    Resolved(String),
+    /// The item is imported but not defined
+    Unknown(ast::ItemKey),
+    Unresolved,
+}
+
+fn error(
+    code: DiagnosticCode,
+    range: &ast::Range,
+    message: String,
+    context_message: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ast::ConstValue> {
+    diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::Error,
+        code,
+        range: range.clone(),
+        message,
+        context_message: Some(context_message.to_owned()),
+        hint: None,
+        related_infos: Vec::new(),
+        fixes: Vec::new(),
+    });
+
+    None
+}
+
+fn mismatched_types(
+    range: &ast::Range,
+    expected: &str,
+    found: &ast::ConstValue,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ast::ConstValue> {
+    error(
+        DiagnosticCode::MismatchedTypes,
+        range,
+        format!(
+            "Mismatched types: expected `{expected}`, found `{}`",
+            found.get_type_name()
+        ),
+        "mismatched types",
+        diagnostics,
+    )
+}
+
+// Integer literals are `int` unless they have a `L` suffix or do not fit into an `int`.
+// Hexadecimal, octal and binary literals are two's complement (e.g. 0xffffffff == -1).
+pub(crate) fn parse_integer(literal: &str) -> Result<ast::ConstValue, String> {
+    let (digits, is_long) = match literal.strip_suffix(['l', 'L']) {
+        Some(digits) => (digits, true),
+        None => (literal, false),
+    };
+
+    let (digits, radix) = if let Some(d) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        (d, 16)
+    } else if let Some(d) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        (d, 2)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+
+    let value = u64::from_str_radix(digits, radix).map_err(|e| match e.kind() {
+        std::num::IntErrorKind::PosOverflow => format!("Integer literal `{literal}` is too large"),
+        _ => format!("Invalid integer literal `{literal}`"),
+    })?;
+
+    if radix == 10 {
+        match (is_long, i32::try_from(value), i64::try_from(value)) {
+            (false, Ok(v), _) => Ok(ast::ConstValue::Int(v)),
+            (_, _, Ok(v)) => Ok(ast::ConstValue::Long(v)),
+            _ => Err(format!("Integer literal `{literal}` is too large")),
+        }
+    } else {
+        match (is_long, u32::try_from(value)) {
+            (false, Ok(v)) => Ok(ast::ConstValue::Int(v as i32)),
+            _ => Ok(ast::ConstValue::Long(value as i64)),
+        }
+    }
+}
+
+// The magnitude of `Integer.MIN_VALUE` and `Long.MIN_VALUE` only fits after the unary
+// minus (e.g. `-2147483648` is an `int` and `-9223372036854775808L` a `long`)
+fn parse_min_integer(literal: &str) -> Option<ast::ConstValue> {
+    match literal {
+        "2147483648" => Some(ast::ConstValue::Int(i32::MIN)),
+        "9223372036854775808" | "9223372036854775808l" | "9223372036854775808L" => {
+            Some(ast::ConstValue::Long(i64::MIN))
+        }
+        _ => None,
+    }
+}
+
+fn parse_float(literal: &str) -> Option<ast::ConstValue> {
+    if let Some(digits) = literal.strip_suffix(['f', 'F']) {
+        digits.parse().ok().map(ast::ConstValue::Float)
+    } else {
+        let digits = literal.strip_suffix(['d', 'D']).unwrap_or(literal);
+        digits.parse().ok().map(ast::ConstValue::Double)
+    }
+}
+
+fn parse_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next()?, chars.as_str()) {
+        ('\\', escaped) => match escaped {
+            "n" => Some('\n'),
+            "r" => Some('\r'),
+            "t" => Some('\t'),
+            "0" => Some('\0'),
+            "\\" => Some('\\'),
+            "\"" => Some('"'),
+            "'" => Some('\''),
+            _ => escaped
+                .strip_prefix('u')
+                .filter(|hex| hex.len() == 4)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(char::from_u32),
+        },
+        (c, "") => Some(c),
+        _ => None,
+    }
+}
+
+// Numeric value after promotion (byte and char are promoted to int)
+#[derive(Clone, Copy)]
+enum Num {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+}
+
+impl Num {
+    fn from_value(value: &ast::ConstValue) -> Option<Self> {
+        match value {
+            ast::ConstValue::Byte(v) => Some(Num::Int(*v as i32)),
+            ast::ConstValue::Char(v) => Some(Num::Int(*v as i32)),
+            ast::ConstValue::Int(v) => Some(Num::Int(*v)),
+            ast::ConstValue::Long(v) => Some(Num::Long(*v)),
+            ast::ConstValue::Float(v) => Some(Num::Float(*v)),
+            ast::ConstValue::Double(v) => Some(Num::Double(*v)),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> ast::ConstValue {
+        match self {
+            Num::Int(v) => ast::ConstValue::Int(v),
+            Num::Long(v) => ast::ConstValue::Long(v),
+            Num::Float(v) => ast::ConstValue::Float(v),
+            Num::Double(v) => ast::ConstValue::Double(v),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Num::Int(_) => 0,
+            Num::Long(_) => 1,
+            Num::Float(_) => 2,
+            Num::Double(_) => 3,
+        }
+    }
+
+    fn promote(self, rank: u8) -> Self {
+        match (self, rank) {
+            (Num::Int(v), 1) => Num::Long(v as i64),
+            (Num::Int(v), 2) => Num::Float(v as f32),
+            (Num::Int(v), 3) => Num::Double(v as f64),
+            (Num::Long(v), 2) => Num::Float(v as f32),
+            (Num::Long(v), 3) => Num::Double(v as f64),
+            (Num::Float(v), 3) => Num::Double(v as f64),
+            (n, _) => n,
+        }
+    }
+}
+
+// Value of an integral type (byte, int or long)
+fn to_integral(value: &ast::ConstValue) -> Option<i64> {
+    match value {
+        ast::ConstValue::Byte(v) => Some(*v as i64),
+        ast::ConstValue::Int(v) => Some(*v as i64),
+        ast::ConstValue::Long(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn evaluate_unary(
+    op: ast::UnaryOp,
+    value: ast::ConstValue,
+    range: &ast::Range,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ast::ConstValue> {
+    if op == ast::UnaryOp::Not {
+        return match value {
+            ast::ConstValue::Bool(b) => Some(ast::ConstValue::Bool(!b)),
+            _ => mismatched_types(range, "boolean", &value, diagnostics),
+        };
+    }
+
+    let num = match Num::from_value(&value) {
+        Some(num) => num,
+        None => return mismatched_types(range, "number", &value, diagnostics),
+    };
+
+    let res = match (op, num) {
+        (ast::UnaryOp::Plus, n) => Some(n),
+        (ast::UnaryOp::Minus, Num::Int(v)) => v.checked_neg().map(Num::Int),
+        (ast::UnaryOp::Minus, Num::Long(v)) => v.checked_neg().map(Num::Long),
+        (ast::UnaryOp::Minus, Num::Float(v)) => Some(Num::Float(-v)),
+        (ast::UnaryOp::Minus, Num::Double(v)) => Some(Num::Double(-v)),
+        (ast::UnaryOp::BitNot, Num::Int(v)) => Some(Num::Int(!v)),
+        (ast::UnaryOp::BitNot, Num::Long(v)) => Some(Num::Long(!v)),
+        (ast::UnaryOp::BitNot, _) => {
+            return mismatched_types(range, "integral type", &value, diagnostics);
+        }
+        (ast::UnaryOp::Not, _) => unreachable!(),
+    };
+
+    match res {
+        Some(n) => Some(n.into_value()),
+        None => overflow(range, diagnostics),
+    }
+}
+
+fn overflow(range: &ast::Range, diagnostics: &mut Vec<Diagnostic>) -> Option<ast::ConstValue> {
+    error(
+        DiagnosticCode::Overflow,
+        range,
+        "Overflow in constant expression".to_owned(),
+        "overflow",
+        diagnostics,
+    )
+}
+
+macro_rules! integral_op {
+    ($op:expr, $a:expr, $b:expr) => {
+        match $op {
+            ast::BinaryOp::Add => $a.checked_add($b),
+            ast::BinaryOp::Sub => $a.checked_sub($b),
+            ast::BinaryOp::Mul => $a.checked_mul($b),
+            ast::BinaryOp::Div => $a.checked_div($b),
+            ast::BinaryOp::Rem => $a.checked_rem($b),
+            ast::BinaryOp::BitAnd => Some($a & $b),
+            ast::BinaryOp::BitOr => Some($a | $b),
+            ast::BinaryOp::BitXor => Some($a ^ $b),
+            _ => unreachable!(),
+        }
+    };
+}
+
+macro_rules! floating_op {
+    ($op:expr, $a:expr, $b:expr) => {
+        match $op {
+            ast::BinaryOp::Add => Some($a + $b),
+            ast::BinaryOp::Sub => Some($a - $b),
+            ast::BinaryOp::Mul => Some($a * $b),
+            ast::BinaryOp::Div => Some($a / $b),
+            ast::BinaryOp::Rem => Some($a % $b),
+            _ => None,
+        }
+    };
+}
+
+fn evaluate_binary(
+    op: ast::BinaryOp,
+    lhs: ast::ConstValue,
+    rhs: ast::ConstValue,
+    range: &ast::Range,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ast::ConstValue> {
+    use ast::BinaryOp as Op;
+    use ast::ConstValue as V;
+
+    match (op, &lhs, &rhs) {
+        // Boolean operators
+        (Op::LogicalAnd | Op::BitAnd, V::Bool(a), V::Bool(b)) => return Some(V::Bool(*a && *b)),
+        (Op::LogicalOr | Op::BitOr, V::Bool(a), V::Bool(b)) => return Some(V::Bool(*a || *b)),
+        (Op::BitXor | Op::Ne, V::Bool(a), V::Bool(b)) => return Some(V::Bool(a != b)),
+        (Op::Eq, V::Bool(a), V::Bool(b)) => return Some(V::Bool(a == b)),
+        (Op::LogicalAnd | Op::LogicalOr, V::Bool(_), _) => {
+            return mismatched_types(range, "boolean", &rhs, diagnostics);
+        }
+        (Op::LogicalAnd | Op::LogicalOr, _, _) => {
+            return mismatched_types(range, "boolean", &lhs, diagnostics);
+        }
+
+        // String concatenation and comparison
+        (Op::Add, V::String(a), V::String(b)) => return Some(V::String(format!("{a}{b}"))),
+        (Op::Eq, V::String(a), V::String(b)) => return Some(V::Bool(a == b)),
+        (Op::Ne, V::String(a), V::String(b)) => return Some(V::Bool(a != b)),
+        (_, V::String(_), _) | (_, V::Array(_), _) | (_, V::Bool(_), _) => {
+            return mismatched_types(range, "number", &lhs, diagnostics);
+        }
+        _ => (),
+    }
+
+    let (a, b) = match (Num::from_value(&lhs), Num::from_value(&rhs)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return mismatched_types(range, "number", &rhs, diagnostics),
+    };
+
+    // Shift: the type of the result is the (promoted) type of the left operand
+    if let Op::Shl | Op::Shr = op {
+        let amount = match b {
+            Num::Int(v) => v as i64,
+            Num::Long(v) => v,
+            _ => return mismatched_types(range, "integral type", &rhs, diagnostics),
+        };
+        let bits = match a {
+            Num::Int(_) => 32,
+            Num::Long(_) => 64,
+            _ => return mismatched_types(range, "integral type", &lhs, diagnostics),
+        };
+        if !(0..bits).contains(&amount) {
+            return error(
+                DiagnosticCode::InvalidShift,
+                range,
+                format!(
+                    "Invalid shift amount {amount}, must be between 0 and {}",
+                    bits - 1
+                ),
+                "invalid shift",
+                diagnostics,
+            );
+        }
+
+        let amount = amount as u32;
+        return Some(match (op, a) {
+            (Op::Shl, Num::Int(v)) => V::Int(v.wrapping_shl(amount)),
+            (Op::Shl, Num::Long(v)) => V::Long(v.wrapping_shl(amount)),
+            (_, Num::Int(v)) => V::Int(v >> amount),
+            (_, Num::Long(v)) => V::Long(v >> amount),
+            _ => unreachable!(),
+        });
+    }
+
+    let rank = a.rank().max(b.rank());
+    let (a, b) = (a.promote(rank), b.promote(rank));
+
+    // Comparison
+    if let Op::Eq | Op::Ne | Op::Lt | Op::Gt | Op::Le | Op::Ge = op {
+        let ordering = match (a, b) {
+            (Num::Int(a), Num::Int(b)) => a.partial_cmp(&b),
+            (Num::Long(a), Num::Long(b)) => a.partial_cmp(&b),
+            (Num::Float(a), Num::Float(b)) => a.partial_cmp(&b),
+            (Num::Double(a), Num::Double(b)) => a.partial_cmp(&b),
+            _ => unreachable!(),
+        };
+        let res = match ordering {
+            Some(ordering) => match op {
+                Op::Eq => ordering.is_eq(),
+                Op::Ne => ordering.is_ne(),
+                Op::Lt => ordering.is_lt(),
+                Op::Gt => ordering.is_gt(),
+                Op::Le => ordering.is_le(),
+                Op::Ge => ordering.is_ge(),
+                _ => unreachable!(),
+            },
+            None => op == Op::Ne, // NaN
+        };
+        return Some(V::Bool(res));
+    }
+
+    let is_integral = matches!(a, Num::Int(_) | Num::Long(_));
+    if is_integral && matches!(op, Op::Div | Op::Rem) && matches!(b, Num::Int(0) | Num::Long(0)) {
+        return error(
+            DiagnosticCode::DivisionByZero,
+            range,
+            "Division by zero in constant expression".to_owned(),
+            "division by zero",
+            diagnostics,
+        );
+    }
+
+    let res = match (a, b) {
+        (Num::Int(a), Num::Int(b)) => integral_op!(op, a, b).map(Num::Int),
+        (Num::Long(a), Num::Long(b)) => integral_op!(op, a, b).map(Num::Long),
+        (Num::Float(a), Num::Float(b)) => match floating_op!(op, a, b) {
+            Some(v) => Some(Num::Float(v)),
+            None => return mismatched_types(range, "integral type", &lhs, diagnostics),
+        },
+        (Num::Double(a), Num::Double(b)) => match floating_op!(op, a, b) {
+            Some(v) => Some(Num::Double(v)),
+            None => return mismatched_types(range, "integral type", &lhs, diagnostics),
+        },
+        _ => unreachable!(),
+    };
+
+    match res {
+        Some(n) => Some(n.into_value()),
+        None => overflow(range, diagnostics),
+    }
+}
+
+// Convert the value into the given type (e.g. `int` to `byte`)
+fn convert(
+    value: ast::ConstValue,
+    type_: &ast::Type,
+    range: &ast::Range,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ast::ConstValue> {
+    let out_of_range = |diagnostics: &mut Vec<Diagnostic>| {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Error,
+            code: DiagnosticCode::ValueOutOfRange,
+            range: range.clone(),
+            message: format!("Value `{value}` is out of range for type `{}`", type_.name),
+            context_message: Some("out of range".to_owned()),
+            hint: None,
+            related_infos: Vec::from([diagnostic::RelatedInfo {
+                message: "declared type".to_owned(),
+                range: type_.symbol_range.clone(),
+                file: None,
+            }]),
+            fixes: Vec::new(),
+        });
+        None
+    };
+
+    match type_.kind {
+        ast::TypeKind::Primitive => match (type_.name.as_str(), &value) {
+            ("boolean", ast::ConstValue::Bool(_)) => Some(value),
+            ("char", ast::ConstValue::Char(_)) => Some(value),
+            ("byte" | "int" | "long", _) if to_integral(&value).is_some() => {
+                let v = to_integral(&value).unwrap_or_default();
+                let converted = match type_.name.as_str() {
+                    "byte" => i8::try_from(v).ok().map(ast::ConstValue::Byte),
+                    "int" => i32::try_from(v).ok().map(ast::ConstValue::Int),
+                    _ => Some(ast::ConstValue::Long(v)),
+                };
+                converted.or_else(|| out_of_range(diagnostics))
+            }
+            ("float", _) => match Num::from_value(&value) {
+                Some(Num::Float(v)) => Some(ast::ConstValue::Float(v)),
+                Some(Num::Double(v)) => Some(ast::ConstValue::Float(v as f32)),
+                Some(Num::Int(v)) if !matches!(value, ast::ConstValue::Char(_)) => {
+                    Some(ast::ConstValue::Float(v as f32))
+                }
+                Some(Num::Long(v)) => Some(ast::ConstValue::Float(v as f32)),
+                _ => mismatched_types(range, &type_.name, &value, diagnostics),
+            },
+            ("double", _) => match Num::from_value(&value) {
+                Some(Num::Float(v)) => Some(ast::ConstValue::Double(v as f64)),
+                Some(Num::Double(v)) => Some(ast::ConstValue::Double(v)),
+                Some(Num::Int(v)) if !matches!(value, ast::ConstValue::Char(_)) => {
+                    Some(ast::ConstValue::Double(v as f64))
+                }
+                Some(Num::Long(v)) => Some(ast::ConstValue::Double(v as f64)),
+                _ => mismatched_types(range, &type_.name, &value, diagnostics),
+            },
+            ("boolean" | "char" | "byte" | "int" | "long", _) => {
+                mismatched_types(range, &type_.name, &value, diagnostics)
+            }
+            _ => None, // e.g. short (not supported by AIDL)
+        },
+        ast::TypeKind::String | ast::TypeKind::CharSequence => match value {
+            ast::ConstValue::String(_) => Some(value),
+            _ => mismatched_types(range, &type_.name, &value, diagnostics),
+        },
+        ast::TypeKind::Array => match value {
+            ast::ConstValue::Array(values) => {
+                let element_type = type_.generic_types.first()?;
+                values
+                    .into_iter()
+                    .map(|v| convert(v, element_type, range, diagnostics))
+                    .collect::<Option<Vec<_>>>()
+                    .map(ast::ConstValue::Array)
+            }
+            _ => mismatched_types(range, "array", &value, diagnostics),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate_const(input: &str) -> (Option<ast::ConstValue>, Vec<Diagnostic>) {
+        let aidl = format!("package test; interface Test {{ {input} }}");
+        let lookup = line_col::LineColLookup::new(&aidl);
+        let mut diagnostics = Vec::new();
+        let tokens = crate::rules::get_grammar_tokens(&aidl, &crate::cst::lex(&aidl));
+        let mut ast = crate::rules::aidl::OptAidlParser::new()
+            .parse(&lookup, &mut diagnostics, &aidl, tokens)
+            .expect("parse")
+            .expect("ast");
+        assert_eq!(diagnostics, []);
+
+        let defined = HashMap::from([("test.Test".to_owned(), ast::ResolvedItemKind::Interface)]);
+        let mut evaluator = Evaluator::new([ast.clone()].iter(), &defined);
+        evaluator.evaluate_ast(&mut ast, &mut diagnostics);
+
+        let interface = ast.item.as_interface().expect("interface");
+        let value = match interface.elements.last() {
+            Some(ast::InterfaceElement::Const(c)) => c.evaluated_value.clone(),
+            _ => unreachable!(),
+        };
+
+        (value, diagnostics)
+    }
+
+    fn evaluate_enum(input: &str) -> (Vec<Option<ast::ConstValue>>, Vec<Diagnostic>) {
+        let aidl = format!("package test; {input}");
+        let lookup = line_col::LineColLookup::new(&aidl);
+        let mut diagnostics = Vec::new();
+        let tokens = crate::rules::get_grammar_tokens(&aidl, &crate::cst::lex(&aidl));
+        let mut ast = crate::rules::aidl::OptAidlParser::new()
+            .parse(&lookup, &mut diagnostics, &aidl, tokens)
+            .expect("parse")
+            .expect("ast");
+        assert_eq!(diagnostics, []);
+
+        let defined = HashMap::from([("test.Test".to_owned(), ast::ResolvedItemKind::Enum)]);
+        let mut evaluator = Evaluator::new([ast.clone()].iter(), &defined);
+        evaluator.evaluate_ast(&mut ast, &mut diagnostics);
+
+        let enum_ = ast.item.as_enum().expect("enum");
+        let values = enum_
+            .elements
+            .iter()
+            .map(|el| el.evaluated_value.clone())
+            .collect();
+
+        (values, diagnostics)
+    }
+
+    macro_rules! assert_value {
+        ($input:expr, $expected:expr) => {
+            let (value, diagnostics) = evaluate_const($input);
+            assert_eq!(diagnostics, [], "{}", $input);
+            assert_eq!(value, Some($expected), "{}", $input);
+        };
+    }
+
+    macro_rules! assert_error {
+        ($input:expr, $message:expr) => {
+            let (value, diagnostics) = evaluate_const($input);
+            assert_eq!(value, None, "{}", $input);
+            assert_eq!(diagnostics.len(), 1, "{}", $input);
+            assert_eq!(diagnostics[0].message, $message, "{}", $input);
+        };
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_value!("const int X = 42;", ast::ConstValue::Int(42));
+        assert_value!("const int X = 0x7f;", ast::ConstValue::Int(127));
+        assert_value!("const int X = 0xffffffff;", ast::ConstValue::Int(-1));
+        assert_value!("const int X = 0b101;", ast::ConstValue::Int(5));
+        assert_value!("const int X = 017;", ast::ConstValue::Int(15));
+        assert_value!("const long X = 3L;", ast::ConstValue::Long(3));
+        assert_value!(
+            "const long X = 0xffffffffffffffffL;",
+            ast::ConstValue::Long(-1)
+        );
+        assert_value!("const int X = -2147483648;", ast::ConstValue::Int(i32::MIN));
+        assert_value!(
+            "const long X = -9223372036854775808L;",
+            ast::ConstValue::Long(i64::MIN)
+        );
+        assert_value!(
+            "const int X = -(2147483647);",
+            ast::ConstValue::Int(-i32::MAX)
+        );
+        assert_value!("const float X = .5f;", ast::ConstValue::Float(0.5));
+        assert_value!("const double X = 1e3;", ast::ConstValue::Double(1000.0));
+        assert_value!("const char X = 'a';", ast::ConstValue::Char('a'));
+        assert_value!("const char X = '\\n';", ast::ConstValue::Char('\n'));
+        assert_value!("const boolean X = true;", ast::ConstValue::Bool(true));
+        assert_value!(
+            "const String X = \"hello\";",
+            ast::ConstValue::String("hello".into())
+        );
+        assert_value!(
+            "const int[] X = {1, 2};",
+            ast::ConstValue::Array(vec![ast::ConstValue::Int(1), ast::ConstValue::Int(2)])
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_value!("const int X = 1 + 2 * 3;", ast::ConstValue::Int(7));
+        assert_value!("const int X = (1 + 2) * 3;", ast::ConstValue::Int(9));
+        assert_value!("const int X = 1 << 4 | 1;", ast::ConstValue::Int(17));
+        assert_value!("const int X = -8 >> 1;", ast::ConstValue::Int(-4));
+        assert_value!("const int X = ~0 ^ 0xf;", ast::ConstValue::Int(-16));
+        assert_value!("const int X = 7 % 4 - 10 / 3;", ast::ConstValue::Int(0));
+        assert_value!("const long X = 1L << 40;", ast::ConstValue::Long(1 << 40));
+        assert_value!("const double X = 1 / 2.0;", ast::ConstValue::Double(0.5));
+        assert_value!("const boolean X = 1 < 2 << 1;", ast::ConstValue::Bool(true));
+        assert_value!(
+            "const boolean X = 1 == 1 && !(2 >= 3) || false;",
+            ast::ConstValue::Bool(true)
+        );
+        assert_value!(
+            "const String X = \"a\" + \"b\";",
+            ast::ConstValue::String("ab".into())
+        );
+        assert_value!("const int X = -2147483648;", ast::ConstValue::Int(i32::MIN));
+    }
+
+    #[test]
+    fn test_references() {
+        assert_value!(
+            "const int A = 1; const int B = A + Test.A;",
+            ast::ConstValue::Int(2)
+        );
+        assert_value!(
+            "const int B = test.Test.A * 2; const int A = 3; const int C = B;",
+            ast::ConstValue::Int(6)
+        );
+        assert_error!("const int X = UNKNOWN;", "Unknown constant `UNKNOWN`");
+        assert_error!(
+            "const int X = Test.UNKNOWN;",
+            "Unknown constant `Test.UNKNOWN`"
+        );
+        assert_error!(
+            "const int A = B; const int B = A;",
+            "Cyclic reference to `A`"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_error!(
+            "const byte X = 300;",
+            "Value `300` is out of range for type `byte`"
+        );
+        assert_error!(
+            "const int X = 2147483648;",
+            "Value `2147483648L` is out of range for type `int`"
+        );
+        assert_error!(
+            "const int X = 2147483647 + 1;",
+            "Overflow in constant expression"
+        );
+        assert_error!(
+            "const int X = 1 / 0;",
+            "Division by zero in constant expression"
+        );
+        assert_error!(
+            "const int X = 1 << 32;",
+            "Invalid shift amount 32, must be between 0 and 31"
+        );
+        assert_error!(
+            "const int X = \"a\";",
+            "Mismatched types: expected `int`, found `String`"
+        );
+        assert_error!(
+            "const String X = \"a\" + 1;",
+            "Mismatched types: expected `number`, found `String`"
+        );
+        assert_error!(
+            "const boolean X = 1;",
+            "Mismatched types: expected `boolean`, found `int`"
+        );
+        assert_error!("const int X = 0843;", "Invalid integer literal `0843`");
+        assert_error!(
+            "const int X = 99999999999999999999;",
+            "Integer literal `99999999999999999999` is too large"
+        );
+    }
+
+    #[test]
+    fn test_enum_values() {
+        use ast::ConstValue::{Byte, Int, Long};
+
+        let (values, diagnostics) = evaluate_enum("enum Test { A, B, C = 10, D, E = A + 1, F }");
+        assert_eq!(diagnostics, []);
+        assert_eq!(
+            values,
+            [Byte(0), Byte(1), Byte(10), Byte(11), Byte(1), Byte(2)].map(Some)
+        );
+
+        let (values, diagnostics) =
+            evaluate_enum("@Backing(type=\"int\") enum Test { A = 0x7fffffff, B }");
+        assert_eq!(values, [Some(Int(i32::MAX)), None]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Value `2147483648L` is out of range for type `int`"
+        );
+
+        let (values, diagnostics) =
+            evaluate_enum("@Backing(type=\"long\") enum Test { A = 1L << 40, B }");
+        assert_eq!(diagnostics, []);
+        assert_eq!(values, [Some(Long(1 << 40)), Some(Long((1 << 40) + 1))]);
+    }
+
+    #[test]
+    fn test_enum_errors() {
+        let (values, diagnostics) = evaluate_enum("enum Test { A = 127, B, C }");
+        assert_eq!(values, [Some(ast::ConstValue::Byte(127)), None, None]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Value `128` is out of range for type `byte`"
+        );
+
+        let (values, diagnostics) = evaluate_enum("enum Test { A = B, B = 1, C = C }");
+        assert_eq!(values, [None, Some(ast::ConstValue::Byte(1)), None]);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "Enum element `B` is referenced before its declaration"
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "Enum element `C` is referenced before its declaration"
+        );
+    }
+}
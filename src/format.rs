@@ -0,0 +1,748 @@
+use crate::ast;
+
+/// Placement of the annotations of items, methods, consts and fields
+///
+/// The annotations of method arguments are always inline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationPlacement {
+    /// Each annotation on its own line, before the declaration
+    OwnLine,
+    /// All the annotations on the same line as the declaration
+    Inline,
+}
+
+/// Options of the formatter, given to format()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatConfig {
+    /// Indentation of one level (4 spaces by default)
+    pub indent: String,
+    /// Sort the imports by qualified name
+    pub sort_imports: bool,
+    /// Separate the imports with a blank line when the first component of their
+    /// qualified name changes (e.g. between `android.*` and `com.*`)
+    pub group_imports: bool,
+    pub annotations: AnnotationPlacement,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        FormatConfig {
+            indent: "    ".to_owned(),
+            sort_imports: true,
+            group_imports: false,
+            annotations: AnnotationPlacement::OwnLine,
+        }
+    }
+}
+
+/// Format the AST into canonical AIDL source
+///
+/// The output only depends on the AST and on the configuration: each declaration
+/// is on its own line, the members of interfaces, parcelables and unions are separated
+/// by one blank line and the constant expressions are normalized.
+///
+/// The comments of the AST (as collected by the parser) are kept before the declaration
+/// which follows them, or at the end of the line if they were at the end of the line
+/// of a declaration. The javadoc of a declaration is written from its `doc` if the
+/// comments do not contain it (e.g. for a modified AST).
+///
+/// Example:
+/// ```
+/// use aidl_parser::format::{self, FormatConfig};
+/// use aidl_parser::Parser;
+///
+/// let mut parser = Parser::new();
+/// parser.add_content("id", "package p;interface IFoo{// Hello\nvoid hello( in int  i ) ;}");
+/// let results = parser.validate();
+///
+/// let ast = results["id"].ast.as_ref().expect("AST");
+/// assert_eq!(
+///     format::format(ast, &FormatConfig::default()),
+///     "package p;\n\ninterface IFoo {\n    // Hello\n    void hello(in int i);\n}\n"
+/// );
+/// ```
+pub fn format(ast: &ast::Aidl, config: &FormatConfig) -> String {
+    let mut formatter = Formatter {
+        config,
+        comments: &ast.comments,
+        next_comment: 0,
+        code_offsets: get_code_offsets(ast),
+        level: 0,
+        output: String::new(),
+    };
+    formatter.write_aidl(ast);
+
+    formatter.output
+}
+
+// Declaration of an interface, parcelable or union body
+enum Member<'a> {
+    Const(&'a ast::Const),
+    Method(&'a ast::Method),
+    Field(&'a ast::Field),
+    EnumElement(&'a ast::EnumElement),
+    Item(&'a ast::Item),
+}
+
+impl<'a> Member<'a> {
+    fn get_start(&self) -> usize {
+        match self {
+            Member::Const(c) => c.full_range.start.offset,
+            Member::Method(m) => m.full_range.start.offset,
+            Member::Field(f) => f.full_range.start.offset,
+            Member::EnumElement(el) => el.full_range.start.offset,
+            Member::Item(i) => i.get_full_range().start.offset,
+        }
+    }
+}
+
+struct Formatter<'a> {
+    config: &'a FormatConfig,
+    comments: &'a [ast::Comment],
+    next_comment: usize,
+    // Start of the declarations and end of the bodies (sorted)
+    code_offsets: Vec<usize>,
+    level: usize,
+    output: String,
+}
+
+impl<'a> Formatter<'a> {
+    fn write_aidl(&mut self, ast: &ast::Aidl) {
+        let package = &ast.package;
+        self.write_comments_before(
+            package.full_range.start.offset,
+            Some(package.full_range.start.line_col.0),
+        );
+        self.write_line(&format!("package {};", package.name));
+        self.write_trailing_comment(&package.full_range.end);
+
+        // Each import with its leading and trailing comments (in the order of the
+        // source), so that the comments move with their import when sorting
+        let mut imports: Vec<(String, String)> = Vec::new();
+        for import in &ast.imports {
+            let output = std::mem::take(&mut self.output);
+            self.write_comments_before(
+                import.full_range.start.offset,
+                Some(import.full_range.start.line_col.0),
+            );
+            let qualified_name = import.get_qualified_name();
+            self.write_line(&format!("import {qualified_name};"));
+            self.write_trailing_comment(&import.full_range.end);
+            imports.push((qualified_name, std::mem::replace(&mut self.output, output)));
+        }
+        if self.config.sort_imports {
+            imports.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+        }
+
+        let mut previous_group = None;
+        for (qualified_name, text) in imports {
+            let group = qualified_name
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .to_owned();
+            match &previous_group {
+                None => self.write_blank_line(),
+                Some(previous) if self.config.group_imports && *previous != group => {
+                    self.write_blank_line()
+                }
+                _ => (),
+            }
+            previous_group = Some(group);
+
+            self.output.push_str(&text);
+        }
+
+        for (i, declared) in ast.declared_parcelables.iter().enumerate() {
+            if i == 0 {
+                self.write_blank_line();
+            }
+            self.write_comments_before(
+                declared.full_range.start.offset,
+                Some(declared.full_range.start.line_col.0),
+            );
+            self.write_line(&format!("parcelable {};", declared.get_qualified_name()));
+            self.write_trailing_comment(&declared.full_range.end);
+        }
+
+        self.write_blank_line();
+        self.write_item(&ast.item);
+
+        // Comments at the end of the file
+        if self.has_comment_before(usize::MAX) {
+            self.write_blank_line();
+            self.write_comments_before(usize::MAX, None);
+        }
+    }
+
+    fn write_item(&mut self, item: &ast::Item) {
+        let (annotations, doc, header, mut members) = match item {
+            ast::Item::Interface(i) => (
+                &i.annotations,
+                &i.doc,
+                format!(
+                    "{}interface {}",
+                    if i.oneway { "oneway " } else { "" },
+                    i.name
+                ),
+                i.elements
+                    .iter()
+                    .map(|el| match el {
+                        ast::InterfaceElement::Const(c) => Member::Const(c),
+                        ast::InterfaceElement::Method(m) => Member::Method(m),
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            ast::Item::Parcelable(p) => (
+                &p.annotations,
+                &p.doc,
+                if p.type_parameters.is_empty() {
+                    format!("parcelable {}", p.name)
+                } else {
+                    format!("parcelable {}<{}>", p.name, p.type_parameters.join(", "))
+                },
+                p.elements
+                    .iter()
+                    .map(|el| match el {
+                        ast::ParcelableElement::Const(c) => Member::Const(c),
+                        ast::ParcelableElement::Field(f) => Member::Field(f),
+                    })
+                    .collect(),
+            ),
+            ast::Item::Union(u) => (
+                &u.annotations,
+                &u.doc,
+                format!("union {}", u.name),
+                u.elements
+                    .iter()
+                    .map(|el| match el {
+                        ast::UnionElement::Const(c) => Member::Const(c),
+                        ast::UnionElement::Field(f) => Member::Field(f),
+                    })
+                    .collect(),
+            ),
+            ast::Item::Enum(e) => (
+                &e.annotations,
+                &e.doc,
+                format!("enum {}", e.name),
+                e.elements.iter().map(Member::EnumElement).collect(),
+            ),
+        };
+
+        // Elements and nested items in the order of the source
+        members.extend(item.get_nested_items().iter().map(Member::Item));
+        members.sort_by_key(Member::get_start);
+
+        let full_range = item.get_full_range();
+        let prefix = self.write_declaration_start(
+            annotations,
+            doc.as_deref(),
+            &full_range.start,
+            item.get_symbol_range().end.offset,
+        );
+
+        // The closing brace is the last character of the item
+        let body_end = full_range.end.offset.saturating_sub(1);
+        if members.is_empty() && !self.has_comment_before(body_end) {
+            self.write_line(&format!("{prefix}{header} {{}}"));
+        } else {
+            self.write_line(&format!("{prefix}{header} {{"));
+            self.level += 1;
+
+            let is_enum = matches!(item, ast::Item::Enum(_));
+            for (i, member) in members.iter().enumerate() {
+                if i > 0 && !is_enum {
+                    self.write_blank_line();
+                }
+                self.write_member(member);
+            }
+
+            // Comments at the end of the body
+            if !members.is_empty() && self.has_comment_before(body_end) {
+                self.write_blank_line();
+            }
+            self.write_comments_before(body_end, None);
+
+            self.level -= 1;
+            self.write_line("}");
+        }
+
+        self.write_trailing_comment(&full_range.end);
+    }
+
+    fn write_member(&mut self, member: &Member) {
+        let (line, full_range) = match member {
+            Member::Const(c) => {
+                let prefix = self.write_declaration_start(
+                    &c.annotations,
+                    c.doc.as_deref(),
+                    &c.full_range.start,
+                    c.full_range.end.offset,
+                );
+                let line = format!(
+                    "{prefix}const {} {} = {};",
+                    format_type(&c.const_type),
+                    c.name,
+                    c.value
+                );
+                (line, &c.full_range)
+            }
+            Member::Method(m) => {
+                let prefix = self.write_declaration_start(
+                    &m.annotations,
+                    m.doc.as_deref(),
+                    &m.full_range.start,
+                    m.full_range.end.offset,
+                );
+                let args: Vec<String> = m.args.iter().map(format_arg).collect();
+                let line = format!(
+                    "{prefix}{}{} {}({}){};",
+                    if m.has_oneway_modifier() {
+                        "oneway "
+                    } else {
+                        ""
+                    },
+                    format_type(&m.return_type),
+                    m.name,
+                    args.join(", "),
+                    m.transact_code
+                        .map(|code| format!(" = {code}"))
+                        .unwrap_or_default(),
+                );
+                (line, &m.full_range)
+            }
+            Member::Field(f) => {
+                let prefix = self.write_declaration_start(
+                    &f.annotations,
+                    f.doc.as_deref(),
+                    &f.full_range.start,
+                    f.full_range.end.offset,
+                );
+                let line = format!(
+                    "{prefix}{} {}{};",
+                    format_type(&f.field_type),
+                    f.name,
+                    f.value
+                        .as_ref()
+                        .map(|v| format!(" = {v}"))
+                        .unwrap_or_default(),
+                );
+                (line, &f.full_range)
+            }
+            Member::EnumElement(el) => {
+                self.write_declaration_start(
+                    &[],
+                    el.doc.as_deref(),
+                    &el.full_range.start,
+                    el.full_range.end.offset,
+                );
+                let line = format!(
+                    "{}{},",
+                    el.name,
+                    el.value
+                        .as_ref()
+                        .map(|v| format!(" = {v}"))
+                        .unwrap_or_default(),
+                );
+                (line, &el.full_range)
+            }
+            Member::Item(item) => return self.write_item(item),
+        };
+
+        self.write_line(&line);
+        self.write_trailing_comment(&full_range.end);
+    }
+
+    // Write the comments (or the doc) and the annotations before a declaration and
+    // return the prefix of the declaration (i.e. the inline annotations)
+    //
+    // The comments inside the declaration (until `inner_end`) are moved before it.
+    fn write_declaration_start(
+        &mut self,
+        annotations: &[ast::Annotation],
+        doc: Option<&str>,
+        start: &ast::Position,
+        inner_end: usize,
+    ) -> String {
+        // The annotations are before the start of the declaration
+        let code_line = start.line_col.0.saturating_sub(annotations.len());
+        let has_javadoc = self.write_comments_before(start.offset, Some(code_line));
+        if let (Some(doc), false) = (doc, has_javadoc) {
+            self.write_doc(doc);
+        }
+        self.write_comments_before(inner_end, None);
+
+        match self.config.annotations {
+            AnnotationPlacement::OwnLine => {
+                for annotation in annotations {
+                    self.write_line(&format_annotation(annotation));
+                }
+                String::new()
+            }
+            AnnotationPlacement::Inline => annotations
+                .iter()
+                .map(|a| format!("{} ", format_annotation(a)))
+                .collect(),
+        }
+    }
+
+    // Write the comments before the given offset, each on its own line, and return
+    // true if one of them is a javadoc
+    //
+    // A blank line of the source between the comments, or between the last comment and
+    // the following code (at `code_line`), is kept.
+    fn write_comments_before(&mut self, offset: usize, code_line: Option<usize>) -> bool {
+        let mut has_javadoc = false;
+
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.full_range.start.offset >= offset {
+                break;
+            }
+            self.next_comment += 1;
+            has_javadoc |= comment.is_javadoc();
+            self.write_comment(comment);
+
+            let next_line = match self.comments.get(self.next_comment) {
+                Some(next) if next.full_range.start.offset < offset => {
+                    Some(next.full_range.start.line_col.0)
+                }
+                _ => code_line,
+            };
+            if next_line.is_some_and(|line| line > comment.full_range.end.line_col.0 + 1) {
+                self.write_blank_line();
+            }
+        }
+
+        has_javadoc
+    }
+
+    // Write the next comment at the end of the last line if it follows the end of the
+    // last declaration on the same line of the source (without code in-between)
+    fn write_trailing_comment(&mut self, end: &ast::Position) {
+        let line = end.line_col.0;
+        let comment = match self.comments.get(self.next_comment) {
+            Some(c) if c.full_range.start.line_col.0 == line => c,
+            _ => return,
+        };
+        let next_code = self.code_offsets.partition_point(|&o| o < end.offset);
+        let is_before_code = self
+            .code_offsets
+            .get(next_code)
+            .is_none_or(|&o| comment.full_range.start.offset < o);
+        if comment.full_range.end.line_col.0 != line || !is_before_code {
+            return;
+        }
+
+        self.next_comment += 1;
+        self.output.pop();
+        self.output.push(' ');
+        self.output.push_str(comment.text.trim_end());
+        self.output.push('\n');
+    }
+
+    // Write the comment, re-indented to the current level
+    fn write_comment(&mut self, comment: &ast::Comment) {
+        let column = comment.full_range.start.line_col.1.saturating_sub(1);
+
+        for (i, line) in comment.text.trim_end().lines().enumerate() {
+            // Remove the original indentation of the continuation lines
+            let indentation: usize = if i == 0 {
+                0
+            } else {
+                line.chars()
+                    .take(column)
+                    .take_while(|c| c.is_whitespace())
+                    .map(char::len_utf8)
+                    .sum()
+            };
+            self.write_line(line[indentation..].trim_end());
+        }
+    }
+
+    fn write_doc(&mut self, doc: &str) {
+        let lines: Vec<&str> = doc.lines().collect();
+        if let [line] = lines[..] {
+            self.write_line(&format!("/** {line} */"));
+            return;
+        }
+
+        self.write_line("/**");
+        for line in lines {
+            self.write_line(format!(" * {line}").trim_end());
+        }
+        self.write_line(" */");
+    }
+
+    fn has_comment_before(&self, offset: usize) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|c| c.full_range.start.offset < offset)
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if !line.is_empty() {
+            for _ in 0..self.level {
+                self.output.push_str(&self.config.indent);
+            }
+            self.output.push_str(line);
+        }
+        self.output.push('\n');
+    }
+
+    // At most one blank line, and none at the start of the file or of a body
+    fn write_blank_line(&mut self) {
+        if !self.output.is_empty()
+            && !self.output.ends_with("\n\n")
+            && !self.output.ends_with("{\n")
+        {
+            self.output.push('\n');
+        }
+    }
+}
+
+fn get_code_offsets(ast: &ast::Aidl) -> Vec<usize> {
+    let mut offsets = Vec::from([ast.package.full_range.start.offset]);
+    for import in ast.imports.iter().chain(&ast.declared_parcelables) {
+        offsets.push(import.full_range.start.offset);
+    }
+
+    for (_, item) in ast.get_all_items() {
+        let full_range = item.get_full_range();
+        offsets.push(full_range.start.offset);
+        offsets.push(full_range.end.offset.saturating_sub(1));
+
+        let element_ranges: Vec<&ast::Range> = match item {
+            ast::Item::Interface(i) => i
+                .elements
+                .iter()
+                .map(|el| match el {
+                    ast::InterfaceElement::Const(c) => &c.full_range,
+                    ast::InterfaceElement::Method(m) => &m.full_range,
+                })
+                .collect(),
+            ast::Item::Parcelable(p) => p
+                .elements
+                .iter()
+                .map(|el| match el {
+                    ast::ParcelableElement::Const(c) => &c.full_range,
+                    ast::ParcelableElement::Field(f) => &f.full_range,
+                })
+                .collect(),
+            ast::Item::Union(u) => u
+                .elements
+                .iter()
+                .map(|el| match el {
+                    ast::UnionElement::Const(c) => &c.full_range,
+                    ast::UnionElement::Field(f) => &f.full_range,
+                })
+                .collect(),
+            ast::Item::Enum(e) => e.elements.iter().map(|el| &el.full_range).collect(),
+        };
+        offsets.extend(element_ranges.iter().map(|r| r.start.offset));
+    }
+
+    offsets.sort_unstable();
+    offsets
+}
+
+pub(crate) fn format_type(type_: &ast::Type) -> String {
+    match (&type_.kind, &type_.generic_types[..]) {
+        (ast::TypeKind::Array, [element_type]) => format!("{}[]", format_type(element_type)),
+        (_, []) => type_.name.clone(),
+        (_, generic_types) => {
+            let generic_types: Vec<String> = generic_types.iter().map(format_type).collect();
+            format!("{}<{}>", type_.name, generic_types.join(", "))
+        }
+    }
+}
+
+fn format_arg(arg: &ast::Arg) -> String {
+    let mut s = String::new();
+    if !matches!(arg.direction, ast::Direction::Unspecified) {
+        s.push_str(&format!("{} ", arg.direction));
+    }
+    for annotation in &arg.annotations {
+        s.push_str(&format!("{} ", format_annotation(annotation)));
+    }
+    s.push_str(&format_type(&arg.arg_type));
+    if let Some(name) = &arg.name {
+        s.push_str(&format!(" {name}"));
+    }
+
+    s
+}
+
+// The parameters are sorted by key (since their order is not kept by the AST)
+pub(crate) fn format_annotation(annotation: &ast::Annotation) -> String {
+    let mut key_values: Vec<(&String, &Option<String>)> = annotation.key_values.iter().collect();
+    key_values.sort();
+
+    match key_values[..] {
+        [] => annotation.name.clone(),
+        [(key, Some(value))] if key == "value" => format!("{}({value})", annotation.name),
+        _ => {
+            let params: Vec<String> = key_values
+                .iter()
+                .map(|(key, value)| match value {
+                    Some(value) => format!("{key}={value}"),
+                    None => key.to_string(),
+                })
+                .collect();
+            format!("{}({})", annotation.name, params.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_content(content: &str, config: &FormatConfig) -> String {
+        let mut parser = crate::Parser::new();
+        parser.add_content("test.aidl", content);
+        let results = parser.validate();
+        let ast = results["test.aidl"].ast.as_ref().expect("AST");
+
+        format(ast, config)
+    }
+
+    #[test]
+    fn test_format() {
+        let input = r#"/*
+ * Copyright
+ */
+package com.example ; // The package
+import com.example.IBar;   import android.os.Bundle;
+import com.example.MyEnum ;
+@VintfStability @JavaDerive(toString=true, equals=true) interface IFoo {
+    const int   MAX=(1+2)*3;
+        /**
+         * Do foo
+         */
+    @nullable String foo(in @nullable Bundle b, out int[] c, IBar) = 3;
+    oneway void bar(in List<String> l, in Map<String,IBar> m);  // Async
+
+
+    // Nested
+    @Backing(type="byte") enum Nested { A, B = 1 << 2, C }
+    // End of body
+}
+// End of file
+"#;
+
+        let expected = r#"/*
+ * Copyright
+ */
+package com.example; // The package
+
+import android.os.Bundle;
+import com.example.IBar;
+import com.example.MyEnum;
+
+@VintfStability
+@JavaDerive(equals=true, toString=true)
+interface IFoo {
+    const int MAX = (1 + 2) * 3;
+
+    /**
+     * Do foo
+     */
+    @nullable
+    String foo(in @nullable Bundle b, out int[] c, IBar) = 3;
+
+    oneway void bar(in List<String> l, in Map<String, IBar> m); // Async
+
+    // Nested
+    @Backing(type="byte")
+    enum Nested {
+        A,
+        B = 1 << 2,
+        C,
+    }
+
+    // End of body
+}
+
+// End of file
+"#;
+
+        let output = format_content(input, &FormatConfig::default());
+        assert_eq!(output, expected);
+
+        // Idempotent
+        assert_eq!(format_content(&output, &FormatConfig::default()), expected);
+    }
+
+    #[test]
+    fn test_format_with_config() {
+        let input = r#"package p;
+import com.b.B;
+import android.a.A;
+import com.c.C;
+/** Doc */
+parcelable Pair<K, V> {
+    @nullable K first;
+    @SuppressWarnings("unused") V second = null;
+}"#;
+
+        let config = FormatConfig {
+            indent: "\t".to_owned(),
+            sort_imports: true,
+            group_imports: true,
+            annotations: AnnotationPlacement::Inline,
+        };
+        let expected = "package p;\n\nimport android.a.A;\n\nimport com.b.B;\nimport com.c.C;\n\n\
+            /** Doc */\nparcelable Pair<K, V> {\n\t@nullable K first;\n\n\
+            \t@SuppressWarnings(\"unused\") V second = null;\n}\n";
+        assert_eq!(format_content(input, &config), expected);
+
+        // Unsorted
+        let config = FormatConfig {
+            sort_imports: false,
+            ..FormatConfig::default()
+        };
+        let output = format_content(input, &config);
+        assert!(output.contains("import com.b.B;\nimport android.a.A;\nimport com.c.C;\n"));
+    }
+
+    #[test]
+    fn test_format_sorted_imports_with_comments() {
+        let input =
+            "package p;\n// Leading B\nimport p.B; // trailing B\nimport a.A; // trailing A\n\
+            parcelable Foo { A a; B b; }\n";
+
+        let expected =
+            "package p;\n\nimport a.A; // trailing A\n// Leading B\nimport p.B; // trailing B\n\n\
+            parcelable Foo {\n    A a;\n\n    B b;\n}\n";
+        let output = format_content(input, &FormatConfig::default());
+        assert_eq!(output, expected);
+        assert_eq!(format_content(&output, &FormatConfig::default()), expected);
+    }
+
+    #[test]
+    fn test_format_oneway_interface() {
+        // The validation marks all the methods of a oneway interface as oneway
+        let input = "package p; oneway interface IFoo { void a(); oneway void b(); }";
+        assert_eq!(
+            format_content(input, &FormatConfig::default()),
+            "package p;\n\noneway interface IFoo {\n    void a();\n\n    oneway void b();\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_doc_without_comments() {
+        let mut parser = crate::Parser::new();
+        parser.add_content(
+            "test.aidl",
+            "package p; /** Title\n * @param a Param */ interface IFoo { /** Hello */ void hello(); }",
+        );
+        let results = parser.validate();
+        let mut ast = results["test.aidl"].ast.clone().expect("AST");
+        ast.comments.clear();
+
+        assert_eq!(
+            format(&ast, &FormatConfig::default()),
+            "package p;\n\n/**\n * Title\n * @param a Param\n */\ninterface IFoo {\n    /** Hello */\n    void hello();\n}\n"
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use serde_derive::Serialize;
+
+use crate::ast;
+use crate::evaluation::Evaluator;
+use crate::traverse;
+
+/// A range inside a given file
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Location<ID> {
+    pub id: ID,
+    pub range: ast::Range,
+}
+
+/// Project-level index of the items, consts and enum elements, built during the
+/// validation (see Parser::validate_with_index()), which gives access to their
+/// definition and to their references across all files
+///
+/// The keys are the qualified names of the items (e.g. `pkg.Item` or
+/// `pkg.Outer.Inner`) and of their consts and enum elements (e.g. `pkg.Item.CONST`).
+///
+/// The references are the imports, the (resolved) types and the references of
+/// the constant expressions. A qualified reference (e.g. `Outer.Inner` or
+/// `Enum.VALUE`) gives one reference per segment. They are sorted by file and
+/// position.
+///
+/// Example:
+/// ```
+/// use aidl_parser::Parser;
+/// use aidl_parser::lint::ValidationConfig;
+///
+/// let mut parser = Parser::new();
+/// parser.add_content("id1", "package p; import p.Bar; interface IFoo { void foo(in Bar b); }");
+/// parser.add_content("id2", "package p; parcelable Bar {}");
+/// let (results, index) = parser.validate_with_index(&ValidationConfig::default());
+/// assert!(results["id1"].diagnostics.is_empty());
+/// assert_eq!(index.references_to("p.Bar").len(), 2);
+///
+/// // Position of `Bar` inside `foo(in Bar b)`
+/// let definition = index.definition_of(&"id1", (1, 55)).expect("definition");
+/// assert_eq!(definition.id, "id2");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProjectIndex<ID>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    definitions: HashMap<ast::ItemKey, Location<ID>>,
+    references: HashMap<ast::ItemKey, Vec<Location<ID>>>,
+
+    // Declarations and references of each file (with the key of the item)
+    occurrences: HashMap<ID, Vec<(ast::Range, ast::ItemKey)>>,
+}
+
+impl<ID> Default for ProjectIndex<ID>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    fn default() -> Self {
+        ProjectIndex {
+            definitions: HashMap::new(),
+            references: HashMap::new(),
+            occurrences: HashMap::new(),
+        }
+    }
+}
+
+impl<ID> ProjectIndex<ID>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    /// Add the declarations of the file (all the declarations must be added before
+    /// the references)
+    ///
+    /// If an item is declared several times, the first declaration is kept.
+    pub(crate) fn add_declarations(&mut self, id: &ID, ast: &ast::Aidl) {
+        let occurrences = self.occurrences.entry(id.clone()).or_default();
+        for (key, range) in get_declarations(ast) {
+            occurrences.push((range.clone(), key.clone()));
+            self.definitions.entry(key).or_insert_with(|| Location {
+                id: id.clone(),
+                range: range.clone(),
+            });
+        }
+    }
+
+    /// Add the references of the (validated) file: imports, resolved types and the
+    /// references of the constant expressions resolved by the evaluator
+    pub(crate) fn add_references(&mut self, id: &ID, ast: &ast::Aidl, evaluator: &Evaluator) {
+        let mut references = Vec::new();
+        for import in &ast.imports {
+            let name = import.get_qualified_name();
+            self.add_segments(&name, &import.symbol_range, &name, &mut references);
+        }
+        traverse::walk_types(ast, |t| {
+            if let ast::TypeKind::ResolvedItem(key, _) = &t.kind {
+                self.add_segments(&t.name, &t.symbol_range, key, &mut references);
+            }
+        });
+        for (expr, key) in evaluator.get_resolved_references(ast) {
+            if let ast::ConstExprKind::Reference(name) = &expr.kind {
+                self.add_segments(name, &expr.full_range, &key, &mut references);
+            }
+        }
+
+        for (range, key) in references {
+            self.references
+                .entry(key.clone())
+                .or_default()
+                .push(Location {
+                    id: id.clone(),
+                    range: range.clone(),
+                });
+            self.occurrences
+                .entry(id.clone())
+                .or_default()
+                .push((range, key));
+        }
+    }
+
+    /// Sort the references by file name and position (the files may be validated in
+    /// any order)
+    pub(crate) fn sort_references(&mut self) {
+        for references in self.references.values_mut() {
+            references.sort_by_cached_key(|l| (format!("{:?}", l.id), l.range.start.offset));
+        }
+    }
+
+    // Split a reference into its segments (from the last one), e.g. `Bar.Inner` referencing
+    // `pkg.Bar.Inner` gives `Inner` (`pkg.Bar.Inner`) and `Bar` (`pkg.Bar`)
+    fn add_segments(
+        &self,
+        name: &str,
+        range: &ast::Range,
+        key: ast::ItemKeyRef,
+        references: &mut Vec<(ast::Range, ast::ItemKey)>,
+    ) {
+        let mut key = key;
+        let mut end = name.len();
+
+        for segment in name.rsplit('.') {
+            let last_component = key.rsplit('.').next().unwrap_or(key);
+            if last_component != segment || !self.definitions.contains_key(key) {
+                break;
+            }
+
+            let start = end - segment.len();
+            let segment_range = ast::Range {
+                start: shift_position(&range.start, start),
+                end: shift_position(&range.start, end),
+            };
+            references.push((segment_range, key.to_owned()));
+
+            match key.rsplit_once('.') {
+                Some((parent_key, _)) if start > 0 => {
+                    key = parent_key;
+                    end = start - 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Location of the declaration of the item (or const or enum element) with the
+    /// given key
+    pub fn get_definition(&self, item_key: ast::ItemKeyRef) -> Option<&Location<ID>> {
+        self.definitions.get(item_key)
+    }
+
+    /// Key of the item (or const or enum element) declared or referenced at the given
+    /// position
+    pub fn get_item_key_at(
+        &self,
+        id: &ID,
+        line_col: (usize, usize),
+    ) -> Option<ast::ItemKeyRef<'_>> {
+        self.occurrences
+            .get(id)?
+            .iter()
+            .find(|(range, _)| traverse::range_contains(range, line_col))
+            .map(|(_, key)| key.as_str())
+    }
+
+    /// Location of the declaration of the item (or const or enum element) which is
+    /// declared or referenced at the given position (e.g. on a type or on an import)
+    pub fn definition_of(&self, id: &ID, line_col: (usize, usize)) -> Option<&Location<ID>> {
+        self.get_definition(self.get_item_key_at(id, line_col)?)
+    }
+
+    /// Locations of all the references to the item (or const or enum element) with
+    /// the given key, without its declaration
+    pub fn references_to(&self, item_key: ast::ItemKeyRef) -> &[Location<ID>] {
+        self.references.get(item_key).map_or(&[], Vec::as_slice)
+    }
+}
+
+// Keys and symbol ranges of the items, consts and enum elements declared in the AST
+fn get_declarations(ast: &ast::Aidl) -> Vec<(ast::ItemKey, &ast::Range)> {
+    let mut declarations = Vec::new();
+
+    for (key, item) in ast.get_all_items() {
+        let elements: Vec<(&str, &ast::Range)> = match item {
+            ast::Item::Interface(i) => i
+                .elements
+                .iter()
+                .filter_map(|el| match el {
+                    ast::InterfaceElement::Const(c) => Some((c.name.as_str(), &c.symbol_range)),
+                    ast::InterfaceElement::Method(_) => None,
+                })
+                .collect(),
+            ast::Item::Parcelable(p) => p
+                .elements
+                .iter()
+                .filter_map(|el| match el {
+                    ast::ParcelableElement::Const(c) => Some((c.name.as_str(), &c.symbol_range)),
+                    ast::ParcelableElement::Field(_) => None,
+                })
+                .collect(),
+            ast::Item::Union(u) => u
+                .elements
+                .iter()
+                .filter_map(|el| match el {
+                    ast::UnionElement::Const(c) => Some((c.name.as_str(), &c.symbol_range)),
+                    ast::UnionElement::Field(_) => None,
+                })
+                .collect(),
+            ast::Item::Enum(e) => e
+                .elements
+                .iter()
+                .map(|el| (el.name.as_str(), &el.symbol_range))
+                .collect(),
+        };
+
+        for (name, range) in elements {
+            declarations.push((format!("{key}.{name}"), range));
+        }
+        declarations.push((key, item.get_symbol_range()));
+    }
+
+    declarations
+}
+
+// Names are on a single line
+fn shift_position(position: &ast::Position, offset: usize) -> ast::Position {
+    ast::Position {
+        offset: position.offset + offset,
+        line_col: (position.line_col.0, position.line_col.1 + offset),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        let mut parser = crate::Parser::new();
+        parser.add_content(
+            "a/IFoo.aidl",
+            r#"package a;
+import b.Bar;
+interface IFoo {
+    Bar foo(in List<Bar> bars, in Bar.Inner inner);
+    Unknown bar();
+    const int LIMIT = Bar.MAX + 1;
+}"#,
+        );
+        parser.add_content(
+            "b/Bar.aidl",
+            r#"package b;
+parcelable Bar {
+    parcelable Inner { Bar bar; }
+    const int MAX = 3;
+}"#,
+        );
+        let (_, index) = parser.validate_with_index(&Default::default());
+
+        // Definitions
+        let bar = index.get_definition("b.Bar").expect("definition");
+        assert_eq!(bar.id, "b/Bar.aidl");
+        assert_eq!(bar.range.start.line_col, (2, 12));
+        let get_line_col = |key| index.get_definition(key).map(|l| l.range.start.line_col);
+        assert_eq!(get_line_col("b.Bar.Inner"), Some((3, 16)));
+        assert_eq!(get_line_col("b.Bar.MAX"), Some((4, 15)));
+        assert_eq!(get_line_col("a.IFoo.LIMIT"), Some((6, 15)));
+        assert_eq!(get_line_col("b.Unknown"), None);
+
+        // Definition from import, types (incl. generic types and nested items), const
+        // references or declaration
+        for line_col in [(2, 10), (4, 5), (4, 21), (4, 35), (6, 23), (2, 12)] {
+            let id = if line_col == (2, 12) {
+                "b/Bar.aidl"
+            } else {
+                "a/IFoo.aidl"
+            };
+            assert_eq!(
+                index.definition_of(&id, line_col),
+                Some(bar),
+                "{line_col:?}"
+            );
+        }
+        let get_line_col = |line_col| {
+            index
+                .definition_of(&"a/IFoo.aidl", line_col)
+                .map(|l| l.range.start.line_col)
+        };
+        assert_eq!(get_line_col((4, 39)), Some((3, 16)));
+        assert_eq!(get_line_col((6, 27)), Some((4, 15)));
+        assert_eq!(get_line_col((2, 8)), None); // package
+        assert_eq!(get_line_col((5, 5)), None);
+        assert_eq!(get_line_col((3, 1)), None);
+
+        // References (sorted by file and position)
+        let get_references = |key| -> Vec<(&str, (usize, usize))> {
+            index
+                .references_to(key)
+                .iter()
+                .map(|l| (l.id, l.range.start.line_col))
+                .collect()
+        };
+        assert_eq!(
+            get_references("b.Bar"),
+            [
+                ("a/IFoo.aidl", (2, 10)),
+                ("a/IFoo.aidl", (4, 5)),
+                ("a/IFoo.aidl", (4, 21)),
+                ("a/IFoo.aidl", (4, 35)),
+                ("a/IFoo.aidl", (6, 23)),
+                ("b/Bar.aidl", (3, 24)),
+            ]
+        );
+        assert_eq!(get_references("b.Bar.Inner"), [("a/IFoo.aidl", (4, 39))]);
+        assert_eq!(get_references("b.Bar.MAX"), [("a/IFoo.aidl", (6, 27))]);
+        assert!(get_references("a.IFoo").is_empty());
+
+        // Segment ranges
+        let inner = &index.references_to("b.Bar.Inner")[0].range;
+        assert_eq!((inner.start.offset, inner.end.offset), (80, 85));
+        assert_eq!(inner.end.line_col, (4, 44));
+    }
+
+    #[test]
+    fn test_index_enum_and_nested_import() {
+        let mut parser = crate::Parser::new();
+        parser.add_content(
+            "a/IFoo.aidl",
+            r#"package a;
+import b.Bar.Inner;
+import b.Color;
+interface IFoo {
+    void foo(in Inner inner);
+    const int C = Color.RED;
+}"#,
+        );
+        parser.add_content(
+            "b/Bar.aidl",
+            "package b;\nparcelable Bar {\n    parcelable Inner {}\n}",
+        );
+        parser.add_content(
+            "b/Color.aidl",
+            "package b;\nenum Color { RED, GREEN = RED }",
+        );
+        let (_, index) = parser.validate_with_index(&Default::default());
+
+        let get_references = |key| {
+            index
+                .references_to(key)
+                .iter()
+                .map(|l| (l.id, l.range.start.line_col, l.range.end.line_col))
+                .collect::<Vec<_>>()
+        };
+
+        // One reference per segment of the nested import
+        assert_eq!(get_references("b.Bar"), [("a/IFoo.aidl", (2, 10), (2, 13))]);
+        assert_eq!(
+            get_references("b.Bar.Inner"),
+            [
+                ("a/IFoo.aidl", (2, 14), (2, 19)),
+                ("a/IFoo.aidl", (5, 17), (5, 22)),
+            ]
+        );
+
+        // Enum elements, referenced from another file or from their enum
+        assert_eq!(
+            get_references("b.Color.RED"),
+            [
+                ("a/IFoo.aidl", (6, 25), (6, 28)),
+                ("b/Color.aidl", (2, 27), (2, 30)),
+            ]
+        );
+        assert_eq!(
+            index.get_item_key_at(&"a/IFoo.aidl", (6, 20)),
+            Some("b.Color")
+        );
+        assert_eq!(
+            index.get_item_key_at(&"b/Color.aidl", (2, 20)),
+            Some("b.Color.GREEN")
+        );
+    }
+
+    #[test]
+    fn test_index_duplicated_declaration() {
+        let mut parser = crate::Parser::new();
+        parser.add_content("b/Bar2.aidl", "package b;\nparcelable Bar {}");
+        parser.add_content("b/Bar1.aidl", "package b;\n\nparcelable Bar {}");
+        let (_, index) = parser.validate_with_index(&Default::default());
+
+        // First declaration (by file name)
+        let definition = index.get_definition("b.Bar").expect("definition");
+        assert_eq!(definition.id, "b/Bar1.aidl");
+        assert_eq!(definition.range.start.line_col, (3, 12));
+
+        // Both declarations are still found
+        assert_eq!(
+            index.get_item_key_at(&"b/Bar2.aidl", (2, 13)),
+            Some("b.Bar")
+        );
+
+        let empty = ProjectIndex::<&str>::default();
+        assert_eq!(empty.get_definition("b.Bar"), None);
+        assert_eq!(empty.get_item_key_at(&"b/Bar1.aidl", (3, 13)), None);
+        assert!(empty.references_to("b.Bar").is_empty());
+    }
+
+    #[test]
+    fn test_add_segments() {
+        let mut parser = crate::Parser::new();
+        parser.add_content(
+            "b/XBar.aidl",
+            "package b;\nparcelable XBar {\n    parcelable Inner {}\n}",
+        );
+        parser.add_content("b/Bar.aidl", "package b;\nparcelable Bar {}");
+        let (_, index) = parser.validate_with_index(&Default::default());
+
+        let range = ast::Range {
+            start: ast::Position {
+                offset: 10,
+                line_col: (2, 5),
+            },
+            end: ast::Position {
+                offset: 19,
+                line_col: (2, 14),
+            },
+        };
+        let get_segments = |name, key| {
+            let mut references = Vec::new();
+            index.add_segments(name, &range, key, &mut references);
+            references
+                .into_iter()
+                .map(|(range, key)| (range.start.line_col, range.end.line_col, key))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            get_segments("XBar.Inner", "b.XBar.Inner"),
+            [
+                ((2, 10), (2, 15), "b.XBar.Inner".to_owned()),
+                ((2, 5), (2, 9), "b.XBar".to_owned()),
+            ]
+        );
+
+        // `Bar` is only the suffix of `XBar`: not a reference to `b.XBar`
+        assert_eq!(
+            get_segments("Bar.Inner", "b.XBar.Inner"),
+            [((2, 9), (2, 14), "b.XBar.Inner".to_owned())]
+        );
+    }
+}
@@ -1,9 +1,25 @@
 #![doc = include_str!("../README.md")]
 
+pub mod annotations;
+pub mod api_dump;
 pub mod ast;
+pub mod codegen;
+pub mod compat;
+pub mod completion;
+pub mod cst;
 pub mod diagnostic;
+pub mod diff;
+mod evaluation;
+pub mod format;
+pub mod index;
 mod javadoc;
+pub mod lint;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 pub mod parser;
+pub mod rename;
+pub mod render;
+pub mod report;
 mod rules;
 pub mod symbol;
 pub mod traverse;
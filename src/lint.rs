@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::ast;
+use crate::diagnostic::{Diagnostic, DiagnosticCode, DiagnosticKind};
+use crate::parser::ParseFileResult;
+use crate::symbol::Symbol;
+use crate::traverse::{self, SymbolFilter};
+
+/// Level of a diagnostic, which overrides its default kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintLevel {
+    /// The diagnostic is suppressed
+    Allow,
+    /// The diagnostic is reported as a warning
+    Warn,
+    /// The diagnostic is reported as an error
+    Deny,
+}
+
+/// Configuration of the validation, given to Parser::validate_with_config()
+///
+/// Each diagnostic can be raised (deny), lowered (warn) or silenced (allow).
+/// The diagnostics without configured level keep their default kind, and the levels
+/// of syntax errors are ignored.
+///
+/// Example:
+/// ```
+/// use aidl_parser::diagnostic::DiagnosticCode;
+/// use aidl_parser::lint::{LintLevel, ValidationConfig};
+///
+/// let mut config = ValidationConfig::new();
+/// config.set_level(DiagnosticCode::NonGenericList, LintLevel::Deny);
+/// config.set_level(DiagnosticCode::UnusedDeclaredParcelable, LintLevel::Allow);
+///
+/// assert_eq!(config.get_level(DiagnosticCode::NonGenericList), Some(LintLevel::Deny));
+/// assert_eq!(config.get_level(DiagnosticCode::UnusedImport), None);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ValidationConfig {
+    levels: HashMap<DiagnosticCode, LintLevel>,
+}
+
+impl ValidationConfig {
+    /// Create a configuration where all the diagnostics keep their default kind
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the level of the given diagnostic
+    pub fn set_level(&mut self, code: DiagnosticCode, level: LintLevel) -> &mut Self {
+        self.levels.insert(code, level);
+        self
+    }
+
+    /// Level of the given diagnostic (if overridden)
+    pub fn get_level(&self, code: DiagnosticCode) -> Option<LintLevel> {
+        self.levels.get(&code).copied()
+    }
+}
+
+/// Comment which suppresses some diagnostics, e.g. `// aidl-lint: allow(unused-import)`
+///
+/// A comment on its own line applies to the next line, a comment after some code
+/// applies to its own line. When placed before the package declaration, it applies
+/// to the whole file.
+#[derive(Clone, Debug)]
+pub(crate) struct AllowComment {
+    line: usize,
+    is_trailing: bool,
+    codes: Vec<DiagnosticCode>,
+}
+
+/// Extract the `// aidl-lint: allow(...)` comments from the comments of the given content
+///
+/// Only line comments are considered, so that e.g. the same text inside a block comment
+/// or a string literal does not suppress anything.
+pub(crate) fn parse_allow_comments(content: &str, comments: &[ast::Comment]) -> Vec<AllowComment> {
+    let re = regex::Regex::new(r"^//\s*aidl-lint:\s*allow\(([^)]*)\)").unwrap();
+
+    comments
+        .iter()
+        .filter_map(|comment| {
+            let codes = re.captures(&comment.text)?.get(1)?.as_str();
+            let start = comment.full_range.start.offset;
+            let line_start = content[..start].rfind(['\n', '\r']).map_or(0, |i| i + 1);
+
+            Some(AllowComment {
+                line: comment.full_range.start.line_col.0,
+                is_trailing: !content[line_start..start].trim().is_empty(),
+                codes: parse_codes(codes.split(',')),
+            })
+        })
+        .collect()
+}
+
+/// Apply the configured levels and the in-source suppressions to the diagnostics
+/// of the given file
+///
+/// The suppressed diagnostics are moved to `suppressed_diagnostics`. Syntax errors are
+/// always reported as errors: they cannot be suppressed nor lowered.
+pub(crate) fn apply<ID>(
+    fr: &mut ParseFileResult<ID>,
+    config: &ValidationConfig,
+    allow_comments: &[AllowComment],
+) where
+    ID: Eq + Hash + Clone + Debug,
+{
+    let package_line = fr
+        .ast
+        .as_ref()
+        .map(|ast| ast.package.full_range.start.line_col.0);
+    let suppressed_ranges = fr
+        .ast
+        .as_ref()
+        .map(get_suppressed_ranges)
+        .unwrap_or_default();
+
+    let is_suppressed = |d: &Diagnostic| {
+        if d.code.is_syntax_error() {
+            return false;
+        }
+        if config.get_level(d.code) == Some(LintLevel::Allow) {
+            return true;
+        }
+
+        let line = d.range.start.line_col.0;
+        let is_allowed_by_comment = allow_comments.iter().any(|c| {
+            let in_scope = match package_line {
+                Some(package_line) if c.line < package_line => true,
+                _ if c.is_trailing => line == c.line,
+                _ => line == c.line + 1,
+            };
+            in_scope && c.codes.contains(&d.code)
+        });
+
+        is_allowed_by_comment
+            || suppressed_ranges
+                .iter()
+                .any(|(range, codes)| contains(range, &d.range) && codes.contains(&d.code))
+    };
+
+    let (suppressed, mut diagnostics): (Vec<_>, Vec<_>) =
+        fr.diagnostics.drain(..).partition(is_suppressed);
+
+    for d in diagnostics.iter_mut().filter(|d| !d.code.is_syntax_error()) {
+        match config.get_level(d.code) {
+            Some(LintLevel::Warn) => d.kind = DiagnosticKind::Warning,
+            Some(LintLevel::Deny) => d.kind = DiagnosticKind::Error,
+            Some(LintLevel::Allow) | None => (),
+        }
+    }
+
+    fr.diagnostics = diagnostics;
+    fr.suppressed_diagnostics.extend(suppressed);
+}
+
+// Ranges of the symbols annotated with `@SuppressWarnings(...)` and the suppressed codes
+fn get_suppressed_ranges(ast: &ast::Aidl) -> Vec<(ast::Range, Vec<DiagnosticCode>)> {
+    let re = regex::Regex::new(r#""([^"]*)""#).unwrap();
+    let mut ranges = Vec::new();
+
+    traverse::walk_symbols(ast, SymbolFilter::All, |smb| {
+        let annotations = match smb {
+            Symbol::Interface(i, _) => &i.annotations,
+            Symbol::Parcelable(p, _) => &p.annotations,
+            Symbol::Union(u, _) => &u.annotations,
+            Symbol::Enum(e, _) => &e.annotations,
+            Symbol::Method(m, _) => &m.annotations,
+            Symbol::Arg(a, _) => &a.annotations,
+            Symbol::Const(c, _) => &c.annotations,
+            Symbol::Field(f, _) => &f.annotations,
+            _ => return,
+        };
+
+        for a in annotations.iter().filter(|a| a.name == "@SuppressWarnings") {
+            if let Some(Some(value)) = a.key_values.get("value") {
+                let names = re.captures_iter(value).filter_map(|c| c.get(1));
+                let codes = parse_codes(names.map(|m| m.as_str()));
+                ranges.push((smb.get_full_range().clone(), codes));
+            }
+        }
+    });
+
+    ranges
+}
+
+// Accept both codes (e.g. `AIDL0007`) and names (e.g. `unused-import`)
+fn parse_codes<'a, I: Iterator<Item = &'a str>>(names: I) -> Vec<DiagnosticCode> {
+    names
+        .map(str::trim)
+        .filter_map(|n| DiagnosticCode::from_code(n).or_else(|| DiagnosticCode::from_name(n)))
+        .collect()
+}
+
+fn contains(outer: &ast::Range, inner: &ast::Range) -> bool {
+    outer.start.offset <= inner.start.offset && inner.end.offset <= outer.end.offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(input: &str, config: &ValidationConfig) -> ParseFileResult<usize> {
+        let mut parser = crate::Parser::new();
+        parser.add_content(0, input);
+        parser
+            .validate_with_config(config)
+            .remove(&0)
+            .expect("result")
+    }
+
+    #[test]
+    fn test_levels() {
+        let input = r#"
+            package test;
+            parcelable Test {
+                List list;
+                Map map;
+            }
+        "#;
+
+        let mut config = ValidationConfig::new();
+        config.set_level(DiagnosticCode::NonGenericList, LintLevel::Deny);
+        config.set_level(DiagnosticCode::NonGenericMap, LintLevel::Allow);
+
+        let res = validate(input, &config);
+        assert_eq!(res.diagnostics.len(), 1);
+        assert_eq!(res.diagnostics[0].code, DiagnosticCode::NonGenericList);
+        assert_eq!(res.diagnostics[0].kind, DiagnosticKind::Error);
+        assert_eq!(res.suppressed_diagnostics.len(), 1);
+        assert_eq!(
+            res.suppressed_diagnostics[0].code,
+            DiagnosticCode::NonGenericMap
+        );
+
+        // Default config
+        let res = validate(input, &ValidationConfig::new());
+        assert_eq!(res.diagnostics.len(), 2);
+        assert!(res
+            .diagnostics
+            .iter()
+            .all(|d| d.kind == DiagnosticKind::Warning));
+        assert_eq!(res.suppressed_diagnostics.len(), 0);
+    }
+
+    #[test]
+    fn test_suppress_warnings_annotation() {
+        let input = r#"
+            package test;
+            parcelable Test {
+                @SuppressWarnings("non-generic-list") List list1;
+                List list2;
+
+                @SuppressWarnings(value={"AIDL0013", "non-generic-map"})
+                parcelable Nested {
+                    List list;
+                    Map map;
+                }
+            }
+        "#;
+
+        let res = validate(input, &ValidationConfig::new());
+        assert_eq!(res.diagnostics.len(), 1);
+        assert_eq!(res.diagnostics[0].range.start.line_col.0, 5);
+        assert_eq!(res.suppressed_diagnostics.len(), 3);
+    }
+
+    #[test]
+    fn test_allow_comments() {
+        let input = r#"
+            package test;
+            parcelable Test {
+                // aidl-lint: allow(non-generic-list)
+                List list1;
+                List list2; // aidl-lint: allow(AIDL0013, non-generic-map)
+                List list3;
+            }
+        "#;
+
+        let res = validate(input, &ValidationConfig::new());
+        assert_eq!(res.diagnostics.len(), 1);
+        assert_eq!(res.diagnostics[0].range.start.line_col.0, 7);
+        assert_eq!(res.suppressed_diagnostics.len(), 2);
+
+        // Before the package declaration -> whole file
+        let input = r#"
+            // aidl-lint: allow(non-generic-list)
+            package test;
+            parcelable Test {
+                List list1;
+                List list2;
+            }
+        "#;
+
+        let res = validate(input, &ValidationConfig::new());
+        assert_eq!(res.diagnostics.len(), 0);
+        assert_eq!(res.suppressed_diagnostics.len(), 2);
+
+        // Not in block comments nor in string literals
+        let input = r#"
+            package test;
+            parcelable Test {
+                /* aidl-lint: allow(non-generic-list) */
+                List list1;
+                const String S = "// aidl-lint: allow(non-generic-list)"; List list2;
+            }
+        "#;
+
+        let res = validate(input, &ValidationConfig::new());
+        assert_eq!(res.diagnostics.len(), 2);
+        assert_eq!(res.suppressed_diagnostics.len(), 0);
+    }
+
+    #[test]
+    fn test_syntax_errors_not_suppressed() {
+        let input = r#"
+            // aidl-lint: allow(unrecognized-token)
+            package test;
+            parcelable Test { int a = 1 < < 2; wrong }
+        "#;
+
+        let mut config = ValidationConfig::new();
+        config
+            .set_level(DiagnosticCode::UnrecognizedToken, LintLevel::Allow)
+            .set_level(DiagnosticCode::InvalidOperator, LintLevel::Warn);
+
+        let res = validate(input, &config);
+        let codes: Vec<(DiagnosticCode, DiagnosticKind)> = res
+            .diagnostics
+            .iter()
+            .map(|d| (d.code, d.kind.clone()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (DiagnosticCode::InvalidOperator, DiagnosticKind::Error),
+                (DiagnosticCode::UnrecognizedToken, DiagnosticKind::Error),
+            ]
+        );
+        assert_eq!(res.suppressed_diagnostics.len(), 0);
+    }
+}
@@ -0,0 +1,1176 @@
+//! Language server (LSP) built on [`Parser`] and [`traverse`](crate::traverse)
+//!
+//! The server communicates via a [`Connection`] (e.g. stdio for the `aidl-ls` binary, or
+//! in-memory for tests). All the `.aidl` files of the workspace folders are parsed at
+//! startup, so that the items can be resolved across files. The opened documents are
+//! then re-parsed and re-validated on each change.
+//!
+//! Supported features:
+//! - diagnostics (published for the opened documents)
+//! - hover
+//! - document symbols
+//! - go-to-definition
+//! - find references
+//! - completion
+//! - rename
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics, ShowMessage,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, References, Rename,
+    Request as LspRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    CompletionTextEdit, DiagnosticRelatedInformation, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, Location, MarkupContent, MarkupKind, MessageType, NumberOrString, OneOf,
+    PublishDiagnosticsParams, ReferenceParams, RenameParams, ServerCapabilities, ServerInfo,
+    ShowMessageParams, SymbolKind, TextDocumentPositionParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::ast;
+use crate::completion::CompletionKind;
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::index::{self, ProjectIndex};
+use crate::rename::{self, RenameError};
+use crate::symbol::{ConstOwner, FieldOwner, Symbol};
+use crate::traverse::{self, SymbolFilter};
+use crate::{ParseFileResult, Parser};
+
+const SERVER_NAME: &str = "aidl-ls";
+
+/// Run the language server until the client requests the shutdown
+///
+/// The initialization handshake is part of the function, i.e. the first message
+/// received via the connection must be the `initialize` request.
+///
+/// Example:
+/// ```no_run
+/// let (connection, io_threads) = lsp_server::Connection::stdio();
+/// aidl_parser::lsp::run(&connection)?;
+/// drop(connection);
+/// io_threads.join()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn run(connection: &Connection) -> Result<()> {
+    let (id, params) = connection.initialize_start()?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    let result = serde_json::json!({
+        "capabilities": get_capabilities(),
+        "serverInfo": ServerInfo {
+            name: SERVER_NAME.to_owned(),
+            version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+        },
+    });
+    connection.initialize_finish(id, result)?;
+
+    let mut server = Server::new(connection);
+    for folder in get_workspace_folders(&params) {
+        server.load_folder(&folder)?;
+    }
+    server.validate();
+
+    server.run()
+}
+
+fn get_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(Vec::from([".".to_owned(), "@".to_owned()])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[allow(deprecated)] // root_uri is still sent by most clients
+fn get_workspace_folders(params: &InitializeParams) -> Vec<PathBuf> {
+    let uris: Vec<&Url> = match (&params.workspace_folders, &params.root_uri) {
+        (Some(folders), _) => folders.iter().map(|f| &f.uri).collect(),
+        (None, Some(root_uri)) => Vec::from([root_uri]),
+        (None, None) => Vec::new(),
+    };
+
+    uris.into_iter()
+        .filter_map(|uri| uri.to_file_path().ok())
+        .collect()
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    parser: Parser<Url>,
+    contents: HashMap<Url, String>,
+    opened: HashSet<Url>,
+    results: HashMap<Url, ParseFileResult<Url>>,
+    index: ProjectIndex<Url>,
+}
+
+impl<'a> Server<'a> {
+    fn new(connection: &'a Connection) -> Self {
+        Server {
+            connection,
+            parser: Parser::new(),
+            contents: HashMap::new(),
+            opened: HashSet::new(),
+            results: HashMap::new(),
+            index: ProjectIndex::default(),
+        }
+    }
+
+    fn run(&mut self) -> Result<()> {
+        for msg in &self.connection.receiver {
+            match msg {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(req);
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(not) => self.handle_notification(not)?,
+                Message::Response(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Add the `.aidl` files of the folder (recursively) to the parser
+    fn load_folder(&mut self, dir: &Path) -> Result<()> {
+        let entries =
+            std::fs::read_dir(dir).with_context(|| format!("cannot read `{}`", dir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.load_folder(&path)?;
+            } else if path.extension().is_some_and(|ext| ext == "aidl") {
+                if let (Ok(uri), Ok(content)) =
+                    (Url::from_file_path(&path), std::fs::read_to_string(&path))
+                {
+                    self.set_content(uri, content);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_content(&mut self, uri: Url, content: String) {
+        self.parser.add_content(uri.clone(), &content);
+        self.contents.insert(uri, content);
+    }
+
+    fn validate(&mut self) {
+        (self.results, self.index) = self.parser.validate_with_index(&Default::default());
+    }
+
+    fn handle_request(&self, req: Request) -> Response {
+        match req.method.as_str() {
+            HoverRequest::METHOD => self.respond::<HoverRequest>(req, Self::hover),
+            DocumentSymbolRequest::METHOD => {
+                self.respond::<DocumentSymbolRequest>(req, Self::document_symbols)
+            }
+            GotoDefinition::METHOD => self.respond::<GotoDefinition>(req, Self::definition),
+            References::METHOD => self.respond::<References>(req, Self::references),
+            Completion::METHOD => self.respond::<Completion>(req, Self::completion),
+            Rename::METHOD => self.try_respond::<Rename, _>(req, Self::rename),
+            _ => Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request `{}`", req.method),
+            ),
+        }
+    }
+
+    fn respond<R: LspRequest>(
+        &self,
+        req: Request,
+        f: impl FnOnce(&Self, R::Params) -> R::Result,
+    ) -> Response {
+        self.try_respond::<R, std::convert::Infallible>(req, |s, params| Ok(f(s, params)))
+    }
+
+    // Same as respond() but the request may fail
+    fn try_respond<R: LspRequest, E: std::fmt::Display>(
+        &self,
+        req: Request,
+        f: impl FnOnce(&Self, R::Params) -> std::result::Result<R::Result, E>,
+    ) -> Response {
+        match serde_json::from_value::<R::Params>(req.params) {
+            Ok(params) => match f(self, params) {
+                Ok(result) => Response::new_ok(req.id, result),
+                Err(e) => Response::new_err(req.id, ErrorCode::RequestFailed as i32, e.to_string()),
+            },
+            Err(e) => Response::new_err(req.id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    // Only the errors of the connection are returned: the invalid notifications are
+    // reported to the client and ignored
+    fn handle_notification(&mut self, not: Notification) -> Result<()> {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    match self.notification_params::<DidOpenTextDocument>(not)? {
+                        Some(params) => params,
+                        None => return Ok(()),
+                    };
+                let uri = params.text_document.uri;
+                self.opened.insert(uri.clone());
+                self.set_content(uri, params.text_document.text);
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    match self.notification_params::<DidChangeTextDocument>(not)? {
+                        Some(params) => params,
+                        None => return Ok(()),
+                    };
+                let uri = params.text_document.uri;
+                let mut content = self.contents.remove(&uri).unwrap_or_default();
+                for change in params.content_changes {
+                    match change.range {
+                        Some(range) => {
+                            let start = to_offset(&content, &range.start);
+                            let end = to_offset(&content, &range.end).max(start);
+                            content.replace_range(start..end, &change.text);
+                        }
+                        None => content = change.text,
+                    }
+                }
+                self.set_content(uri, content);
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    match self.notification_params::<DidCloseTextDocument>(not)? {
+                        Some(params) => params,
+                        None => return Ok(()),
+                    };
+                let uri = params.text_document.uri;
+                self.opened.remove(&uri);
+
+                // Restore the content of the file (if it is part of the workspace)
+                match uri.to_file_path().map(std::fs::read_to_string) {
+                    Ok(Ok(content)) => self.set_content(uri.clone(), content),
+                    _ => {
+                        self.parser.remove_content(uri.clone());
+                        self.contents.remove(&uri);
+                    }
+                }
+                self.publish_diagnostics(uri, Vec::new())?;
+            }
+            _ => return Ok(()),
+        }
+
+        self.validate();
+        for uri in self.opened.clone() {
+            let diagnostics = self.results.get(&uri).map_or(Vec::new(), |fr| {
+                fr.diagnostics
+                    .iter()
+                    .map(|d| to_lsp_diagnostic(d, &uri, &self.contents))
+                    .collect()
+            });
+            self.publish_diagnostics(uri, diagnostics)?;
+        }
+
+        Ok(())
+    }
+
+    // Params of the notification, or None (after showing an error message) if invalid
+    fn notification_params<N: LspNotification>(
+        &self,
+        not: Notification,
+    ) -> Result<Option<N::Params>> {
+        match serde_json::from_value::<N::Params>(not.params) {
+            Ok(params) => Ok(Some(params)),
+            Err(e) => {
+                let params = ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    message: format!("{SERVER_NAME}: invalid `{}` notification: {e}", N::METHOD),
+                };
+                let not = Notification::new(ShowMessage::METHOD.to_owned(), params);
+                self.connection.sender.send(Message::Notification(not))?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: Url, diagnostics: Vec<lsp_types::Diagnostic>) -> Result<()> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        let not = Notification::new(PublishDiagnostics::METHOD.to_owned(), params);
+        self.connection.sender.send(Message::Notification(not))?;
+
+        Ok(())
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let (uri, symbol) = self.find_symbol(&params.text_document_position_params)?;
+        let range = to_lsp_range(symbol.get_range(), &self.contents[uri]);
+
+        // Resolved types: display the corresponding item
+        let symbol = match symbol {
+            Symbol::Type(ast::Type {
+                kind: ast::TypeKind::ResolvedItem(key, _),
+                ..
+            }) => self.find_item(key).unwrap_or(symbol),
+            _ => symbol,
+        };
+
+        let mut value = format!("```aidl\n{}\n```", symbol.get_signature());
+        if let Some(doc) = symbol.get_doc() {
+            value.push_str("\n\n");
+            value.push_str(doc);
+        }
+
+        // Methods: transaction code, and methods which would be renumbered by an insertion
+        if let Symbol::Method(method, interface) = symbol {
+            if let Some(code) = method.effective_transact_code {
+                value.push_str(&format!("\n\nTransaction code: `{code}`"));
+            }
+            if method.effective_transact_code.is_some() && method.transact_code.is_none() {
+                let position = interface
+                    .elements
+                    .iter()
+                    .filter_map(|el| el.as_method())
+                    .position(|m| std::ptr::eq(m, method))
+                    .unwrap_or_default();
+                let renumbered: Vec<String> = interface
+                    .get_renumbered_methods(position)
+                    .iter()
+                    .map(|m| format!("`{}`", m.name))
+                    .collect();
+                value.push_str(&format!(
+                    " (implicit)\n\nInserting a method without explicit code before this one would renumber {}",
+                    renumbered.join(", ")
+                ));
+            }
+        }
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range),
+        })
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let uri = &params.text_document.uri;
+        let ast = self.results.get(uri)?.ast.as_ref()?;
+        let content = &self.contents[uri];
+
+        Some(DocumentSymbolResponse::Nested(Vec::from([
+            to_document_symbol_tree(&ast.item, &ast.package, content),
+        ])))
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let (uri, line_col) = self.get_line_col(&params.text_document_position_params)?;
+        let location = self.index.definition_of(uri, line_col)?;
+
+        Some(GotoDefinitionResponse::Scalar(
+            self.to_lsp_location(location),
+        ))
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let (uri, line_col) = self.get_line_col(&params.text_document_position)?;
+        let key = self.index.get_item_key_at(uri, line_col)?;
+
+        let declaration = match params.context.include_declaration {
+            true => self.index.get_definition(key),
+            false => None,
+        };
+
+        Some(
+            declaration
+                .into_iter()
+                .chain(self.index.references_to(key))
+                .map(|location| self.to_lsp_location(location))
+                .collect(),
+        )
+    }
+
+    fn rename(
+        &self,
+        params: RenameParams,
+    ) -> std::result::Result<Option<WorkspaceEdit>, RenameError> {
+        let (uri, symbol) = self
+            .find_symbol(&params.text_document_position)
+            .ok_or(RenameError::NotRenamable)?;
+        let edits = rename::rename(&self.results, &self.index, uri, &symbol, &params.new_name)?;
+
+        let changes = edits
+            .into_iter()
+            .map(|(uri, fixes)| {
+                let content = &self.contents[&uri];
+                let edits = fixes
+                    .iter()
+                    .map(|fix| TextEdit {
+                        range: to_lsp_range(&fix.range, content),
+                        new_text: fix.replacement.clone(),
+                    })
+                    .collect();
+                (uri, edits)
+            })
+            .collect();
+
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let (uri, line_col) = self.get_line_col(&params.text_document_position)?;
+        let content = &self.contents[uri];
+
+        let items = self
+            .parser
+            .complete(uri, line_col)
+            .into_iter()
+            .map(|item| CompletionItem {
+                label: item.label.clone(),
+                kind: Some(to_completion_item_kind(item.kind)),
+                detail: item.detail,
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: to_lsp_range(&item.range, content),
+                    new_text: item.label,
+                })),
+                additional_text_edits: (!item.fixes.is_empty()).then(|| {
+                    item.fixes
+                        .iter()
+                        .map(|fix| TextEdit {
+                            range: to_lsp_range(&fix.range, content),
+                            new_text: fix.replacement.clone(),
+                        })
+                        .collect()
+                }),
+                ..Default::default()
+            })
+            .collect();
+
+        Some(CompletionResponse::Array(items))
+    }
+
+    // Symbol at the given position
+    fn find_symbol(&self, params: &TextDocumentPositionParams) -> Option<(&Url, Symbol<'_>)> {
+        let (uri, line_col) = self.get_line_col(params)?;
+        let ast = self.results.get(uri)?.ast.as_ref()?;
+
+        let symbol = traverse::find_symbol_at_line_col(ast, SymbolFilter::All, line_col)?;
+        Some((uri, symbol))
+    }
+
+    // Document and 1-based line and column (as used by the AST)
+    fn get_line_col(&self, params: &TextDocumentPositionParams) -> Option<(&Url, (usize, usize))> {
+        let (uri, content) = self.contents.get_key_value(&params.text_document.uri)?;
+        let lookup = line_col::LineColLookup::new(content);
+
+        Some((
+            uri,
+            lookup.get_by_cluster(to_offset(content, &params.position)),
+        ))
+    }
+
+    // Declaration of the item with the given key
+    fn find_item(&self, key: ast::ItemKeyRef) -> Option<Symbol<'_>> {
+        let location = self.index.get_definition(key)?;
+        let ast = self.results.get(&location.id)?.ast.as_ref()?;
+        let (_, item) = ast.get_all_items().into_iter().find(|(k, _)| k == key)?;
+
+        Some(to_item_symbol(item, &ast.package))
+    }
+
+    fn to_lsp_location(&self, location: &index::Location<Url>) -> Location {
+        Location {
+            uri: location.id.clone(),
+            range: to_lsp_range(&location.range, &self.contents[&location.id]),
+        }
+    }
+}
+
+fn to_item_symbol<'a>(item: &'a ast::Item, package: &'a ast::Package) -> Symbol<'a> {
+    match item {
+        ast::Item::Interface(i) => Symbol::Interface(i, package),
+        ast::Item::Parcelable(p) => Symbol::Parcelable(p, package),
+        ast::Item::Union(u) => Symbol::Union(u, package),
+        ast::Item::Enum(e) => Symbol::Enum(e, package),
+    }
+}
+
+fn to_completion_item_kind(kind: CompletionKind) -> CompletionItemKind {
+    match kind {
+        CompletionKind::Keyword => CompletionItemKind::KEYWORD,
+        CompletionKind::Type => CompletionItemKind::CLASS,
+        CompletionKind::Interface => CompletionItemKind::INTERFACE,
+        CompletionKind::Parcelable | CompletionKind::Union => CompletionItemKind::STRUCT,
+        CompletionKind::Enum => CompletionItemKind::ENUM,
+        CompletionKind::EnumElement => CompletionItemKind::ENUM_MEMBER,
+        CompletionKind::Const => CompletionItemKind::CONSTANT,
+        CompletionKind::Annotation => CompletionItemKind::PROPERTY,
+        CompletionKind::Package => CompletionItemKind::MODULE,
+    }
+}
+
+fn to_document_symbol_tree(
+    item: &ast::Item,
+    package: &ast::Package,
+    content: &str,
+) -> DocumentSymbol {
+    let mut children = Vec::new();
+    match item {
+        ast::Item::Interface(i) => {
+            for el in &i.elements {
+                children.push(match el {
+                    ast::InterfaceElement::Const(c) => {
+                        let s = Symbol::Const(c, ConstOwner::Interface(i));
+                        to_document_symbol(s, SymbolKind::CONSTANT, Vec::new(), content)
+                    }
+                    ast::InterfaceElement::Method(m) => to_document_symbol(
+                        Symbol::Method(m, i),
+                        SymbolKind::METHOD,
+                        Vec::new(),
+                        content,
+                    ),
+                });
+            }
+        }
+        ast::Item::Parcelable(p) => {
+            for el in &p.elements {
+                children.push(match el {
+                    ast::ParcelableElement::Const(c) => {
+                        let s = Symbol::Const(c, ConstOwner::Parcelable(p));
+                        to_document_symbol(s, SymbolKind::CONSTANT, Vec::new(), content)
+                    }
+                    ast::ParcelableElement::Field(f) => {
+                        let s = Symbol::Field(f, FieldOwner::Parcelable(p));
+                        to_document_symbol(s, SymbolKind::FIELD, Vec::new(), content)
+                    }
+                });
+            }
+        }
+        ast::Item::Union(u) => {
+            for el in &u.elements {
+                children.push(match el {
+                    ast::UnionElement::Const(c) => {
+                        let s = Symbol::Const(c, ConstOwner::Union(u));
+                        to_document_symbol(s, SymbolKind::CONSTANT, Vec::new(), content)
+                    }
+                    ast::UnionElement::Field(f) => {
+                        let s = Symbol::Field(f, FieldOwner::Union(u));
+                        to_document_symbol(s, SymbolKind::FIELD, Vec::new(), content)
+                    }
+                });
+            }
+        }
+        ast::Item::Enum(e) => {
+            for el in &e.elements {
+                let s = Symbol::EnumElement(el, e);
+                children.push(to_document_symbol(
+                    s,
+                    SymbolKind::ENUM_MEMBER,
+                    Vec::new(),
+                    content,
+                ));
+            }
+        }
+    }
+
+    for nested in item.get_nested_items() {
+        children.push(to_document_symbol_tree(nested, package, content));
+    }
+
+    let kind = match item {
+        ast::Item::Interface(_) => SymbolKind::INTERFACE,
+        ast::Item::Parcelable(_) | ast::Item::Union(_) => SymbolKind::STRUCT,
+        ast::Item::Enum(_) => SymbolKind::ENUM,
+    };
+
+    to_document_symbol(to_item_symbol(item, package), kind, children, content)
+}
+
+#[allow(deprecated)] // DocumentSymbol::deprecated has to be set
+fn to_document_symbol(
+    symbol: Symbol,
+    kind: SymbolKind,
+    children: Vec<DocumentSymbol>,
+    content: &str,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name: symbol.get_name().unwrap_or_default(),
+        detail: symbol.get_details(),
+        kind,
+        tags: None,
+        deprecated: None,
+        range: to_lsp_range(symbol.get_full_range(), content),
+        selection_range: to_lsp_range(symbol.get_range(), content),
+        children: (!children.is_empty()).then_some(children),
+    }
+}
+
+fn to_lsp_diagnostic(
+    diagnostic: &Diagnostic,
+    uri: &Url,
+    contents: &HashMap<Url, String>,
+) -> lsp_types::Diagnostic {
+    let content = contents.get(uri).map_or("", String::as_str);
+    let severity = match diagnostic.kind {
+        DiagnosticKind::Error => DiagnosticSeverity::ERROR,
+        DiagnosticKind::Warning => DiagnosticSeverity::WARNING,
+    };
+
+    let mut message = diagnostic.message.clone();
+    if let Some(hint) = &diagnostic.hint {
+        message.push_str(&format!("\nhelp: {hint}"));
+    }
+
+    let related_information = diagnostic
+        .related_infos
+        .iter()
+        .filter_map(|info| {
+            let location = match &info.file {
+                None => Location::new(uri.clone(), to_lsp_range(&info.range, content)),
+                // Other file (e.g. a URI or a path), which may not be loaded
+                Some(file) => {
+                    let uri = Url::parse(file)
+                        .or_else(|_| Url::from_file_path(file))
+                        .ok()?;
+                    let range = match contents.get(&uri) {
+                        Some(content) => to_lsp_range(&info.range, content),
+                        None => from_line_col(&info.range),
+                    };
+                    Location::new(uri, range)
+                }
+            };
+            Some(DiagnosticRelatedInformation {
+                location,
+                message: info.message.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    lsp_types::Diagnostic {
+        range: to_lsp_range(&diagnostic.range, content),
+        severity: Some(severity),
+        code: Some(NumberOrString::String(diagnostic.code.as_str().to_owned())),
+        source: Some(SERVER_NAME.to_owned()),
+        message,
+        related_information: (!related_information.is_empty()).then_some(related_information),
+        ..Default::default()
+    }
+}
+
+// LSP positions are 0-based and the character is counted in UTF-16 code units
+fn to_lsp_position(position: &ast::Position, content: &str) -> lsp_types::Position {
+    let mut offset = position.offset.min(content.len());
+    while !content.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    lsp_types::Position {
+        line: content[..offset].matches('\n').count() as u32,
+        character: content[line_start..offset].encode_utf16().count() as u32,
+    }
+}
+
+// Range from the (1-based) lines and columns, when the content is not known
+fn from_line_col(range: &ast::Range) -> lsp_types::Range {
+    let to_position = |(line, col): (usize, usize)| {
+        lsp_types::Position::new(line.saturating_sub(1) as u32, col.saturating_sub(1) as u32)
+    };
+    lsp_types::Range::new(
+        to_position(range.start.line_col),
+        to_position(range.end.line_col),
+    )
+}
+
+fn to_lsp_range(range: &ast::Range, content: &str) -> lsp_types::Range {
+    lsp_types::Range {
+        start: to_lsp_position(&range.start, content),
+        end: to_lsp_position(&range.end, content),
+    }
+}
+
+// Byte offset of the LSP position (clamped to the line and to the content)
+fn to_offset(content: &str, position: &lsp_types::Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match content[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return content.len(),
+        }
+    }
+
+    let mut utf16_count = 0;
+    for (i, c) in content[line_start..].char_indices() {
+        if utf16_count >= position.character as usize || c == '\n' {
+            return line_start + i;
+        }
+        utf16_count += c.len_utf16();
+    }
+
+    content.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::notification::{Exit, Initialized};
+    use lsp_types::request::{Initialize, Shutdown};
+    use lsp_types::{
+        PartialResultParams, Position, ReferenceContext, TextDocumentContentChangeEvent,
+        TextDocumentIdentifier, TextDocumentItem, VersionedTextDocumentIdentifier,
+        WorkDoneProgressParams,
+    };
+
+    // In-process LSP client
+    struct Client {
+        connection: Connection,
+        thread: Option<std::thread::JoinHandle<Result<()>>>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (server, connection) = Connection::memory();
+            let thread = std::thread::spawn(move || run(&server));
+            let mut client = Client {
+                connection,
+                thread: Some(thread),
+                next_id: 0,
+            };
+
+            let result = client.request::<Initialize>(InitializeParams::default());
+            assert!(result.capabilities.hover_provider.is_some());
+            client.notify::<Initialized>(lsp_types::InitializedParams {});
+
+            client
+        }
+
+        fn request<R: LspRequest>(&mut self, params: R::Params) -> R::Result {
+            self.next_id += 1;
+            let req = Request::new(self.next_id.into(), R::METHOD.to_owned(), params);
+            self.connection.sender.send(req.into()).expect("send");
+
+            loop {
+                match self.connection.receiver.recv().expect("receive") {
+                    Message::Response(resp) => {
+                        assert!(resp.error.is_none(), "{:?}", resp.error);
+                        return serde_json::from_value(resp.result.unwrap_or_default())
+                            .expect("result");
+                    }
+                    _ => continue,
+                }
+            }
+        }
+
+        fn notify<N: LspNotification>(&self, params: N::Params) {
+            let not = Notification::new(N::METHOD.to_owned(), params);
+            self.connection.sender.send(not.into()).expect("send");
+        }
+
+        fn receive_diagnostics(&self) -> PublishDiagnosticsParams {
+            loop {
+                if let Message::Notification(not) =
+                    self.connection.receiver.recv().expect("receive")
+                {
+                    if not.method == PublishDiagnostics::METHOD {
+                        return serde_json::from_value(not.params).expect("params");
+                    }
+                }
+            }
+        }
+
+        fn open(&self, uri: &Url, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "aidl".to_owned(),
+                    version: 1,
+                    text: text.to_owned(),
+                },
+            });
+            self.receive_diagnostics()
+        }
+
+        fn shutdown(mut self) {
+            self.request::<Shutdown>(());
+            self.notify::<Exit>(());
+            if let Some(thread) = self.thread.take() {
+                thread.join().expect("join").expect("server result");
+            }
+        }
+    }
+
+    fn position_params(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position: Position { line, character },
+        }
+    }
+
+    fn uri(s: &str) -> Url {
+        Url::parse(s).expect("url")
+    }
+
+    const IFOO: &str = r#"package test;
+
+import test.sub.Bar;
+
+/** The foo interface */
+interface IFoo {
+    /** Do foo */
+    void foo(in Bar bar);
+    Bar getBar();
+}
+"#;
+
+    const BAR: &str = "package test.sub;\nparcelable Bar { int i; }\n";
+
+    #[test]
+    fn test_diagnostics() {
+        let client = Client::start();
+        let foo_uri = uri("file:///project/test/IFoo.aidl");
+
+        // Unresolved import (Bar has not been opened yet)
+        let diagnostics = client.open(&foo_uri, IFOO);
+        assert_eq!(diagnostics.uri, foo_uri);
+        assert_eq!(diagnostics.diagnostics.len(), 1);
+        assert!(diagnostics
+            .diagnostics
+            .iter()
+            .any(|d| d.code == Some(NumberOrString::String("AIDL0008".to_owned()))));
+
+        // Bar is now known
+        let bar_uri = uri("file:///project/test/sub/Bar.aidl");
+        client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                uri: bar_uri.clone(),
+                language_id: "aidl".to_owned(),
+                version: 1,
+                text: BAR.to_owned(),
+            },
+        });
+        let mut published = HashMap::new();
+        for _ in 0..2 {
+            let params = client.receive_diagnostics();
+            published.insert(params.uri, params.diagnostics);
+        }
+        assert_eq!(published[&foo_uri], Vec::new());
+        assert_eq!(published[&bar_uri], Vec::new());
+
+        // Incremental change: `int` -> `List`
+        client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: bar_uri.clone(),
+                version: 2,
+            },
+            content_changes: Vec::from([TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range {
+                    start: Position::new(1, 17),
+                    end: Position::new(1, 20),
+                }),
+                range_length: None,
+                text: "List".to_owned(),
+            }]),
+        });
+        let mut published = HashMap::new();
+        for _ in 0..2 {
+            let params = client.receive_diagnostics();
+            published.insert(params.uri, params.diagnostics);
+        }
+        let bar_diagnostics = &published[&bar_uri];
+        assert_eq!(bar_diagnostics.len(), 1);
+        assert_eq!(
+            bar_diagnostics[0].code,
+            Some(NumberOrString::String("AIDL0013".to_owned()))
+        );
+        assert_eq!(
+            bar_diagnostics[0].range,
+            lsp_types::Range::new(Position::new(1, 17), Position::new(1, 21))
+        );
+
+        // Close Bar => unresolved again
+        client.notify::<DidCloseTextDocument>(DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier {
+                uri: bar_uri.clone(),
+            },
+        });
+        assert_eq!(client.receive_diagnostics().diagnostics, Vec::new());
+        assert_eq!(client.receive_diagnostics().diagnostics.len(), 1);
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_invalid_notification() {
+        let client = Client::start();
+
+        // Missing `textDocument`: the error is shown and the server keeps running
+        let not = Notification::new(
+            DidOpenTextDocument::METHOD.to_owned(),
+            serde_json::json!({ "foo": 1 }),
+        );
+        client.connection.sender.send(not.into()).expect("send");
+        let message = loop {
+            if let Message::Notification(not) = client.connection.receiver.recv().expect("receive")
+            {
+                if not.method == ShowMessage::METHOD {
+                    break serde_json::from_value::<ShowMessageParams>(not.params).expect("params");
+                }
+            }
+        };
+        assert_eq!(message.typ, MessageType::ERROR);
+        assert!(message.message.contains("textDocument/didOpen"));
+
+        let bar_uri = uri("file:///project/test/sub/Bar.aidl");
+        assert_eq!(client.open(&bar_uri, BAR).diagnostics, Vec::new());
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut client = Client::start();
+        let foo_uri = uri("file:///project/test/IFoo.aidl");
+        let bar_uri = uri("file:///project/test/sub/Bar.aidl");
+        client.open(&bar_uri, BAR);
+        client.open(&foo_uri, IFOO);
+        client.receive_diagnostics();
+
+        // Hover on method
+        let hover = client
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: position_params(&foo_uri, 7, 10),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .expect("hover");
+        assert_eq!(
+            hover.contents,
+            HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: "```aidl\nvoid foo(in Bar bar)\n```\n\nDo foo\n\nTransaction code: `0` (implicit)\n\nInserting a method without explicit code before this one would renumber `foo`, `getBar`".to_owned(),
+            })
+        );
+
+        // Hover on method with mixed transaction codes => no (implicit) code
+        let mixed_uri = uri("file:///project/test/IMixed.aidl");
+        client.open(
+            &mixed_uri,
+            "package test;\ninterface IMixed {\n    void a();\n    void b() = 1;\n}\n",
+        );
+        let hover = client
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: position_params(&mixed_uri, 2, 10),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .expect("hover");
+        assert!(
+            matches!(hover.contents, HoverContents::Markup(m) if m.value == "```aidl\nvoid a()\n```")
+        );
+
+        // Hover on type => resolved item
+        let hover = client
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: position_params(&foo_uri, 8, 5),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .expect("hover");
+        assert!(
+            matches!(hover.contents, HoverContents::Markup(m) if m.value == "```aidl\nparcelable Bar\n```")
+        );
+
+        // Document symbols
+        let symbols = client
+            .request::<DocumentSymbolRequest>(DocumentSymbolParams {
+                text_document: TextDocumentIdentifier {
+                    uri: foo_uri.clone(),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .expect("symbols");
+        let DocumentSymbolResponse::Nested(symbols) = symbols else {
+            panic!("nested symbols expected");
+        };
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].name, "IFoo");
+        assert_eq!(symbols[0].kind, SymbolKind::INTERFACE);
+        let children = symbols[0].children.as_ref().expect("children");
+        assert_eq!(
+            children.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+            ["foo", "getBar"]
+        );
+
+        // Go to definition (from type and from import)
+        let bar_location = Location {
+            uri: bar_uri.clone(),
+            range: lsp_types::Range::new(Position::new(1, 11), Position::new(1, 14)),
+        };
+        for (line, character) in [(7, 17), (2, 17)] {
+            let definition = client.request::<GotoDefinition>(GotoDefinitionParams {
+                text_document_position_params: position_params(&foo_uri, line, character),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            });
+            assert_eq!(
+                definition,
+                Some(GotoDefinitionResponse::Scalar(bar_location.clone()))
+            );
+        }
+
+        // Find references (from declaration)
+        let references = client
+            .request::<References>(ReferenceParams {
+                text_document_position: position_params(&bar_uri, 1, 12),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                context: ReferenceContext {
+                    include_declaration: true,
+                },
+            })
+            .expect("references");
+        let lines: Vec<(&str, u32)> = references
+            .iter()
+            .map(|l| (l.uri.path(), l.range.start.line))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("/project/test/sub/Bar.aidl", 1),
+                ("/project/test/IFoo.aidl", 2),
+                ("/project/test/IFoo.aidl", 7),
+                ("/project/test/IFoo.aidl", 8),
+            ]
+        );
+
+        // Rename (from type)
+        let edit = client
+            .request::<Rename>(RenameParams {
+                text_document_position: position_params(&foo_uri, 7, 17),
+                new_name: "Baz".to_owned(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .expect("edit");
+        let changes = edit.changes.expect("changes");
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[&foo_uri].len(), 3);
+        assert_eq!(
+            changes[&bar_uri],
+            [TextEdit {
+                range: lsp_types::Range::new(Position::new(1, 11), Position::new(1, 14)),
+                new_text: "Baz".to_owned(),
+            }]
+        );
+
+        // Completion
+        let completion = client.request::<Completion>(CompletionParams {
+            text_document_position: position_params(&foo_uri, 8, 0),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: None,
+        });
+        let Some(CompletionResponse::Array(items)) = completion else {
+            panic!("completion items expected");
+        };
+        assert!(items.iter().any(|i| i.label == "int"));
+        assert!(items.iter().any(|i| i.label == "IBinder"));
+        assert!(items
+            .iter()
+            .any(|i| i.label == "Bar" && i.detail.as_deref() == Some("test.sub.Bar")));
+
+        // Completion (in the middle of a word)
+        let completion = client.request::<Completion>(CompletionParams {
+            text_document_position: position_params(&foo_uri, 7, 18),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: None,
+        });
+        let Some(CompletionResponse::Array(items)) = completion else {
+            panic!("completion items expected");
+        };
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "Bar");
+        assert_eq!(
+            items[0].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: lsp_types::Range::new(Position::new(7, 16), Position::new(7, 18)),
+                new_text: "Bar".to_owned(),
+            }))
+        );
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_to_offset() {
+        let content = "ab\né😀x\n";
+        assert_eq!(to_offset(content, &Position::new(0, 1)), 1);
+        assert_eq!(to_offset(content, &Position::new(0, 10)), 2);
+        assert_eq!(to_offset(content, &Position::new(1, 1)), 5);
+        assert_eq!(to_offset(content, &Position::new(1, 3)), 9);
+        assert_eq!(to_offset(content, &Position::new(5, 0)), content.len());
+
+        let position = ast::Position {
+            offset: 9,
+            line_col: (2, 3),
+        };
+        assert_eq!(to_lsp_position(&position, content), Position::new(1, 3));
+    }
+
+    #[test]
+    fn test_related_info_in_other_file() {
+        let (old_content, content) = crate::compat::CHANGED_TRANSACT_CODE;
+        let results = crate::compat::check_changed_transact_code("/");
+        let diagnostics = &results["/new/IFoo.aidl"].diagnostics;
+
+        let new_uri = uri("file:///new/IFoo.aidl");
+        let old_uri = uri("file:///old/IFoo.aidl");
+        let mut contents = HashMap::from([(new_uri.clone(), content.to_owned())]);
+        let get_related = |contents: &HashMap<Url, String>| {
+            let d = to_lsp_diagnostic(&diagnostics[0], &new_uri, contents);
+            d.related_information
+                .expect("related information")
+                .remove(0)
+        };
+
+        // Not loaded: from the line and column
+        let related = get_related(&contents);
+        assert_eq!(related.location.uri, old_uri);
+        assert_eq!(related.location.range.start, Position::new(0, 33));
+
+        // Loaded
+        contents.insert(old_uri, old_content.to_owned());
+        assert_eq!(
+            get_related(&contents).location.range.end,
+            Position::new(0, 34)
+        );
+    }
+}
@@ -7,7 +7,11 @@ use std::{
 };
 
 use crate::ast;
+use crate::completion;
+use crate::cst;
 use crate::diagnostic::Diagnostic;
+use crate::index::ProjectIndex;
+use crate::lint::{self, ValidationConfig};
 use crate::rules;
 use crate::validation;
 
@@ -64,6 +68,8 @@ where
     ID: Eq + Hash + Clone + Debug,
 {
     lalrpop_results: HashMap<ID, ParseFileResult<ID>>,
+    allow_comments: HashMap<ID, Vec<lint::AllowComment>>,
+    contents: HashMap<ID, String>,
 }
 
 /// The parse result of 1 file with its corresponding ID as given via
@@ -76,6 +82,21 @@ where
     pub id: ID,
     pub ast: Option<ast::Aidl>,
     pub diagnostics: Vec<Diagnostic>,
+
+    /// Diagnostics which have been suppressed, either via ValidationConfig or
+    /// in the source (`@SuppressWarnings(...)` or `// aidl-lint: allow(...)`)
+    pub suppressed_diagnostics: Vec<Diagnostic>,
+}
+
+impl<ID> ParseFileResult<ID>
+where
+    ID: Eq + Hash + Clone + Debug + AsRef<Path>,
+{
+    /// Name of the file (the path given as ID), e.g. used to display or export the
+    /// diagnostics
+    pub fn get_file_name(&self) -> String {
+        self.id.as_ref().display().to_string()
+    }
 }
 
 impl<ID> Parser<ID>
@@ -86,6 +107,8 @@ where
     pub fn new() -> Self {
         Parser {
             lalrpop_results: HashMap::new(),
+            allow_comments: HashMap::new(),
+            contents: HashMap::new(),
         }
     }
 
@@ -96,55 +119,141 @@ where
     /// Note: if a content with the same id already exists, the old content will be replaced.
     pub fn add_content(&mut self, id: ID, content: &str) {
         let lookup = line_col::LineColLookup::new(content);
-        let mut diagnostics = Vec::new();
-
-        let rule_result =
-            rules::aidl::OptAidlParser::new().parse(&lookup, &mut diagnostics, content);
-
-        let lalrpop_result = match rule_result {
-            Ok(file) => ParseFileResult {
-                id: id.clone(),
-                ast: file,
-                diagnostics,
-            },
-            Err(e) => {
-                // Append the parse error to the diagnostics
-                if let Some(diagnostic) = Diagnostic::from_parse_error(&lookup, e) {
-                    diagnostics.push(diagnostic)
-                }
-
-                ParseFileResult {
-                    id: id.clone(),
-                    ast: None,
-                    diagnostics,
-                }
-            }
+        let tokens = cst::lex_with_lookup(content, &lookup);
+        let (ast, diagnostics) = parse_tokens(content, &tokens, &lookup);
+
+        // The comments are also needed when the content could not be parsed
+        let allow_comments = match &ast {
+            Some(ast) => lint::parse_allow_comments(content, &ast.comments),
+            None => lint::parse_allow_comments(content, &rules::get_comments(&tokens)),
         };
 
+        let lalrpop_result = ParseFileResult {
+            id: id.clone(),
+            ast,
+            diagnostics,
+            suppressed_diagnostics: Vec::new(),
+        };
+
+        self.allow_comments.insert(id.clone(), allow_comments);
+        self.contents.insert(id.clone(), content.to_owned());
         self.lalrpop_results.insert(id, lalrpop_result);
     }
 
     /// Remove the file with the given key
     pub fn remove_content(&mut self, id: ID) {
+        self.allow_comments.remove(&id);
+        self.contents.remove(&id);
         self.lalrpop_results.remove(&id);
     }
 
     /// Validate the results of all files previously added to the parser and return the
     /// collected results (AST + diagnostics)
     pub fn validate(&self) -> HashMap<ID, ParseFileResult<ID>> {
+        self.validate_with_config(&ValidationConfig::default())
+    }
+
+    /// Same as Parser::validate() but the diagnostics are adjusted according to the
+    /// given configuration and to the in-source suppressions
+    pub fn validate_with_config(
+        &self,
+        config: &ValidationConfig,
+    ) -> HashMap<ID, ParseFileResult<ID>> {
+        self.validate_with_index(config).0
+    }
+
+    /// Same as Parser::validate_with_config() but also return the index of the items
+    /// of the project (e.g. for navigation or renaming), built during the validation
+    pub fn validate_with_index(
+        &self,
+        config: &ValidationConfig,
+    ) -> (HashMap<ID, ParseFileResult<ID>>, ProjectIndex<ID>) {
         let keys = self.collect_item_keys();
-        validation::validate(keys, self.lalrpop_results.clone())
+        let mut index = ProjectIndex::default();
+        let mut results = validation::validate(
+            keys,
+            self.lalrpop_results.clone(),
+            &self.contents,
+            &mut index,
+        );
+
+        for (id, fr) in results.iter_mut() {
+            let allow_comments = self.allow_comments.get(id).map_or(&[][..], Vec::as_slice);
+            lint::apply(fr, config, allow_comments);
+        }
+
+        (results, index)
+    }
+
+    /// Suggestions for the (usually partially typed) content of the file with the
+    /// given key at the given position (1-based line and column)
+    ///
+    /// The suggestions depend on the context, e.g. types after `in `, item names after
+    /// `import `, enum elements after `MyEnum.` or annotations after `@`. They are based
+    /// on the items of all the files which have been parsed successfully.
+    ///
+    /// Example:
+    /// ```
+    /// use aidl_parser::Parser;
+    ///
+    /// let mut parser = Parser::new();
+    /// parser.add_content("id1", "package p; enum Color { RED, GREEN }");
+    /// parser.add_content("id2", "package p; interface IFoo { const int C = Color.");
+    ///
+    /// let items = parser.complete(&"id2", (1, 49));
+    /// let labels: Vec<_> = items.iter().map(|i| i.label.as_str()).collect();
+    /// assert_eq!(labels, ["RED", "GREEN"]);
+    /// ```
+    pub fn complete(&self, id: &ID, line_col: (usize, usize)) -> Vec<completion::CompletionItem> {
+        let content = match self.contents.get(id) {
+            Some(content) => content,
+            None => return Vec::new(),
+        };
+        let asts = self.lalrpop_results.values().flat_map(|fr| &fr.ast);
+
+        completion::complete(content, line_col, asts)
     }
 
     fn collect_item_keys(&self) -> HashMap<ast::ItemKey, ast::ResolvedItemKind> {
         self.lalrpop_results
             .values()
             .flat_map(|fr| &fr.ast)
-            .map(|f| (f.get_key(), f.item.get_kind()))
+            .flat_map(|f| f.get_all_keys())
             .collect()
     }
 }
 
+/// Parse the content of 1 file from its tokens (without validation)
+///
+/// The tokens are the ones of the CST lexer (see [`cst::lex`]), split from the content.
+pub(crate) fn parse_tokens(
+    content: &str,
+    tokens: &[cst::Token],
+    lookup: &line_col::LineColLookup,
+) -> (Option<ast::Aidl>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let grammar_tokens = rules::get_grammar_tokens(content, tokens);
+
+    match rules::aidl::OptAidlParser::new().parse(lookup, &mut diagnostics, content, grammar_tokens)
+    {
+        Ok(ast) => {
+            let ast = ast.map(|ast| ast::Aidl {
+                comments: rules::get_comments(tokens),
+                ..ast
+            });
+            (ast, diagnostics)
+        }
+        Err(e) => {
+            // Append the parse error to the diagnostics
+            if let Some(diagnostic) = Diagnostic::from_parse_error(lookup, e) {
+                diagnostics.push(diagnostic)
+            }
+
+            (None, diagnostics)
+        }
+    }
+}
+
 impl Parser<PathBuf> {
     /// Add a file to the parser and use its path as key.
     ///
@@ -0,0 +1,501 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+
+use crate::ast;
+use crate::diagnostic::Fix;
+use crate::index::ProjectIndex;
+use crate::parser::ParseFileResult;
+use crate::symbol::{ConstOwner, FieldOwner, Symbol};
+
+/// Keywords of AIDL and of the Java backend which cannot be used as names
+pub const RESERVED_KEYWORDS: &[&str] = &[
+    // AIDL
+    "parcelable",
+    "import",
+    "package",
+    "in",
+    "out",
+    "inout",
+    "cpp_header",
+    "ndk_header",
+    "rust_type",
+    "const",
+    "true",
+    "false",
+    "interface",
+    "oneway",
+    "enum",
+    "union",
+    "void",
+    "boolean",
+    "byte",
+    "char",
+    "short",
+    "int",
+    "long",
+    "float",
+    "double",
+    "String",
+    "CharSequence",
+    "List",
+    "Map",
+    // Java
+    "abstract",
+    "assert",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "continue",
+    "default",
+    "do",
+    "else",
+    "extends",
+    "final",
+    "finally",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "instanceof",
+    "native",
+    "new",
+    "null",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "try",
+    "volatile",
+    "while",
+];
+
+/// Edits (as fixes which replace the old name) for each affected file
+pub type WorkspaceEdit<ID> = HashMap<ID, Vec<Fix>>;
+
+/// Reason why a rename is refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    /// The new name is not a valid identifier
+    InvalidName(String),
+    /// The new name is a reserved keyword
+    ReservedKeyword(String),
+    /// The new name is already used in the same scope
+    Collision(String),
+    /// The symbol cannot be renamed (e.g. package, primitive type or unknown item)
+    NotRenamable,
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::InvalidName(name) => write!(f, "`{name}` is not a valid name"),
+            RenameError::ReservedKeyword(name) => write!(f, "`{name}` is a reserved keyword"),
+            RenameError::Collision(name) => write!(f, "`{name}` is already defined"),
+            RenameError::NotRenamable => write!(f, "The symbol cannot be renamed"),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {}
+
+/// Rename the given symbol (which must come from the results of the file `id`) and
+/// return the edits of all the affected files
+///
+/// The results and the index are the ones returned by Parser::validate_with_index().
+///
+/// Items, consts and enum elements are renamed together with all their references
+/// (imports, types and constant expressions). Methods, fields and arguments cannot
+/// be referenced, so only their declaration is renamed. A type or an import renames
+/// the referenced item.
+///
+/// Note: the files are not renamed, even if the name of their item changes.
+///
+/// Example:
+/// ```
+/// use aidl_parser::{diagnostic, rename, traverse, Parser};
+///
+/// let content = "package p; enum Color { RED, GREEN = RED + 1 }";
+/// let mut parser = Parser::new();
+/// parser.add_content("id", content);
+/// let (results, index) = parser.validate_with_index(&Default::default());
+///
+/// let ast = results["id"].ast.as_ref().expect("ast");
+/// let symbol = traverse::find_symbol(ast, traverse::SymbolFilter::All, |s| {
+///     s.get_name().as_deref() == Some("RED")
+/// })
+/// .expect("symbol");
+///
+/// let edits = rename::rename(&results, &index, &"id", &symbol, "BLUE").expect("edits");
+/// assert_eq!(
+///     diagnostic::apply_fixes(content, &edits["id"]),
+///     "package p; enum Color { BLUE, GREEN = BLUE + 1 }"
+/// );
+/// ```
+pub fn rename<ID>(
+    results: &HashMap<ID, ParseFileResult<ID>>,
+    index: &ProjectIndex<ID>,
+    id: &ID,
+    symbol: &Symbol,
+    new_name: &str,
+) -> Result<WorkspaceEdit<ID>, RenameError>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    check_name(new_name)?;
+
+    // Items can be referenced by their simple name (via imports or within their package)
+    let is_item = !matches!(symbol, Symbol::Const(..) | Symbol::EnumElement(..));
+
+    let key = match symbol {
+        Symbol::Package(_) => return Err(RenameError::NotRenamable),
+        Symbol::Import(i) => i.get_qualified_name(),
+        Symbol::Type(t) => match &t.kind {
+            ast::TypeKind::ResolvedItem(key, _) => key.clone(),
+            _ => return Err(RenameError::NotRenamable),
+        },
+        Symbol::Method(m, i) => {
+            let names = i
+                .elements
+                .iter()
+                .filter_map(|el| el.as_method())
+                .map(|m| &m.name);
+            return rename_declaration(id, &m.name, &m.symbol_range, names, new_name);
+        }
+        Symbol::Field(f, owner) => {
+            let names = get_field_names(owner);
+            return rename_declaration(id, &f.name, &f.symbol_range, names, new_name);
+        }
+        Symbol::Arg(a, m) => {
+            let name = a.name.as_ref().ok_or(RenameError::NotRenamable)?;
+            let names = m.args.iter().filter_map(|a| a.name.as_ref());
+            return rename_declaration(id, name, &a.symbol_range, names, new_name);
+        }
+        Symbol::Const(c, ConstOwner::Parcelable(p)) => {
+            check_collision(get_field_names(&FieldOwner::Parcelable(p)), new_name)?;
+            get_declared_key(index, id, &c.symbol_range)?
+        }
+        Symbol::Const(c, ConstOwner::Union(u)) => {
+            check_collision(get_field_names(&FieldOwner::Union(u)), new_name)?;
+            get_declared_key(index, id, &c.symbol_range)?
+        }
+        Symbol::Interface(..)
+        | Symbol::Parcelable(..)
+        | Symbol::Union(..)
+        | Symbol::Enum(..)
+        | Symbol::Const(..)
+        | Symbol::EnumElement(..) => get_declared_key(index, id, symbol.get_range())?,
+    };
+
+    let definition = index
+        .get_definition(&key)
+        .ok_or(RenameError::NotRenamable)?;
+    let old_name = key.rsplit('.').next().unwrap_or(&key);
+    if old_name == new_name {
+        return Ok(WorkspaceEdit::new());
+    }
+
+    // Same key (e.g. `pkg.NewName` or `pkg.Item.NEW_NAME`)
+    let new_key = match key.rsplit_once('.') {
+        Some((parent, _)) => format!("{parent}.{new_name}"),
+        None => new_name.to_owned(),
+    };
+    if index.get_definition(&new_key).is_some() {
+        return Err(RenameError::Collision(new_name.to_owned()));
+    }
+
+    let references = index.references_to(&key);
+
+    // Same name imported into a file which references the item, or item with the same
+    // name in the package of that file
+    for location in references {
+        let ast = match results.get(&location.id).and_then(|fr| fr.ast.as_ref()) {
+            Some(ast) => ast,
+            None => continue,
+        };
+        let is_imported = ast.imports.iter().any(|i| i.name == new_name);
+        let is_in_package = is_item
+            && index
+                .get_definition(&format!("{}.{new_name}", ast.package.name))
+                .is_some();
+        if is_imported || is_in_package {
+            return Err(RenameError::Collision(new_name.to_owned()));
+        }
+    }
+
+    let mut edits = WorkspaceEdit::new();
+    for location in [definition].into_iter().chain(references) {
+        edits
+            .entry(location.id.clone())
+            .or_insert_with(Vec::new)
+            .push(create_fix(old_name, new_name, &location.range));
+    }
+
+    Ok(edits)
+}
+
+fn check_name(name: &str) -> Result<(), RenameError> {
+    let mut chars = name.chars();
+    let is_valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !is_valid {
+        Err(RenameError::InvalidName(name.to_owned()))
+    } else if RESERVED_KEYWORDS.contains(&name) {
+        Err(RenameError::ReservedKeyword(name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_collision<'a, I>(names: I, new_name: &str) -> Result<(), RenameError>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    if names.into_iter().any(|n| n == new_name) {
+        Err(RenameError::Collision(new_name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+// Key of the item, const or enum element declared at the given range
+fn get_declared_key<ID>(
+    index: &ProjectIndex<ID>,
+    id: &ID,
+    range: &ast::Range,
+) -> Result<ast::ItemKey, RenameError>
+where
+    ID: Eq + Hash + Clone + Debug,
+{
+    index
+        .get_item_key_at(id, range.start.line_col)
+        .filter(|key| index.get_definition(key).is_some_and(|l| l.range == *range))
+        .map(str::to_owned)
+        .ok_or(RenameError::NotRenamable)
+}
+
+// Elements which cannot be referenced (methods, fields and args)
+fn rename_declaration<'a, ID, I>(
+    id: &ID,
+    old_name: &str,
+    range: &ast::Range,
+    sibling_names: I,
+    new_name: &str,
+) -> Result<WorkspaceEdit<ID>, RenameError>
+where
+    ID: Eq + Hash + Clone + Debug,
+    I: IntoIterator<Item = &'a String>,
+{
+    if old_name == new_name {
+        return Ok(WorkspaceEdit::new());
+    }
+    check_collision(sibling_names, new_name)?;
+
+    Ok(WorkspaceEdit::from([(
+        id.clone(),
+        Vec::from([create_fix(old_name, new_name, range)]),
+    )]))
+}
+
+// Names of the fields and consts
+fn get_field_names<'a>(owner: &FieldOwner<'a>) -> Vec<&'a String> {
+    match owner {
+        FieldOwner::Parcelable(p) => p
+            .elements
+            .iter()
+            .map(|el| match el {
+                ast::ParcelableElement::Const(c) => &c.name,
+                ast::ParcelableElement::Field(f) => &f.name,
+            })
+            .collect(),
+        FieldOwner::Union(u) => u
+            .elements
+            .iter()
+            .map(|el| match el {
+                ast::UnionElement::Const(c) => &c.name,
+                ast::UnionElement::Field(f) => &f.name,
+            })
+            .collect(),
+    }
+}
+
+fn create_fix(old_name: &str, new_name: &str, range: &ast::Range) -> Fix {
+    Fix {
+        message: format!("Rename `{old_name}` to `{new_name}`"),
+        range: range.clone(),
+        replacement: new_name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::apply_fixes;
+    use crate::traverse::{self, SymbolFilter};
+
+    const IFOO: &str = r#"package a;
+import b.Bar;
+import b.Color;
+interface IFoo {
+    Bar foo(in List<Bar> bars, in Bar.Inner inner, Color c);
+    const int LIMIT = Bar.MAX + Color.RED;
+    void bar();
+}"#;
+
+    const BAR: &str = r#"package b;
+parcelable Bar {
+    parcelable Inner { Bar bar; }
+    const int MAX = 3;
+    int count = MAX;
+}"#;
+
+    const COLOR: &str = "package b;\nenum Color { RED, GREEN = RED + 1 }";
+
+    fn rename_symbol(
+        id: &'static str,
+        name: &str,
+        new_name: &str,
+    ) -> Result<HashMap<&'static str, String>, RenameError> {
+        let mut parser = crate::Parser::new();
+        let contents = HashMap::from([
+            ("a/IFoo.aidl", IFOO),
+            ("b/Bar.aidl", BAR),
+            ("b/Color.aidl", COLOR),
+        ]);
+        for (id, content) in &contents {
+            parser.add_content(*id, content);
+        }
+        let (results, index) = parser.validate_with_index(&Default::default());
+
+        let ast = results[id].ast.as_ref().expect("ast");
+        let symbol = traverse::find_symbol(ast, SymbolFilter::All, |s| {
+            s.get_name().as_deref() == Some(name)
+        })
+        .expect("symbol");
+
+        let edits = rename(&results, &index, &id, &symbol, new_name)?;
+        Ok(edits
+            .into_iter()
+            .map(|(id, fixes)| (id, apply_fixes(contents[id], &fixes)))
+            .collect())
+    }
+
+    #[test]
+    fn test_rename_item() -> Result<(), RenameError> {
+        let renamed = rename_symbol("b/Bar.aidl", "Bar", "Baz")?;
+        assert_eq!(renamed.len(), 2);
+        insta::assert_snapshot!(renamed["a/IFoo.aidl"], @r###"
+        package a;
+        import b.Baz;
+        import b.Color;
+        interface IFoo {
+            Baz foo(in List<Baz> bars, in Baz.Inner inner, Color c);
+            const int LIMIT = Baz.MAX + Color.RED;
+            void bar();
+        }
+        "###);
+        insta::assert_snapshot!(renamed["b/Bar.aidl"], @r###"
+        package b;
+        parcelable Baz {
+            parcelable Inner { Baz bar; }
+            const int MAX = 3;
+            int count = MAX;
+        }
+        "###);
+
+        // Nested item
+        let renamed = rename_symbol("b/Bar.aidl", "Inner", "Nested")?;
+        assert!(renamed["a/IFoo.aidl"].contains("in Bar.Nested inner"));
+        assert!(renamed["b/Bar.aidl"].contains("parcelable Nested {"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_elements() -> Result<(), RenameError> {
+        // Enum element
+        let renamed = rename_symbol("b/Color.aidl", "RED", "BLUE")?;
+        assert_eq!(
+            renamed["b/Color.aidl"],
+            "package b;\nenum Color { BLUE, GREEN = BLUE + 1 }"
+        );
+        assert!(renamed["a/IFoo.aidl"].contains("Bar.MAX + Color.BLUE;"));
+
+        // Const
+        let renamed = rename_symbol("b/Bar.aidl", "MAX", "MAXIMUM")?;
+        assert!(renamed["b/Bar.aidl"].contains("const int MAXIMUM = 3;"));
+        assert!(renamed["b/Bar.aidl"].contains("int count = MAXIMUM;"));
+        assert!(renamed["a/IFoo.aidl"].contains("Bar.MAXIMUM + Color.RED;"));
+
+        // Method (declaration only)
+        let renamed = rename_symbol("a/IFoo.aidl", "foo", "fooBar")?;
+        assert_eq!(renamed.len(), 1);
+        assert!(renamed["a/IFoo.aidl"].contains("Bar fooBar(in"));
+
+        // Same name
+        assert_eq!(rename_symbol("b/Color.aidl", "RED", "RED")?, HashMap::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_errors() {
+        let err = |id, name, new_name| rename_symbol(id, name, new_name).unwrap_err();
+
+        assert_eq!(
+            err("b/Bar.aidl", "Bar", "1Bar"),
+            RenameError::InvalidName("1Bar".to_owned())
+        );
+        assert_eq!(
+            err("b/Bar.aidl", "Bar", "Bar.Baz"),
+            RenameError::InvalidName("Bar.Baz".to_owned())
+        );
+        assert_eq!(
+            err("b/Bar.aidl", "Bar", "interface"),
+            RenameError::ReservedKeyword("interface".to_owned())
+        );
+        assert_eq!(
+            err("b/Color.aidl", "RED", "class"),
+            RenameError::ReservedKeyword("class".to_owned())
+        );
+
+        // Collisions
+        assert_eq!(
+            err("b/Bar.aidl", "Bar", "Color"),
+            RenameError::Collision("Color".to_owned())
+        );
+        assert_eq!(
+            err("b/Color.aidl", "RED", "GREEN"),
+            RenameError::Collision("GREEN".to_owned())
+        );
+        assert_eq!(
+            err("b/Bar.aidl", "MAX", "count"),
+            RenameError::Collision("count".to_owned())
+        );
+        // Item of the package of a referencing file (`a.IFoo`)
+        assert_eq!(
+            err("b/Bar.aidl", "Bar", "IFoo"),
+            RenameError::Collision("IFoo".to_owned())
+        );
+        assert_eq!(
+            err("a/IFoo.aidl", "foo", "bar"),
+            RenameError::Collision("bar".to_owned())
+        );
+
+        // Not renamable
+        assert_eq!(err("a/IFoo.aidl", "a", "b"), RenameError::NotRenamable);
+    }
+}
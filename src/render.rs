@@ -0,0 +1,158 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::path::Path;
+
+use codespan_reporting::diagnostic::{self as cs, Label, LabelStyle};
+use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term::{self, termcolor};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::parser::ParseFileResult;
+
+/// Output style of the rendered diagnostics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// Colored output (ANSI escape codes), e.g. for terminals
+    Color,
+    /// Plain text, e.g. for log files
+    Plain,
+}
+
+/// Render the diagnostics of a file in a human-readable form (similar to rustc)
+///
+/// The `source` must be the content which has been given to the parser.
+///
+/// Example:
+/// ```
+/// use aidl_parser::Parser;
+/// use aidl_parser::render::{self, Style};
+///
+/// let content = "package test; interface Test { void hello(MyType t); }";
+/// let mut parser = Parser::new();
+/// parser.add_content("test.aidl", content);
+/// let results = parser.validate();
+///
+/// let output = render::render(&results["test.aidl"], content, Style::Plain);
+/// assert!(output.starts_with("error[AIDL0005]: Unknown type `MyType`"));
+/// ```
+pub fn render<ID>(result: &ParseFileResult<ID>, source: &str, style: Style) -> String
+where
+    ID: Eq + Hash + Clone + Debug + AsRef<Path>,
+{
+    render_diagnostics(&result.get_file_name(), source, &result.diagnostics, style)
+}
+
+/// Render the given diagnostics of a file (see [`render`])
+pub fn render_diagnostics(
+    file_name: &str,
+    source: &str,
+    diagnostics: &[Diagnostic],
+    style: Style,
+) -> String {
+    let file = SimpleFile::new(file_name, source);
+    let config = term::Config::default();
+    let mut buffer = match style {
+        Style::Color => termcolor::Buffer::ansi(),
+        Style::Plain => termcolor::Buffer::no_color(),
+    };
+
+    for diagnostic in diagnostics {
+        // Writing into a buffer only fails for invalid ranges
+        let _ = term::emit(
+            &mut buffer,
+            &config,
+            &file,
+            &to_codespan(diagnostic, source),
+        );
+    }
+
+    String::from_utf8_lossy(buffer.as_slice()).into_owned()
+}
+
+fn to_codespan(diagnostic: &Diagnostic, source: &str) -> cs::Diagnostic<()> {
+    let severity = match diagnostic.kind {
+        DiagnosticKind::Error => cs::Severity::Error,
+        DiagnosticKind::Warning => cs::Severity::Warning,
+    };
+
+    // Make sure that the ranges can be displayed (e.g. empty range at the end of the file)
+    let clamp = |offset: usize| offset.min(source.len());
+
+    let primary = Label::new(
+        LabelStyle::Primary,
+        (),
+        clamp(diagnostic.range.start.offset)..clamp(diagnostic.range.end.offset),
+    )
+    .with_message(diagnostic.context_message.clone().unwrap_or_default());
+
+    let secondaries = diagnostic
+        .related_infos
+        .iter()
+        .filter(|info| info.file.is_none())
+        .map(|info| {
+            Label::new(
+                LabelStyle::Secondary,
+                (),
+                clamp(info.range.start.offset)..clamp(info.range.end.offset),
+            )
+            .with_message(&info.message)
+        });
+
+    // The related infos of other files (whose source is not known) are given as notes
+    let other_files = diagnostic.related_infos.iter().filter_map(|info| {
+        let (line, col) = info.range.start.line_col;
+        let file = info.file.as_ref()?;
+        Some(format!("note: {} at {file}:{line}:{col}", info.message))
+    });
+
+    let notes = other_files
+        .chain(diagnostic.hint.iter().map(|hint| format!("help: {hint}")))
+        .collect();
+
+    cs::Diagnostic::new(severity)
+        .with_code(diagnostic.code.as_str())
+        .with_message(&diagnostic.message)
+        .with_labels([primary].into_iter().chain(secondaries).collect())
+        .with_notes(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let content = r#"package test;
+
+oneway interface Test {
+    oneway void hello(MyType t);
+    void bye(List l);
+}
+"#;
+
+        let mut parser = crate::Parser::new();
+        parser.add_content("test.aidl", content);
+        let results = parser.validate();
+
+        let output = render(&results["test.aidl"], content, Style::Plain);
+        insta::assert_snapshot!(output);
+
+        // Same output with ANSI escape codes
+        let colored = render(&results["test.aidl"], content, Style::Color);
+        assert!(colored.contains("\u{1b}["));
+        assert_ne!(colored, output);
+    }
+
+    #[test]
+    fn test_render_related_info_in_other_file() {
+        let results = crate::compat::check_changed_transact_code("");
+        let (_, content) = crate::compat::CHANGED_TRANSACT_CODE;
+
+        let output = render(&results["new/IFoo.aidl"], content, Style::Plain);
+        assert!(output.contains("new/IFoo.aidl:2:23"), "{output}");
+        assert!(
+            output.contains("note: previous definition at old/IFoo.aidl:1:34"),
+            "{output}"
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::path::{Component, Path};
+
+use serde_derive::Serialize;
+
+use crate::ast;
+use crate::diagnostic::{Diagnostic, DiagnosticCode, DiagnosticKind};
+use crate::parser::ParseFileResult;
+
+const TOOL_NAME: &str = "aidl-parser";
+const TOOL_URI: &str = "https://github.com/bwalter/rust-aidl-parser";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Convert the results of Parser::validate() into a SARIF 2.1.0 log
+///
+/// Each diagnostic code is described as a rule of the tool (`AIDL0001`, ...) and the
+/// files are sorted by name, so that the output is stable. The IDs of the files are
+/// their paths: relative paths give relative URIs and absolute paths `file://` URIs.
+pub fn to_sarif<ID>(results: &HashMap<ID, ParseFileResult<ID>>) -> String
+where
+    ID: Eq + Hash + Clone + Debug + AsRef<Path>,
+{
+    let rules = DiagnosticCode::ALL
+        .iter()
+        .map(|code| SarifRule {
+            id: code.as_str(),
+            name: code.name(),
+            short_description: SarifMessage {
+                text: code.explanation().to_owned(),
+            },
+        })
+        .collect();
+
+    let results = get_sorted_diagnostics(results)
+        .into_iter()
+        .map(|(path, d)| (get_uri(path), d))
+        .map(|(uri, d)| SarifResult {
+            rule_id: d.code.as_str(),
+            rule_index: DiagnosticCode::ALL
+                .iter()
+                .position(|c| *c == d.code)
+                .unwrap_or_default(),
+            level: get_severity(&d.kind),
+            message: SarifMessage {
+                text: d.message.clone(),
+            },
+            locations: Vec::from([SarifLocation {
+                id: None,
+                physical_location: SarifPhysicalLocation::new(&uri, &d.range),
+                message: None,
+            }]),
+            related_locations: d
+                .related_infos
+                .iter()
+                .enumerate()
+                .map(|(i, info)| SarifLocation {
+                    id: Some(i),
+                    physical_location: SarifPhysicalLocation::new(
+                        &info
+                            .file
+                            .as_ref()
+                            .map_or(uri.clone(), |f| get_uri(Path::new(f))),
+                        &info.range,
+                    ),
+                    message: Some(SarifMessage {
+                        text: info.message.clone(),
+                    }),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let log = SarifLog {
+        schema: SARIF_SCHEMA,
+        version: "2.1.0",
+        runs: Vec::from([SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: TOOL_NAME,
+                    version: env!("CARGO_PKG_VERSION"),
+                    information_uri: TOOL_URI,
+                    rules,
+                },
+            },
+            // Columns of the AST positions count characters, not UTF-16 code units
+            column_kind: "unicodeCodePoints",
+            results,
+        }]),
+    };
+
+    serde_json::to_string_pretty(&log).unwrap_or_default()
+}
+
+/// Convert the results of Parser::validate() into JSON Lines (one JSON object per
+/// diagnostic and per line)
+///
+/// The files are sorted by name, so that the output is stable.
+pub fn to_json_lines<ID>(results: &HashMap<ID, ParseFileResult<ID>>) -> String
+where
+    ID: Eq + Hash + Clone + Debug + AsRef<Path>,
+{
+    get_sorted_diagnostics(results)
+        .into_iter()
+        .map(|(path, d)| JsonDiagnostic {
+            file: path.display().to_string(),
+            code: d.code.as_str(),
+            name: d.code.name(),
+            severity: get_severity(&d.kind),
+            message: &d.message,
+            range: JsonRange::new(&d.range),
+            context_message: d.context_message.as_deref(),
+            hint: d.hint.as_deref(),
+            related_infos: d
+                .related_infos
+                .iter()
+                .map(|info| JsonRelatedInfo {
+                    file: info.file.as_deref(),
+                    message: &info.message,
+                    range: JsonRange::new(&info.range),
+                })
+                .collect(),
+            fixes: d
+                .fixes
+                .iter()
+                .map(|fix| JsonFix {
+                    message: &fix.message,
+                    range: JsonRange::new(&fix.range),
+                    replacement: &fix.replacement,
+                })
+                .collect(),
+        })
+        .filter_map(|d| serde_json::to_string(&d).ok())
+        .map(|line| line + "\n")
+        .collect()
+}
+
+fn get_sorted_diagnostics<ID>(
+    results: &HashMap<ID, ParseFileResult<ID>>,
+) -> Vec<(&Path, &Diagnostic)>
+where
+    ID: Eq + Hash + Clone + Debug + AsRef<Path>,
+{
+    let mut results: Vec<&ParseFileResult<ID>> = results.values().collect();
+    results.sort_by(|fr1, fr2| fr1.id.as_ref().cmp(fr2.id.as_ref()));
+
+    results
+        .into_iter()
+        .flat_map(|fr| fr.diagnostics.iter().map(move |d| (fr.id.as_ref(), d)))
+        .collect()
+}
+
+// Relative URI reference (with `/` separators) for a relative path, `file://` URI for
+// an absolute path, with the special characters percent-encoded
+fn get_uri(path: &Path) -> String {
+    let segments: Vec<String> = path
+        .components()
+        .filter_map(|c| match c {
+            // e.g. `C:` on Windows
+            Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().into_owned()),
+            Component::RootDir => None,
+            Component::CurDir => Some(".".to_owned()),
+            Component::ParentDir => Some("..".to_owned()),
+            Component::Normal(name) => Some(percent_encode(&name.to_string_lossy())),
+        })
+        .collect();
+
+    if path.has_root() {
+        format!("file:///{}", segments.join("/"))
+    } else {
+        segments.join("/")
+    }
+}
+
+fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn get_severity(kind: &DiagnosticKind) -> &'static str {
+    match kind {
+        DiagnosticKind::Error => "error",
+        DiagnosticKind::Warning => "warning",
+    }
+}
+
+// SARIF 2.1.0 (subset), see https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+#[derive(Serialize)]
+struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRun {
+    tool: SarifTool,
+    column_kind: &'static str,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: &'static str,
+    name: &'static str,
+    short_description: SarifMessage,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<SarifLocation>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    physical_location: SarifPhysicalLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+}
+
+impl SarifPhysicalLocation {
+    fn new(file_name: &str, range: &ast::Range) -> Self {
+        SarifPhysicalLocation {
+            artifact_location: SarifArtifactLocation {
+                uri: file_name.to_owned(),
+            },
+            region: SarifRegion {
+                start_line: range.start.line_col.0,
+                start_column: range.start.line_col.1,
+                end_line: range.end.line_col.0,
+                end_column: range.end.line_col.1,
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+// JSON Lines
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    file: String,
+    code: &'static str,
+    name: &'static str,
+    severity: &'static str,
+    message: &'a str,
+    range: JsonRange,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context_message: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_infos: Vec<JsonRelatedInfo<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixes: Vec<JsonFix<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonRelatedInfo<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    message: &'a str,
+    range: JsonRange,
+}
+
+#[derive(Serialize)]
+struct JsonFix<'a> {
+    message: &'a str,
+    range: JsonRange,
+    replacement: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonRange {
+    start: JsonPosition,
+    end: JsonPosition,
+}
+
+impl JsonRange {
+    fn new(range: &ast::Range) -> Self {
+        JsonRange {
+            start: JsonPosition::new(&range.start),
+            end: JsonPosition::new(&range.end),
+        }
+    }
+}
+
+/// 1-based line and column + 0-based byte offset
+#[derive(Serialize)]
+struct JsonPosition {
+    line: usize,
+    column: usize,
+    offset: usize,
+}
+
+impl JsonPosition {
+    fn new(position: &ast::Position) -> Self {
+        JsonPosition {
+            line: position.line_col.0,
+            column: position.line_col.1,
+            offset: position.offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate() -> HashMap<&'static str, ParseFileResult<&'static str>> {
+        let mut parser = crate::Parser::new();
+        parser.add_content(
+            "b/IFoo.aidl",
+            "package b;\nimport a.Bar;\noneway interface IFoo { oneway void foo(); }",
+        );
+        parser.add_content("a/Bar.aidl", "package a;\nparcelable Bar { List l; }");
+        parser.validate()
+    }
+
+    #[test]
+    fn test_sarif() {
+        let sarif = to_sarif(&validate());
+        let value: serde_json::Value = serde_json::from_str(&sarif).expect("valid json");
+
+        assert_eq!(value["version"], "2.1.0");
+        let run = &value["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "aidl-parser");
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+        assert_eq!(
+            run["tool"]["driver"]["rules"].as_array().map(Vec::len),
+            Some(DiagnosticCode::ALL.len())
+        );
+
+        let results = run["results"].as_array().expect("results");
+        assert_eq!(results.len(), 3);
+
+        // Sorted by file name
+        assert_eq!(results[0]["ruleId"], "AIDL0013");
+        assert_eq!(results[0]["level"], "warning");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "a/Bar.aidl"
+        );
+        assert_eq!(results[1]["ruleId"], "AIDL0007");
+        assert_eq!(results[2]["ruleId"], "AIDL0021");
+        assert_eq!(
+            results[2]["ruleIndex"],
+            DiagnosticCode::ALL
+                .iter()
+                .position(|c| *c == DiagnosticCode::RedundantOneway)
+                .expect("index")
+        );
+
+        insta::assert_snapshot!(serde_json::to_string_pretty(&results).expect("json"));
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let json_lines = to_json_lines(&validate());
+        insta::assert_snapshot!(json_lines);
+
+        for line in json_lines.lines() {
+            let value: serde_json::Value = serde_json::from_str(line).expect("valid json");
+            assert!(value["code"]
+                .as_str()
+                .unwrap_or_default()
+                .starts_with("AIDL"));
+        }
+    }
+
+    #[test]
+    fn test_get_uri() {
+        assert_eq!(get_uri(Path::new("a/Bar.aidl")), "a/Bar.aidl");
+        assert_eq!(get_uri(Path::new("./a/../Bar.aidl")), "./a/../Bar.aidl");
+        assert_eq!(
+            get_uri(Path::new("dir/My File#1.aidl")),
+            "dir/My%20File%231.aidl"
+        );
+        assert_eq!(get_uri(Path::new("/abs/x.aidl")), "file:///abs/x.aidl");
+        assert_eq!(get_uri(Path::new("/abs/é.aidl")), "file:///abs/%C3%A9.aidl");
+    }
+
+    #[test]
+    fn test_related_info_in_other_file() {
+        let results = crate::compat::check_changed_transact_code("");
+
+        let sarif = to_sarif(&results);
+        let value: serde_json::Value = serde_json::from_str(&sarif).expect("valid json");
+        let result = &value["runs"][0]["results"][0];
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "new/IFoo.aidl"
+        );
+        let related = &result["relatedLocations"][0]["physicalLocation"];
+        assert_eq!(related["artifactLocation"]["uri"], "old/IFoo.aidl");
+        assert_eq!(related["region"]["startColumn"], 34);
+
+        let json_lines = to_json_lines(&results);
+        let value: serde_json::Value = serde_json::from_str(&json_lines).expect("valid json");
+        assert_eq!(value["file"], "new/IFoo.aidl");
+        assert_eq!(value["related_infos"][0]["file"], "old/IFoo.aidl");
+    }
+}
@@ -2,22 +2,231 @@ use lalrpop_util::lalrpop_mod;
 
 lalrpop_mod!(#[allow(clippy::all, dead_code, unused_imports)] pub aidl);
 
+use crate::ast;
+use crate::cst::{self, TokenKind};
+use crate::diagnostic::{Diagnostic, DiagnosticCode, DiagnosticKind};
+use crate::evaluation;
+
+/// Member of an interface or parcelable body: either an element or a nested item
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Member<E> {
+    Element(E),
+    NestedItem(ast::Item),
+}
+
+/// Split the members into elements and nested items (and drop the invalid ones)
+pub(crate) fn split_members<E>(members: Vec<Option<Member<E>>>) -> (Vec<E>, Vec<ast::Item>) {
+    let mut elements = Vec::new();
+    let mut nested_items = Vec::new();
+
+    for member in members.into_iter().flatten() {
+        match member {
+            Member::Element(e) => elements.push(e),
+            Member::NestedItem(i) => nested_items.push(i),
+        }
+    }
+
+    (elements, nested_items)
+}
+
+/// Token of the grammar
+///
+/// The grammar has no lexer of its own: it is fed with the tokens of the CST lexer (see
+/// [`cst::lex`](crate::cst::lex)), so that the AST is derived from the same tokens as
+/// the CST. The keywords are split into the groups of terminals used by the grammar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token<'input> {
+    Keyword(&'input str),
+    Direction(&'input str),
+    Primitive(&'input str),
+    Boolean(&'input str),
+    /// Keyword of Java or C++ which cannot be used as a name
+    ReservedKeyword(&'input str),
+    Ident(&'input str),
+    Annotation(&'input str),
+    Integer(&'input str),
+    Float(&'input str),
+    QuotedString(&'input str),
+    CharLiteral(&'input str),
+    Punct(&'input str),
+}
+
+impl std::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Keyword(s)
+            | Token::Direction(s)
+            | Token::Primitive(s)
+            | Token::Boolean(s)
+            | Token::ReservedKeyword(s)
+            | Token::Ident(s)
+            | Token::Annotation(s)
+            | Token::Integer(s)
+            | Token::Float(s)
+            | Token::QuotedString(s)
+            | Token::CharLiteral(s)
+            | Token::Punct(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// Error of the lexer: characters which are not part of the AIDL syntax
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidToken {
+    pub location: usize,
+}
+
+impl std::fmt::Display for InvalidToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid token at offset {}", self.location)
+    }
+}
+
+pub(crate) type SpannedToken<'input> = Result<(usize, Token<'input>, usize), InvalidToken>;
+
+/// Convert the tokens of the CST lexer into the tokens of the grammar (without the
+/// whitespaces and comments)
+///
+/// The text of the tokens is taken from the input, which must be the content the tokens
+/// have been split from.
+pub(crate) fn get_grammar_tokens<'input>(
+    input: &'input str,
+    tokens: &[cst::Token],
+) -> Vec<SpannedToken<'input>> {
+    tokens
+        .iter()
+        .filter(|t| !t.kind.is_trivia())
+        .map(|t| {
+            let (start, end) = (t.full_range.start.offset, t.full_range.end.offset);
+            let text = &input[start..end];
+            let token = match t.kind {
+                TokenKind::Keyword => get_keyword_token(text),
+                TokenKind::Ident => Token::Ident(text),
+                TokenKind::Annotation => Token::Annotation(text),
+                TokenKind::Integer => Token::Integer(text),
+                TokenKind::Float => Token::Float(text),
+                TokenKind::String => Token::QuotedString(text),
+                TokenKind::Char => Token::CharLiteral(text),
+                TokenKind::Punct => Token::Punct(text),
+                TokenKind::Unknown => return Err(InvalidToken { location: start }),
+                TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment => {
+                    unreachable!()
+                }
+            };
+            Ok((start, token, end))
+        })
+        .collect()
+}
+
+// Terminal of the grammar of a keyword
+fn get_keyword_token(text: &str) -> Token<'_> {
+    match text {
+        "in" | "out" | "inout" => Token::Direction(text),
+        "byte" | "short" | "int" | "long" | "float" | "double" | "boolean" | "char" => {
+            Token::Primitive(text)
+        }
+        "true" | "false" => Token::Boolean(text),
+        "package" | "import" | "interface" | "parcelable" | "union" | "enum" | "oneway"
+        | "const" | "void" | "String" | "CharSequence" | "List" | "Map" => Token::Keyword(text),
+        _ => Token::ReservedKeyword(text),
+    }
+}
+
+/// Comments of the input, which are skipped by the grammar
+pub(crate) fn get_comments(tokens: &[cst::Token]) -> Vec<ast::Comment> {
+    tokens
+        .iter()
+        .filter(|t| matches!(t.kind, TokenKind::LineComment | TokenKind::BlockComment))
+        .map(|t| ast::Comment {
+            text: t.text.clone(),
+            full_range: t.full_range.clone(),
+        })
+        .collect()
+}
+
+/// Transaction code of a method (e.g. `= 3` or `= 0x10`), which must be a positive `int`
+pub(crate) fn parse_transact_code(literal: &str) -> Result<u32, String> {
+    match evaluation::parse_integer(literal)? {
+        ast::ConstValue::Int(v) if v >= 0 => Ok(v as u32),
+        ast::ConstValue::Long(v) if (0..=i32::MAX as i64).contains(&v) => Ok(v as u32),
+        _ => Err(format!("`{literal}` is not a positive int")),
+    }
+}
+
+/// Build the expression tree of relational and shift operators, which are parsed
+/// at the same level, so that shift operators get the higher precedence
+/// (e.g. `a < b << c` => `a < (b << c)`)
+pub(crate) fn fold_relational_and_shift(
+    first: ast::ConstExpr,
+    rest: Vec<(ast::BinaryOp, ast::ConstExpr)>,
+) -> ast::ConstExpr {
+    let is_shift = |op: &ast::BinaryOp| matches!(op, ast::BinaryOp::Shl | ast::BinaryOp::Shr);
+
+    // Fold the shift operators into the operands of the relational operators
+    let mut operands = vec![first];
+    let mut relational_ops = Vec::new();
+    for (op, rhs) in rest {
+        if is_shift(&op) {
+            let lhs = operands.pop().expect("at least one operand");
+            operands.push(ast::ConstExpr::binary(lhs, op, rhs));
+        } else {
+            relational_ops.push(op);
+            operands.push(rhs);
+        }
+    }
+
+    // Fold the relational operators (left-associative)
+    let mut operands = operands.into_iter();
+    let first = operands.next().expect("at least one operand");
+    relational_ops
+        .into_iter()
+        .zip(operands)
+        .fold(first, |lhs, (op, rhs)| ast::ConstExpr::binary(lhs, op, rhs))
+}
+
+/// Shift operators are parsed as 2 tokens (e.g. `<` and `<`) which must be adjacent
+pub(crate) fn check_adjacent_shift_op(
+    start: usize,
+    end: usize,
+    op: &str,
+    lookup: &line_col::LineColLookup,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if end - start != 2 {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidOperator,
+            range: ast::Range::new(lookup, start, end),
+            message: format!("Invalid operator, expected `{op}`"),
+            context_message: Some("invalid operator".to_owned()),
+            hint: Some("remove the whitespace between the two characters".to_owned()),
+            related_infos: Vec::new(),
+            fixes: Vec::new(),
+        });
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::single_element_loop)]
 mod tests {
+    use crate::cst;
     use crate::rules;
     use anyhow::Result;
 
-    fn lookup(input: &str) -> line_col::LineColLookup {
+    fn lookup(input: &str) -> line_col::LineColLookup<'_> {
         line_col::LineColLookup::new(input)
     }
 
+    fn tokens(input: &str) -> Vec<rules::SpannedToken<'_>> {
+        rules::get_grammar_tokens(input, &cst::lex(input))
+    }
+
     // Replace ranges into "..." and check parse output via insta Ron snapshot
     macro_rules! assert_parser {
         ($input:ident, $parser:expr) => {
             let mut diagnostics = Vec::new();
             let lookup = lookup($input);
-            let res = $parser.parse(&lookup, &mut diagnostics, $input)?;
+            let res = $parser.parse(&lookup, &mut diagnostics, $input, tokens($input))?;
             ::insta::assert_ron_snapshot!(res, {
                 ".**.symbol_range" => "...",
                 ".**.full_range" => "...",
@@ -29,7 +238,7 @@ mod tests {
 
         ($input:ident, $parser:expr, $diag:expr) => {
             let lookup = lookup($input);
-            let res = $parser.parse(&lookup, $diag, $input)?;
+            let res = $parser.parse(&lookup, $diag, $input, tokens($input))?;
             ::insta::assert_ron_snapshot!(res, {
                 ".**.symbol_range" => "...",
                 ".**.full_range" => "...",
@@ -62,15 +271,66 @@ mod tests {
     fn test_aidl_with_unrecovered_error() -> Result<()> {
         use crate::diagnostic::ParseError;
 
-        let input = "wrong, wrong and wrong!";
-        let lookup = lookup(input);
-        let res = rules::aidl::OptAidlParser::new().parse(&lookup, &mut Vec::new(), input);
+        let parse = |input| {
+            let lookup = lookup(input);
+            rules::aidl::OptAidlParser::new().parse(&lookup, &mut Vec::new(), input, tokens(input))
+        };
+
+        let res = parse("wrong, wrong and wrong!");
+        assert!(matches!(res, Err(ParseError::UnrecognizedToken { .. })));
 
-        assert!(matches!(res, Err(ParseError::InvalidToken { .. })));
+        // `!` is a valid token (logical not) but `#` is not
+        let res = parse("wrong, wrong and wrong#");
+        assert!(matches!(
+            res,
+            Err(ParseError::User {
+                error: rules::InvalidToken { location: 22 }
+            })
+        ));
 
         Ok(())
     }
 
+    #[test]
+    fn test_get_grammar_tokens() {
+        use rules::Token;
+
+        let input = "inout int true while String Foo @Hide 0x1F 1.5f \"s\" '\\n' <= /* c */ €";
+        let tokens = tokens(input);
+
+        let (last, tokens) = tokens.split_last().expect("tokens");
+        let tokens: Vec<Token> = tokens.iter().map(|t| t.expect("token").1).collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::Direction("inout"),
+                Token::Primitive("int"),
+                Token::Boolean("true"),
+                Token::ReservedKeyword("while"),
+                Token::Keyword("String"),
+                Token::Ident("Foo"),
+                Token::Annotation("@Hide"),
+                Token::Integer("0x1F"),
+                Token::Float("1.5f"),
+                Token::QuotedString("\"s\""),
+                Token::CharLiteral("'\\n'"),
+                Token::Punct("<="),
+            ]
+        );
+        assert_eq!(last, &Err(rules::InvalidToken { location: 68 }));
+    }
+
+    #[test]
+    fn test_get_comments() {
+        let input = "// c1\nconst String S = \"// not a comment\"; /* c2\n */ char c = '\"'; // c3";
+        let comments = rules::get_comments(&cst::lex(input));
+
+        let texts: Vec<&str> = comments.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["// c1", "/* c2\n */", "// c3"]);
+        assert_eq!(comments[1].full_range.start.line_col, (2, 38));
+        assert_eq!(comments[1].full_range.end.line_col, (3, 4));
+    }
+
     #[test]
     fn test_aidl_with_recovered_error() -> Result<()> {
         let input = r#"package x.y.z;
@@ -84,11 +344,13 @@ mod tests {
         [
           Diagnostic(
             kind: Error,
+            code: "AIDL0003",
             range: "...",
-            message: "Invalid item - Unrecognized token `oops_interface`.\nExpected one of ANNOTATION, ENUM, IMPORT, INTERFACE or PARCELABLE",
+            message: "Invalid item - Unrecognized token `oops_interface`.\nExpected one of ANNOTATION, ENUM, IMPORT, INTERFACE, ONEWAY, PARCELABLE or UNION",
             context_message: Some("unrecognized token"),
             hint: None,
             related_infos: [],
+            fixes: [],
           ),
         ]
         "###);
@@ -167,6 +429,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_interface_with_nested_items() -> Result<()> {
+        let input = r#"interface Outer {
+            union InnerUnion {
+                int field1;
+            }
+
+            void method1(in InnerUnion u);
+
+            interface ICallback {
+                void onEvent();
+            }
+        }"#;
+        assert_parser!(input, rules::aidl::InterfaceParser::new());
+
+        Ok(())
+    }
+
     #[test]
     fn test_oneway_interface() -> Result<()> {
         let input = r#"oneway interface OneWayInterface {}"#;
@@ -215,19 +495,23 @@ mod tests {
         [
           Diagnostic(
             kind: Error,
+            code: "AIDL0003",
             range: "...",
             message: "Invalid interface element - Unrecognized token `;`.\nExpected \"(\"",
             context_message: Some("unrecognized token"),
             hint: None,
             related_infos: [],
+            fixes: [],
           ),
           Diagnostic(
             kind: Error,
+            code: "AIDL0003",
             range: "...",
-            message: "Invalid interface element - Unrecognized token `;`.\nExpected one of \")\", \",\", \".\", \">\" or IDENT",
+            message: "Invalid interface element - Unrecognized token `;`.\nExpected one of \")\", \",\", \"<\", \">\", \"[\" or IDENT",
             context_message: Some("unrecognized token"),
             hint: None,
             related_infos: [],
+            fixes: [],
           ),
         ]
         "###);
@@ -257,6 +541,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parcelable_with_nested_items() -> Result<()> {
+        let input = r#"parcelable Outer {
+            int field1;
+
+            /** Inner documentation */
+            parcelable Inner {
+                String field2;
+            }
+
+            enum InnerEnum { ELEMENT1 }
+
+            Inner field3;
+        }"#;
+        assert_parser!(input, rules::aidl::ParcelableParser::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generic_parcelable() -> Result<()> {
+        let input = r#"parcelable Pair<A, B> {
+            A first;
+            B second;
+            List<Pair<A, String>> others;
+        }"#;
+        assert_parser!(input, rules::aidl::ParcelableParser::new());
+
+        Ok(())
+    }
+
     #[test]
     fn test_parcelable_with_javadoc() -> Result<()> {
         let input = r#"
@@ -284,11 +599,60 @@ mod tests {
         [
           Diagnostic(
             kind: Error,
+            code: "AIDL0003",
             range: "...",
-            message: "Invalid parcelable element - Unrecognized token `;`.\nExpected one of \",\", \".\", \">\" or IDENT",
+            message: "Invalid parcelable element - Unrecognized token `;`.\nExpected one of \")\", \",\", \"<\", \">\", \"[\" or IDENT",
             context_message: Some("unrecognized token"),
             hint: None,
             related_infos: [],
+            fixes: [],
+          ),
+        ]
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_union() -> Result<()> {
+        let input = r#"union Potato {
+            /**
+             * const1 documentation
+             */
+            const int const1 = 1;
+
+            /**
+             * field1 documentation
+             */
+            int field1 = 3;
+
+            String field2;
+        }"#;
+        assert_parser!(input, rules::aidl::UnionParser::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_union_with_errors() -> Result<()> {
+        let input = r#"union Potato {
+            int field1;
+            wrongfield2;
+            String field3;
+        }"#;
+        let mut diagnostics = Vec::new();
+        assert_parser!(input, rules::aidl::UnionParser::new(), &mut diagnostics);
+        assert_diagnostics!(diagnostics, @r###"
+        [
+          Diagnostic(
+            kind: Error,
+            code: "AIDL0003",
+            range: "...",
+            message: "Invalid union element - Unrecognized token `;`.\nExpected one of \",\", \"<\", \">\", \"[\" or IDENT",
+            context_message: Some("unrecognized token"),
+            hint: None,
+            related_infos: [],
+            fixes: [],
           ),
         ]
         "###);
@@ -342,19 +706,23 @@ mod tests {
         [
           Diagnostic(
             kind: Error,
+            code: "AIDL0003",
             range: "...",
-            message: "Invalid enum element - Unrecognized token `=`.\nExpected one of BOOLEAN, FLOAT or QUOTED_STRING",
+            message: "Invalid enum element - Unrecognized token `==`.\nExpected one of \",\", \"=\" or \"}\"",
             context_message: Some("unrecognized token"),
             hint: None,
             related_infos: [],
+            fixes: [],
           ),
           Diagnostic(
             kind: Error,
+            code: "AIDL0003",
             range: "...",
-            message: "Invalid enum element - Unrecognized token `0843`.\nExpected one of \"}\" or IDENT",
+            message: "Invalid enum element - Unrecognized token `0843`.\nExpected one of \"}\", ANNOTATION or IDENT",
             context_message: Some("unrecognized token"),
             hint: None,
             related_infos: [],
+            fixes: [],
           ),
         ]
         "###);
@@ -417,12 +785,46 @@ mod tests {
     fn test_method_with_invalid_transact_code() -> Result<()> {
         let input = "TypeName myMethod() = 12.3;";
         assert!(rules::aidl::MethodParser::new()
-            .parse(&lookup(input), &mut Vec::new(), input)
+            .parse(&lookup(input), &mut Vec::new(), input, tokens(input))
             .is_err());
 
         Ok(())
     }
 
+    #[test]
+    fn test_method_with_hex_transact_code() -> Result<()> {
+        for (input, code) in [
+            ("void myMethod() = 0x1;", 1),
+            ("void myMethod() = 0X1F;", 31),
+            ("void myMethod() = 3L;", 3),
+        ] {
+            let mut diagnostics = Vec::new();
+            let method = rules::aidl::MethodParser::new()
+                .parse(&lookup(input), &mut diagnostics, input, tokens(input))
+                .unwrap();
+            assert_eq!(method.transact_code, Some(code));
+            assert_eq!(diagnostics, []);
+        }
+
+        for input in [
+            "void myMethod() = 0xffffffff;",
+            "void myMethod() = 4294967296;",
+        ] {
+            let mut diagnostics = Vec::new();
+            let method = rules::aidl::MethodParser::new()
+                .parse(&lookup(input), &mut diagnostics, input, tokens(input))
+                .unwrap();
+            assert_eq!(method.transact_code, None);
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(
+                diagnostics[0].code,
+                crate::diagnostic::DiagnosticCode::InvalidTransactCode
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_method_with_annotation() -> Result<()> {
         let input = "@AnnotationName void myMethod();";
@@ -633,7 +1035,7 @@ mod tests {
     fn test_type_list_invalid() -> Result<()> {
         let input = "List<A, B>";
         assert!(rules::aidl::ValueParser::new()
-            .parse(&lookup(input), &mut Vec::new(), input)
+            .parse(&lookup(input), &mut Vec::new(), input, tokens(input))
             .is_err());
 
         Ok(())
@@ -659,12 +1061,12 @@ mod tests {
     fn test_type_map_invalid() -> Result<()> {
         let input = "Map<A>";
         assert!(rules::aidl::ValueParser::new()
-            .parse(&lookup(input), &mut Vec::new(), input)
+            .parse(&lookup(input), &mut Vec::new(), input, tokens(input))
             .is_err());
 
         let input = "Map<A,B,C>";
         assert!(rules::aidl::ValueParser::new()
-            .parse(&lookup(input), &mut Vec::new(), input)
+            .parse(&lookup(input), &mut Vec::new(), input, tokens(input))
             .is_err());
 
         Ok(())
@@ -672,68 +1074,110 @@ mod tests {
 
     #[test]
     fn test_value() -> Result<()> {
+        let parse = |input| -> Result<String> {
+            let value = rules::aidl::ValueParser::new().parse(
+                &lookup(input),
+                &mut Vec::new(),
+                input,
+                tokens(input),
+            )?;
+            Ok(value.to_string())
+        };
+
         // Numbers
-        for input in ["12", "-12", "-0.12", "-.12", "-.12f"].into_iter() {
-            assert_eq!(
-                rules::aidl::ValueParser::new().parse(&lookup(input), &mut Vec::new(), input)?,
-                input
-            );
+        for input in [
+            "12", "-12", "-0.12", "-.12", "-.12f", "0x7F", "0b101", "017", "3L", "1e3",
+        ] {
+            assert_eq!(parse(input)?, input);
         }
 
         // Invalid numbers
-        for input in ["-.", "--12", "0..2", "0.2y"].into_iter() {
-            assert!(rules::aidl::ValueParser::new()
-                .parse(&lookup(input), &mut Vec::new(), input)
-                .is_err());
+        for input in ["-.", "0..2", "0.2y", "0x", "12LL"].into_iter() {
+            assert!(parse(input).is_err());
         }
 
-        // Strings
-        for input in ["\"hello\"", "\"\"", "\"\t\""].into_iter() {
-            assert_eq!(
-                rules::aidl::ValueParser::new().parse(&lookup(input), &mut Vec::new(), input)?,
-                input
-            );
+        // Strings and chars
+        for input in ["\"hello\"", "\"\"", "\"\t\"", "'a'", "'\\n'"].into_iter() {
+            assert_eq!(parse(input)?, input);
         }
 
         // Invalid strings
-        {
-            let input = "\"\"\"";
-            assert!(rules::aidl::ValueParser::new()
-                .parse(&lookup(input), &mut Vec::new(), input)
-                .is_err());
+        for input in ["\"\"\"", "'ab'"].into_iter() {
+            assert!(parse(input).is_err());
         }
 
-        // Empty objects
+        // Empty arrays
         for input in ["{}", "{ }", "{      }"].into_iter() {
-            assert_eq!(
-                rules::aidl::ValueParser::new().parse(&lookup(input), &mut Vec::new(), input)?,
-                "{}"
-            );
+            assert_eq!(parse(input)?, "{}");
         }
 
-        // Non-empty objects
-        for input in ["{\"hello{<\"}", "{1}", "{1, 2}", "{1, 2, 3, }"].into_iter() {
-            assert_eq!(
-                rules::aidl::ValueParser::new().parse(&lookup(input), &mut Vec::new(), input)?,
-                "{...}"
-            );
+        // Non-empty arrays
+        for (input, expected) in [
+            ("{\"hello{<\"}", "{\"hello{<\"}"),
+            ("{1}", "{1}"),
+            ("{1, 2}", "{1, 2}"),
+            ("{1, 2, 3, }", "{1, 2, 3}"),
+        ] {
+            assert_eq!(parse(input)?, expected);
         }
 
-        // Enum value
-        for input in ["MyEnum.WOOF"].into_iter() {
-            assert_eq!(
-                rules::aidl::ValueParser::new().parse(&lookup(input), &mut Vec::new(), input)?,
-                "MyEnum.WOOF"
-            );
+        // References (e.g. enum value)
+        for input in ["MyEnum.WOOF", "CONST", "x.y.MyParcelable.CONST"].into_iter() {
+            assert_eq!(parse(input)?, input);
         }
 
-        // Invalid objects
-        for input in ["MyEnum", "{\"hello{<\"", "{1sfewf}", "{1, 2, 3,, }"].into_iter() {
-            assert!(rules::aidl::ValueParser::new()
-                .parse(&lookup(input), &mut Vec::new(), input)
-                .is_err());
+        // Operators
+        for (input, expected) in [
+            ("1+2*3", "1 + 2 * 3"),
+            ("(1 + 2) * 3", "(1 + 2) * 3"),
+            ("1 - (2 - 3)", "1 - (2 - 3)"),
+            ("(1 - 2) - 3", "1 - 2 - 3"),
+            ("-(1 + 2)", "-(1 + 2)"),
+            ("--12", "--12"),
+            ("~A | B & C ^ D", "~A | B & C ^ D"),
+            ("1 << 2 < 3 >> 1", "1 << 2 < 3 >> 1"),
+            ("(1 < 2) << 3", "(1 < 2) << 3"),
+            ("a <= b == c >= d != !e", "a <= b == c >= d != !e"),
+            ("a && b || c % d / e", "a && b || c % d / e"),
+            ("\"a\" + \"b\"", "\"a\" + \"b\""),
+        ] {
+            assert_eq!(parse(input)?, expected);
         }
 
+        // Invalid objects and expressions
+        for input in [
+            "{\"hello{<\"",
+            "{1sfewf}",
+            "{1, 2, 3,, }",
+            "1 +",
+            "(1",
+            "1 = 2",
+        ]
+        .into_iter()
+        {
+            assert!(parse(input).is_err());
+        }
+
+        // Shift operators must not contain whitespaces
+        let mut diagnostics = Vec::new();
+        let input = "1 < < 2";
+        rules::aidl::ValueParser::new().parse(
+            &lookup(input),
+            &mut diagnostics,
+            input,
+            tokens(input),
+        )?;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Invalid operator, expected `<<`");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_expression() -> Result<()> {
+        let input = "1 + 2 * -Other.FOO < 0x10 << 1 || true";
+        assert_parser!(input, rules::aidl::ValueParser::new());
+
         Ok(())
     }
 
@@ -781,11 +1225,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_annotation6() -> Result<()> {
+        let input = "@SuppressWarnings(value={\"inout-parameter\", \"mixed-oneway\"})";
+        assert_parser!(input, rules::aidl::OptAnnotationParser::new());
+
+        let input = "@SuppressWarnings(\"unused-import\")";
+        assert_parser!(input, rules::aidl::OptAnnotationParser::new());
+
+        Ok(())
+    }
+
     #[test]
     fn test_reserved_keywords() -> Result<()> {
         let input = "package a.for.b;";
         assert!(rules::aidl::PackageParser::new()
-            .parse(&lookup(input), &mut Vec::new(), input)
+            .parse(&lookup(input), &mut Vec::new(), input, tokens(input))
             .is_err());
 
         Ok(())
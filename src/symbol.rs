@@ -8,11 +8,12 @@ pub enum Symbol<'a> {
     Import(&'a ast::Import),
     Interface(&'a ast::Interface, &'a ast::Package),
     Parcelable(&'a ast::Parcelable, &'a ast::Package),
+    Union(&'a ast::Union, &'a ast::Package),
     Enum(&'a ast::Enum, &'a ast::Package),
     Method(&'a ast::Method, &'a ast::Interface),
     Arg(&'a ast::Arg, &'a ast::Method),
     Const(&'a ast::Const, ConstOwner<'a>),
-    Field(&'a ast::Field, &'a ast::Parcelable),
+    Field(&'a ast::Field, FieldOwner<'a>),
     EnumElement(&'a ast::EnumElement, &'a ast::Enum),
     Type(&'a ast::Type),
 }
@@ -21,6 +22,7 @@ pub enum Symbol<'a> {
 pub enum ConstOwner<'a> {
     Interface(&'a ast::Interface),
     Parcelable(&'a ast::Parcelable),
+    Union(&'a ast::Union),
 }
 
 impl<'a> ConstOwner<'a> {
@@ -28,6 +30,22 @@ impl<'a> ConstOwner<'a> {
         match self {
             ConstOwner::Interface(i) => &i.name,
             ConstOwner::Parcelable(p) => &p.name,
+            ConstOwner::Union(u) => &u.name,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub enum FieldOwner<'a> {
+    Parcelable(&'a ast::Parcelable),
+    Union(&'a ast::Union),
+}
+
+impl<'a> FieldOwner<'a> {
+    pub fn get_name(&self) -> &str {
+        match self {
+            FieldOwner::Parcelable(p) => &p.name,
+            FieldOwner::Union(u) => &u.name,
         }
     }
 }
@@ -39,6 +57,7 @@ impl<'a> Symbol<'a> {
             Symbol::Import(i) => Some(i.get_qualified_name()),
             Symbol::Interface(i, _) => Some(i.name.clone()),
             Symbol::Parcelable(p, _) => Some(p.name.clone()),
+            Symbol::Union(u, _) => Some(u.name.clone()),
             Symbol::Enum(e, _) => Some(e.name.clone()),
             Symbol::Method(m, _) => Some(m.name.clone()),
             Symbol::Arg(a, _) => a.name.clone(),
@@ -55,11 +74,12 @@ impl<'a> Symbol<'a> {
             Symbol::Import(i) => Some(i.get_qualified_name()),
             Symbol::Interface(i, pkg) => Some(format!("{}.{}", pkg.name, i.name)),
             Symbol::Parcelable(p, pkg) => Some(format!("{}.{}", pkg.name, p.name)),
+            Symbol::Union(u, pkg) => Some(format!("{}.{}", pkg.name, u.name)),
             Symbol::Enum(e, pkg) => Some(format!("{}{}", pkg.name, e.name)),
             Symbol::Method(m, i) => Some(format!("{}::{}", i.name, m.name)),
             Symbol::Arg(a, _) => a.name.clone(),
             Symbol::Const(c, o) => Some(format!("{}::{}", o.get_name(), c.name)),
-            Symbol::Field(m, p) => Some(format!("{}::{}", p.name, m.name)),
+            Symbol::Field(m, o) => Some(format!("{}::{}", o.get_name(), m.name)),
             Symbol::EnumElement(el, e) => Some(format!("{}::{}", e.name, el.name)),
             Symbol::Type(ast::Type {
                 kind: ast::TypeKind::ResolvedItem(qualified_name, _),
@@ -75,6 +95,7 @@ impl<'a> Symbol<'a> {
             Symbol::Import(i) => &i.symbol_range,
             Symbol::Interface(i, _) => &i.symbol_range,
             Symbol::Parcelable(p, _) => &p.symbol_range,
+            Symbol::Union(u, _) => &u.symbol_range,
             Symbol::Enum(e, _) => &e.symbol_range,
             Symbol::Method(m, _) => &m.symbol_range,
             Symbol::Arg(a, _) => &a.symbol_range,
@@ -91,6 +112,7 @@ impl<'a> Symbol<'a> {
             Symbol::Import(i) => &i.full_range,
             Symbol::Interface(i, _) => &i.full_range,
             Symbol::Parcelable(p, _) => &p.full_range,
+            Symbol::Union(u, _) => &u.full_range,
             Symbol::Enum(e, _) => &e.full_range,
             Symbol::Method(m, _) => &m.full_range,
             Symbol::Arg(a, _) => &a.full_range,
@@ -101,6 +123,21 @@ impl<'a> Symbol<'a> {
         }
     }
 
+    pub fn get_doc(&self) -> Option<&str> {
+        match self {
+            Symbol::Package(_) | Symbol::Import(_) | Symbol::Type(_) => None,
+            Symbol::Interface(i, _) => i.doc.as_deref(),
+            Symbol::Parcelable(p, _) => p.doc.as_deref(),
+            Symbol::Union(u, _) => u.doc.as_deref(),
+            Symbol::Enum(e, _) => e.doc.as_deref(),
+            Symbol::Method(m, _) => m.doc.as_deref(),
+            Symbol::Arg(a, _) => a.doc.as_deref(),
+            Symbol::Const(c, _) => c.doc.as_deref(),
+            Symbol::Field(m, _) => m.doc.as_deref(),
+            Symbol::EnumElement(e, _) => e.doc.as_deref(),
+        }
+    }
+
     pub fn get_details(&self) -> Option<String> {
         fn get_type_str(t: &ast::Type) -> String {
             if t.generic_types.is_empty() {
@@ -134,6 +171,7 @@ impl<'a> Symbol<'a> {
             Symbol::Import(..) => String::from("import"),
             Symbol::Interface(..) => String::from("interface"),
             Symbol::Parcelable(..) => String::from("parcelable"),
+            Symbol::Union(..) => String::from("union"),
             Symbol::Enum(..) => String::from("enum"),
             Symbol::Method(m, _) => {
                 format!(
@@ -187,7 +225,11 @@ impl<'a> Symbol<'a> {
         match self {
             Symbol::Package(p) => format!("package {}", p.name),
             Symbol::Import(i) => format!("import {}", i.get_qualified_name()),
+            Symbol::Parcelable(p, _) if !p.type_parameters.is_empty() => {
+                format!("parcelable {}<{}>", p.name, p.type_parameters.join(", "))
+            }
             Symbol::Parcelable(p, _) => format!("parcelable {}", p.name),
+            Symbol::Union(u, _) => format!("union {}", u.name),
             Symbol::Interface(i, _) => format!("interface {}", i.name),
             Symbol::Enum(e, _) => format!("enum {}", e.name),
             Symbol::Method(m, _) => {
@@ -1,7 +1,7 @@
 use std::ops::ControlFlow;
 
 use crate::ast;
-use crate::symbol::{ConstOwner, Symbol};
+use crate::symbol::{ConstOwner, FieldOwner, Symbol};
 
 /// Determine the depth of traversal functions
 #[derive(Clone, Copy)]
@@ -18,7 +18,7 @@ pub enum SymbolFilter {
 ///
 /// This function works like the visitor pattern.
 pub fn walk_symbols<'a, F: FnMut(Symbol<'a>)>(ast: &'a ast::Aidl, filter: SymbolFilter, mut f: F) {
-    walk_symbols_with_control_flow(ast, filter, |smb| -> ControlFlow<()> {
+    let _ = walk_symbols_with_control_flow(ast, filter, |smb| -> ControlFlow<()> {
         f(smb);
         ControlFlow::Continue(())
     });
@@ -78,11 +78,10 @@ pub fn find_symbol_at_line_col(
     ast: &ast::Aidl,
     filter: SymbolFilter,
     line_col: (usize, usize),
-) -> Option<Symbol> {
+) -> Option<Symbol<'_>> {
     find_symbol(ast, filter, |smb| range_contains(smb.get_range(), line_col))
 }
 
-#[allow(clippy::needless_borrow)] // because of false-positives when invoking macros...
 fn walk_symbols_with_control_flow<'a, V, F>(
     ast: &'a ast::Aidl,
     filter: SymbolFilter,
@@ -91,35 +90,51 @@ fn walk_symbols_with_control_flow<'a, V, F>(
 where
     F: FnMut(Symbol<'a>) -> ControlFlow<V>,
 {
-    macro_rules! visit_type_helper {
-        ($t:expr, $f:ident) => {
-            if $t.kind == ast::TypeKind::Array {
-                // For arrays, start with the array element type, then on the array itself
-                $t.generic_types
-                    .iter()
-                    .try_for_each(|t| $f(Symbol::Type(t)))?;
-                $f(Symbol::Type($t))?;
-            } else {
-                // For other types, start with the main type and then its generic types
-                $f(Symbol::Type($t))?;
-                $t.generic_types
-                    .iter()
-                    .try_for_each(|t| $f(Symbol::Type(t)))?;
-            }
-        };
-    }
-
     if let SymbolFilter::All = filter {
-        f(Symbol::Package(&ast.package));
+        f(Symbol::Package(&ast.package))?;
 
         for import in &ast.imports {
             f(Symbol::Import(import))?;
         }
     }
 
-    match ast.item {
+    walk_item_symbols(&ast.item, &ast.package, filter, &mut f)
+}
+
+// Visit the type and its generic types recursively (e.g. `List<Pair<A, B>>`)
+fn walk_type_symbols<'a, V, F>(type_: &'a ast::Type, f: &mut F) -> ControlFlow<V>
+where
+    F: FnMut(Symbol<'a>) -> ControlFlow<V>,
+{
+    if type_.kind == ast::TypeKind::Array {
+        // For arrays, start with the array element type, then on the array itself
+        type_
+            .generic_types
+            .iter()
+            .try_for_each(|t| walk_type_symbols(t, f))?;
+        f(Symbol::Type(type_))
+    } else {
+        // For other types, start with the main type and then its generic types
+        f(Symbol::Type(type_))?;
+        type_
+            .generic_types
+            .iter()
+            .try_for_each(|t| walk_type_symbols(t, f))
+    }
+}
+
+fn walk_item_symbols<'a, V, F>(
+    item: &'a ast::Item,
+    package: &'a ast::Package,
+    filter: SymbolFilter,
+    f: &mut F,
+) -> ControlFlow<V>
+where
+    F: FnMut(Symbol<'a>) -> ControlFlow<V>,
+{
+    match item {
         ast::Item::Interface(ref i) => {
-            f(Symbol::Interface(i, &ast.package))?;
+            f(Symbol::Interface(i, package))?;
             if let SymbolFilter::ItemsOnly = filter {
                 return ControlFlow::Continue(());
             }
@@ -128,10 +143,10 @@ where
                 ast::InterfaceElement::Method(m) => {
                     f(Symbol::Method(m, i))?;
                     if let SymbolFilter::All = filter {
-                        visit_type_helper!(&m.return_type, f);
+                        walk_type_symbols(&m.return_type, f)?;
                         m.args.iter().try_for_each(|arg| {
                             f(Symbol::Arg(arg, m))?;
-                            visit_type_helper!(&arg.arg_type, f);
+                            walk_type_symbols(&arg.arg_type, f)?;
                             ControlFlow::Continue(())
                         })?;
                     }
@@ -140,23 +155,23 @@ where
                 ast::InterfaceElement::Const(c) => {
                     f(Symbol::Const(c, ConstOwner::Interface(i)))?;
                     if let SymbolFilter::All = filter {
-                        visit_type_helper!(&c.const_type, f);
+                        walk_type_symbols(&c.const_type, f)?;
                     }
                     ControlFlow::Continue(())
                 }
             })?;
         }
         ast::Item::Parcelable(ref p) => {
-            f(Symbol::Parcelable(p, &ast.package))?;
+            f(Symbol::Parcelable(p, package))?;
             if let SymbolFilter::ItemsOnly = filter {
                 return ControlFlow::Continue(());
             }
 
             p.elements.iter().try_for_each(|el| match el {
                 ast::ParcelableElement::Field(fi) => {
-                    f(Symbol::Field(fi, p))?;
+                    f(Symbol::Field(fi, FieldOwner::Parcelable(p)))?;
                     if let SymbolFilter::All = filter {
-                        visit_type_helper!(&fi.field_type, f);
+                        walk_type_symbols(&fi.field_type, f)?;
                     }
 
                     ControlFlow::Continue(())
@@ -164,14 +179,38 @@ where
                 ast::ParcelableElement::Const(c) => {
                     f(Symbol::Const(c, ConstOwner::Parcelable(p)))?;
                     if let SymbolFilter::All = filter {
-                        visit_type_helper!(&c.const_type, f);
+                        walk_type_symbols(&c.const_type, f)?;
+                    }
+                    ControlFlow::Continue(())
+                }
+            })?;
+        }
+        ast::Item::Union(ref u) => {
+            f(Symbol::Union(u, package))?;
+            if let SymbolFilter::ItemsOnly = filter {
+                return ControlFlow::Continue(());
+            }
+
+            u.elements.iter().try_for_each(|el| match el {
+                ast::UnionElement::Field(fi) => {
+                    f(Symbol::Field(fi, FieldOwner::Union(u)))?;
+                    if let SymbolFilter::All = filter {
+                        walk_type_symbols(&fi.field_type, f)?;
+                    }
+
+                    ControlFlow::Continue(())
+                }
+                ast::UnionElement::Const(c) => {
+                    f(Symbol::Const(c, ConstOwner::Union(u)))?;
+                    if let SymbolFilter::All = filter {
+                        walk_type_symbols(&c.const_type, f)?;
                     }
                     ControlFlow::Continue(())
                 }
             })?;
         }
         ast::Item::Enum(ref e) => {
-            f(Symbol::Enum(e, &ast.package))?;
+            f(Symbol::Enum(e, package))?;
             if let SymbolFilter::ItemsOnly = filter {
                 return ControlFlow::Continue(());
             }
@@ -183,10 +222,13 @@ where
         }
     }
 
-    ControlFlow::Continue(())
+    // Nested items (after the elements of the enclosing item)
+    item.get_nested_items()
+        .iter()
+        .try_for_each(|nested| walk_item_symbols(nested, package, filter, f))
 }
 
-fn range_contains(range: &ast::Range, line_col: (usize, usize)) -> bool {
+pub(crate) fn range_contains(range: &ast::Range, line_col: (usize, usize)) -> bool {
     if range.start.line_col.0 > line_col.0 {
         return false;
     }
@@ -206,21 +248,64 @@ fn range_contains(range: &ast::Range, line_col: (usize, usize)) -> bool {
     true
 }
 
+/// Traverse the AST and provide the items (incl. nested items) to the given closure
+///
+/// The enclosing item is always visited before its nested items.
+pub fn walk_items<'a, F: FnMut(&'a ast::Item)>(ast: &'a ast::Aidl, mut f: F) {
+    fn walk<'a, F: FnMut(&'a ast::Item)>(item: &'a ast::Item, f: &mut F) {
+        f(item);
+        item.get_nested_items()
+            .iter()
+            .for_each(|nested| walk(nested, f));
+    }
+
+    walk(&ast.item, &mut f);
+}
+
+/// Same as walk_items() but with mutable items and the key of the item (e.g. `pkg.Outer.Inner`)
+pub(crate) fn walk_items_mut<F: FnMut(&mut ast::Item, ast::ItemKeyRef)>(
+    ast: &mut ast::Aidl,
+    mut f: F,
+) {
+    fn walk<F: FnMut(&mut ast::Item, ast::ItemKeyRef)>(
+        item: &mut ast::Item,
+        key: ast::ItemKeyRef,
+        f: &mut F,
+    ) {
+        f(item, key);
+        let nested_items = match item {
+            ast::Item::Interface(i) => &mut i.nested_items,
+            ast::Item::Parcelable(p) => &mut p.nested_items,
+            ast::Item::Union(_) | ast::Item::Enum(_) => return,
+        };
+
+        for nested in nested_items.iter_mut() {
+            let nested_key = format!("{}.{}", key, nested.get_name());
+            walk(nested, &nested_key, f);
+        }
+    }
+
+    let key = ast.get_key();
+    walk(&mut ast.item, &key, &mut f);
+}
+
 /// Traverse the AST and provide the types to the given closure
 pub fn walk_types<F: FnMut(&ast::Type)>(ast: &ast::Aidl, mut f: F) {
-    let mut visit_type_helper = move |type_: &ast::Type| {
+    fn visit<F: FnMut(&ast::Type)>(type_: &ast::Type, f: &mut F) {
         if type_.kind == ast::TypeKind::Array {
             // For arrays, start with the array element type, then on the array itself
-            type_.generic_types.iter().for_each(&mut f);
+            type_.generic_types.iter().for_each(|t| visit(t, f));
             f(type_);
         } else {
             // For other types, start with the main type and then its generic types
             f(type_);
-            type_.generic_types.iter().for_each(&mut f);
+            type_.generic_types.iter().for_each(|t| visit(t, f));
         }
-    };
+    }
 
-    match ast.item {
+    let mut visit_type_helper = move |type_: &ast::Type| visit(type_, &mut f);
+
+    walk_items(ast, |item| match item {
         ast::Item::Interface(ref i) => {
             i.elements.iter().for_each(|el| match el {
                 ast::InterfaceElement::Method(m) => {
@@ -244,62 +329,111 @@ pub fn walk_types<F: FnMut(&ast::Type)>(ast: &ast::Aidl, mut f: F) {
                 }
             });
         }
-        ast::Item::Enum(_) => (),
-    }
-}
-
-pub(crate) fn walk_types_mut<F: FnMut(&mut ast::Type)>(ast: &mut ast::Aidl, mut f: F) {
-    let mut visit_type_helper = move |type_: &mut ast::Type| {
-        f(type_);
-        type_.generic_types.iter_mut().for_each(&mut f);
-    };
-
-    match ast.item {
-        ast::Item::Interface(ref mut i) => {
-            i.elements.iter_mut().for_each(|el| match el {
-                ast::InterfaceElement::Method(m) => {
-                    visit_type_helper(&mut m.return_type);
-                    m.args.iter_mut().for_each(|arg| {
-                        visit_type_helper(&mut arg.arg_type);
-                    })
-                }
-                ast::InterfaceElement::Const(c) => {
-                    visit_type_helper(&mut c.const_type);
-                }
-            });
-        }
-        ast::Item::Parcelable(ref mut p) => {
-            p.elements.iter_mut().for_each(|el| match el {
-                ast::ParcelableElement::Field(fi) => {
-                    visit_type_helper(&mut fi.field_type);
+        ast::Item::Union(ref u) => {
+            u.elements.iter().for_each(|el| match el {
+                ast::UnionElement::Field(fi) => {
+                    visit_type_helper(&fi.field_type);
                 }
-                ast::ParcelableElement::Const(c) => {
-                    visit_type_helper(&mut c.const_type);
+                ast::UnionElement::Const(c) => {
+                    visit_type_helper(&c.const_type);
                 }
             });
         }
         ast::Item::Enum(_) => (),
+    });
+}
+
+/// Same as walk_types() but with mutable types and the key of the item
+/// (e.g. `pkg.Outer.Inner`) where the type is declared
+pub(crate) fn walk_types_mut<F: FnMut(&mut ast::Type, ast::ItemKeyRef)>(
+    ast: &mut ast::Aidl,
+    mut f: F,
+) {
+    fn walk<F: FnMut(&mut ast::Type, ast::ItemKeyRef)>(
+        item: &mut ast::Item,
+        key: ast::ItemKeyRef,
+        f: &mut F,
+    ) {
+        fn visit<F: FnMut(&mut ast::Type, ast::ItemKeyRef)>(
+            type_: &mut ast::Type,
+            key: ast::ItemKeyRef,
+            f: &mut F,
+        ) {
+            f(type_, key);
+            type_
+                .generic_types
+                .iter_mut()
+                .for_each(|t| visit(t, key, f));
+        }
+
+        let mut visit_type_helper = |type_: &mut ast::Type| visit(type_, key, f);
+
+        let nested_items = match item {
+            ast::Item::Interface(ref mut i) => {
+                i.elements.iter_mut().for_each(|el| match el {
+                    ast::InterfaceElement::Method(m) => {
+                        visit_type_helper(&mut m.return_type);
+                        m.args.iter_mut().for_each(|arg| {
+                            visit_type_helper(&mut arg.arg_type);
+                        })
+                    }
+                    ast::InterfaceElement::Const(c) => {
+                        visit_type_helper(&mut c.const_type);
+                    }
+                });
+                &mut i.nested_items
+            }
+            ast::Item::Parcelable(ref mut p) => {
+                p.elements.iter_mut().for_each(|el| match el {
+                    ast::ParcelableElement::Field(fi) => {
+                        visit_type_helper(&mut fi.field_type);
+                    }
+                    ast::ParcelableElement::Const(c) => {
+                        visit_type_helper(&mut c.const_type);
+                    }
+                });
+                &mut p.nested_items
+            }
+            ast::Item::Union(ref mut u) => {
+                u.elements.iter_mut().for_each(|el| match el {
+                    ast::UnionElement::Field(fi) => {
+                        visit_type_helper(&mut fi.field_type);
+                    }
+                    ast::UnionElement::Const(c) => {
+                        visit_type_helper(&mut c.const_type);
+                    }
+                });
+                return;
+            }
+            ast::Item::Enum(_) => return,
+        };
+
+        for nested in nested_items.iter_mut() {
+            let nested_key = format!("{}.{}", key, nested.get_name());
+            walk(nested, &nested_key, f);
+        }
     }
+
+    let key = ast.get_key();
+    walk(&mut ast.item, &key, &mut f);
 }
 
 /// Traverse the AST and provide the methods to the given closure
 pub fn walk_methods<'a, F: FnMut(&'a ast::Method)>(ast: &'a ast::Aidl, mut f: F) {
-    match ast.item {
-        ast::Item::Interface(ref i) => {
+    walk_items(ast, |item| {
+        if let ast::Item::Interface(ref i) = item {
             i.elements.iter().for_each(|el| match el {
                 ast::InterfaceElement::Method(m) => f(m),
                 ast::InterfaceElement::Const(_) => (),
             });
         }
-        ast::Item::Parcelable(_) => (),
-        ast::Item::Enum(_) => (),
-    }
+    });
 }
 
 /// Traverse the AST and provide the method arguments to the given closure
 pub fn walk_args<'a, F: FnMut(&'a ast::Method, &'a ast::Arg)>(ast: &'a ast::Aidl, mut f: F) {
-    match ast.item {
-        ast::Item::Interface(ref i) => {
+    walk_items(ast, |item| {
+        if let ast::Item::Interface(ref i) = item {
             i.elements.iter().for_each(|el| match el {
                 ast::InterfaceElement::Method(m) => m.args.iter().for_each(|arg| {
                     f(m, arg);
@@ -307,7 +441,5 @@ pub fn walk_args<'a, F: FnMut(&'a ast::Method, &'a ast::Arg)>(ast: &'a ast::Aidl
                 ast::InterfaceElement::Const(_) => (),
             });
         }
-        ast::Item::Parcelable(_) => (),
-        ast::Item::Enum(_) => (),
-    }
+    });
 }
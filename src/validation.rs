@@ -2,14 +2,19 @@ use std::collections::{hash_map, HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::Hash;
 
+use crate::annotations;
 use crate::ast;
-use crate::diagnostic::{self, Diagnostic, DiagnosticKind};
+use crate::diagnostic::{self, Diagnostic, DiagnosticCode, DiagnosticKind};
+use crate::evaluation::Evaluator;
+use crate::index::ProjectIndex;
 use crate::parser::ParseFileResult;
 use crate::traverse;
 
 pub(crate) fn validate<ID>(
     keys: HashMap<String, ast::ResolvedItemKind>,
     lalrpop_results: HashMap<ID, ParseFileResult<ID>>,
+    contents: &HashMap<ID, String>,
+    index: &mut ProjectIndex<ID>,
 ) -> HashMap<ID, ParseFileResult<ID>>
 where
     ID: Eq + Hash + Clone + Debug,
@@ -17,7 +22,33 @@ where
     // Defined imports: all the imported item keys + add the Android built-in (as unknown)
     let defined = keys;
 
-    lalrpop_results
+    // Number of type parameters of the generic parcelables (e.g. `pkg.Pair` => 2)
+    let type_parameter_counts: HashMap<ast::ItemKey, usize> = lalrpop_results
+        .values()
+        .filter_map(|fr| fr.ast.as_ref())
+        .flat_map(|ast| ast.get_all_items())
+        .filter(|(_, item)| !item.get_type_parameters().is_empty())
+        .map(|(key, item)| (key, item.get_type_parameters().len()))
+        .collect();
+
+    // Evaluator of the constant expressions (which may reference consts from other files)
+    let mut evaluator = Evaluator::new(
+        lalrpop_results.values().filter_map(|fr| fr.ast.as_ref()),
+        &defined,
+    );
+
+    // Declarations of the project (the first declaration of an item, by file name, is
+    // its definition)
+    let mut asts: Vec<(&ID, &ast::Aidl)> = lalrpop_results
+        .iter()
+        .filter_map(|(id, fr)| fr.ast.as_ref().map(|ast| (id, ast)))
+        .collect();
+    asts.sort_by_cached_key(|(id, _)| format!("{id:?}"));
+    for (id, ast) in asts {
+        index.add_declarations(id, ast);
+    }
+
+    let results = lalrpop_results
         .into_iter()
         .map(|(id, mut fr)| {
             let mut ast = match fr.ast {
@@ -25,6 +56,8 @@ where
                 None => return (id, ParseFileResult { ast: None, ..fr }),
             };
 
+            let content = FileContent::new(contents.get(&id).map_or("", String::as_str));
+
             // Imports as qualified names
             let imports: HashSet<String> =
                 ast.imports.iter().map(|i| i.get_qualified_name()).collect();
@@ -37,35 +70,74 @@ where
                 .collect();
 
             // Resolve types (check custom types and set definition if found in imports)
-            let resolved = resolve_types(
+            let mut resolved = resolve_types(
                 &mut ast,
                 &imports,
                 &declared_parcelables,
                 &defined,
+                &content,
                 &mut fr.diagnostics,
             );
 
+            // Items referenced by constant expressions (e.g. `Other.FOO`) are also used
+            for key in evaluator.get_referenced_items(&ast) {
+                mark_as_resolved(&key, &defined, &mut resolved);
+            }
+
             // Check imports (e.g. unresolved, unused, duplicated)
-            let import_map = check_imports(&ast.imports, &resolved, &defined, &mut fr.diagnostics);
+            let import_map = check_imports(
+                &ast.imports,
+                &resolved,
+                &defined,
+                &content,
+                &mut fr.diagnostics,
+            );
 
             // Check declared parcelables
             check_declared_parcelables(
                 &ast.declared_parcelables,
                 &import_map,
                 &resolved,
+                &content,
                 &mut fr.diagnostics,
             );
 
             // Check containers (e.g.: map parameters)
             check_containers(&ast, &mut fr.diagnostics);
 
-            if let ast::Item::Interface(ref mut interface) = ast.item {
-                // Set up oneway interface (adjust methods to be oneway)
-                set_up_oneway_interface(interface, &mut fr.diagnostics);
-            }
+            // Check type arguments of generic parcelables (e.g.: `Pair<String, int>`)
+            check_type_arguments(&ast, &type_parameter_counts, &mut fr.diagnostics);
 
-            // Check methods (e.g.: return type of async methods)
-            check_methods(&ast, &mut fr.diagnostics);
+            // Evaluate constant expressions (e.g.: const values, enum values)
+            evaluator.evaluate_ast(&mut ast, &mut fr.diagnostics);
+
+            // References to the items, consts and enum elements
+            index.add_references(&id, &ast, &evaluator);
+
+            traverse::walk_items_mut(&mut ast, |item, _| {
+                if let ast::Item::Interface(ref mut interface) = item {
+                    // Set up oneway interface (adjust methods to be oneway)
+                    set_up_oneway_interface(interface, &mut fr.diagnostics);
+
+                    // Set the transaction codes of the methods (explicit or implicit)
+                    set_effective_transact_codes(interface);
+                }
+            });
+
+            traverse::walk_items(&ast, |item| match item {
+                // Check methods (e.g.: return type of async methods)
+                ast::Item::Interface(ref interface) => {
+                    check_methods(interface, &mut fr.diagnostics)
+                }
+                // Check union (e.g.: fields and default values)
+                ast::Item::Union(ref union) => check_union(union, &mut fr.diagnostics),
+                // Check enum (e.g.: backing type and duplicated values)
+                ast::Item::Enum(ref enum_) => check_enum(enum_, &mut fr.diagnostics),
+                ast::Item::Parcelable(_) => (),
+            });
+
+            // Check annotations (e.g.: unknown, misplaced or with invalid parameters)
+            annotations::check_annotations(&ast, &mut fr.diagnostics);
 
             // Sort diagnostics by line
             fr.diagnostics.sort_by_key(|d| d.range.start.line_col.0);
@@ -78,7 +150,11 @@ where
                 },
             )
         })
-        .collect()
+        .collect();
+
+    index.sort_references();
+
+    results
 }
 
 fn set_up_oneway_interface(interface: &mut ast::Interface, diagnostics: &mut Vec<Diagnostic>) {
@@ -97,6 +173,7 @@ fn set_up_oneway_interface(interface: &mut ast::Interface, diagnostics: &mut Vec
             if method.oneway {
                 diagnostics.push(Diagnostic {
                     kind: DiagnosticKind::Warning,
+                    code: DiagnosticCode::RedundantOneway,
                     range: method.oneway_range.clone(),
                     message: format!(
                         "Method `{}` of oneway interface does not need to be marked as oneway",
@@ -107,7 +184,15 @@ fn set_up_oneway_interface(interface: &mut ast::Interface, diagnostics: &mut Vec
                     related_infos: Vec::from([diagnostic::RelatedInfo {
                         message: "oneway interface".to_owned(),
                         range: interface.symbol_range.clone(),
+                        file: None,
                     }]),
+                    fixes: Vec::from([diagnostic::Fix::remove(
+                        "remove oneway",
+                        ast::Range {
+                            start: method.oneway_range.start.clone(),
+                            end: method.return_type.full_range.start.clone(),
+                        },
+                    )]),
                 });
             } else {
                 // Force me
@@ -116,21 +201,83 @@ fn set_up_oneway_interface(interface: &mut ast::Interface, diagnostics: &mut Vec
         });
 }
 
+// Like the binder backends, the methods without explicit transaction code are numbered
+// by position. Mixed usage is an error (reported by check_methods()) and the codes are
+// then left undefined.
+fn set_effective_transact_codes(interface: &mut ast::Interface) {
+    let mut methods: Vec<&mut ast::Method> = interface
+        .elements
+        .iter_mut()
+        .filter_map(|el| match el {
+            ast::InterfaceElement::Const(_) => None,
+            ast::InterfaceElement::Method(m) => Some(m),
+        })
+        .collect();
+
+    let all_explicit = methods.iter().all(|m| m.transact_code.is_some());
+    let all_implicit = methods.iter().all(|m| m.transact_code.is_none());
+
+    methods.iter_mut().enumerate().for_each(|(i, method)| {
+        method.effective_transact_code = if all_explicit {
+            method.transact_code
+        } else if all_implicit {
+            Some(i as u32)
+        } else {
+            None
+        };
+    });
+}
+
 fn resolve_types(
     ast: &mut ast::Aidl,
     imports: &HashSet<String>,
     declared_parcelables: &HashSet<String>,
     defined: &HashMap<String, ast::ResolvedItemKind>,
+    content: &FileContent,
     diagnostics: &mut Vec<Diagnostic>,
 ) -> HashSet<String> {
     let mut resolved = HashSet::new();
+    let top_level_key = ast.get_key();
+    let import_insertion = ImportInsertion::new(ast, content);
+
+    // Type parameters of the generic parcelables, only visible inside their own body
+    let type_parameters: HashMap<ast::ItemKey, Vec<String>> = ast
+        .get_all_items()
+        .into_iter()
+        .map(|(key, item)| (key, item.get_type_parameters().to_vec()))
+        .filter(|(_, params)| !params.is_empty())
+        .collect();
+
+    traverse::walk_types_mut(ast, |type_: &mut ast::Type, item_key| {
+        if type_.kind == ast::TypeKind::Unresolved
+            && type_parameters
+                .get(item_key)
+                .is_some_and(|params| params.contains(&type_.name))
+        {
+            type_.kind = ast::TypeKind::TypeParameter;
+            return;
+        }
 
-    traverse::walk_types_mut(ast, |type_: &mut ast::Type| {
-        resolve_type(type_, imports, declared_parcelables, defined, diagnostics);
+        // Keys of the enclosing items, from the innermost to the top-level item
+        let scope: Vec<&str> = item_key
+            .match_indices('.')
+            .map(|(i, _)| &item_key[..i])
+            .chain([item_key])
+            .filter(|key| key.len() >= top_level_key.len())
+            .rev()
+            .collect();
+
+        resolve_type(
+            type_,
+            &scope,
+            imports,
+            declared_parcelables,
+            defined,
+            &import_insertion,
+            diagnostics,
+        );
         match &type_.kind {
-            ast::TypeKind::ResolvedItem(key, _) => {
-                resolved.insert(key.clone());
-            }
+            ast::TypeKind::ResolvedItem(key, _) => mark_as_resolved(key, defined, &mut resolved),
             ast::TypeKind::CharSequence => {
                 resolved.insert("java.lang.CharSequence".to_owned());
             }
@@ -147,11 +294,28 @@ fn resolve_types(
     resolved
 }
 
+// Also mark the enclosing items as resolved (e.g. `Outer` for `Outer.Inner`)
+fn mark_as_resolved(
+    key: &str,
+    defined: &HashMap<String, ast::ResolvedItemKind>,
+    resolved: &mut HashSet<String>,
+) {
+    key.match_indices('.')
+        .map(|(i, _)| &key[..i])
+        .filter(|prefix| defined.contains_key(*prefix))
+        .for_each(|prefix| {
+            resolved.insert(prefix.to_owned());
+        });
+    resolved.insert(key.to_owned());
+}
+
 fn resolve_type(
     type_: &mut ast::Type,
+    scope: &[ast::ItemKeyRef],
     imports: &HashSet<String>,
     declared_parcelables: &HashSet<String>,
     defined: &HashMap<String, ast::ResolvedItemKind>,
+    import_insertion: &ImportInsertion,
     diagnostics: &mut Vec<Diagnostic>,
 ) {
     if type_.kind != ast::TypeKind::Unresolved {
@@ -167,21 +331,9 @@ fn resolve_type(
         }
     }
 
-    // Unresolved type is in import path?
-    if let Some(import_path) = imports.iter().find(|import_path| {
-        &type_.name == *import_path || import_path.ends_with(&format!(".{}", type_.name))
-    }) {
-        if let Some(item_kind) = defined.get(import_path) {
-            // Imported type is defined => set resolved item
-            type_.kind = ast::TypeKind::ResolvedItem(import_path.to_owned(), item_kind.clone());
-            return;
-        }
-
-        // Imported but not defined => set resolved item as unknown import
-        type_.kind = ast::TypeKind::ResolvedItem(
-            import_path.to_owned(),
-            ast::ResolvedItemKind::UnknownImport,
-        );
+    // Unresolved type is visible from the current scope or in import path?
+    if let Some((key, item_kind)) = resolve_item_key(&type_.name, scope, imports, defined) {
+        type_.kind = ast::TypeKind::ResolvedItem(key, item_kind);
         return;
     }
 
@@ -218,18 +370,215 @@ fn resolve_type(
     // Unresolved type
     diagnostics.push(Diagnostic {
         kind: DiagnosticKind::Error,
+        code: DiagnosticCode::UnknownType,
         range: type_.symbol_range.clone(),
         message: format!("Unknown type `{}`", type_.name),
         context_message: Some("unknown type".to_owned()),
         hint: None,
         related_infos: Vec::new(),
+        fixes: get_import_candidates(&type_.name, defined)
+            .into_iter()
+            .map(|import| import_insertion.create_fix(&import))
+            .collect(),
     });
 }
 
+// Where to insert a missing import (after the last import or after the package)
+struct ImportInsertion {
+    position: ast::Position,
+    prefix: &'static str,
+    suffix: &'static str,
+}
+
+impl ImportInsertion {
+    fn new(ast: &ast::Aidl, content: &FileContent) -> Self {
+        // At the start of the line after the last import, so that the fix does not
+        // overlap with the removal of the last import (e.g. if it is unused)
+        match ast.imports.last() {
+            Some(import) => match content.get_next_line_start(&import.full_range) {
+                Some(position) => ImportInsertion {
+                    position,
+                    prefix: "",
+                    suffix: "\n",
+                },
+                None => ImportInsertion {
+                    position: content.get_statement_end(&import.full_range),
+                    prefix: "\n",
+                    suffix: "",
+                },
+            },
+            None => ImportInsertion {
+                position: content.get_statement_end(&ast.package.full_range),
+                prefix: "\n\n",
+                suffix: "",
+            },
+        }
+    }
+
+    fn create_fix(&self, import: &str) -> diagnostic::Fix {
+        diagnostic::Fix::insert(
+            &format!("import `{import}`"),
+            &self.position,
+            format!("{}import {import};{}", self.prefix, self.suffix),
+        )
+    }
+}
+
+// Content of the file, for the fixes which go beyond the ranges of the AST (e.g. the `;`
+// and the end of line after an import)
+struct FileContent<'a> {
+    content: &'a str,
+    lookup: line_col::LineColLookup<'a>,
+}
+
+impl<'a> FileContent<'a> {
+    fn new(content: &'a str) -> Self {
+        FileContent {
+            content,
+            lookup: line_col::LineColLookup::new(content),
+        }
+    }
+
+    // Position after the `;` which terminates the statement (e.g. `import a.B;`)
+    fn get_statement_end(&self, range: &ast::Range) -> ast::Position {
+        match self.find_statement_end(range) {
+            Some(end) => ast::Position::new(&self.lookup, end),
+            None => range.end.clone(),
+        }
+    }
+
+    // Start of the line after the statement (None if it is on the last line)
+    fn get_next_line_start(&self, range: &ast::Range) -> Option<ast::Position> {
+        let end = self.find_statement_end(range)?;
+        let line_end = end + self.content[end..].find('\n')? + 1;
+        Some(ast::Position::new(&self.lookup, line_end))
+    }
+
+    // Range which removes the statement with its `;`, and its whole line if there is
+    // nothing else on it (so that no empty line is left)
+    fn get_statement_removal_range(&self, range: &ast::Range) -> ast::Range {
+        let end = match self.find_statement_end(range) {
+            Some(end) => end,
+            None => return range.clone(),
+        };
+        let start = range.start.offset;
+
+        let content = self.content;
+        let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = content[end..]
+            .find('\n')
+            .map_or(content.len(), |i| end + i + 1);
+        if content[line_start..start].trim().is_empty() && content[end..line_end].trim().is_empty()
+        {
+            ast::Range::new(&self.lookup, line_start, line_end)
+        } else {
+            ast::Range::new(&self.lookup, start, end)
+        }
+    }
+
+    fn find_statement_end(&self, range: &ast::Range) -> Option<usize> {
+        let end = range.end.offset;
+        if self.content.get(..end)?.ends_with(';') {
+            // The range already includes the `;` (e.g. `parcelable Foo;`)
+            return Some(end);
+        }
+        let rest = self.content.get(end..)?;
+        let semicolon = rest.find(|c: char| !c.is_whitespace())?;
+        rest[semicolon..]
+            .starts_with(';')
+            .then_some(end + semicolon + 1)
+    }
+}
+
+// Defined items which would resolve the given type name if imported, e.g.:
+// - `Name` => `pkg.Name`
+// - `Outer.Inner` => `pkg.Outer` (for `pkg.Outer.Inner`)
+fn get_import_candidates(
+    name: &str,
+    defined: &HashMap<String, ast::ResolvedItemKind>,
+) -> Vec<String> {
+    let first = name.split('.').next().unwrap_or(name);
+    let suffix = format!(".{name}");
+
+    let mut candidates: Vec<String> = defined
+        .keys()
+        .filter_map(|key| key.strip_suffix(&suffix))
+        .map(|path| format!("{path}.{first}"))
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Resolve the name of an item (e.g. `Other` or `Outer.Inner`) from the given scope
+///
+/// Items which are imported but not defined are resolved as unknown imports.
+pub(crate) fn resolve_item_key(
+    name: &str,
+    scope: &[ast::ItemKeyRef],
+    imports: &HashSet<String>,
+    defined: &HashMap<String, ast::ResolvedItemKind>,
+) -> Option<(String, ast::ResolvedItemKind)> {
+    // Item visible from the current scope, e.g. a nested item (`Inner` or `Outer.Inner`)
+    // or the top-level item itself?
+    if let Some((key, item_kind)) = resolve_in_scope(name, scope, defined) {
+        return Some((key, item_kind.clone()));
+    }
+
+    // Item in import path?
+    let qualified_name = imports
+        .iter()
+        .find_map(|import_path| resolve_with_import(name, import_path))?;
+    let item_kind = defined
+        .get(&qualified_name)
+        .cloned()
+        .unwrap_or(ast::ResolvedItemKind::UnknownImport);
+
+    Some((qualified_name, item_kind))
+}
+
+// Look for the item in the enclosing scopes (from the innermost to the top-level item)
+fn resolve_in_scope<'a>(
+    name: &str,
+    scope: &[ast::ItemKeyRef],
+    defined: &'a HashMap<String, ast::ResolvedItemKind>,
+) -> Option<(String, &'a ast::ResolvedItemKind)> {
+    let top_level_key = scope.last()?;
+
+    // Inside the enclosing items (e.g. `Inner` from `Outer`)
+    let candidates = scope.iter().map(|key| format!("{key}.{name}"));
+
+    // Inside the package but only for the top-level item (e.g. `Outer.Inner` or `Outer`)
+    let package_candidate = top_level_key
+        .rsplit_once('.')
+        .map(|(package, _)| format!("{package}.{name}"))
+        .filter(|key| key == top_level_key || key.starts_with(&format!("{top_level_key}.")));
+
+    candidates
+        .chain(package_candidate)
+        .find_map(|key| defined.get(&key).map(|kind| (key, kind)))
+}
+
+// Return the qualified name of the type if it matches the import, e.g.:
+// - `Name` with import `pkg.Name` => `pkg.Name`
+// - `Outer.Inner` with import `pkg.Outer` => `pkg.Outer.Inner`
+fn resolve_with_import(name: &str, import_path: &str) -> Option<String> {
+    if name == import_path || import_path.ends_with(&format!(".{name}")) {
+        return Some(import_path.to_owned());
+    }
+
+    let (first, rest) = name.split_once('.')?;
+    if import_path.ends_with(&format!(".{first}")) {
+        Some(format!("{import_path}.{rest}"))
+    } else {
+        None
+    }
+}
+
 fn check_imports<'a>(
     imports: &'a [ast::Import],
     resolved: &'a HashSet<String>,
     defined: &'a HashMap<String, ast::ResolvedItemKind>,
+    content: &FileContent,
     diagnostics: &mut Vec<Diagnostic>,
 ) -> HashMap<String, &'a ast::Import> {
     // - detect duplicated imports
@@ -240,6 +589,7 @@ fn check_imports<'a>(
                 hash_map::Entry::Occupied(previous) => {
                     diagnostics.push(Diagnostic {
                         kind: DiagnosticKind::Error,
+                        code: DiagnosticCode::DuplicatedImport,
                         range: import.symbol_range.clone(),
                         message: format!("Duplicated import `{}`", import.get_qualified_name()),
                         context_message: Some("duplicated import".to_owned()),
@@ -247,7 +597,12 @@ fn check_imports<'a>(
                         related_infos: Vec::from([diagnostic::RelatedInfo {
                             message: "previous location".to_owned(),
                             range: previous.get().symbol_range.clone(),
+                            file: None,
                         }]),
+                        fixes: Vec::from([diagnostic::Fix::remove(
+                            "remove duplicated import",
+                            content.get_statement_removal_range(&import.full_range),
+                        )]),
                     });
                 }
                 hash_map::Entry::Vacant(v) => {
@@ -265,6 +620,7 @@ fn check_imports<'a>(
             // No item can be found with the given import path
             diagnostics.push(Diagnostic {
                 kind: DiagnosticKind::Warning,
+                code: DiagnosticCode::UnresolvedImport,
                 range: import.symbol_range.clone(),
                 message: format!("Unresolved import `{qualified_import}`"),
                 context_message: Some("unresolved import".to_owned()),
@@ -272,16 +628,22 @@ fn check_imports<'a>(
                     "Note: this is fine if your client is able to import the same item".to_owned(),
                 ),
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             });
         } else if !resolved.contains(qualified_import) {
             // No type resolved for this import
             diagnostics.push(Diagnostic {
                 kind: DiagnosticKind::Warning,
+                code: DiagnosticCode::UnusedImport,
                 range: import.symbol_range.clone(),
                 message: format!("Unused import `{qualified_import}`"),
                 context_message: Some("unused import".to_owned()),
                 hint: None,
                 related_infos: Vec::new(),
+                fixes: Vec::from([diagnostic::Fix::remove(
+                    "remove unused import",
+                    content.get_statement_removal_range(&import.full_range),
+                )]),
             });
         }
     }
@@ -293,6 +655,7 @@ fn check_declared_parcelables(
     declared_parcelables: &[ast::Import],
     imports: &HashMap<String, &ast::Import>,
     resolved: &HashSet<String>,
+    content: &FileContent,
     diagnostics: &mut Vec<Diagnostic>,
 ) {
     // - detect duplicated parcelables (or name which was already imported)
@@ -309,6 +672,7 @@ fn check_declared_parcelables(
                 {
                     diagnostics.push(Diagnostic {
                         kind: DiagnosticKind::Error,
+                        code: DiagnosticCode::ConflictingDeclaredParcelable,
                         range: declared_parcelable.symbol_range.clone(),
                         message: format!(
                             "Declared parcelable conflicts with import `{}`",
@@ -319,7 +683,9 @@ fn check_declared_parcelables(
                         related_infos: Vec::from([diagnostic::RelatedInfo {
                             message: "location of conflicting import".to_owned(),
                             range: conflicting_import.symbol_range.clone(),
+                            file: None,
                         }]),
+                        fixes: Vec::new(),
                     });
 
                     return map;
@@ -329,6 +695,7 @@ fn check_declared_parcelables(
                     hash_map::Entry::Occupied(previous) => {
                         diagnostics.push(Diagnostic {
                             kind: DiagnosticKind::Error,
+                            code: DiagnosticCode::MultipleParcelableDeclarations,
                             range: declared_parcelable.symbol_range.clone(),
                             message: format!("Multiple parcelable declarations `{qualified_name}`"),
                             context_message: Some("duplicated declaration".to_owned()),
@@ -336,7 +703,9 @@ fn check_declared_parcelables(
                             related_infos: Vec::from([diagnostic::RelatedInfo {
                                 message: "previous location".to_owned(),
                                 range: previous.get().symbol_range.clone(),
+                                file: None,
                             }]),
+                            fixes: Vec::new(),
                         });
                     }
                     hash_map::Entry::Vacant(v) => {
@@ -352,20 +721,27 @@ fn check_declared_parcelables(
             // No type resolved for this import
             diagnostics.push(Diagnostic {
                 kind: DiagnosticKind::Warning,
+                code: DiagnosticCode::UnusedDeclaredParcelable,
                 range: declared_parcelable.symbol_range.clone(),
                 message: format!("Unused declared parcelable `{}`", declared_parcelable.name),
                 context_message: Some("unused declared parcelable".to_owned()),
                 hint: None,
                 related_infos: Vec::new(),
+                fixes: Vec::from([diagnostic::Fix::remove(
+                    "remove declared parcelable",
+                    content.get_statement_removal_range(&declared_parcelable.full_range),
+                )]),
             });
         } else {
             diagnostics.push(Diagnostic {
                 kind: DiagnosticKind::Warning,
+                code: DiagnosticCode::DeclaredParcelableUsage,
                 range: declared_parcelable.full_range.clone(),
                 message: format!("Usage of declared parcelable `{}`", declared_parcelable.name),
                 context_message: Some(String::from("declared parcelable")),
                 hint: Some(String::from("It is recommended to define parcelables in AIDL to garantee compatilibity between languages")),
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             });
         }
     }
@@ -387,11 +763,13 @@ fn check_container(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
                 0 => {
                     diagnostics.push(Diagnostic {
                         kind: DiagnosticKind::Warning,
+                        code: DiagnosticCode::NonGenericList,
                         message: String::from("Declaring a non-generic list is not recommended"),
                         context_message: Some("non-generic list".to_owned()),
                         range: type_.symbol_range.clone(),
                         hint: Some("consider adding a parameter (e.g.: List<String>)".to_owned()),
                         related_infos: Vec::new(),
+                        fixes: Vec::new(),
                     });
                     return;
                 }
@@ -408,6 +786,7 @@ fn check_container(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
                 0 => {
                     diagnostics.push(Diagnostic {
                         kind: DiagnosticKind::Warning,
+                        code: DiagnosticCode::NonGenericMap,
                         message: String::from("Declaring a non-generic map is not recommended"),
                         context_message: Some("non-generic map".to_owned()),
                         range: type_.symbol_range.clone(),
@@ -416,6 +795,7 @@ fn check_container(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
                                 .to_owned(),
                         ),
                         related_infos: Vec::new(),
+                        fixes: Vec::new(),
                     });
                     return;
                 }
@@ -431,109 +811,217 @@ fn check_container(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
     };
 }
 
-fn check_methods(file: &ast::Aidl, diagnostics: &mut Vec<Diagnostic>) {
+fn check_type_arguments(
+    ast: &ast::Aidl,
+    type_parameter_counts: &HashMap<ast::ItemKey, usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    traverse::walk_types(ast, |type_: &ast::Type| {
+        check_type_argument_count(type_, type_parameter_counts, diagnostics)
+    });
+}
+
+// The number of type arguments must match the number of type parameters of the item
+fn check_type_argument_count(
+    type_: &ast::Type,
+    type_parameter_counts: &HashMap<ast::ItemKey, usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let expected = match &type_.kind {
+        ast::TypeKind::ResolvedItem(
+            _,
+            ast::ResolvedItemKind::ForwardDeclaredParcelable | ast::ResolvedItemKind::UnknownImport,
+        ) => return, // we don't know
+        ast::TypeKind::ResolvedItem(key, _) => {
+            type_parameter_counts.get(key).copied().unwrap_or_default()
+        }
+        ast::TypeKind::TypeParameter if !type_.generic_types.is_empty() => 0,
+        _ => return,
+    };
+
+    let found = type_.generic_types.len();
+    if found == expected {
+        return;
+    }
+
+    diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::Error,
+        code: DiagnosticCode::WrongTypeArgumentCount,
+        range: type_.full_range.clone(),
+        message: if expected == 0 {
+            format!("`{}` is not a generic type", type_.name)
+        } else {
+            format!(
+                "Wrong number of type arguments for `{}`: expected {expected}, found {found}",
+                type_.name
+            )
+        },
+        context_message: Some("wrong number of type arguments".to_owned()),
+        hint: None,
+        related_infos: Vec::new(),
+        fixes: Vec::new(),
+    });
+}
+
+fn check_methods(interface: &ast::Interface, diagnostics: &mut Vec<Diagnostic>) {
     let mut method_names: HashMap<String, &ast::Method> = HashMap::new();
     let mut first_method_without_id: Option<&ast::Method> = None;
     let mut first_method_with_id: Option<&ast::Method> = None;
     let mut method_ids: HashMap<u32, &ast::Method> = HashMap::new();
 
-    traverse::walk_methods(file, |method: &ast::Method| {
-        // Check individual method (e.g. return value, args, ...)
-        check_method(method, diagnostics);
+    interface
+        .elements
+        .iter()
+        .filter_map(|el| el.as_method())
+        .for_each(|method| {
+            // Check individual method (e.g. return value, args, ...)
+            check_method(method, diagnostics);
 
-        if let Some(previous) = method_names.get(&method.name) {
-            // Found already exists => ERROR
-            diagnostics.push(Diagnostic {
-                kind: DiagnosticKind::Error,
-                range: method.symbol_range.clone(),
-                message: format!("Duplicated method name `{}`", method.name),
-                context_message: Some("duplicated method name".to_owned()),
-                hint: None,
-                related_infos: Vec::from([diagnostic::RelatedInfo {
-                    message: "previous location".to_owned(),
-                    range: previous.symbol_range.clone(),
-                }]),
-            });
-            return;
-        }
+            if let Some(previous) = method_names.get(&method.name) {
+                // Found already exists => ERROR
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::Error,
+                    code: DiagnosticCode::DuplicatedMethodName,
+                    range: method.symbol_range.clone(),
+                    message: format!("Duplicated method name `{}`", method.name),
+                    context_message: Some("duplicated method name".to_owned()),
+                    hint: None,
+                    related_infos: Vec::from([diagnostic::RelatedInfo {
+                        message: "previous location".to_owned(),
+                        range: previous.symbol_range.clone(),
+                        file: None,
+                    }]),
+                    fixes: Vec::new(),
+                });
+                return;
+            }
+
+            method_names.insert(method.name.clone(), method);
+
+            let is_mixed_now_with_id = first_method_with_id.is_none()
+                && first_method_without_id.is_some()
+                && method.transact_code.is_some();
+            let is_mixed_now_without_id = first_method_without_id.is_none()
+                && !method_ids.is_empty()
+                && method.transact_code.is_none();
+
+            if is_mixed_now_with_id || is_mixed_now_without_id {
+                let info_previous = if is_mixed_now_with_id {
+                    diagnostic::RelatedInfo {
+                        message: "method without id".to_owned(),
+                        range: first_method_without_id
+                            .as_ref()
+                            .unwrap()
+                            .transact_code_range
+                            .clone(),
+                        file: None,
+                    }
+                } else {
+                    diagnostic::RelatedInfo {
+                        message: "method with id".to_owned(),
+                        range: first_method_with_id
+                            .as_ref()
+                            .unwrap()
+                            .transact_code_range
+                            .clone(),
+                        file: None,
+                    }
+                };
+
+                // Methods are mixed (with/without id)
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::Error,
+                    code: DiagnosticCode::MixedMethodIds,
+                    range: method.transact_code_range.clone(),
+                    message: String::from("Mixed usage of method ids"),
+                    context_message: None,
+                    hint: Some(String::from(
+                        "Either all methods should have an id or none of them",
+                    )),
+                    related_infos: Vec::from([info_previous]),
+                    fixes: Vec::new(),
+                });
+            }
 
-        method_names.insert(method.name.clone(), method);
-
-        let is_mixed_now_with_id = first_method_with_id.is_none()
-            && first_method_without_id.is_some()
-            && method.transact_code.is_some();
-        let is_mixed_now_without_id = first_method_without_id.is_none()
-            && !method_ids.is_empty()
-            && method.transact_code.is_none();
-
-        if is_mixed_now_with_id || is_mixed_now_without_id {
-            let info_previous = if is_mixed_now_with_id {
-                diagnostic::RelatedInfo {
-                    message: "method without id".to_owned(),
-                    range: first_method_without_id
-                        .as_ref()
-                        .unwrap()
-                        .transact_code_range
-                        .clone(),
+            if method.transact_code.is_some() {
+                // First method with id
+                if first_method_with_id.is_none() {
+                    first_method_with_id = Some(method);
                 }
             } else {
-                diagnostic::RelatedInfo {
-                    message: "method with id".to_owned(),
-                    range: first_method_with_id
-                        .as_ref()
-                        .unwrap()
-                        .transact_code_range
-                        .clone(),
+                // First method without id
+                if first_method_without_id.is_none() {
+                    first_method_without_id = Some(method);
                 }
-            };
-
-            // Methods are mixed (with/without id)
-            diagnostics.push(Diagnostic {
-                kind: DiagnosticKind::Error,
-                range: method.transact_code_range.clone(),
-                message: String::from("Mixed usage of method ids"),
-                context_message: None,
-                hint: Some(String::from(
-                    "Either all methods should have an id or none of them",
-                )),
-                related_infos: Vec::from([info_previous]),
-            });
-        }
-
-        if method.transact_code.is_some() {
-            // First method with id
-            if first_method_with_id.is_none() {
-                first_method_with_id = Some(method);
-            }
-        } else {
-            // First method without id
-            if first_method_without_id.is_none() {
-                first_method_without_id = Some(method);
             }
-        }
 
-        if let Some(id) = method.transact_code {
-            match method_ids.entry(id) {
-                hash_map::Entry::Occupied(oe) => {
-                    // Method id already defined
-                    diagnostics.push(Diagnostic {
-                        kind: DiagnosticKind::Error,
-                        range: method.transact_code_range.clone(),
-                        message: String::from("Duplicated method id"),
-                        context_message: Some("duplicated import".to_owned()),
-                        hint: None,
-                        related_infos: Vec::from([diagnostic::RelatedInfo {
-                            range: oe.get().transact_code_range.clone(),
-                            message: String::from("previous method"),
-                        }]),
-                    });
-                }
-                hash_map::Entry::Vacant(ve) => {
-                    // First method with this id
-                    ve.insert(method);
+            if let Some(id) = method.transact_code {
+                match method_ids.entry(id) {
+                    hash_map::Entry::Occupied(oe) => {
+                        // Method id already defined
+                        diagnostics.push(Diagnostic {
+                            kind: DiagnosticKind::Error,
+                            code: DiagnosticCode::DuplicatedMethodId,
+                            range: method.transact_code_range.clone(),
+                            message: String::from("Duplicated method id"),
+                            context_message: Some("duplicated import".to_owned()),
+                            hint: None,
+                            related_infos: Vec::from([diagnostic::RelatedInfo {
+                                range: oe.get().transact_code_range.clone(),
+                                message: String::from("previous method"),
+                                file: None,
+                            }]),
+                            fixes: Vec::new(),
+                        });
+                    }
+                    hash_map::Entry::Vacant(ve) => {
+                        // First method with this id
+                        ve.insert(method);
+                    }
                 }
             }
-        }
+        });
+
+    check_implicit_transact_codes(interface, diagnostics);
+}
+
+// Methods of stable interfaces are renumbered when a method is inserted before them
+fn check_implicit_transact_codes(interface: &ast::Interface, diagnostics: &mut Vec<Diagnostic>) {
+    if !interface
+        .annotations
+        .iter()
+        .any(|a| a.name == "@VintfStability")
+    {
+        return;
+    }
+
+    let methods: Vec<&ast::Method> = interface
+        .elements
+        .iter()
+        .filter_map(|el| el.as_method())
+        .collect();
+    if methods.is_empty() || methods.iter().any(|m| m.transact_code.is_some()) {
+        return;
+    }
+
+    diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::Warning,
+        code: DiagnosticCode::ImplicitTransactCode,
+        range: interface.symbol_range.clone(),
+        message: format!(
+            "Implicit transaction codes in stable interface `{}`",
+            interface.name
+        ),
+        context_message: Some("implicit transaction codes".to_owned()),
+        hint: Some(String::from(
+            "Inserting or reordering methods changes the transaction codes of the methods below: only append new methods, or give explicit transaction codes (e.g. `void foo() = 0;`)",
+        )),
+        related_infos: Vec::from([diagnostic::RelatedInfo {
+            range: methods[0].symbol_range.clone(),
+            message: String::from("transaction code 0, then numbered in declaration order"),
+            file: None,
+        }]),
+        fixes: Vec::new(),
     });
 }
 
@@ -541,6 +1029,7 @@ fn check_method(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
     if method.oneway && method.return_type.kind != ast::TypeKind::Void {
         diagnostics.push(Diagnostic {
             kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidAsyncReturnType,
             message: format!(
                 "Invalid return type of async method `{}`",
                 method.return_type.name,
@@ -549,6 +1038,7 @@ fn check_method(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
             range: method.return_type.symbol_range.clone(),
             hint: Some("return type of async methods must be `void`".to_owned()),
             related_infos: Vec::new(),
+            fixes: Vec::new(),
         });
     }
 
@@ -574,11 +1064,17 @@ fn check_method_args(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
                 if arg.direction == ast::Direction::Unspecified {
                     diagnostics.push(Diagnostic {
                         kind: DiagnosticKind::Error,
+                        code: DiagnosticCode::MissingDirection,
                         message: format!("Missing direction for `{}`", arg.arg_type.name,),
                         context_message: Some("missing direction".to_owned()),
                         range: range.clone(),
                         hint: Some(format!("direction is required for {for_elements}")),
                         related_infos: Vec::new(),
+                        fixes: Vec::from([diagnostic::Fix::insert(
+                            "add `in` direction",
+                            &arg.full_range.start,
+                            "in ".to_owned(),
+                        )]),
                     });
                 }
             }
@@ -589,11 +1085,13 @@ fn check_method_args(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
                 ) {
                     diagnostics.push(Diagnostic {
                         kind: DiagnosticKind::Error,
+                        code: DiagnosticCode::InvalidDirection,
                         message: format!("Invalid direction for `{}`", arg.arg_type.name),
                         context_message: Some("invalid direction".to_owned()),
                         range: range.clone(),
                         hint: Some(format!("{for_elements} can only be `in` or omitted")),
                         related_infos: Vec::new(),
+                        fixes: Vec::new(),
                     });
                 }
             }
@@ -604,6 +1102,7 @@ fn check_method_args(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
                 ) {
                     diagnostics.push(Diagnostic {
                         kind: DiagnosticKind::Error,
+                        code: DiagnosticCode::InvalidDirection,
                         message: format!("Invalid direction for `{}`", arg.arg_type.name),
                         context_message: Some("invalid direction".to_owned()),
                         range: range.clone(),
@@ -613,17 +1112,20 @@ fn check_method_args(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
                             format!("{for_elements} must be specified")
                         }),
                         related_infos: Vec::new(),
+                        fixes: Vec::new(),
                     });
                 }
             }
             RequirementForArgDirection::CannotBeAnArg(for_elements) => {
                 diagnostics.push(Diagnostic {
                     kind: DiagnosticKind::Error,
+                    code: DiagnosticCode::InvalidArgument,
                     message: format!("Invalid argument `{}`", arg.arg_type.name,),
                     context_message: Some("invalid argument".to_owned()),
                     range: range.clone(),
                     hint: Some(format!("{for_elements} cannot be an argument")),
                     related_infos: Vec::new(),
+                    fixes: Vec::new(),
                 });
             }
             RequirementForArgDirection::NoRequirement => (),
@@ -637,6 +1139,7 @@ fn check_method_args(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
         {
             diagnostics.push(Diagnostic {
                 kind: DiagnosticKind::Error,
+                code: DiagnosticCode::InvalidDirection,
                 message: format!("Invalid direction for `{}`", arg.arg_type.name),
                 context_message: Some("invalid direction".to_owned()),
                 range,
@@ -644,6 +1147,7 @@ fn check_method_args(method: &ast::Method, diagnostics: &mut Vec<Diagnostic>) {
                     "arguments of oneway methods can be neither `out` nor `inout`".to_owned(),
                 ),
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             });
         }
     }
@@ -688,6 +1192,9 @@ fn get_requirement_for_arg_direction(type_: &ast::Type) -> RequirementForArgDire
             _,
             ast::ResolvedItemKind::Parcelable | ast::ResolvedItemKind::ForwardDeclaredParcelable,
         ) => RequirementForArgDirection::DirectionRequired("parcelables"),
+        ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Union) => {
+            RequirementForArgDirection::DirectionRequired("unions")
+        }
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Interface) => {
             RequirementForArgDirection::CanOnlyBeInOrUnspecified("interfaces")
         }
@@ -697,10 +1204,101 @@ fn get_requirement_for_arg_direction(type_: &ast::Type) -> RequirementForArgDire
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => {
             RequirementForArgDirection::CanOnlyBeInOrUnspecified("objects")
         }
+        ast::TypeKind::TypeParameter => RequirementForArgDirection::NoRequirement,
         ast::TypeKind::Unresolved => RequirementForArgDirection::NoRequirement,
     }
 }
 
+// A union must have at least one field and only its first field may have a default value
+fn check_union(union: &ast::Union, diagnostics: &mut Vec<Diagnostic>) {
+    let fields: Vec<&ast::Field> = union
+        .elements
+        .iter()
+        .filter_map(|el| el.as_field())
+        .collect();
+
+    if fields.is_empty() {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Error,
+            code: DiagnosticCode::EmptyUnion,
+            range: union.symbol_range.clone(),
+            message: format!("Union `{}` has no field", union.name),
+            context_message: Some("empty union".to_owned()),
+            hint: Some("a union needs at least one field".to_owned()),
+            related_infos: Vec::new(),
+            fixes: Vec::new(),
+        });
+        return;
+    }
+
+    for field in fields.iter().skip(1).filter(|f| f.value.is_some()) {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidUnionDefaultValue,
+            range: field.symbol_range.clone(),
+            message: format!("Invalid default value for union field `{}`", field.name),
+            context_message: Some("invalid default value".to_owned()),
+            hint: Some("only the first field of a union may have a default value".to_owned()),
+            related_infos: Vec::from([diagnostic::RelatedInfo {
+                message: "first field".to_owned(),
+                range: fields[0].symbol_range.clone(),
+                file: None,
+            }]),
+            fixes: Vec::new(),
+        });
+    }
+}
+
+fn check_enum(enum_: &ast::Enum, diagnostics: &mut Vec<Diagnostic>) {
+    let backing_type = enum_.get_backing_type();
+    if !matches!(backing_type, "byte" | "int" | "long") {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidBackingType,
+            range: enum_.symbol_range.clone(),
+            message: format!(
+                "Invalid backing type `{backing_type}` for enum `{}`",
+                enum_.name
+            ),
+            context_message: Some("invalid backing type".to_owned()),
+            hint: Some("must be byte, int or long".to_owned()),
+            related_infos: Vec::new(),
+            fixes: Vec::new(),
+        });
+        return;
+    }
+
+    let mut elements_by_value: HashMap<i64, &ast::EnumElement> = HashMap::new();
+    for el in &enum_.elements {
+        let value = match el.evaluated_value {
+            Some(ast::ConstValue::Byte(v)) => v as i64,
+            Some(ast::ConstValue::Int(v)) => v as i64,
+            Some(ast::ConstValue::Long(v)) => v,
+            _ => continue,
+        };
+
+        match elements_by_value.get(&value) {
+            Some(previous) => diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::Warning,
+                code: DiagnosticCode::DuplicatedEnumValue,
+                range: el.symbol_range.clone(),
+                message: format!("Duplicated value `{value}` for enum element `{}`", el.name),
+                context_message: Some("duplicated value".to_owned()),
+                hint: Some(format!("same value as `{}`", previous.name)),
+                related_infos: Vec::from([diagnostic::RelatedInfo {
+                    message: "previous element".to_owned(),
+                    range: previous.symbol_range.clone(),
+                    file: None,
+                }]),
+                fixes: Vec::new(),
+            }),
+            None => {
+                elements_by_value.insert(value, el);
+            }
+        }
+    }
+}
+
 // Can only have one dimensional arrays
 // "Binder" type cannot be an array (with interface element...)
 // TODO: not allowed for ParcelableHolder, allowed for IBinder, ...
@@ -710,11 +1308,13 @@ fn check_array_element(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
         ast::TypeKind::Array => {
             diagnostics.push(Diagnostic {
                 kind: DiagnosticKind::Error,
+                code: DiagnosticCode::MultiDimensionalArray,
                 message: String::from("Unsupported multi-dimensional array"),
                 context_message: Some("unsupported array".to_owned()),
                 range: type_.symbol_range.clone(),
                 hint: Some("must be one-dimensional".to_owned()),
                 related_infos: Vec::new(),
+                fixes: Vec::new(),
             });
             return;
         }
@@ -729,16 +1329,19 @@ fn check_array_element(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
         ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelFileDescriptor) => true,
         ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder) => false,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Parcelable) => true,
+        ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Union) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Interface) => false,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Enum) => true, // OK: enum is backed by a primitive
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::ForwardDeclaredParcelable) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => true, // OK: it is an unknown object
-        ast::TypeKind::Unresolved => true, // we don't know
+        ast::TypeKind::TypeParameter => true, // checked on instantiation
+        ast::TypeKind::Unresolved => true,    // we don't know
     };
 
     if !ok {
         diagnostics.push(Diagnostic {
             kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidArrayElement,
             message: format!("Invalid array element `{}`", type_.name),
             context_message: Some("invalid parameter".to_owned()),
             range: type_.symbol_range.clone(),
@@ -746,6 +1349,7 @@ fn check_array_element(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
                 "must be a primitive, an enum, a String, a parcelable or a IBinder".to_owned(),
             ),
             related_infos: Vec::new(),
+            fixes: Vec::new(),
         });
     }
 }
@@ -765,16 +1369,19 @@ fn check_list_element(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
         ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelFileDescriptor) => true,
         ast::TypeKind::AndroidType(ast::AndroidTypeKind::ParcelableHolder) => false,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Parcelable) => true,
+        ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Union) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Interface) => false,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Enum) => false, // NO: enum is backed by a primitive
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::ForwardDeclaredParcelable) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => true, // OK: it is an (unknown) object
-        ast::TypeKind::Unresolved => true, // we don't know
+        ast::TypeKind::TypeParameter => true, // checked on instantiation
+        ast::TypeKind::Unresolved => true,    // we don't know
     };
 
     if !ok {
         diagnostics.push(Diagnostic {
             kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidListElement,
             message: format!("Invalid list element `{}`", type_.name),
             context_message: Some("invalid element".to_owned()),
             range: type_.symbol_range.clone(),
@@ -783,6 +1390,7 @@ fn check_list_element(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
                     .to_owned(),
             ),
             related_infos: Vec::new(),
+            fixes: Vec::new(),
         });
     }
 }
@@ -792,6 +1400,7 @@ fn check_map_key(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
     if !matches!(type_.kind, ast::TypeKind::String if type_.name == "String") {
         diagnostics.push(Diagnostic {
             kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidMapKey,
             message: format!("Invalid map key `{}`", type_.name),
             context_message: Some("invalid map key".to_owned()),
             range: type_.symbol_range.clone(),
@@ -800,6 +1409,7 @@ fn check_map_key(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
                     .to_owned(),
             ),
             related_infos: Vec::new(),
+            fixes: Vec::new(),
         });
     }
 }
@@ -816,21 +1426,25 @@ fn check_map_value(type_: &ast::Type, diagnostics: &mut Vec<Diagnostic>) {
         ast::TypeKind::Void => false,
         ast::TypeKind::AndroidType(_) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Parcelable) => true,
+        ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Union) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Interface) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::Enum) => false,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::ForwardDeclaredParcelable) => true,
         ast::TypeKind::ResolvedItem(_, ast::ResolvedItemKind::UnknownImport) => true,
-        ast::TypeKind::Unresolved => true, // we don't know
+        ast::TypeKind::TypeParameter => true, // checked on instantiation
+        ast::TypeKind::Unresolved => true,    // we don't know
     };
 
     if !ok {
         diagnostics.push(Diagnostic {
             kind: DiagnosticKind::Error,
+            code: DiagnosticCode::InvalidMapValue,
             message: format!("Invalid map value `{}`", type_.name),
             context_message: Some("invalid map value".to_owned()),
             range: type_.symbol_range.clone(),
             hint: Some("cannot not be a primitive".to_owned()),
             related_infos: Vec::new(),
+            fixes: Vec::new(),
         });
     }
 }
@@ -846,19 +1460,23 @@ mod tests {
     #[test]
     fn test_check_resolve_type() {
         let defined = HashMap::from([]);
+        let import_insertion = ImportInsertion {
+            position: utils::create_range(0).start,
+            prefix: "\n",
+            suffix: "",
+        };
 
         {
             // IBinder properly resolved
-            let mut t = utils::create_unresolved_type(
-                ast::AndroidTypeKind::IBinder.get_name(),
-                1,
-            );
+            let mut t = utils::create_unresolved_type(ast::AndroidTypeKind::IBinder.get_name(), 1);
             let mut diagnostics = Vec::new();
             resolve_type(
                 &mut t,
+                &[],
                 &HashSet::new(),
                 &HashSet::new(),
                 &defined,
+                &import_insertion,
                 &mut diagnostics,
             );
             assert_eq!(
@@ -877,9 +1495,11 @@ mod tests {
             let mut diagnostics = Vec::new();
             resolve_type(
                 &mut t,
+                &[],
                 &HashSet::new(),
                 &HashSet::new(),
                 &defined,
+                &import_insertion,
                 &mut diagnostics,
             );
             assert_eq!(t.kind, ast::TypeKind::Unresolved);
@@ -895,9 +1515,11 @@ mod tests {
             let mut diagnostics = Vec::new();
             resolve_type(
                 &mut t,
+                &[],
                 &HashSet::new(),
                 &HashSet::new(),
                 &defined,
+                &import_insertion,
                 &mut diagnostics,
             );
             assert_eq!(
@@ -913,9 +1535,11 @@ mod tests {
             let mut diagnostics = Vec::new();
             resolve_type(
                 &mut t,
+                &[],
                 &HashSet::new(),
                 &HashSet::new(),
                 &defined,
+                &import_insertion,
                 &mut diagnostics,
             );
             assert_eq!(t.kind, ast::TypeKind::Unresolved);
@@ -929,9 +1553,11 @@ mod tests {
             let mut diagnostics = Vec::new();
             resolve_type(
                 &mut t,
+                &[],
                 &imports,
                 &HashSet::new(),
                 &defined,
+                &import_insertion,
                 &mut diagnostics,
             );
             assert_eq!(
@@ -944,6 +1570,72 @@ mod tests {
             assert_eq!(diagnostics.len(), 0);
         }
 
+        {
+            // Nested item (`Inner` and `Outer.Inner`) from the scope of the outer item
+            let defined = HashMap::from([
+                ("test.Outer".into(), ast::ResolvedItemKind::Parcelable),
+                ("test.Outer.Inner".into(), ast::ResolvedItemKind::Enum),
+            ]);
+            for name in ["Inner", "Outer.Inner"] {
+                let mut t = utils::create_unresolved_type(name, 1);
+                let mut diagnostics = Vec::new();
+                resolve_type(
+                    &mut t,
+                    &["test.Outer"],
+                    &HashSet::new(),
+                    &HashSet::new(),
+                    &defined,
+                    &import_insertion,
+                    &mut diagnostics,
+                );
+                assert_eq!(
+                    t.kind,
+                    ast::TypeKind::ResolvedItem(
+                        "test.Outer.Inner".to_owned(),
+                        ast::ResolvedItemKind::Enum
+                    )
+                );
+                assert_eq!(diagnostics.len(), 0);
+            }
+
+            // Nested item from another item (via import of the outer item)
+            let mut t = utils::create_unresolved_type("Outer.Inner", 1);
+            let imports = HashSet::from(["test.Outer".to_owned()]);
+            let mut diagnostics = Vec::new();
+            resolve_type(
+                &mut t,
+                &["test.Other"],
+                &imports,
+                &HashSet::new(),
+                &defined,
+                &import_insertion,
+                &mut diagnostics,
+            );
+            assert_eq!(
+                t.kind,
+                ast::TypeKind::ResolvedItem(
+                    "test.Outer.Inner".to_owned(),
+                    ast::ResolvedItemKind::Enum
+                )
+            );
+            assert_eq!(diagnostics.len(), 0);
+
+            // Nested item from another item (without import)
+            let mut t = utils::create_unresolved_type("Inner", 1);
+            let mut diagnostics = Vec::new();
+            resolve_type(
+                &mut t,
+                &["test.Other"],
+                &HashSet::new(),
+                &HashSet::new(),
+                &defined,
+                &import_insertion,
+                &mut diagnostics,
+            );
+            assert_eq!(t.kind, ast::TypeKind::Unresolved);
+            assert_eq!(diagnostics.len(), 1);
+        }
+
         {
             // Forward-declared parcelable
             let mut t = utils::create_unresolved_type("ForwardDeclaredParcelable", 1);
@@ -951,9 +1643,11 @@ mod tests {
             let mut diagnostics = Vec::new();
             resolve_type(
                 &mut t,
+                &[],
                 &HashSet::new(),
                 &declared_parcelables,
                 &defined,
+                &import_insertion,
                 &mut diagnostics,
             );
             assert_eq!(
@@ -974,9 +1668,11 @@ mod tests {
             let mut diagnostics = Vec::new();
             resolve_type(
                 &mut t,
+                &[],
                 &HashSet::new(),
                 &declared_parcelables,
                 &defined,
+                &import_insertion,
                 &mut diagnostics,
             );
             assert_eq!(t.kind, ast::TypeKind::Unresolved);
@@ -1014,7 +1710,13 @@ mod tests {
         ]);
         let mut diagnostics = Vec::new();
 
-        check_imports(&imports, &resolved, &defined, &mut diagnostics);
+        check_imports(
+            &imports,
+            &resolved,
+            &defined,
+            &FileContent::new(""),
+            &mut diagnostics,
+        );
 
         diagnostics.sort_by_key(|d| d.range.start.line_col.0);
 
@@ -1070,6 +1772,7 @@ mod tests {
             &declared_parcelables,
             &import_map,
             &resolved,
+            &FileContent::new(""),
             &mut diagnostics,
         );
 
@@ -1309,6 +2012,7 @@ mod tests {
                 .into_iter()
                 .map(ast::InterfaceElement::Method)
                 .collect(),
+            nested_items: Vec::new(),
             annotations: Vec::new(),
             doc: None,
             full_range: utils::create_range(5),
@@ -1349,6 +2053,7 @@ mod tests {
             args: Vec::new(),
             annotations: Vec::new(),
             transact_code: None,
+            effective_transact_code: None,
             doc: None,
             symbol_range: utils::create_range(0),
             full_range: utils::create_range(0),
@@ -1387,30 +2092,22 @@ mod tests {
             create_method_with_name_and_id("method3", Some(1), 50),
         ]);
 
-        let ast = ast::Aidl {
-            package: ast::Package {
-                name: "test.package".into(),
-                symbol_range: utils::create_range(0),
-                full_range: utils::create_range(0),
-            },
-            imports: Vec::new(),
-            declared_parcelables: Vec::new(),
-            item: ast::Item::Interface(ast::Interface {
-                oneway: false,
-                name: "testMethod".into(),
-                elements: methods
-                    .into_iter()
-                    .map(ast::InterfaceElement::Method)
-                    .collect(),
-                annotations: Vec::new(),
-                doc: None,
-                full_range: utils::create_range(0),
-                symbol_range: utils::create_range(0),
-            }),
+        let interface = ast::Interface {
+            oneway: false,
+            name: "testMethod".into(),
+            elements: methods
+                .into_iter()
+                .map(ast::InterfaceElement::Method)
+                .collect(),
+            nested_items: Vec::new(),
+            annotations: Vec::new(),
+            doc: None,
+            full_range: utils::create_range(0),
+            symbol_range: utils::create_range(0),
         };
 
         let mut diagnostics = Vec::new();
-        check_methods(&ast, &mut diagnostics);
+        check_methods(&interface, &mut diagnostics);
 
         assert_eq!(diagnostics.len(), 3);
 
@@ -1430,6 +2127,275 @@ mod tests {
         assert_eq!(diagnostics[2].range.start.line_col.0, 51);
     }
 
+    #[test]
+    fn test_set_effective_transact_codes() {
+        let methods = Vec::from([
+            create_method_with_name_and_id("method0", None, 10),
+            create_method_with_name_and_id("method1", None, 20),
+            create_method_with_name_and_id("method2", Some(7), 30),
+        ]);
+
+        let mut interface = ast::Interface {
+            oneway: false,
+            name: "testMethod".into(),
+            elements: methods
+                .into_iter()
+                .map(ast::InterfaceElement::Method)
+                .collect(),
+            nested_items: Vec::new(),
+            annotations: Vec::new(),
+            doc: None,
+            full_range: utils::create_range(0),
+            symbol_range: utils::create_range(0),
+        };
+
+        set_effective_transact_codes(&mut interface);
+        let codes: Vec<Option<u32>> = interface
+            .elements
+            .iter()
+            .filter_map(|el| el.as_method())
+            .map(|m| m.effective_transact_code)
+            .collect();
+        assert_eq!(codes, [None, None, None]);
+
+        // Inserting a method before method1 would renumber it (but not method2)
+        let renumbered: Vec<&str> = interface
+            .get_renumbered_methods(1)
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(renumbered, ["method1"]);
+
+        let get_codes = |transact_codes: [Option<u32>; 3]| {
+            let mut interface = interface.clone();
+            interface
+                .elements
+                .iter_mut()
+                .zip(transact_codes)
+                .for_each(|(el, code)| {
+                    if let ast::InterfaceElement::Method(m) = el {
+                        m.transact_code = code;
+                    }
+                });
+            set_effective_transact_codes(&mut interface);
+            interface
+                .elements
+                .iter()
+                .filter_map(|el| el.as_method())
+                .map(|m| m.effective_transact_code)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(get_codes([None; 3]), [Some(0), Some(1), Some(2)]);
+        assert_eq!(
+            get_codes([Some(3), Some(1), Some(2)]),
+            [Some(3), Some(1), Some(2)]
+        );
+    }
+
+    #[test]
+    fn test_check_implicit_transact_codes() {
+        let mut methods = Vec::from([
+            create_method_with_name_and_id("method0", None, 10),
+            create_method_with_name_and_id("method1", None, 20),
+        ]);
+
+        let mut interface = ast::Interface {
+            oneway: false,
+            name: "IFoo".into(),
+            elements: methods
+                .clone()
+                .into_iter()
+                .map(ast::InterfaceElement::Method)
+                .collect(),
+            nested_items: Vec::new(),
+            annotations: Vec::new(),
+            doc: None,
+            full_range: utils::create_range(0),
+            symbol_range: utils::create_range(5),
+        };
+
+        // Not a stable interface
+        let mut diagnostics = Vec::new();
+        check_implicit_transact_codes(&interface, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 0);
+
+        interface.annotations.push(ast::Annotation {
+            name: "@VintfStability".into(),
+            key_values: HashMap::new(),
+        });
+        check_implicit_transact_codes(&interface, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Warning);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::ImplicitTransactCode);
+        assert_eq!(diagnostics[0].range.start.line_col.0, 5);
+
+        // Only the first method (whatever the number of methods)
+        assert_eq!(diagnostics[0].related_infos.len(), 1);
+        let info = &diagnostics[0].related_infos[0];
+        assert_eq!(
+            info.message,
+            "transaction code 0, then numbered in declaration order"
+        );
+        assert_eq!(info.range.start.line_col.0, 10);
+
+        // Explicit transaction codes
+        methods.iter_mut().zip(0..).for_each(|(m, i)| {
+            m.transact_code = Some(i);
+        });
+        interface.elements = methods
+            .into_iter()
+            .map(ast::InterfaceElement::Method)
+            .collect();
+        let mut diagnostics = Vec::new();
+        check_implicit_transact_codes(&interface, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 0);
+    }
+
+    #[test]
+    fn test_check_union() {
+        let mut union = ast::Union {
+            name: "TestUnion".into(),
+            elements: Vec::new(),
+            annotations: Vec::new(),
+            doc: None,
+            full_range: utils::create_range(0),
+            symbol_range: utils::create_range(0),
+        };
+
+        // Union without field -> error
+        let mut diagnostics = Vec::new();
+        check_union(&union, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Error);
+        assert!(diagnostics[0].message.contains("has no field"));
+
+        // Default value for the first field -> ok
+        union.elements = Vec::from([
+            ast::UnionElement::Field(utils::create_field("field1", Some("3"), 1)),
+            ast::UnionElement::Field(utils::create_field("field2", None, 2)),
+        ]);
+        let mut diagnostics = Vec::new();
+        check_union(&union, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 0);
+
+        // Default value for another field -> error
+        union
+            .elements
+            .push(ast::UnionElement::Field(utils::create_field(
+                "field3",
+                Some("4"),
+                3,
+            )));
+        let mut diagnostics = Vec::new();
+        check_union(&union, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Error);
+        assert!(diagnostics[0].message.contains("Invalid default value"));
+        assert_eq!(diagnostics[0].range.start.line_col.0, 3);
+        assert_eq!(diagnostics[0].related_infos[0].range.start.line_col.0, 1);
+    }
+
+    #[test]
+    fn test_check_enum() {
+        let create_element = |name: &str, value: i8, line: usize| ast::EnumElement {
+            name: name.into(),
+            value: None,
+            evaluated_value: Some(ast::ConstValue::Byte(value)),
+            doc: None,
+            full_range: utils::create_range(line),
+            symbol_range: utils::create_range(line),
+        };
+        let mut enum_ = ast::Enum {
+            name: "TestEnum".into(),
+            elements: Vec::from([create_element("A", 0, 1), create_element("B", 1, 2)]),
+            annotations: Vec::new(),
+            doc: None,
+            full_range: utils::create_range(0),
+            symbol_range: utils::create_range(0),
+        };
+
+        // Distinct values -> ok
+        let mut diagnostics = Vec::new();
+        check_enum(&enum_, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 0);
+
+        // Duplicated value -> warning
+        enum_.elements.push(create_element("C", 1, 3));
+        let mut diagnostics = Vec::new();
+        check_enum(&enum_, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Warning);
+        assert!(diagnostics[0].message.contains("Duplicated value `1`"));
+        assert_eq!(diagnostics[0].range.start.line_col.0, 3);
+        assert_eq!(diagnostics[0].related_infos[0].range.start.line_col.0, 2);
+
+        // Invalid backing type -> error
+        enum_.annotations.push(ast::Annotation {
+            name: "@Backing".into(),
+            key_values: HashMap::from([("type".into(), Some("\"String\"".into()))]),
+        });
+        let mut diagnostics = Vec::new();
+        check_enum(&enum_, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Error);
+        assert!(diagnostics[0]
+            .message
+            .contains("Invalid backing type `String`"));
+    }
+
+    #[test]
+    fn test_check_type_argument_count() {
+        let counts = HashMap::from([("test.Pair".to_owned(), 2)]);
+        let create_pair = |generic_types: Vec<ast::Type>| ast::Type {
+            generic_types,
+            ..utils::create_resolved_item_type("test.Pair", ast::ResolvedItemKind::Parcelable, 1)
+        };
+
+        // Same number of type arguments as type parameters -> ok
+        let mut diagnostics = Vec::new();
+        let t = create_pair(Vec::from([utils::create_string(1), utils::create_int(1)]));
+        check_type_argument_count(&t, &counts, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 0);
+
+        // Missing type argument -> error
+        let mut diagnostics = Vec::new();
+        let t = create_pair(Vec::from([utils::create_string(1)]));
+        check_type_argument_count(&t, &counts, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::Error);
+        assert!(diagnostics[0].message.contains("expected 2, found 1"));
+
+        // Generic parcelable without type argument -> error
+        let mut diagnostics = Vec::new();
+        let t = create_pair(Vec::new());
+        check_type_argument_count(&t, &counts, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("expected 2, found 0"));
+
+        // Type arguments for a non-generic item -> error
+        let mut diagnostics = Vec::new();
+        let t = ast::Type {
+            generic_types: Vec::from([utils::create_string(1)]),
+            ..utils::create_resolved_item_type("test.Other", ast::ResolvedItemKind::Parcelable, 1)
+        };
+        check_type_argument_count(&t, &counts, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("is not a generic type"));
+
+        // Forward-declared parcelable -> we don't know
+        let mut diagnostics = Vec::new();
+        let t = ast::Type {
+            generic_types: Vec::from([utils::create_string(1)]),
+            ..utils::create_resolved_item_type(
+                "Other",
+                ast::ResolvedItemKind::ForwardDeclaredParcelable,
+                1,
+            )
+        };
+        check_type_argument_count(&t, &counts, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 0);
+    }
+
     #[test]
     fn test_check_method_args() {
         let base_method = ast::Method {
@@ -1438,6 +2404,7 @@ mod tests {
             return_type: utils::create_void(0),
             args: Vec::new(),
             transact_code: None,
+            effective_transact_code: None,
             annotations: Vec::new(),
             doc: None,
             symbol_range: utils::create_range(0),
@@ -1759,6 +2726,7 @@ mod tests {
                 args: Vec::new(),
                 annotations: Vec::new(),
                 transact_code: id,
+                effective_transact_code: None,
                 doc: None,
                 symbol_range: create_range(line),
                 full_range: create_range(line),
@@ -1766,6 +2734,22 @@ mod tests {
                 oneway_range: create_range(line + 2),
             }
         }
+        pub fn create_field(name: &str, value: Option<&str>, line: usize) -> ast::Field {
+            ast::Field {
+                name: name.into(),
+                field_type: create_int(line),
+                value: value.map(|v| ast::ConstExpr {
+                    kind: ast::ConstExprKind::Integer(v.to_owned()),
+                    full_range: create_range(line),
+                }),
+                evaluated_value: None,
+                annotations: Vec::new(),
+                doc: None,
+                symbol_range: create_range(line),
+                full_range: create_range(line),
+            }
+        }
+
         pub fn create_arg(arg_type: ast::Type, direction: ast::Direction) -> ast::Arg {
             ast::Arg {
                 direction,
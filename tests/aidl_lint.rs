@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// Temporary directory with the given AIDL files
+fn create_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aidl-lint-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    for (path, content) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+        std::fs::write(path, content).expect("write file");
+    }
+
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aidl-lint"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("run aidl-lint")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_aidl_lint() {
+    let dir = create_dir(
+        "lint",
+        &[
+            (
+                "src/test/IFoo.aidl",
+                "package test;\nimport lib.Bar;\ninterface IFoo { void foo(in Bar b); }\n",
+            ),
+            (
+                "src/test/IWarn.aidl",
+                "package test;\noneway interface IWarn { oneway void foo(); }\n",
+            ),
+            (
+                "lib/lib/Bar.aidl",
+                "package lib;\nparcelable Bar { List l; }\n",
+            ),
+            (
+                "other/IErr.aidl",
+                "package other;\ninterface IErr { void foo(Unknown u); }\n",
+            ),
+        ],
+    );
+
+    // Warnings only (Bar is resolved via the import root, its own warning is not reported)
+    let output = run(&dir, &["-I", "lib", "--format", "plain", "src"]);
+    assert_eq!(output.status.code(), Some(0));
+    let text = stdout(&output);
+    assert!(text.contains("warning[AIDL0021]"));
+    assert!(!text.contains("AIDL0013"));
+    assert!(text.ends_with("0 error(s), 1 warning(s)\n"));
+
+    // Warnings are denied
+    let output = run(&dir, &["-I", "lib", "--deny-warnings", "src"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // Unresolved import without import root
+    let output = run(&dir, &["--format", "plain", "src/test/IFoo.aidl"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("warning[AIDL0008]"));
+
+    // Glob pattern and JSON Lines
+    let output = run(&dir, &["-Ilib", "--format=json-lines", "*/*.aidl"]);
+    assert_eq!(output.status.code(), Some(1));
+    let text = stdout(&output);
+    assert_eq!(text.lines().count(), 1);
+    assert!(text.contains(r#""code":"AIDL0005""#));
+
+    // SARIF
+    let output = run(&dir, &["-I", "lib", "-f", "sarif", "src", "other"]);
+    assert_eq!(output.status.code(), Some(1));
+    let sarif: serde_json::Value = serde_json::from_slice(&output.stdout).expect("valid json");
+    assert_eq!(
+        sarif["runs"][0]["results"].as_array().map(Vec::len),
+        Some(2)
+    );
+
+    // Invalid arguments or paths
+    assert_eq!(run(&dir, &[]).status.code(), Some(2));
+    assert_eq!(
+        run(&dir, &["--format", "xml", "src"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(run(&dir, &["missing/*.aidl"]).status.code(), Some(2));
+    assert_eq!(run(&dir, &["-I", "missing", "src"]).status.code(), Some(2));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -68,6 +68,11 @@ fn test_parse() {
         .message
         .contains("Unresolved import"));
     assert!(res["id1"].diagnostics[2].message.contains("Unused import"));
+    assert_eq!(
+        res["id1"].diagnostics[2].code,
+        aidl_parser::diagnostic::DiagnosticCode::UnusedImport
+    );
+    assert_eq!(res["id1"].diagnostics[2].code.as_str(), "AIDL0007");
     assert!(res["id2"].diagnostics.is_empty());
     assert!(res["id3"].diagnostics.is_empty());
 
@@ -79,6 +84,333 @@ fn test_parse() {
     assert_eq!(methods[1].name, "method2");
 }
 
+#[test]
+fn test_nested_types() {
+    let outer_aidl = r#"
+        package com.bwa.aidl_test;
+
+        parcelable Outer {
+            Inner inner;
+            Outer.InnerEnum innerEnum;
+
+            parcelable Inner {
+                InnerEnum e;
+            }
+
+            enum InnerEnum { VALUE1, VALUE2 }
+        }
+    "#;
+
+    let interface_aidl = r#"
+        package com.bwa.aidl_test;
+
+        import com.bwa.aidl_test.Outer;
+        import com.bwa.aidl_test.Outer.Inner;
+
+        interface MyInterface {
+            void method1(in Inner inner, Outer.InnerEnum e);
+        }
+    "#;
+
+    let mut parser = aidl_parser::Parser::new();
+    parser.add_content("id1", outer_aidl);
+    parser.add_content("id2", interface_aidl);
+    let res = parser.validate();
+
+    assert_eq!(res["id1"].diagnostics, []);
+    assert_eq!(res["id2"].diagnostics, []);
+
+    let ast1 = res["id1"].ast.as_ref().expect("ast");
+    assert_eq!(
+        ast1.get_all_keys()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>(),
+        [
+            "com.bwa.aidl_test.Outer.Inner",
+            "com.bwa.aidl_test.Outer.InnerEnum",
+            "com.bwa.aidl_test.Outer",
+        ]
+    );
+
+    let mut types = Vec::new();
+    aidl_parser::traverse::walk_types(ast1, |t| types.push(t.kind.clone()));
+    assert_eq!(
+        types,
+        [
+            aidl_parser::ast::TypeKind::ResolvedItem(
+                "com.bwa.aidl_test.Outer.Inner".into(),
+                aidl_parser::ast::ResolvedItemKind::Parcelable
+            ),
+            aidl_parser::ast::TypeKind::ResolvedItem(
+                "com.bwa.aidl_test.Outer.InnerEnum".into(),
+                aidl_parser::ast::ResolvedItemKind::Enum
+            ),
+            aidl_parser::ast::TypeKind::ResolvedItem(
+                "com.bwa.aidl_test.Outer.InnerEnum".into(),
+                aidl_parser::ast::ResolvedItemKind::Enum
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_generic_parcelable() {
+    let pair_aidl = r#"
+        package com.bwa.aidl_test;
+
+        parcelable Pair<A, B> {
+            A first;
+            B second;
+        }
+    "#;
+
+    let interface_aidl = r#"
+        package com.bwa.aidl_test;
+
+        import com.bwa.aidl_test.Pair;
+
+        interface MyInterface {
+            void method1(in Pair<String, int> pair);
+            void method2(in Pair<String> pair);
+        }
+    "#;
+
+    let mut parser = aidl_parser::Parser::new();
+    parser.add_content("id1", pair_aidl);
+    parser.add_content("id2", interface_aidl);
+    let res = parser.validate();
+
+    assert_eq!(res["id1"].diagnostics, []);
+    assert_eq!(res["id2"].diagnostics.len(), 1);
+    assert_eq!(
+        res["id2"].diagnostics[0].message,
+        "Wrong number of type arguments for `Pair`: expected 2, found 1"
+    );
+
+    let ast1 = res["id1"].ast.as_ref().expect("ast");
+    let parcelable = ast1.item.as_parcelable().expect("parcelable");
+    assert_eq!(parcelable.type_parameters, ["A", "B"]);
+
+    let mut types = Vec::new();
+    aidl_parser::traverse::walk_types(ast1, |t| types.push(t.kind.clone()));
+    assert_eq!(
+        types,
+        [
+            aidl_parser::ast::TypeKind::TypeParameter,
+            aidl_parser::ast::TypeKind::TypeParameter,
+        ]
+    );
+}
+
+#[test]
+fn test_const_expressions() {
+    use aidl_parser::ast::{ConstValue, InterfaceElement, ParcelableElement};
+
+    let constants_aidl = r#"
+        package com.bwa.aidl_test;
+
+        interface Constants {
+            const int BASE = 0x10;
+            const long MASK = (1L << 40) - 1;
+            const String PREFIX = "aidl" + "_";
+            const byte TOO_BIG = BASE * 20;
+        }
+    "#;
+
+    let parcelable_aidl = r#"
+        package com.bwa.aidl_test;
+
+        import com.bwa.aidl_test.Constants;
+
+        parcelable MyParcelable {
+            const int NEXT = Constants.BASE + 1;
+            const int CYCLE1 = CYCLE2;
+            const int CYCLE2 = CYCLE1;
+            int value = NEXT * 2;
+        }
+    "#;
+
+    let mut parser = aidl_parser::Parser::new();
+    parser.add_content("id1", constants_aidl);
+    parser.add_content("id2", parcelable_aidl);
+    let res = parser.validate();
+
+    // Diagnostics are reported where the constant is declared
+    assert_eq!(res["id1"].diagnostics.len(), 1);
+    assert_eq!(
+        res["id1"].diagnostics[0].message,
+        "Value `320` is out of range for type `byte`"
+    );
+    assert_eq!(res["id2"].diagnostics.len(), 1);
+    assert_eq!(
+        res["id2"].diagnostics[0].message,
+        "Cyclic reference to `CYCLE1`"
+    );
+
+    let ast1 = res["id1"].ast.as_ref().expect("ast");
+    let values: Vec<_> = ast1
+        .item
+        .as_interface()
+        .expect("interface")
+        .elements
+        .iter()
+        .map(|el| match el {
+            InterfaceElement::Const(c) => c.evaluated_value.clone(),
+            InterfaceElement::Method(_) => unreachable!(),
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [
+            Some(ConstValue::Int(16)),
+            Some(ConstValue::Long((1 << 40) - 1)),
+            Some(ConstValue::String("aidl_".into())),
+            None,
+        ]
+    );
+
+    let ast2 = res["id2"].ast.as_ref().expect("ast");
+    let values: Vec<_> = ast2
+        .item
+        .as_parcelable()
+        .expect("parcelable")
+        .elements
+        .iter()
+        .map(|el| match el {
+            ParcelableElement::Const(c) => c.evaluated_value.clone(),
+            ParcelableElement::Field(f) => f.evaluated_value.clone(),
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [
+            Some(ConstValue::Int(17)),
+            None,
+            None,
+            Some(ConstValue::Int(34))
+        ]
+    );
+}
+
+#[test]
+fn test_enum_values() {
+    use aidl_parser::ast::{ConstValue, InterfaceElement};
+
+    let enum_aidl = r#"
+        package com.bwa.aidl_test;
+
+        @Backing(type="int")
+        enum MyEnum {
+            VALUE1,
+            VALUE2,
+            VALUE10 = 10,
+            VALUE11,
+            ALIAS = VALUE2,
+        }
+    "#;
+
+    let interface_aidl = r#"
+        package com.bwa.aidl_test;
+
+        import com.bwa.aidl_test.MyEnum;
+
+        interface MyInterface {
+            const int NEXT = MyEnum.VALUE11 + 1;
+        }
+    "#;
+
+    let mut parser = aidl_parser::Parser::new();
+    parser.add_content("id1", enum_aidl);
+    parser.add_content("id2", interface_aidl);
+    let res = parser.validate();
+
+    assert_eq!(res["id1"].diagnostics.len(), 1);
+    assert_eq!(
+        res["id1"].diagnostics[0].message,
+        "Duplicated value `1` for enum element `ALIAS`"
+    );
+    assert_eq!(res["id2"].diagnostics, []);
+
+    let ast1 = res["id1"].ast.as_ref().expect("ast");
+    let values: Vec<_> = ast1
+        .item
+        .as_enum()
+        .expect("enum")
+        .elements
+        .iter()
+        .map(|el| el.evaluated_value.clone())
+        .collect();
+    assert_eq!(values, [0, 1, 10, 11, 1].map(|v| Some(ConstValue::Int(v))));
+
+    let ast2 = res["id2"].ast.as_ref().expect("ast");
+    let interface = ast2.item.as_interface().expect("interface");
+    match &interface.elements[0] {
+        InterfaceElement::Const(c) => assert_eq!(c.evaluated_value, Some(ConstValue::Int(12))),
+        InterfaceElement::Method(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn test_fixes() {
+    let parcelable_aidl = r#"
+        package com.bwa.aidl_test;
+
+        parcelable MyParcelable {}
+    "#;
+
+    let other_aidl = r#"
+        package com.bwa.other;
+
+        parcelable Other {}
+    "#;
+
+    let interface_aidl = "package com.bwa.aidl_test;
+
+import com.bwa.aidl_test.MyParcelable;
+import com.bwa.aidl_test.MyParcelable;
+import com.bwa.aidl_test.Unused;
+parcelable UnusedForward;
+
+oneway interface MyInterface {
+    oneway void method1(MyParcelable p, in Other o);
+}
+";
+
+    let mut parser = aidl_parser::Parser::new();
+    parser.add_content("id1", parcelable_aidl);
+    parser.add_content("id2", other_aidl);
+    parser.add_content("id3", interface_aidl);
+    parser.add_content("id4", "package com.bwa.aidl_test; parcelable Unused {}");
+    let res = parser.validate();
+
+    let diagnostics = &res["id3"].diagnostics;
+    assert_eq!(diagnostics.len(), 6);
+    assert!(diagnostics.iter().all(|d| d.fixes.len() == 1));
+
+    let fixed = aidl_parser::diagnostic::apply_fixes(
+        interface_aidl,
+        diagnostics.iter().flat_map(|d| &d.fixes),
+    );
+    assert_eq!(
+        fixed,
+        "package com.bwa.aidl_test;
+
+import com.bwa.aidl_test.MyParcelable;
+import com.bwa.other.Other;
+
+oneway interface MyInterface {
+    void method1(in MyParcelable p, in Other o);
+}
+"
+    );
+
+    // The fixed content is valid
+    parser.add_content("id3", &fixed);
+    let res = parser.validate();
+    assert_eq!(res["id3"].diagnostics, []);
+}
+
 #[test]
 fn test_parse_error() -> Result<()> {
     let aidl = "package x.y.z; completly wrong item {}";
@@ -94,6 +426,7 @@ fn test_parse_error() -> Result<()> {
     [
       Diagnostic(
         kind: Error,
+        code: "AIDL0003",
         range: Range(
           start: Position(
             offset: 15,
@@ -104,10 +437,11 @@ fn test_parse_error() -> Result<()> {
             line_col: (1, 25),
           ),
         ),
-        message: "Invalid item - Unrecognized token `completly`.\nExpected one of ANNOTATION, ENUM, IMPORT, INTERFACE or PARCELABLE",
+        message: "Invalid item - Unrecognized token `completly`.\nExpected one of ANNOTATION, ENUM, IMPORT, INTERFACE, ONEWAY, PARCELABLE or UNION",
         context_message: Some("unrecognized token"),
         hint: None,
         related_infos: [],
+        fixes: [],
       ),
     ]
     "###);